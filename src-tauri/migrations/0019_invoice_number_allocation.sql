@@ -0,0 +1,24 @@
+-- Counter reset policy and the period the counter currently belongs to
+ALTER TABLE organizations ADD COLUMN invoice_number_reset TEXT DEFAULT 'never';
+ALTER TABLE organizations ADD COLUMN invoice_number_period TEXT;
+
+-- Remember which counter value each invoice was numbered from
+ALTER TABLE invoices ADD COLUMN numberSequence INTEGER;
+ALTER TABLE invoices ADD COLUMN numberPeriod TEXT;
+
+-- Enforce unique invoice numbers per organization for new and changed rows.
+-- Triggers are used instead of a unique index so existing duplicates don't block the migration.
+CREATE TRIGGER invoices_unique_number_insert
+BEFORE INSERT ON invoices
+WHEN EXISTS (SELECT 1 FROM invoices WHERE organizationId = NEW.organizationId AND number = NEW.number)
+BEGIN
+  SELECT RAISE(ABORT, 'Invoice number already exists');
+END;
+
+CREATE TRIGGER invoices_unique_number_update
+BEFORE UPDATE OF number ON invoices
+WHEN NEW.number IS NOT OLD.number
+  AND EXISTS (SELECT 1 FROM invoices WHERE organizationId = NEW.organizationId AND number = NEW.number AND id != NEW.id)
+BEGIN
+  SELECT RAISE(ABORT, 'Invoice number already exists');
+END;
@@ -0,0 +1,17 @@
+-- Normalize any unexpected invoice states before they become a typed enum
+UPDATE invoices
+SET state = 'draft'
+WHERE state NOT IN ('draft', 'sent', 'partially_paid', 'overdue', 'paid', 'void');
+
+-- Create invoiceStateHistory table
+CREATE TABLE invoiceStateHistory (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  invoiceId TEXT NOT NULL,
+  fromState TEXT,
+  toState TEXT NOT NULL,
+  changedBy TEXT NOT NULL,
+  note TEXT,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (invoiceId) REFERENCES invoices(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_invoiceStateHistory_invoiceId ON invoiceStateHistory(invoiceId);
@@ -0,0 +1,9 @@
+-- Credit notes are stored as invoices of kind 'credit_note' referencing the credited invoice
+ALTER TABLE invoices ADD COLUMN kind TEXT NOT NULL DEFAULT 'invoice';
+ALTER TABLE invoices ADD COLUMN creditedInvoiceId TEXT REFERENCES invoices(id);
+CREATE INDEX idx_invoices_creditedInvoiceId ON invoices(creditedInvoiceId);
+
+-- Credit notes have their own numbering series
+ALTER TABLE organizations ADD COLUMN credit_note_number_format TEXT DEFAULT 'CN-{year}-{number}';
+ALTER TABLE organizations ADD COLUMN credit_note_number_counter INTEGER DEFAULT 0;
+ALTER TABLE organizations ADD COLUMN credit_note_number_period TEXT;
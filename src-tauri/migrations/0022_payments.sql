@@ -0,0 +1,14 @@
+-- Create payments table
+CREATE TABLE payments (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  invoiceId TEXT NOT NULL,
+  amount INTEGER NOT NULL,
+  date INTEGER NOT NULL,
+  method TEXT,
+  reference TEXT,
+  note TEXT,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (invoiceId) REFERENCES invoices(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_payments_invoiceId ON payments(invoiceId);
+CREATE INDEX idx_payments_date ON payments(date);
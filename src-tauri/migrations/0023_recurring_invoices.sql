@@ -0,0 +1,40 @@
+-- Create recurringInvoices table
+CREATE TABLE recurringInvoices (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  organizationId TEXT NOT NULL,
+  clientId TEXT NOT NULL,
+  name TEXT,
+  currency TEXT NOT NULL DEFAULT 'EUR',
+  customerNotes TEXT,
+  overdueCharge REAL DEFAULT 0,
+  dueDays INTEGER,
+  interval TEXT NOT NULL DEFAULT 'monthly',
+  dayOfMonth INTEGER,
+  startDate INTEGER NOT NULL,
+  endDate INTEGER,
+  autoSend INTEGER DEFAULT 0,
+  isActive INTEGER DEFAULT 1,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (organizationId) REFERENCES organizations(id) ON DELETE CASCADE,
+  FOREIGN KEY (clientId) REFERENCES clients(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_recurringInvoices_organizationId ON recurringInvoices(organizationId);
+
+-- Create recurringInvoiceLineItems table
+CREATE TABLE recurringInvoiceLineItems (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  recurringInvoiceId TEXT NOT NULL,
+  description TEXT,
+  quantity REAL NOT NULL DEFAULT 1,
+  unitPrice INTEGER NOT NULL,
+  taxRate TEXT,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (taxRate) REFERENCES taxRates(id) ON DELETE SET NULL,
+  FOREIGN KEY (recurringInvoiceId) REFERENCES recurringInvoices(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_recurringInvoiceLineItems_recurringInvoiceId ON recurringInvoiceLineItems(recurringInvoiceId);
+
+-- Link generated invoices to their schedule. One invoice per schedule and period.
+ALTER TABLE invoices ADD COLUMN recurringInvoiceId TEXT REFERENCES recurringInvoices(id) ON DELETE SET NULL;
+ALTER TABLE invoices ADD COLUMN recurringPeriod TEXT;
+CREATE UNIQUE INDEX idx_invoices_recurring ON invoices(recurringInvoiceId, recurringPeriod);
@@ -0,0 +1,44 @@
+-- Create estimates table
+CREATE TABLE estimates (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  organizationId TEXT NOT NULL,
+  number TEXT NOT NULL,
+  state TEXT NOT NULL DEFAULT 'draft',
+  clientId TEXT NOT NULL,
+  date INTEGER NOT NULL,
+  validUntil INTEGER,
+  currency TEXT NOT NULL DEFAULT 'EUR',
+  customerNotes TEXT,
+  total INTEGER NOT NULL DEFAULT 0,
+  taxTotal INTEGER NOT NULL DEFAULT 0,
+  subTotal INTEGER NOT NULL DEFAULT 0,
+  numberSequence INTEGER,
+  numberPeriod TEXT,
+  invoiceId TEXT,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (organizationId) REFERENCES organizations(id) ON DELETE CASCADE,
+  FOREIGN KEY (clientId) REFERENCES clients(id) ON DELETE CASCADE,
+  FOREIGN KEY (invoiceId) REFERENCES invoices(id) ON DELETE SET NULL
+);
+CREATE INDEX idx_estimates_organizationId ON estimates(organizationId);
+CREATE INDEX idx_estimates_clientId ON estimates(clientId);
+CREATE UNIQUE INDEX idx_estimates_number ON estimates(organizationId, number);
+
+-- Create estimateLineItems table
+CREATE TABLE estimateLineItems (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  estimateId TEXT NOT NULL,
+  description TEXT,
+  quantity REAL NOT NULL DEFAULT 1,
+  unitPrice INTEGER NOT NULL,
+  taxRate TEXT,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (taxRate) REFERENCES taxRates(id) ON DELETE SET NULL,
+  FOREIGN KEY (estimateId) REFERENCES estimates(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_estimateLineItems_estimateId ON estimateLineItems(estimateId);
+
+-- Estimates have their own numbering series
+ALTER TABLE organizations ADD COLUMN estimate_number_format TEXT DEFAULT 'EST-{year}-{number}';
+ALTER TABLE organizations ADD COLUMN estimate_number_counter INTEGER DEFAULT 0;
+ALTER TABLE organizations ADD COLUMN estimate_number_period TEXT;
@@ -0,0 +1,2 @@
+-- Buyer country is required on structured e-invoices
+ALTER TABLE clients ADD COLUMN country TEXT;
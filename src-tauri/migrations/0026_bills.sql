@@ -0,0 +1,54 @@
+-- Create bills table for purchase invoices received from suppliers
+CREATE TABLE bills (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  organizationId TEXT NOT NULL,
+  supplierId TEXT NOT NULL,
+  kind TEXT NOT NULL DEFAULT 'invoice',
+  number TEXT NOT NULL,
+  date INTEGER NOT NULL,
+  dueDate INTEGER,
+  currency TEXT NOT NULL DEFAULT 'EUR',
+  notes TEXT,
+  paymentReference TEXT,
+  iban TEXT,
+  total INTEGER NOT NULL DEFAULT 0,
+  taxTotal INTEGER NOT NULL DEFAULT 0,
+  subTotal INTEGER NOT NULL DEFAULT 0,
+  amountDue INTEGER NOT NULL DEFAULT 0,
+  format TEXT NOT NULL,
+  xml TEXT NOT NULL,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (organizationId) REFERENCES organizations(id) ON DELETE CASCADE,
+  FOREIGN KEY (supplierId) REFERENCES clients(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_bills_organizationId ON bills(organizationId);
+CREATE INDEX idx_bills_supplierId ON bills(supplierId);
+CREATE UNIQUE INDEX idx_bills_number ON bills(organizationId, supplierId, kind, number);
+
+-- Create billLineItems table
+CREATE TABLE billLineItems (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  billId TEXT NOT NULL,
+  description TEXT,
+  quantity REAL NOT NULL DEFAULT 1,
+  unitCode TEXT,
+  unitPrice INTEGER NOT NULL,
+  total INTEGER NOT NULL,
+  taxCategory TEXT NOT NULL,
+  taxPercentage REAL NOT NULL DEFAULT 0,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (billId) REFERENCES bills(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_billLineItems_billId ON billLineItems(billId);
+
+-- Create billTaxes table with the tax breakdown as stated by the supplier
+CREATE TABLE billTaxes (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  billId TEXT NOT NULL,
+  category TEXT NOT NULL,
+  percentage REAL NOT NULL DEFAULT 0,
+  taxableAmount INTEGER NOT NULL,
+  amount INTEGER NOT NULL,
+  FOREIGN KEY (billId) REFERENCES bills(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_billTaxes_billId ON billTaxes(billId);
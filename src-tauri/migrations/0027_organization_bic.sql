@@ -0,0 +1,2 @@
+-- BIC of the organization bank account, used in payment QR codes
+ALTER TABLE organizations ADD COLUMN bic TEXT;
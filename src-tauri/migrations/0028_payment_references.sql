@@ -0,0 +1,4 @@
+-- Structured payment references derived from invoice numbers
+ALTER TABLE organizations ADD COLUMN payment_reference_format TEXT;
+ALTER TABLE invoices ADD COLUMN paymentReference TEXT;
+CREATE INDEX idx_invoices_paymentReference ON invoices(organizationId, paymentReference);
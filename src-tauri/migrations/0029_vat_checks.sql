@@ -0,0 +1,18 @@
+-- VAT number lookups in VIES, kept as evidence of a client's registration
+CREATE TABLE vatChecks (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  countryCode TEXT NOT NULL,
+  vatNumber TEXT NOT NULL,
+  valid INTEGER NOT NULL,
+  name TEXT,
+  address TEXT,
+  requester TEXT,
+  consultationNumber TEXT,
+  checkedAt INTEGER NOT NULL
+);
+CREATE INDEX idx_vatChecks_vatNumber ON vatChecks(countryCode, vatNumber, checkedAt);
+
+-- Latest verification of the client's VAT number
+ALTER TABLE clients ADD COLUMN vatin_valid INTEGER;
+ALTER TABLE clients ADD COLUMN vatin_checked_at INTEGER;
+ALTER TABLE clients ADD COLUMN vatin_consultation_number TEXT;
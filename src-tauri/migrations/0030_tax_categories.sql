@@ -0,0 +1,6 @@
+-- Tax categories with the legal note printed for non-standard rates
+ALTER TABLE taxRates ADD COLUMN category TEXT NOT NULL DEFAULT 'standard';
+ALTER TABLE taxRates ADD COLUMN exemptionReason TEXT;
+
+-- 0% rates were exported as zero rated before
+UPDATE taxRates SET category = 'zero_rated' WHERE percentage = 0;
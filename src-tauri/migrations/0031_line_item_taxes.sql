@@ -0,0 +1,23 @@
+-- Several taxes per line, e.g. GST and PST. Compound taxes are charged on
+-- the line amount plus the taxes before them. Shared by the line items of
+-- invoices, estimates and recurring invoices, so lineItemId has no foreign key.
+CREATE TABLE lineItemTaxes (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  lineItemId TEXT NOT NULL,
+  taxRateId TEXT NOT NULL,
+  compound INTEGER NOT NULL DEFAULT 0,
+  position INTEGER NOT NULL DEFAULT 0,
+  FOREIGN KEY (taxRateId) REFERENCES taxRates(id) ON DELETE CASCADE
+);
+CREATE INDEX idx_lineItemTaxes_lineItemId ON lineItemTaxes(lineItemId);
+
+-- Whether the rate is charged on top of a line's other taxes when added to it
+ALTER TABLE taxRates ADD COLUMN compound INTEGER NOT NULL DEFAULT 0;
+
+-- Existing lines keep their single tax, if it still exists
+INSERT INTO lineItemTaxes (id, lineItemId, taxRateId)
+SELECT lower(hex(randomblob(8))), id, taxRate FROM invoiceLineItems WHERE taxRate IN (SELECT id FROM taxRates)
+UNION ALL
+SELECT lower(hex(randomblob(8))), id, taxRate FROM estimateLineItems WHERE taxRate IN (SELECT id FROM taxRates)
+UNION ALL
+SELECT lower(hex(randomblob(8))), id, taxRate FROM recurringInvoiceLineItems WHERE taxRate IN (SELECT id FROM taxRates);
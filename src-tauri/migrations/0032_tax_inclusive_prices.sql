@@ -0,0 +1,7 @@
+-- Whether unit prices include tax, and whether tax is rounded per line or on
+-- the totals. Organizations hold the defaults for new invoices.
+ALTER TABLE organizations ADD COLUMN prices_include_tax INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE organizations ADD COLUMN tax_rounding TEXT NOT NULL DEFAULT 'per_total';
+
+ALTER TABLE invoices ADD COLUMN pricesIncludeTax INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN taxRounding TEXT NOT NULL DEFAULT 'per_total';
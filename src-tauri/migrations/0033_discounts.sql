@@ -0,0 +1,13 @@
+-- Percentage or fixed discounts on line items and whole invoices, taken off
+-- before tax. Fixed discounts are in cents.
+ALTER TABLE invoiceLineItems ADD COLUMN discount REAL;
+ALTER TABLE invoiceLineItems ADD COLUMN discountKind TEXT NOT NULL DEFAULT 'percentage';
+ALTER TABLE estimateLineItems ADD COLUMN discount REAL;
+ALTER TABLE estimateLineItems ADD COLUMN discountKind TEXT NOT NULL DEFAULT 'percentage';
+ALTER TABLE recurringInvoiceLineItems ADD COLUMN discount REAL;
+ALTER TABLE recurringInvoiceLineItems ADD COLUMN discountKind TEXT NOT NULL DEFAULT 'percentage';
+
+ALTER TABLE invoices ADD COLUMN discount REAL;
+ALTER TABLE invoices ADD COLUMN discountKind TEXT NOT NULL DEFAULT 'percentage';
+-- Line and invoice discounts together, in the same terms as the prices
+ALTER TABLE invoices ADD COLUMN discountTotal INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,5 @@
+-- Withholding rates (e.g. IRPF, ritenuta d'acconto) are kept back by the
+-- client and reduce the amount due instead of adding to it
+ALTER TABLE taxRates ADD COLUMN withholding INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE invoices ADD COLUMN withholdingTotal INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,21 @@
+-- Catalog of products and services to fill line items from. Lines copy the
+-- description and price, so editing an item doesn't change existing invoices.
+CREATE TABLE items (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  organizationId TEXT NOT NULL,
+  name TEXT NOT NULL,
+  description TEXT,
+  unit TEXT,
+  unitPrice INTEGER NOT NULL DEFAULT 0,
+  taxRateId TEXT,
+  sku TEXT,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP,
+  FOREIGN KEY (organizationId) REFERENCES organizations(id) ON DELETE CASCADE,
+  FOREIGN KEY (taxRateId) REFERENCES taxRates(id) ON DELETE SET NULL
+);
+CREATE INDEX idx_items_organizationId ON items(organizationId);
+CREATE UNIQUE INDEX idx_items_sku ON items(organizationId, sku) WHERE sku IS NOT NULL;
+
+ALTER TABLE invoiceLineItems ADD COLUMN itemId TEXT REFERENCES items(id) ON DELETE SET NULL;
+ALTER TABLE estimateLineItems ADD COLUMN itemId TEXT REFERENCES items(id) ON DELETE SET NULL;
+ALTER TABLE recurringInvoiceLineItems ADD COLUMN itemId TEXT REFERENCES items(id) ON DELETE SET NULL;
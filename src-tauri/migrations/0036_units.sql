@@ -0,0 +1,8 @@
+-- UN/ECE Recommendation 20 unit codes on line items, e.g. HUR for hours, and
+-- the number of decimals quantities are rounded to when saved
+ALTER TABLE invoiceLineItems ADD COLUMN unitCode TEXT;
+ALTER TABLE estimateLineItems ADD COLUMN unitCode TEXT;
+ALTER TABLE recurringInvoiceLineItems ADD COLUMN unitCode TEXT;
+ALTER TABLE items RENAME COLUMN unit TO unitCode;
+
+ALTER TABLE organizations ADD COLUMN quantity_precision INTEGER NOT NULL DEFAULT 2;
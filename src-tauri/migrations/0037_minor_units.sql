@@ -0,0 +1,135 @@
+-- Amounts are kept in each currency's minor unit (ISO 4217) instead of always
+-- in hundredths: yen for JPY, fils for BHD. Amounts in currencies without two
+-- decimals are rescaled, and hourly rates move from REAL units to INTEGER.
+-- Fixed discounts move out of the REAL discount columns, which keep only
+-- percentages, into INTEGER discountAmount columns.
+CREATE TEMP TABLE minorUnits (
+  currency TEXT PRIMARY KEY NOT NULL,
+  scale INTEGER NOT NULL
+);
+INSERT INTO minorUnits (currency, scale) VALUES
+  ('BIF', 1), ('CLP', 1), ('DJF', 1), ('GNF', 1), ('ISK', 1), ('JPY', 1), ('KMF', 1), ('KRW', 1), ('PYG', 1),
+  ('RWF', 1), ('UGX', 1), ('UYI', 1), ('VND', 1), ('VUV', 1), ('XAF', 1), ('XOF', 1), ('XPF', 1),
+  ('BHD', 1000), ('IQD', 1000), ('JOD', 1000), ('KWD', 1000), ('LYD', 1000), ('OMR', 1000), ('TND', 1000),
+  ('CLF', 10000), ('UYW', 10000);
+
+UPDATE invoices
+SET total = CAST(ROUND(total * m.scale / 100.0) AS INTEGER),
+    taxTotal = CAST(ROUND(taxTotal * m.scale / 100.0) AS INTEGER),
+    subTotal = CAST(ROUND(subTotal * m.scale / 100.0) AS INTEGER),
+    discountTotal = CAST(ROUND(discountTotal * m.scale / 100.0) AS INTEGER),
+    withholdingTotal = CAST(ROUND(withholdingTotal * m.scale / 100.0) AS INTEGER)
+FROM minorUnits m
+WHERE m.currency = invoices.currency;
+
+UPDATE invoiceLineItems
+SET unitPrice = CAST(ROUND(unitPrice * m.scale / 100.0) AS INTEGER)
+FROM invoices i
+JOIN minorUnits m ON m.currency = i.currency
+WHERE i.id = invoiceLineItems.invoiceId;
+
+UPDATE payments
+SET amount = CAST(ROUND(amount * m.scale / 100.0) AS INTEGER)
+FROM invoices i
+JOIN minorUnits m ON m.currency = i.currency
+WHERE i.id = payments.invoiceId;
+
+UPDATE estimates
+SET total = CAST(ROUND(total * m.scale / 100.0) AS INTEGER),
+    taxTotal = CAST(ROUND(taxTotal * m.scale / 100.0) AS INTEGER),
+    subTotal = CAST(ROUND(subTotal * m.scale / 100.0) AS INTEGER)
+FROM minorUnits m
+WHERE m.currency = estimates.currency;
+
+UPDATE estimateLineItems
+SET unitPrice = CAST(ROUND(unitPrice * m.scale / 100.0) AS INTEGER)
+FROM estimates e
+JOIN minorUnits m ON m.currency = e.currency
+WHERE e.id = estimateLineItems.estimateId;
+
+UPDATE recurringInvoiceLineItems
+SET unitPrice = CAST(ROUND(unitPrice * m.scale / 100.0) AS INTEGER)
+FROM recurringInvoices r
+JOIN minorUnits m ON m.currency = r.currency
+WHERE r.id = recurringInvoiceLineItems.recurringInvoiceId;
+
+UPDATE bills
+SET total = CAST(ROUND(total * m.scale / 100.0) AS INTEGER),
+    taxTotal = CAST(ROUND(taxTotal * m.scale / 100.0) AS INTEGER),
+    subTotal = CAST(ROUND(subTotal * m.scale / 100.0) AS INTEGER),
+    amountDue = CAST(ROUND(amountDue * m.scale / 100.0) AS INTEGER)
+FROM minorUnits m
+WHERE m.currency = bills.currency;
+
+UPDATE billLineItems
+SET unitPrice = CAST(ROUND(unitPrice * m.scale / 100.0) AS INTEGER),
+    total = CAST(ROUND(billLineItems.total * m.scale / 100.0) AS INTEGER)
+FROM bills b
+JOIN minorUnits m ON m.currency = b.currency
+WHERE b.id = billLineItems.billId;
+
+UPDATE billTaxes
+SET taxableAmount = CAST(ROUND(taxableAmount * m.scale / 100.0) AS INTEGER),
+    amount = CAST(ROUND(amount * m.scale / 100.0) AS INTEGER)
+FROM bills b
+JOIN minorUnits m ON m.currency = b.currency
+WHERE b.id = billTaxes.billId;
+
+-- Fixed discounts, rescaled like the amounts they come off
+ALTER TABLE invoices ADD COLUMN discountAmount INTEGER;
+UPDATE invoices
+SET discountAmount = CAST(ROUND(discount * COALESCE(
+  (SELECT m.scale FROM minorUnits m WHERE m.currency = invoices.currency),
+  100
+) / 100.0) AS INTEGER),
+    discount = NULL
+WHERE discountKind = 'amount';
+
+ALTER TABLE invoiceLineItems ADD COLUMN discountAmount INTEGER;
+UPDATE invoiceLineItems
+SET discountAmount = CAST(ROUND(discount * COALESCE(
+  (SELECT m.scale FROM invoices i JOIN minorUnits m ON m.currency = i.currency WHERE i.id = invoiceLineItems.invoiceId),
+  100
+) / 100.0) AS INTEGER),
+    discount = NULL
+WHERE discountKind = 'amount';
+
+ALTER TABLE estimateLineItems ADD COLUMN discountAmount INTEGER;
+UPDATE estimateLineItems
+SET discountAmount = CAST(ROUND(discount * COALESCE(
+  (SELECT m.scale FROM estimates e JOIN minorUnits m ON m.currency = e.currency WHERE e.id = estimateLineItems.estimateId),
+  100
+) / 100.0) AS INTEGER),
+    discount = NULL
+WHERE discountKind = 'amount';
+
+ALTER TABLE recurringInvoiceLineItems ADD COLUMN discountAmount INTEGER;
+UPDATE recurringInvoiceLineItems
+SET discountAmount = CAST(ROUND(discount * COALESCE(
+  (SELECT m.scale FROM recurringInvoices r JOIN minorUnits m ON m.currency = r.currency
+    WHERE r.id = recurringInvoiceLineItems.recurringInvoiceId),
+  100
+) / 100.0) AS INTEGER),
+    discount = NULL
+WHERE discountKind = 'amount';
+
+-- Catalog prices are in the organization's currency
+UPDATE items
+SET unitPrice = CAST(ROUND(unitPrice * m.scale / 100.0) AS INTEGER)
+FROM organizations o
+JOIN minorUnits m ON m.currency = o.currency
+WHERE o.id = items.organizationId;
+
+-- Hourly rates were in currency units, they become minor units of the
+-- organization's currency
+ALTER TABLE timeEntries ADD COLUMN hourlyRateMinor INTEGER;
+UPDATE timeEntries
+SET hourlyRateMinor = CAST(ROUND(hourlyRate * COALESCE(
+  (SELECT m.scale FROM organizations o JOIN minorUnits m ON m.currency = o.currency WHERE o.id = timeEntries.organizationId),
+  100
+)) AS INTEGER)
+WHERE hourlyRate IS NOT NULL;
+ALTER TABLE timeEntries DROP COLUMN hourlyRate;
+ALTER TABLE timeEntries RENAME COLUMN hourlyRateMinor TO hourlyRate;
+
+DROP TABLE minorUnits;
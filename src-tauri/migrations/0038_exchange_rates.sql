@@ -0,0 +1,18 @@
+-- Reference exchange rates, quoted as units of `currency` for one unit of
+-- `baseCurrency` ("1 EUR = 1.0921 USD"). Rates are kept as the published
+-- decimal text so conversions stay exact.
+CREATE TABLE exchangeRates (
+  id TEXT(21) PRIMARY KEY NOT NULL,
+  baseCurrency TEXT NOT NULL,
+  currency TEXT NOT NULL,
+  date TEXT NOT NULL,
+  rate TEXT NOT NULL,
+  source TEXT NOT NULL,
+  createdAt TEXT DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX idx_exchangeRates_pair_date ON exchangeRates(baseCurrency, currency, date);
+
+-- Rate to the organization's currency, taken when the invoice is issued
+ALTER TABLE invoices ADD COLUMN baseCurrency TEXT;
+ALTER TABLE invoices ADD COLUMN exchangeRate TEXT;
+ALTER TABLE invoices ADD COLUMN exchangeRateDate TEXT;
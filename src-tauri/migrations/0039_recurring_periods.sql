@@ -0,0 +1,27 @@
+-- Recurring invoices are keyed by their billing period (2025-03, 2025-Q1 or
+-- 2025) instead of their exact date, which changed when a schedule's day or
+-- start date was edited. Should a period already hold more than one invoice
+-- the later ones keep their date key.
+CREATE TEMP TABLE recurringPeriods AS
+SELECT id, period, ROW_NUMBER() OVER (PARTITION BY recurringInvoiceId, period ORDER BY date, id) AS position
+FROM (
+  SELECT
+    invoices.id,
+    invoices.recurringInvoiceId,
+    invoices.date,
+    CASE r.interval
+      WHEN 'monthly' THEN substr(invoices.recurringPeriod, 1, 7)
+      WHEN 'quarterly' THEN substr(invoices.recurringPeriod, 1, 4) || '-Q' || ((CAST(substr(invoices.recurringPeriod, 6, 2) AS INTEGER) + 2) / 3)
+      ELSE substr(invoices.recurringPeriod, 1, 4)
+    END AS period
+  FROM invoices
+  JOIN recurringInvoices r ON r.id = invoices.recurringInvoiceId
+  WHERE length(invoices.recurringPeriod) = 10
+);
+
+UPDATE invoices
+SET recurringPeriod = p.period
+FROM recurringPeriods p
+WHERE p.id = invoices.id AND p.position = 1;
+
+DROP TABLE recurringPeriods;
@@ -0,0 +1,17 @@
+-- Line taxes keep their tax rate's name, percentage and treatment as they
+-- were when the line was saved, so PDFs and e-invoices of issued invoices
+-- don't change when the rate is edited later
+ALTER TABLE lineItemTaxes ADD COLUMN name TEXT;
+ALTER TABLE lineItemTaxes ADD COLUMN percentage REAL;
+ALTER TABLE lineItemTaxes ADD COLUMN category TEXT;
+ALTER TABLE lineItemTaxes ADD COLUMN exemptionReason TEXT;
+ALTER TABLE lineItemTaxes ADD COLUMN withholding INTEGER;
+
+UPDATE lineItemTaxes
+SET name = t.name,
+    percentage = t.percentage,
+    category = t.category,
+    exemptionReason = t.exemptionReason,
+    withholding = t.withholding
+FROM taxRates t
+WHERE t.id = lineItemTaxes.taxRateId;
@@ -0,0 +1,8 @@
+-- Estimates keep the tax mode they were totalled with and pass it on to the
+-- invoice they are converted to. Existing ones take the organization's.
+ALTER TABLE estimates ADD COLUMN pricesIncludeTax INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE estimates ADD COLUMN taxRounding TEXT NOT NULL DEFAULT 'per_total';
+
+UPDATE estimates
+SET pricesIncludeTax = (SELECT prices_include_tax FROM organizations WHERE organizations.id = estimates.organizationId),
+    taxRounding = (SELECT tax_rounding FROM organizations WHERE organizations.id = estimates.organizationId);
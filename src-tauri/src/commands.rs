@@ -1,12 +1,26 @@
 use crate::db::{
     Client, CreateClientRequest, Database, UpdateClientRequest,
     Invoice, InvoiceLineItem, CreateInvoiceRequest, UpdateInvoiceRequest,
+    CreateInvoiceLineItemRequest, InvoiceTotals, InvoiceState, InvoiceStateChange, TaxRounding, Discount, DiscountKind,
+    CreateCreditNoteRequest, NumberSeries, WithholdingReportRow, RevenueReportRow, ExchangeRate,
+    Payment, CreatePaymentRequest, UpdatePaymentRequest,
+    RecurringInvoice, RecurringInvoiceLineItem, CreateRecurringInvoiceRequest, UpdateRecurringInvoiceRequest,
+    Estimate, EstimateLineItem, EstimateState, CreateEstimateRequest, UpdateEstimateRequest,
+    Bill, BillLineItem, BillTax,
     Organization, CreateOrganizationRequest, UpdateOrganizationRequest,
     TaxRate, CreateTaxRateRequest, UpdateTaxRateRequest,
+    Item, CreateItemRequest, UpdateItemRequest,
     Tag, CreateTagRequest, UpdateTagRequest,
     TimeEntry, CreateTimeEntryRequest, UpdateTimeEntryRequest,
     Project, CreateProjectRequest, UpdateProjectRequest
 };
+use crate::einvoice::{EInvoiceError, EInvoiceFormat};
+use crate::einvoice::cii::FacturXProfile;
+use crate::pdf::InvoiceTemplate;
+use crate::qr::{QrImage, QrImageFormat};
+use crate::validation::FieldError;
+use crate::vat_registry::ViesRegistry;
+use serde::Serialize;
 use tauri::{AppHandle, Manager, State};
 use chrono::{DateTime, Utc};
 use std::fs;
@@ -16,6 +30,21 @@ fn handle_db_error(error: sqlx::Error, operation: &str) -> String {
     format!("{}: {}", operation, error)
 }
 
+// Error of the commands that save forms. Invalid fields are listed so the form
+// can show them next to their inputs, other errors are messages as elsewhere.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum FormError {
+    Fields { fields: Vec<FieldError> },
+    Message(String),
+}
+
+impl From<Vec<FieldError>> for FormError {
+    fn from(fields: Vec<FieldError>) -> Self {
+        Self::Fields { fields }
+    }
+}
+
 #[tauri::command]
 pub async fn get_clients(
     organization_id: String,
@@ -37,10 +66,12 @@ pub async fn get_client(client_id: String, db: State<'_, Database>) -> Result<Op
 pub async fn create_client(
     client: CreateClientRequest,
     db: State<'_, Database>,
-) -> Result<Client, String> {
+) -> Result<Client, FormError> {
+    client.validate()?;
+
     db.create_client(client)
         .await
-        .map_err(|e| handle_db_error(e, "create_client"))
+        .map_err(|e| FormError::Message(handle_db_error(e, "create_client")))
 }
 
 #[tauri::command]
@@ -48,10 +79,12 @@ pub async fn update_client(
     client_id: String,
     updates: UpdateClientRequest,
     db: State<'_, Database>,
-) -> Result<Client, String> {
+) -> Result<Client, FormError> {
+    updates.validate()?;
+
     db.update_client(&client_id, updates)
         .await
-        .map_err(|e| handle_db_error(e, "update_client"))
+        .map_err(|e| FormError::Message(handle_db_error(e, "update_client")))
 }
 
 #[tauri::command]
@@ -68,6 +101,17 @@ pub async fn get_client_invoice_count(client_id: String, db: State<'_, Database>
         .map_err(|e| handle_db_error(e, "get_client_invoice_count"))
 }
 
+#[tauri::command]
+pub async fn verify_client_vatin(
+    client_id: String,
+    db: State<'_, Database>,
+    registry: State<'_, ViesRegistry>,
+) -> Result<Client, String> {
+    db.verify_client_vatin(registry.inner(), &client_id)
+        .await
+        .map_err(|e| handle_db_error(e, "verify_client_vatin"))
+}
+
 #[tauri::command]
 pub async fn get_invoices(
     organization_id: String,
@@ -85,6 +129,17 @@ pub async fn get_invoice(invoice_id: String, db: State<'_, Database>) -> Result<
         .map_err(|e| handle_db_error(e, "get_invoice"))
 }
 
+#[tauri::command]
+pub async fn find_invoice_by_payment_reference(
+    organization_id: String,
+    reference: String,
+    db: State<'_, Database>,
+) -> Result<Option<Invoice>, String> {
+    db.find_invoice_by_payment_reference(&organization_id, &reference)
+        .await
+        .map_err(|e| handle_db_error(e, "find_invoice_by_payment_reference"))
+}
+
 #[tauri::command]
 pub async fn get_invoice_line_items(
     invoice_id: String,
@@ -95,6 +150,38 @@ pub async fn get_invoice_line_items(
         .map_err(|e| handle_db_error(e, "get_invoice_line_items"))
 }
 
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn calculate_invoice_totals(
+    organization_id: String,
+    line_items: Vec<CreateInvoiceLineItemRequest>,
+    prices_include_tax: Option<bool>,
+    tax_rounding: Option<TaxRounding>,
+    discount: Option<f64>,
+    discount_kind: Option<DiscountKind>,
+    currency: String,
+    db: State<'_, Database>,
+) -> Result<InvoiceTotals, String> {
+    let discount = Discount::new(discount, discount_kind.unwrap_or_default());
+    db.get_invoice_totals(&organization_id, &line_items, prices_include_tax, tax_rounding, discount, &currency)
+        .await
+        .map_err(|e| handle_db_error(e, "calculate_invoice_totals"))
+}
+
+#[tauri::command]
+pub async fn preview_invoice_number(
+    organization_id: String,
+    client_id: Option<String>,
+    date: i64,
+    series: Option<NumberSeries>,
+    db: State<'_, Database>,
+) -> Result<String, String> {
+    let series = series.unwrap_or(NumberSeries::Invoice);
+    db.preview_invoice_number(series, &organization_id, client_id.as_deref(), date)
+        .await
+        .map_err(|e| handle_db_error(e, "preview_invoice_number"))
+}
+
 #[tauri::command]
 pub async fn create_invoice(
     invoice: CreateInvoiceRequest,
@@ -105,6 +192,16 @@ pub async fn create_invoice(
         .map_err(|e| handle_db_error(e, "create_invoice"))
 }
 
+#[tauri::command]
+pub async fn create_credit_note(
+    credit_note: CreateCreditNoteRequest,
+    db: State<'_, Database>,
+) -> Result<Invoice, String> {
+    db.create_credit_note(credit_note)
+        .await
+        .map_err(|e| handle_db_error(e, "create_credit_note"))
+}
+
 #[tauri::command]
 pub async fn update_invoice(
     invoice_id: String,
@@ -119,14 +216,25 @@ pub async fn update_invoice(
 #[tauri::command]
 pub async fn update_invoice_state(
     invoice_id: String,
-    state: String,
+    state: InvoiceState,
+    note: Option<String>,
     db: State<'_, Database>,
 ) -> Result<Invoice, String> {
-    db.update_invoice_state(&invoice_id, &state)
+    db.update_invoice_state(&invoice_id, state, note.as_deref())
         .await
         .map_err(|e| handle_db_error(e, "update_invoice_state"))
 }
 
+#[tauri::command]
+pub async fn get_invoice_state_history(
+    invoice_id: String,
+    db: State<'_, Database>,
+) -> Result<Vec<InvoiceStateChange>, String> {
+    db.get_invoice_state_history(&invoice_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_invoice_state_history"))
+}
+
 #[tauri::command]
 pub async fn delete_invoice(invoice_id: String, db: State<'_, Database>) -> Result<bool, String> {
     db.delete_invoice(&invoice_id)
@@ -134,6 +242,360 @@ pub async fn delete_invoice(invoice_id: String, db: State<'_, Database>) -> Resu
         .map_err(|e| handle_db_error(e, "delete_invoice"))
 }
 
+#[tauri::command]
+pub async fn get_withholding_report(
+    organization_id: String,
+    year: i32,
+    db: State<'_, Database>,
+) -> Result<Vec<WithholdingReportRow>, String> {
+    db.get_withholding_report(&organization_id, year)
+        .await
+        .map_err(|e| handle_db_error(e, "get_withholding_report"))
+}
+
+#[tauri::command]
+pub async fn get_revenue_report(
+    organization_id: String,
+    year: i32,
+    db: State<'_, Database>,
+) -> Result<Vec<RevenueReportRow>, String> {
+    db.get_revenue_report(&organization_id, year)
+        .await
+        .map_err(|e| handle_db_error(e, "get_revenue_report"))
+}
+
+#[tauri::command]
+pub async fn get_exchange_rates(db: State<'_, Database>) -> Result<Vec<ExchangeRate>, String> {
+    db.get_exchange_rates()
+        .await
+        .map_err(|e| handle_db_error(e, "get_exchange_rates"))
+}
+
+// ECB reference rates as downloaded, XML or CSV
+#[tauri::command]
+pub async fn import_exchange_rates(path: String, db: State<'_, Database>) -> Result<u64, String> {
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read exchange rates: {}", e))?;
+
+    db.import_exchange_rates(&contents)
+        .await
+        .map_err(|e| handle_db_error(e, "import_exchange_rates"))
+}
+
+#[tauri::command]
+pub async fn get_payments(invoice_id: String, db: State<'_, Database>) -> Result<Vec<Payment>, String> {
+    db.get_payments(&invoice_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_payments"))
+}
+
+#[tauri::command]
+pub async fn get_payment(payment_id: String, db: State<'_, Database>) -> Result<Option<Payment>, String> {
+    db.get_payment(&payment_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_payment"))
+}
+
+#[tauri::command]
+pub async fn create_payment(
+    payment: CreatePaymentRequest,
+    db: State<'_, Database>,
+) -> Result<Payment, String> {
+    db.create_payment(payment)
+        .await
+        .map_err(|e| handle_db_error(e, "create_payment"))
+}
+
+#[tauri::command]
+pub async fn update_payment(
+    payment_id: String,
+    updates: UpdatePaymentRequest,
+    db: State<'_, Database>,
+) -> Result<Payment, String> {
+    db.update_payment(&payment_id, updates)
+        .await
+        .map_err(|e| handle_db_error(e, "update_payment"))
+}
+
+#[tauri::command]
+pub async fn delete_payment(payment_id: String, db: State<'_, Database>) -> Result<bool, String> {
+    db.delete_payment(&payment_id)
+        .await
+        .map_err(|e| handle_db_error(e, "delete_payment"))
+}
+
+#[tauri::command]
+pub async fn get_recurring_invoices(
+    organization_id: String,
+    db: State<'_, Database>,
+) -> Result<Vec<RecurringInvoice>, String> {
+    db.get_recurring_invoices(&organization_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_recurring_invoices"))
+}
+
+#[tauri::command]
+pub async fn get_recurring_invoice(
+    recurring_invoice_id: String,
+    db: State<'_, Database>,
+) -> Result<Option<RecurringInvoice>, String> {
+    db.get_recurring_invoice(&recurring_invoice_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_recurring_invoice"))
+}
+
+#[tauri::command]
+pub async fn get_recurring_invoice_line_items(
+    recurring_invoice_id: String,
+    db: State<'_, Database>,
+) -> Result<Vec<RecurringInvoiceLineItem>, String> {
+    db.get_recurring_invoice_line_items(&recurring_invoice_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_recurring_invoice_line_items"))
+}
+
+#[tauri::command]
+pub async fn create_recurring_invoice(
+    recurring_invoice: CreateRecurringInvoiceRequest,
+    db: State<'_, Database>,
+) -> Result<RecurringInvoice, String> {
+    db.create_recurring_invoice(recurring_invoice)
+        .await
+        .map_err(|e| handle_db_error(e, "create_recurring_invoice"))
+}
+
+#[tauri::command]
+pub async fn update_recurring_invoice(
+    recurring_invoice_id: String,
+    updates: UpdateRecurringInvoiceRequest,
+    db: State<'_, Database>,
+) -> Result<RecurringInvoice, String> {
+    db.update_recurring_invoice(&recurring_invoice_id, updates)
+        .await
+        .map_err(|e| handle_db_error(e, "update_recurring_invoice"))
+}
+
+#[tauri::command]
+pub async fn delete_recurring_invoice(recurring_invoice_id: String, db: State<'_, Database>) -> Result<bool, String> {
+    db.delete_recurring_invoice(&recurring_invoice_id)
+        .await
+        .map_err(|e| handle_db_error(e, "delete_recurring_invoice"))
+}
+
+#[tauri::command]
+pub async fn run_recurring_invoices(db: State<'_, Database>) -> Result<Vec<Invoice>, String> {
+    db.run_recurring_invoices()
+        .await
+        .map_err(|e| handle_db_error(e, "run_recurring_invoices"))
+}
+
+#[tauri::command]
+pub async fn get_estimates(organization_id: String, db: State<'_, Database>) -> Result<Vec<Estimate>, String> {
+    db.get_estimates(&organization_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_estimates"))
+}
+
+#[tauri::command]
+pub async fn get_estimate(estimate_id: String, db: State<'_, Database>) -> Result<Option<Estimate>, String> {
+    db.get_estimate(&estimate_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_estimate"))
+}
+
+#[tauri::command]
+pub async fn get_estimate_line_items(
+    estimate_id: String,
+    db: State<'_, Database>,
+) -> Result<Vec<EstimateLineItem>, String> {
+    db.get_estimate_line_items(&estimate_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_estimate_line_items"))
+}
+
+#[tauri::command]
+pub async fn create_estimate(
+    estimate: CreateEstimateRequest,
+    db: State<'_, Database>,
+) -> Result<Estimate, String> {
+    db.create_estimate(estimate)
+        .await
+        .map_err(|e| handle_db_error(e, "create_estimate"))
+}
+
+#[tauri::command]
+pub async fn update_estimate(
+    estimate_id: String,
+    updates: UpdateEstimateRequest,
+    db: State<'_, Database>,
+) -> Result<Estimate, String> {
+    db.update_estimate(&estimate_id, updates)
+        .await
+        .map_err(|e| handle_db_error(e, "update_estimate"))
+}
+
+#[tauri::command]
+pub async fn update_estimate_state(
+    estimate_id: String,
+    state: EstimateState,
+    db: State<'_, Database>,
+) -> Result<Estimate, String> {
+    db.update_estimate_state(&estimate_id, state)
+        .await
+        .map_err(|e| handle_db_error(e, "update_estimate_state"))
+}
+
+#[tauri::command]
+pub async fn delete_estimate(estimate_id: String, db: State<'_, Database>) -> Result<bool, String> {
+    db.delete_estimate(&estimate_id)
+        .await
+        .map_err(|e| handle_db_error(e, "delete_estimate"))
+}
+
+#[tauri::command]
+pub async fn convert_estimate_to_invoice(estimate_id: String, db: State<'_, Database>) -> Result<Invoice, String> {
+    db.convert_estimate_to_invoice(&estimate_id)
+        .await
+        .map_err(|e| handle_db_error(e, "convert_estimate_to_invoice"))
+}
+
+#[tauri::command]
+pub async fn render_invoice_pdf(
+    invoice_id: String,
+    template: Option<InvoiceTemplate>,
+    locale: String,
+    db: State<'_, Database>,
+) -> Result<Vec<u8>, String> {
+    db.render_invoice_pdf(&invoice_id, template.unwrap_or_default(), &locale)
+        .await
+        .map_err(|e| handle_db_error(e, "render_invoice_pdf"))
+}
+
+#[tauri::command]
+pub async fn export_invoice_pdf(
+    invoice_id: String,
+    template: Option<InvoiceTemplate>,
+    locale: String,
+    path: String,
+    db: State<'_, Database>,
+) -> Result<String, String> {
+    let pdf = db.render_invoice_pdf(&invoice_id, template.unwrap_or_default(), &locale)
+        .await
+        .map_err(|e| handle_db_error(e, "export_invoice_pdf"))?;
+
+    fs::write(&path, pdf)
+        .map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn validate_einvoice(
+    invoice_id: String,
+    format: Option<EInvoiceFormat>,
+    db: State<'_, Database>,
+) -> Result<Vec<EInvoiceError>, String> {
+    db.validate_einvoice(&invoice_id, format.unwrap_or_default())
+        .await
+        .map_err(|e| handle_db_error(e, "validate_einvoice"))
+}
+
+#[tauri::command]
+pub async fn export_ubl_invoice(invoice_id: String, db: State<'_, Database>) -> Result<String, String> {
+    db.export_ubl_invoice(&invoice_id)
+        .await
+        .map_err(|e| handle_db_error(e, "export_ubl_invoice"))
+}
+
+#[tauri::command]
+pub async fn export_factur_x(
+    invoice_id: String,
+    profile: FacturXProfile,
+    template: Option<InvoiceTemplate>,
+    locale: String,
+    path: String,
+    db: State<'_, Database>,
+) -> Result<String, String> {
+    let pdf = db.export_factur_x(&invoice_id, profile, template.unwrap_or_default(), &locale)
+        .await
+        .map_err(|e| handle_db_error(e, "export_factur_x"))?;
+
+    fs::write(&path, pdf)
+        .map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn get_epc_qr_code(
+    invoice_id: String,
+    format: Option<QrImageFormat>,
+    db: State<'_, Database>,
+) -> Result<QrImage, String> {
+    db.get_epc_qr_code(&invoice_id, format.unwrap_or_default())
+        .await
+        .map_err(|e| handle_db_error(e, "get_epc_qr_code"))
+}
+
+#[tauri::command]
+pub async fn get_swiss_qr_bill(invoice_id: String, locale: String, db: State<'_, Database>) -> Result<QrImage, String> {
+    db.get_swiss_qr_bill_svg(&invoice_id, &locale)
+        .await
+        .map_err(|e| handle_db_error(e, "get_swiss_qr_bill"))
+}
+
+#[tauri::command]
+pub async fn get_bills(organization_id: String, db: State<'_, Database>) -> Result<Vec<Bill>, String> {
+    db.get_bills(&organization_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_bills"))
+}
+
+#[tauri::command]
+pub async fn get_bill(bill_id: String, db: State<'_, Database>) -> Result<Option<Bill>, String> {
+    db.get_bill(&bill_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_bill"))
+}
+
+#[tauri::command]
+pub async fn get_bill_line_items(bill_id: String, db: State<'_, Database>) -> Result<Vec<BillLineItem>, String> {
+    db.get_bill_line_items(&bill_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_bill_line_items"))
+}
+
+#[tauri::command]
+pub async fn get_bill_taxes(bill_id: String, db: State<'_, Database>) -> Result<Vec<BillTax>, String> {
+    db.get_bill_taxes(&bill_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_bill_taxes"))
+}
+
+#[tauri::command]
+pub async fn get_bill_xml(bill_id: String, db: State<'_, Database>) -> Result<String, String> {
+    db.get_bill_xml(&bill_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_bill_xml"))
+}
+
+#[tauri::command]
+pub async fn import_bill(organization_id: String, path: String, db: State<'_, Database>) -> Result<Bill, String> {
+    let xml = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read e-invoice: {}", e))?;
+
+    db.import_bill(&organization_id, &xml)
+        .await
+        .map_err(|e| handle_db_error(e, "import_bill"))
+}
+
+#[tauri::command]
+pub async fn delete_bill(bill_id: String, db: State<'_, Database>) -> Result<bool, String> {
+    db.delete_bill(&bill_id)
+        .await
+        .map_err(|e| handle_db_error(e, "delete_bill"))
+}
+
 #[tauri::command]
 pub async fn get_organizations(db: State<'_, Database>) -> Result<Vec<Organization>, String> {
     db.get_organizations()
@@ -152,10 +614,12 @@ pub async fn get_organization(organization_id: String, db: State<'_, Database>)
 pub async fn create_organization(
     organization: CreateOrganizationRequest,
     db: State<'_, Database>,
-) -> Result<Organization, String> {
+) -> Result<Organization, FormError> {
+    organization.validate()?;
+
     db.create_organization(organization)
         .await
-        .map_err(|e| handle_db_error(e, "create_organization"))
+        .map_err(|e| FormError::Message(handle_db_error(e, "create_organization")))
 }
 
 #[tauri::command]
@@ -163,10 +627,12 @@ pub async fn update_organization(
     organization_id: String,
     updates: UpdateOrganizationRequest,
     db: State<'_, Database>,
-) -> Result<Organization, String> {
+) -> Result<Organization, FormError> {
+    updates.validate()?;
+
     db.update_organization(&organization_id, updates)
         .await
-        .map_err(|e| handle_db_error(e, "update_organization"))
+        .map_err(|e| FormError::Message(handle_db_error(e, "update_organization")))
 }
 
 #[tauri::command]
@@ -221,6 +687,45 @@ pub async fn delete_tax_rate(tax_rate_id: String, db: State<'_, Database>) -> Re
         .map_err(|e| handle_db_error(e, "delete_tax_rate"))
 }
 
+#[tauri::command]
+pub async fn get_items(organization_id: String, db: State<'_, Database>) -> Result<Vec<Item>, String> {
+    db.get_items(&organization_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_items"))
+}
+
+#[tauri::command]
+pub async fn get_item(item_id: String, db: State<'_, Database>) -> Result<Option<Item>, String> {
+    db.get_item(&item_id)
+        .await
+        .map_err(|e| handle_db_error(e, "get_item"))
+}
+
+#[tauri::command]
+pub async fn create_item(item: CreateItemRequest, db: State<'_, Database>) -> Result<Item, String> {
+    db.create_item(item)
+        .await
+        .map_err(|e| handle_db_error(e, "create_item"))
+}
+
+#[tauri::command]
+pub async fn update_item(
+    item_id: String,
+    updates: UpdateItemRequest,
+    db: State<'_, Database>,
+) -> Result<Item, String> {
+    db.update_item(&item_id, updates)
+        .await
+        .map_err(|e| handle_db_error(e, "update_item"))
+}
+
+#[tauri::command]
+pub async fn delete_item(item_id: String, db: State<'_, Database>) -> Result<bool, String> {
+    db.delete_item(&item_id)
+        .await
+        .map_err(|e| handle_db_error(e, "delete_item"))
+}
+
 #[tauri::command]
 pub async fn backup_database(app: AppHandle) -> Result<String, String> {
     use tauri_plugin_dialog::DialogExt;
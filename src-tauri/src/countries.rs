@@ -0,0 +1,280 @@
+// Country names as stored by the settings and client forms
+// (src/utils/countries.tsx) with their ISO 3166-1 alpha-2 codes
+const COUNTRIES: &[(&str, &str)] = &[
+    ("Afghanistan", "AF"),
+    ("Albania", "AL"),
+    ("Algeria", "DZ"),
+    ("American Samoa", "AS"),
+    ("Andorra", "AD"),
+    ("Angola", "AO"),
+    ("Anguilla", "AI"),
+    ("Antarctica", "AQ"),
+    ("Antigua and Barbuda", "AG"),
+    ("Argentina", "AR"),
+    ("Armenia", "AM"),
+    ("Aruba", "AW"),
+    ("Australia", "AU"),
+    ("Austria", "AT"),
+    ("Azerbaijan", "AZ"),
+    ("Bahamas", "BS"),
+    ("Bahrain", "BH"),
+    ("Bangladesh", "BD"),
+    ("Barbados", "BB"),
+    ("Belarus", "BY"),
+    ("Belgium", "BE"),
+    ("Belize", "BZ"),
+    ("Benin", "BJ"),
+    ("Bermuda", "BM"),
+    ("Bhutan", "BT"),
+    ("Bolivia", "BO"),
+    ("Bosnia and Herzegovina", "BA"),
+    ("Botswana", "BW"),
+    ("Bouvet Island", "BV"),
+    ("Brazil", "BR"),
+    ("British Indian Ocean Territory", "IO"),
+    ("Brunei", "BN"),
+    ("Bulgaria", "BG"),
+    ("Burkina Faso", "BF"),
+    ("Burundi", "BI"),
+    ("Cambodia", "KH"),
+    ("Cameroon", "CM"),
+    ("Canada", "CA"),
+    ("Cape Verde", "CV"),
+    ("Cayman Islands", "KY"),
+    ("Central African Republic", "CF"),
+    ("Chad", "TD"),
+    ("Chile", "CL"),
+    ("China", "CN"),
+    ("Christmas Island", "CX"),
+    ("Cocos (Keeling) Islands", "CC"),
+    ("Colombia", "CO"),
+    ("Comoros", "KM"),
+    ("Congo", "CG"),
+    ("Cook Islands", "CK"),
+    ("Costa Rica", "CR"),
+    ("Croatia", "HR"),
+    ("Cuba", "CU"),
+    ("Cyprus", "CY"),
+    ("Czech Republic", "CZ"),
+    ("Denmark", "DK"),
+    ("Djibouti", "DJ"),
+    ("Dominica", "DM"),
+    ("Dominican Republic", "DO"),
+    ("East Timor", "TL"),
+    ("Ecuador", "EC"),
+    ("Egypt", "EG"),
+    ("El Salvador", "SV"),
+    ("England", "GB"),
+    ("Equatorial Guinea", "GQ"),
+    ("Eritrea", "ER"),
+    ("Estonia", "EE"),
+    ("Ethiopia", "ET"),
+    ("Falkland Islands", "FK"),
+    ("Faroe Islands", "FO"),
+    ("Fiji Islands", "FJ"),
+    ("Finland", "FI"),
+    ("France", "FR"),
+    ("French Guiana", "GF"),
+    ("French Polynesia", "PF"),
+    ("French Southern territories", "TF"),
+    ("Gabon", "GA"),
+    ("Gambia", "GM"),
+    ("Georgia", "GE"),
+    ("Germany", "DE"),
+    ("Ghana", "GH"),
+    ("Gibraltar", "GI"),
+    ("Greece", "GR"),
+    ("Greenland", "GL"),
+    ("Grenada", "GD"),
+    ("Guadeloupe", "GP"),
+    ("Guam", "GU"),
+    ("Guatemala", "GT"),
+    ("Guinea", "GN"),
+    ("Guinea-Bissau", "GW"),
+    ("Guyana", "GY"),
+    ("Haiti", "HT"),
+    ("Heard Island and McDonald Islands", "HM"),
+    ("Holy See (Vatican City State)", "VA"),
+    ("Honduras", "HN"),
+    ("Hong Kong", "HK"),
+    ("Hungary", "HU"),
+    ("Iceland", "IS"),
+    ("India", "IN"),
+    ("Indonesia", "ID"),
+    ("Iran", "IR"),
+    ("Iraq", "IQ"),
+    ("Ireland", "IE"),
+    ("Israel", "IL"),
+    ("Italy", "IT"),
+    ("Ivory Coast", "CI"),
+    ("Jamaica", "JM"),
+    ("Japan", "JP"),
+    ("Jordan", "JO"),
+    ("Kazakhstan", "KZ"),
+    ("Kenya", "KE"),
+    ("Kiribati", "KI"),
+    ("Kuwait", "KW"),
+    ("Kyrgyzstan", "KG"),
+    ("Laos", "LA"),
+    ("Latvia", "LV"),
+    ("Lebanon", "LB"),
+    ("Lesotho", "LS"),
+    ("Liberia", "LR"),
+    ("Libyan Arab Jamahiriya", "LY"),
+    ("Liechtenstein", "LI"),
+    ("Lithuania", "LT"),
+    ("Luxembourg", "LU"),
+    ("Macau", "MO"),
+    ("North Macedonia", "MK"),
+    ("Madagascar", "MG"),
+    ("Malawi", "MW"),
+    ("Malaysia", "MY"),
+    ("Maldives", "MV"),
+    ("Mali", "ML"),
+    ("Malta", "MT"),
+    ("Marshall Islands", "MH"),
+    ("Martinique", "MQ"),
+    ("Mauritania", "MR"),
+    ("Mauritius", "MU"),
+    ("Mayotte", "YT"),
+    ("Mexico", "MX"),
+    ("Micronesia, Federated States of", "FM"),
+    ("Moldova", "MD"),
+    ("Monaco", "MC"),
+    ("Mongolia", "MN"),
+    ("Montserrat", "MS"),
+    ("Morocco", "MA"),
+    ("Mozambique", "MZ"),
+    ("Myanmar", "MM"),
+    ("Namibia", "NA"),
+    ("Nauru", "NR"),
+    ("Nepal", "NP"),
+    ("Netherlands", "NL"),
+    ("Netherlands Antilles", "AN"),
+    ("New Caledonia", "NC"),
+    ("New Zealand", "NZ"),
+    ("Nicaragua", "NI"),
+    ("Niger", "NE"),
+    ("Nigeria", "NG"),
+    ("Niue", "NU"),
+    ("Norfolk Island", "NF"),
+    ("North Korea", "KP"),
+    ("Northern Ireland", "GB"),
+    ("Northern Mariana Islands", "MP"),
+    ("Norway", "NO"),
+    ("Oman", "OM"),
+    ("Pakistan", "PK"),
+    ("Palau", "PW"),
+    ("Palestine", "PS"),
+    ("Panama", "PA"),
+    ("Papua New Guinea", "PG"),
+    ("Paraguay", "PY"),
+    ("Peru", "PE"),
+    ("Philippines", "PH"),
+    ("Pitcairn Islands", "PN"),
+    ("Poland", "PL"),
+    ("Portugal", "PT"),
+    ("Puerto Rico", "PR"),
+    ("Qatar", "QA"),
+    ("Reunion", "RE"),
+    ("Romania", "RO"),
+    ("Russian Federation", "RU"),
+    ("Rwanda", "RW"),
+    ("Saint Helena", "SH"),
+    ("Saint Kitts and Nevis", "KN"),
+    ("Saint Lucia", "LC"),
+    ("Saint Pierre and Miquelon", "PM"),
+    ("Saint Vincent and the Grenadines", "VC"),
+    ("Samoa", "WS"),
+    ("San Marino", "SM"),
+    ("Sao Tome and Principe", "ST"),
+    ("Saudi Arabia", "SA"),
+    ("Scotland", "GB"),
+    ("Senegal", "SN"),
+    ("Serbia", "RS"),
+    ("Seychelles", "SC"),
+    ("Sierra Leone", "SL"),
+    ("Singapore", "SG"),
+    ("Slovakia", "SK"),
+    ("Slovenia", "SI"),
+    ("Solomon Islands", "SB"),
+    ("Somalia", "SO"),
+    ("South Africa", "ZA"),
+    ("South Georgia and the South Sandwich Islands", "GS"),
+    ("South Korea", "KR"),
+    ("South Sudan", "SS"),
+    ("Spain", "ES"),
+    ("Sri Lanka", "LK"),
+    ("Sudan", "SD"),
+    ("Suriname", "SR"),
+    ("Svalbard and Jan Mayen", "SJ"),
+    ("Swaziland", "SZ"),
+    ("Sweden", "SE"),
+    ("Switzerland", "CH"),
+    ("Syria", "SY"),
+    ("Tajikistan", "TJ"),
+    ("Tanzania", "TZ"),
+    ("Thailand", "TH"),
+    ("The Democratic Republic of Congo", "CD"),
+    ("Togo", "TG"),
+    ("Tokelau", "TK"),
+    ("Tonga", "TO"),
+    ("Trinidad and Tobago", "TT"),
+    ("Tunisia", "TN"),
+    ("Turkey", "TR"),
+    ("Turkmenistan", "TM"),
+    ("Turks and Caicos Islands", "TC"),
+    ("Tuvalu", "TV"),
+    ("Uganda", "UG"),
+    ("Ukraine", "UA"),
+    ("United Arab Emirates", "AE"),
+    ("United Kingdom", "GB"),
+    ("United States", "US"),
+    ("United States Minor Outlying Islands", "UM"),
+    ("Uruguay", "UY"),
+    ("Uzbekistan", "UZ"),
+    ("Vanuatu", "VU"),
+    ("Venezuela", "VE"),
+    ("Vietnam", "VN"),
+    ("Virgin Islands, British", "VG"),
+    ("Virgin Islands, U.S.", "VI"),
+    ("Wales", "GB"),
+    ("Wallis and Futuna", "WF"),
+    ("Western Sahara", "EH"),
+    ("Yemen", "YE"),
+    ("Zambia", "ZM"),
+    ("Zimbabwe", "ZW"),
+];
+
+// ISO 3166-1 alpha-2 code for a stored country, which may be a name from the
+// country list or already a code
+pub fn country_code(country: &str) -> Option<&'static str> {
+    let country = country.trim();
+    COUNTRIES
+        .iter()
+        .find(|(name, code)| name.eq_ignore_ascii_case(country) || code.eq_ignore_ascii_case(country))
+        .map(|(_, code)| *code)
+}
+
+// Country list name for an ISO 3166-1 alpha-2 code. GB is listed under its
+// constituent countries as well, the United Kingdom is the one to pick.
+pub fn country_name(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+    if code.eq_ignore_ascii_case("GB") {
+        return Some("United Kingdom");
+    }
+    COUNTRIES
+        .iter()
+        .find(|(_, country_code)| country_code.eq_ignore_ascii_case(code))
+        .map(|(name, _)| *name)
+}
+
+// EU member states, between which reverse charge applies
+const EU_MEMBER_STATES: &[&str] = &[
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR", "HR", "HU",
+    "IE", "IT", "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+pub fn is_eu_member_state(code: &str) -> bool {
+    EU_MEMBER_STATES.iter().any(|member| member.eq_ignore_ascii_case(code.trim()))
+}
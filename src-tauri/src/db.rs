@@ -1,18 +1,37 @@
 // Modern Rust 2018+ module structure
 // Splits the large database.rs file into manageable modules
 
+pub mod bill;
 pub mod client;
 pub mod project;
 pub mod organization;
 pub mod invoice;
+pub mod item;
+pub mod estimate;
+pub mod exchange_rate;
+pub mod invoice_number;
+pub mod invoice_state;
+pub mod payment;
+pub mod recurring_invoice;
+pub mod report;
 pub mod tax_rate;
 pub mod time_tracking;
+pub mod vat_check;
 
 // Re-export all public types for easy access
+pub use bill::*;
 pub use client::*;
 pub use project::*;
 pub use organization::*;
 pub use invoice::*;
+pub use item::*;
+pub use estimate::*;
+pub use exchange_rate::*;
+pub use invoice_number::*;
+pub use invoice_state::*;
+pub use payment::*;
+pub use recurring_invoice::*;
+pub use report::*;
 pub use tax_rate::*;
 pub use time_tracking::*;
 
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::{insert_client, CreateClientRequest, Database, InvoiceKind};
+
+// Purchase invoice received from a supplier. Supplier credit notes are
+// stored with negative amounts, the same as issued credit notes.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Bill {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    #[sqlx(rename = "organizationId")]
+    pub organization_id: String,
+    #[serde(rename = "supplierId")]
+    #[sqlx(rename = "supplierId")]
+    pub supplier_id: String,
+    pub kind: InvoiceKind,
+    pub number: String,
+    pub date: i64,
+    #[serde(rename = "dueDate")]
+    #[sqlx(rename = "dueDate")]
+    pub due_date: Option<i64>,
+    pub currency: String,
+    pub notes: Option<String>,
+    #[serde(rename = "paymentReference")]
+    #[sqlx(rename = "paymentReference")]
+    pub payment_reference: Option<String>,
+    pub iban: Option<String>,
+    pub total: i64,  // Minor units
+    #[serde(rename = "taxTotal")]
+    #[sqlx(rename = "taxTotal")]
+    pub tax_total: i64,  // Minor units
+    #[serde(rename = "subTotal")]
+    #[sqlx(rename = "subTotal")]
+    pub sub_total: i64,  // Minor units
+    #[serde(rename = "amountDue")]
+    #[sqlx(rename = "amountDue")]
+    pub amount_due: i64,  // Minor units
+    // Syntax of the original document, "ubl" or "cii"
+    pub format: String,
+    #[serde(rename = "createdAt")]
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<String>,
+    // Only for joined queries
+    #[serde(rename = "supplierName")]
+    #[sqlx(rename = "supplierName")]
+    pub supplier_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BillLineItem {
+    pub id: String,
+    #[serde(rename = "billId")]
+    #[sqlx(rename = "billId")]
+    pub bill_id: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    #[serde(rename = "unitCode")]
+    #[sqlx(rename = "unitCode")]
+    pub unit_code: Option<String>,
+    #[serde(rename = "unitPrice")]
+    #[sqlx(rename = "unitPrice")]
+    pub unit_price: i64,  // Minor units
+    pub total: i64,  // Minor units
+    #[serde(rename = "taxCategory")]
+    #[sqlx(rename = "taxCategory")]
+    pub tax_category: String,
+    #[serde(rename = "taxPercentage")]
+    #[sqlx(rename = "taxPercentage")]
+    pub tax_percentage: f64,
+    #[serde(rename = "createdAt")]
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BillTax {
+    pub id: String,
+    #[serde(rename = "billId")]
+    #[sqlx(rename = "billId")]
+    pub bill_id: String,
+    pub category: String,
+    pub percentage: f64,
+    #[serde(rename = "taxableAmount")]
+    #[sqlx(rename = "taxableAmount")]
+    pub taxable_amount: i64,  // Minor units
+    pub amount: i64,  // Minor units
+}
+
+#[derive(Debug)]
+pub struct CreateBillLineItemRequest {
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit_code: Option<String>,
+    pub unit_price: i64,
+    pub total: i64,
+    pub tax_category: String,
+    pub tax_percentage: f64,
+}
+
+#[derive(Debug)]
+pub struct CreateBillTaxRequest {
+    pub category: String,
+    pub percentage: f64,
+    pub taxable_amount: i64,
+    pub amount: i64,
+}
+
+// Bills are only created by the e-invoice import
+#[derive(Debug)]
+pub struct CreateBillRequest {
+    pub id: String,
+    pub organization_id: String,
+    pub supplier_id: String,
+    pub kind: InvoiceKind,
+    pub number: String,
+    pub date: i64,
+    pub due_date: Option<i64>,
+    pub currency: String,
+    pub notes: Option<String>,
+    pub payment_reference: Option<String>,
+    pub iban: Option<String>,
+    pub total: i64,
+    pub tax_total: i64,
+    pub sub_total: i64,
+    pub amount_due: i64,
+    pub format: String,
+    pub xml: String,
+    pub line_items: Vec<CreateBillLineItemRequest>,
+    pub taxes: Vec<CreateBillTaxRequest>,
+}
+
+impl Database {
+    pub async fn get_bills(&self, organization_id: &str) -> Result<Vec<Bill>, sqlx::Error> {
+        sqlx::query_as::<_, Bill>(
+            r#"
+            SELECT
+                bills.id, bills.organizationId, bills.supplierId, bills.kind, bills.number, bills.date,
+                bills.dueDate, bills.currency, bills.notes, bills.paymentReference, bills.iban, bills.total,
+                bills.taxTotal, bills.subTotal, bills.amountDue, bills.format, bills.createdAt,
+                clients.name AS supplierName
+            FROM
+                bills
+            INNER JOIN
+                clients ON bills.supplierId = clients.id
+            WHERE
+                bills.organizationId = ?
+            ORDER BY
+                bills.date DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_bill(&self, bill_id: &str) -> Result<Option<Bill>, sqlx::Error> {
+        sqlx::query_as::<_, Bill>(
+            r#"
+            SELECT
+                bills.id, bills.organizationId, bills.supplierId, bills.kind, bills.number, bills.date,
+                bills.dueDate, bills.currency, bills.notes, bills.paymentReference, bills.iban, bills.total,
+                bills.taxTotal, bills.subTotal, bills.amountDue, bills.format, bills.createdAt,
+                clients.name AS supplierName
+            FROM
+                bills
+            INNER JOIN
+                clients ON bills.supplierId = clients.id
+            WHERE
+                bills.id = ?
+            LIMIT 1
+            "#,
+        )
+        .bind(bill_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn get_bill_line_items(&self, bill_id: &str) -> Result<Vec<BillLineItem>, sqlx::Error> {
+        sqlx::query_as::<_, BillLineItem>("SELECT * FROM billLineItems WHERE billId = ? ORDER BY rowid")
+            .bind(bill_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_bill_taxes(&self, bill_id: &str) -> Result<Vec<BillTax>, sqlx::Error> {
+        sqlx::query_as::<_, BillTax>("SELECT * FROM billTaxes WHERE billId = ? ORDER BY rowid")
+            .bind(bill_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    // The document as it was received
+    pub async fn get_bill_xml(&self, bill_id: &str) -> Result<String, sqlx::Error> {
+        sqlx::query_scalar::<_, String>("SELECT xml FROM bills WHERE id = ?")
+            .bind(bill_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    // A supplier not yet among the contacts is added along with the bill, so
+    // nothing is left behind when the bill is refused
+    pub async fn create_bill(
+        &self,
+        bill: CreateBillRequest,
+        new_supplier: Option<CreateClientRequest>,
+    ) -> Result<Bill, sqlx::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        if let Some(supplier) = &new_supplier {
+            insert_client(&mut tx, supplier).await?;
+        }
+
+        let exists = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM bills WHERE organizationId = ? AND supplierId = ? AND kind = ? AND number = ?",
+        )
+        .bind(&bill.organization_id)
+        .bind(&bill.supplier_id)
+        .bind(bill.kind)
+        .bind(&bill.number)
+        .fetch_one(&mut *tx)
+        .await?;
+        if exists > 0 {
+            return Err(sqlx::Error::Protocol(format!("Bill {} has already been imported", bill.number)));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO bills (
+                id, organizationId, supplierId, kind, number, date, dueDate, currency, notes, paymentReference,
+                iban, total, taxTotal, subTotal, amountDue, format, xml
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&bill.id)
+        .bind(&bill.organization_id)
+        .bind(&bill.supplier_id)
+        .bind(bill.kind)
+        .bind(&bill.number)
+        .bind(bill.date)
+        .bind(bill.due_date)
+        .bind(&bill.currency)
+        .bind(&bill.notes)
+        .bind(&bill.payment_reference)
+        .bind(&bill.iban)
+        .bind(bill.total)
+        .bind(bill.tax_total)
+        .bind(bill.sub_total)
+        .bind(bill.amount_due)
+        .bind(&bill.format)
+        .bind(&bill.xml)
+        .execute(&mut *tx)
+        .await?;
+
+        for line_item in &bill.line_items {
+            sqlx::query(
+                r#"
+                INSERT INTO billLineItems (id, billId, description, quantity, unitCode, unitPrice, total, taxCategory, taxPercentage)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(nanoid::nanoid!())
+            .bind(&bill.id)
+            .bind(&line_item.description)
+            .bind(line_item.quantity)
+            .bind(&line_item.unit_code)
+            .bind(line_item.unit_price)
+            .bind(line_item.total)
+            .bind(&line_item.tax_category)
+            .bind(line_item.tax_percentage)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for tax in &bill.taxes {
+            sqlx::query(
+                r#"
+                INSERT INTO billTaxes (id, billId, category, percentage, taxableAmount, amount)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(nanoid::nanoid!())
+            .bind(&bill.id)
+            .bind(&tax.category)
+            .bind(tax.percentage)
+            .bind(tax.taxable_amount)
+            .bind(tax.amount)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_bill(&bill.id).await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    pub async fn delete_bill(&self, bill_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM bills WHERE id = ?")
+            .bind(bill_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
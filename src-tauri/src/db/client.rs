@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, SqliteConnection};
 
 use super::Database;
+use crate::validation::{validate_vatin, FieldError, FieldErrors};
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Client {
@@ -12,6 +13,7 @@ pub struct Client {
     pub name: Option<String>,
     pub code: Option<String>,
     pub address: Option<String>,
+    pub country: Option<String>,
     pub emails: Option<String>,
     pub phone: Option<String>,
     pub website: Option<String>,
@@ -20,6 +22,10 @@ pub struct Client {
     #[serde(rename = "createdAt")]
     #[sqlx(rename = "createdAt")]
     pub created_at: Option<String>,
+    // Latest VIES verification, cleared when the VAT number changes
+    pub vatin_valid: Option<bool>,
+    pub vatin_checked_at: Option<i64>,
+    pub vatin_consultation_number: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +36,7 @@ pub struct CreateClientRequest {
     pub name: Option<String>,
     pub code: Option<String>,
     pub address: Option<String>,
+    pub country: Option<String>,
     pub emails: Option<String>,
     pub phone: Option<String>,
     pub website: Option<String>,
@@ -42,6 +49,7 @@ pub struct UpdateClientRequest {
     pub name: Option<String>,
     pub code: Option<String>,
     pub address: Option<String>,
+    pub country: Option<String>,
     pub emails: Option<String>,
     pub phone: Option<String>,
     pub website: Option<String>,
@@ -49,6 +57,47 @@ pub struct UpdateClientRequest {
     pub vatin: Option<String>,
 }
 
+fn validate_client(country: Option<&str>, vatin: Option<&str>) -> Result<(), Vec<FieldError>> {
+    let mut errors = FieldErrors::default();
+    errors.check("vatin", vatin, |vatin| validate_vatin(vatin, country));
+    errors.into_result()
+}
+
+impl CreateClientRequest {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        validate_client(self.country.as_deref(), self.vatin.as_deref())
+    }
+}
+
+impl UpdateClientRequest {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        validate_client(self.country.as_deref(), self.vatin.as_deref())
+    }
+}
+
+pub(crate) async fn insert_client(conn: &mut SqliteConnection, client: &CreateClientRequest) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO clients (id, organizationId, name, code, address, country, emails, phone, website, registration_number, vatin)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&client.id)
+    .bind(&client.organization_id)
+    .bind(&client.name)
+    .bind(&client.code)
+    .bind(&client.address)
+    .bind(&client.country)
+    .bind(&client.emails)
+    .bind(&client.phone)
+    .bind(&client.website)
+    .bind(&client.registration_number)
+    .bind(&client.vatin)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
 impl Database {
     pub async fn get_clients(&self, organization_id: &str) -> Result<Vec<Client>, sqlx::Error> {
         sqlx::query_as::<_, Client>(
@@ -78,25 +127,37 @@ impl Database {
         .await
     }
 
-    pub async fn create_client(&self, client: CreateClientRequest) -> Result<Client, sqlx::Error> {
-        sqlx::query(
+    // VAT numbers are compared without spaces and with or without the
+    // country prefix, as they're entered both ways
+    pub async fn find_client_by_vatin(
+        &self,
+        organization_id: &str,
+        country_code: &str,
+        vatin: &str,
+    ) -> Result<Option<Client>, sqlx::Error> {
+        let vatin: String = vatin.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+        let prefix = if country_code == "GR" { "EL" } else { country_code };
+        let number = vatin.strip_prefix(prefix).unwrap_or(&vatin);
+
+        sqlx::query_as::<_, Client>(
             r#"
-            INSERT INTO clients (id, organizationId, name, code, address, emails, phone, website, registration_number, vatin)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            SELECT *
+            FROM clients
+            WHERE organizationId = ? AND UPPER(REPLACE(vatin, ' ', '')) IN (?, ?)
+            ORDER BY createdAt ASC
+            LIMIT 1
             "#,
         )
-        .bind(&client.id)
-        .bind(&client.organization_id)
-        .bind(&client.name)
-        .bind(&client.code)
-        .bind(&client.address)
-        .bind(&client.emails)
-        .bind(&client.phone)
-        .bind(&client.website)
-        .bind(&client.registration_number)
-        .bind(&client.vatin)
-        .execute(&self.pool)
-        .await?;
+        .bind(organization_id)
+        .bind(format!("{}{}", prefix, number))
+        .bind(number)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn create_client(&self, client: CreateClientRequest) -> Result<Client, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        insert_client(&mut conn, &client).await?;
 
         self.get_client(&client.id).await?
             .ok_or_else(|| sqlx::Error::RowNotFound)
@@ -110,18 +171,25 @@ impl Database {
         sqlx::query(
             r#"
             UPDATE clients
-            SET name = ?, code = ?, address = ?, emails = ?, phone = ?, website = ?, registration_number = ?, vatin = ?
+            SET name = ?, code = ?, address = ?, country = ?, emails = ?, phone = ?, website = ?, registration_number = ?, vatin = ?,
+                vatin_valid = CASE WHEN vatin IS ? THEN vatin_valid END,
+                vatin_checked_at = CASE WHEN vatin IS ? THEN vatin_checked_at END,
+                vatin_consultation_number = CASE WHEN vatin IS ? THEN vatin_consultation_number END
             WHERE id = ?
             "#,
         )
         .bind(&updates.name)
         .bind(&updates.code)
         .bind(&updates.address)
+        .bind(&updates.country)
         .bind(&updates.emails)
         .bind(&updates.phone)
         .bind(&updates.website)
         .bind(&updates.registration_number)
         .bind(&updates.vatin)
+        .bind(&updates.vatin)
+        .bind(&updates.vatin)
+        .bind(&updates.vatin)
         .bind(client_id)
         .execute(&self.pool)
         .await?;
@@ -0,0 +1,621 @@
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqliteConnection};
+
+use super::{
+    allocate_invoice_number, calculate_invoice_totals, insert_invoice, release_invoice_number, resolve_tax_mode,
+    delete_line_item_taxes, discount_columns, discount_from_row, fetch_line_item_taxes, insert_line_item_taxes,
+    line_item_description, normalize_line_items,
+    CreateInvoiceLineItemRequest, CreateInvoiceRequest, Database, DiscountKind, Invoice, InvoiceKind, InvoiceState, LineItemTax, NumberSeries, TaxMode,
+    TaxRate, TaxRounding,
+};
+use crate::money::Money;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum EstimateState {
+    Draft,
+    Sent,
+    Accepted,
+    Declined,
+    Expired,
+}
+
+impl EstimateState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Sent => "sent",
+            Self::Accepted => "accepted",
+            Self::Declined => "declined",
+            Self::Expired => "expired",
+        }
+    }
+
+    pub fn can_transition_to(&self, to: EstimateState) -> bool {
+        use EstimateState::*;
+
+        matches!(
+            (self, to),
+            (Draft, Sent | Accepted)
+                | (Sent, Accepted | Declined | Expired)
+                | (Expired, Sent)
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Estimate {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    pub organization_id: String,
+    pub number: String,
+    pub state: EstimateState,
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub date: i64,
+    #[serde(rename = "validUntil")]
+    pub valid_until: Option<i64>,
+    pub currency: String,
+    #[serde(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    // Taken from the organization when the estimate is created
+    #[serde(rename = "pricesIncludeTax")]
+    pub prices_include_tax: bool,
+    #[serde(rename = "taxRounding")]
+    pub tax_rounding: TaxRounding,
+    pub total: Money,
+    #[serde(rename = "taxTotal")]
+    pub tax_total: Money,
+    #[serde(rename = "subTotal")]
+    pub sub_total: Money,
+    // Set once the estimate has been converted
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    // Only for joined queries
+    #[serde(rename = "clientName")]
+    pub client_name: Option<String>,
+}
+
+// Amounts are stored as minor units of the estimate's currency
+impl<'r> FromRow<'r, SqliteRow> for Estimate {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        let money = |column: &str| row.try_get(column).map(|amount_minor| Money::new(amount_minor, &currency));
+        Ok(Self {
+            id: row.try_get("id")?,
+            organization_id: row.try_get("organizationId")?,
+            number: row.try_get("number")?,
+            state: row.try_get("state")?,
+            client_id: row.try_get("clientId")?,
+            date: row.try_get("date")?,
+            valid_until: row.try_get("validUntil")?,
+            customer_notes: row.try_get("customerNotes")?,
+            prices_include_tax: row.try_get("pricesIncludeTax")?,
+            tax_rounding: row.try_get("taxRounding")?,
+            total: money("total")?,
+            tax_total: money("taxTotal")?,
+            sub_total: money("subTotal")?,
+            invoice_id: row.try_get("invoiceId")?,
+            created_at: row.try_get("createdAt")?,
+            client_name: row.try_get("clientName")?,
+            currency,
+        })
+    }
+}
+
+impl Estimate {
+    pub fn tax_mode(&self) -> TaxMode {
+        TaxMode { prices_include_tax: self.prices_include_tax, rounding: self.tax_rounding }
+    }
+
+    // Valid through the whole of its last day
+    fn is_expired(&self, now: DateTime<Local>) -> bool {
+        self.valid_until.is_some_and(|valid_until| valid_until < start_of_day(now).timestamp_millis())
+    }
+}
+
+// Same shape as InvoiceLineItem
+#[derive(Debug, Serialize)]
+pub struct EstimateLineItem {
+    pub id: String,
+    #[serde(rename = "estimateId")]
+    pub estimate_id: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    #[serde(rename = "unitCode")]
+    pub unit_code: Option<String>,
+    #[serde(rename = "unitPrice")]
+    pub unit_price: Money,
+    // Percentage, or minor units off the line amount
+    pub discount: Option<f64>,
+    #[serde(rename = "discountKind")]
+    pub discount_kind: DiscountKind,
+    #[serde(rename = "taxRate")]
+    pub tax_rate: Option<String>,
+    // Catalog item the line was filled from. Description and price are copies.
+    #[serde(rename = "itemId")]
+    pub item_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    // Loaded from lineItemTaxes
+    pub taxes: Vec<LineItemTax>,
+}
+
+// Read with the estimate's currency joined in
+impl<'r> FromRow<'r, SqliteRow> for EstimateLineItem {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        let discount_kind = row.try_get("discountKind")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            estimate_id: row.try_get("estimateId")?,
+            description: row.try_get("description")?,
+            quantity: row.try_get("quantity")?,
+            unit_code: row.try_get("unitCode")?,
+            unit_price: Money::new(row.try_get("unitPrice")?, &currency),
+            discount: discount_from_row(row, discount_kind)?,
+            discount_kind,
+            tax_rate: row.try_get("taxRate")?,
+            item_id: row.try_get("itemId")?,
+            created_at: row.try_get("createdAt")?,
+            taxes: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateEstimateRequest {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    pub organization_id: String,
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub date: i64,
+    #[serde(rename = "validUntil")]
+    pub valid_until: Option<i64>,
+    pub currency: String,
+    #[serde(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    #[serde(rename = "lineItems")]
+    pub line_items: Vec<CreateInvoiceLineItemRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateEstimateRequest {
+    #[serde(rename = "clientId")]
+    pub client_id: Option<String>,
+    pub date: Option<i64>,
+    #[serde(rename = "validUntil")]
+    pub valid_until: Option<i64>,
+    pub currency: Option<String>,
+    #[serde(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    #[serde(rename = "lineItems")]
+    pub line_items: Option<Vec<CreateInvoiceLineItemRequest>>,
+}
+
+fn start_of_day(now: DateTime<Local>) -> DateTime<Local> {
+    Local
+        .from_local_datetime(&now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .unwrap_or(now)
+}
+
+async fn fetch_estimate(conn: &mut SqliteConnection, estimate_id: &str) -> Result<Estimate, sqlx::Error> {
+    sqlx::query_as::<_, Estimate>("SELECT estimates.*, NULL AS clientName FROM estimates WHERE id = ?")
+        .bind(estimate_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)
+}
+
+async fn fetch_tax_rates(conn: &mut SqliteConnection, organization_id: &str) -> Result<Vec<TaxRate>, sqlx::Error> {
+    sqlx::query_as::<_, TaxRate>("SELECT * FROM taxRates WHERE organizationId = ?")
+        .bind(organization_id)
+        .fetch_all(conn)
+        .await
+}
+
+async fn fetch_estimate_line_items(
+    conn: &mut SqliteConnection,
+    estimate_id: &str,
+) -> Result<Vec<EstimateLineItem>, sqlx::Error> {
+    let mut line_items = sqlx::query_as::<_, EstimateLineItem>(
+        r#"
+        SELECT estimateLineItems.*, estimates.currency
+        FROM estimateLineItems
+        INNER JOIN estimates ON estimates.id = estimateLineItems.estimateId
+        WHERE estimateLineItems.estimateId = ?
+        ORDER BY estimateLineItems.createdAt ASC, estimateLineItems.rowid ASC
+        "#,
+    )
+    .bind(estimate_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut taxes = fetch_line_item_taxes(conn, "estimateLineItems", "estimateId", estimate_id).await?;
+    for line_item in &mut line_items {
+        line_item.taxes = taxes.remove(&line_item.id).unwrap_or_default();
+    }
+
+    Ok(line_items)
+}
+
+async fn insert_estimate_line_items(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    estimate_id: &str,
+    line_items: &[CreateInvoiceLineItemRequest],
+) -> Result<(), sqlx::Error> {
+    for line_item in line_items {
+        let line_item_id = nanoid::nanoid!();
+        let taxes = line_item.line_taxes();
+        let description = line_item_description(&mut *conn, organization_id, line_item).await?;
+        let (discount, discount_amount) = discount_columns(line_item.discount());
+        sqlx::query(
+            r#"
+            INSERT INTO estimateLineItems (
+                id, estimateId, description, quantity, unitCode, unitPrice, discount, discountAmount, discountKind,
+                taxRate, itemId
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&line_item_id)
+        .bind(estimate_id)
+        .bind(&description)
+        .bind(&line_item.quantity)
+        .bind(&line_item.unit_code)
+        .bind(&line_item.unit_price)
+        .bind(discount)
+        .bind(discount_amount)
+        .bind(line_item.discount_kind)
+        .bind(taxes.first().map(|tax| &tax.tax_rate))
+        .bind(&line_item.item_id)
+        .execute(&mut *conn)
+        .await?;
+
+        insert_line_item_taxes(&mut *conn, &line_item_id, &taxes).await?;
+    }
+
+    Ok(())
+}
+
+fn converted_estimate_error(estimate: &Estimate) -> sqlx::Error {
+    sqlx::Error::Protocol(format!("Estimate {} has already been converted to an invoice", estimate.number))
+}
+
+impl Database {
+    pub async fn get_estimates(&self, organization_id: &str) -> Result<Vec<Estimate>, sqlx::Error> {
+        sqlx::query_as::<_, Estimate>(
+            r#"
+            SELECT
+                estimates.*,
+                clients.name AS clientName
+            FROM
+                estimates
+            INNER JOIN
+                clients ON estimates.clientId = clients.id
+            WHERE
+                estimates.organizationId = ?
+            ORDER BY
+                estimates.date DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_estimate(&self, estimate_id: &str) -> Result<Option<Estimate>, sqlx::Error> {
+        sqlx::query_as::<_, Estimate>(
+            r#"
+            SELECT
+                estimates.*,
+                clients.name AS clientName
+            FROM
+                estimates
+            INNER JOIN
+                clients ON estimates.clientId = clients.id
+            WHERE
+                estimates.id = ?
+            LIMIT 1
+            "#,
+        )
+        .bind(estimate_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn get_estimate_line_items(&self, estimate_id: &str) -> Result<Vec<EstimateLineItem>, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_estimate_line_items(&mut conn, estimate_id).await
+    }
+
+    pub async fn create_estimate(&self, mut estimate: CreateEstimateRequest) -> Result<Estimate, sqlx::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        normalize_line_items(&mut tx, &estimate.organization_id, &mut estimate.line_items).await?;
+
+        let tax_rates = fetch_tax_rates(&mut tx, &estimate.organization_id).await?;
+        // Estimates are totalled with the organization's defaults and keep them for the invoice
+        let mode = resolve_tax_mode(&mut tx, &estimate.organization_id, None, None).await?;
+        let totals = calculate_invoice_totals(&estimate.line_items, &tax_rates, mode, None, &estimate.currency)?;
+
+        let number = allocate_invoice_number(
+            &mut tx,
+            NumberSeries::Estimate,
+            &estimate.organization_id,
+            &estimate.client_id,
+            estimate.date,
+        )
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO estimates (
+                id, organizationId, number, state, clientId, date, validUntil, currency, customerNotes,
+                pricesIncludeTax, taxRounding, total, taxTotal, subTotal, numberSequence, numberPeriod
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&estimate.id)
+        .bind(&estimate.organization_id)
+        .bind(&number.number)
+        .bind(EstimateState::Draft)
+        .bind(&estimate.client_id)
+        .bind(&estimate.date)
+        .bind(&estimate.valid_until)
+        .bind(&estimate.currency)
+        .bind(&estimate.customer_notes)
+        .bind(mode.prices_include_tax)
+        .bind(mode.rounding)
+        .bind(totals.total.amount_minor)
+        .bind(totals.tax_total.amount_minor)
+        .bind(totals.sub_total.amount_minor)
+        .bind(number.sequence)
+        .bind(&number.period)
+        .execute(&mut *tx)
+        .await?;
+
+        insert_estimate_line_items(&mut tx, &estimate.organization_id, &estimate.id, &estimate.line_items).await?;
+
+        tx.commit().await?;
+
+        self.get_estimate(&estimate.id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn update_estimate(
+        &self,
+        estimate_id: &str,
+        mut updates: UpdateEstimateRequest,
+    ) -> Result<Estimate, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = fetch_estimate(&mut tx, estimate_id).await?;
+        if current.invoice_id.is_some() {
+            return Err(converted_estimate_error(&current));
+        }
+        if let Some(line_items) = &mut updates.line_items {
+            normalize_line_items(&mut tx, &current.organization_id, line_items).await?;
+        }
+
+        let totals = match &updates.line_items {
+            Some(line_items) => {
+                let tax_rates = fetch_tax_rates(&mut tx, &current.organization_id).await?;
+                let currency = updates.currency.as_deref().unwrap_or(&current.currency);
+                Some(calculate_invoice_totals(line_items, &tax_rates, current.tax_mode(), None, currency)?)
+            }
+            None => None,
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE estimates
+            SET clientId = COALESCE(?, clientId),
+                date = COALESCE(?, date),
+                validUntil = COALESCE(?, validUntil),
+                currency = COALESCE(?, currency),
+                customerNotes = COALESCE(?, customerNotes),
+                total = COALESCE(?, total),
+                taxTotal = COALESCE(?, taxTotal),
+                subTotal = COALESCE(?, subTotal)
+            WHERE id = ?
+            "#,
+        )
+        .bind(&updates.client_id)
+        .bind(&updates.date)
+        .bind(&updates.valid_until)
+        .bind(&updates.currency)
+        .bind(&updates.customer_notes)
+        .bind(totals.as_ref().map(|totals| totals.total.amount_minor))
+        .bind(totals.as_ref().map(|totals| totals.tax_total.amount_minor))
+        .bind(totals.as_ref().map(|totals| totals.sub_total.amount_minor))
+        .bind(estimate_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(line_items) = updates.line_items {
+            delete_line_item_taxes(&mut tx, "estimateLineItems", "estimateId", estimate_id).await?;
+            sqlx::query("DELETE FROM estimateLineItems WHERE estimateId = ?")
+                .bind(estimate_id)
+                .execute(&mut *tx)
+                .await?;
+
+            insert_estimate_line_items(&mut tx, &current.organization_id, estimate_id, &line_items).await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_estimate(estimate_id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn update_estimate_state(&self, estimate_id: &str, state: EstimateState) -> Result<Estimate, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = fetch_estimate(&mut tx, estimate_id).await?;
+        if current.state != state && !current.state.can_transition_to(state) {
+            return Err(sqlx::Error::Protocol(format!(
+                "Estimate cannot be moved from {} to {}",
+                current.state.as_str(),
+                state.as_str()
+            )));
+        }
+
+        sqlx::query("UPDATE estimates SET state = ? WHERE id = ?")
+            .bind(state)
+            .bind(estimate_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.get_estimate(estimate_id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn delete_estimate(&self, estimate_id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let numbering = sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<String>)>(
+            "SELECT organizationId, invoiceId, numberSequence, numberPeriod FROM estimates WHERE id = ?"
+        )
+        .bind(estimate_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // Converted estimates stay on record next to the invoice they became
+        if numbering.as_ref().is_some_and(|(_, invoice_id, _, _)| invoice_id.is_some()) {
+            return Err(sqlx::Error::Protocol(
+                "Estimates that have been converted to an invoice can't be deleted".into(),
+            ));
+        }
+
+        delete_line_item_taxes(&mut tx, "estimateLineItems", "estimateId", estimate_id).await?;
+        sqlx::query("DELETE FROM estimateLineItems WHERE estimateId = ?")
+            .bind(estimate_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let result = sqlx::query("DELETE FROM estimates WHERE id = ?")
+            .bind(estimate_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Hand the number back if nothing was numbered after this estimate
+        if let Some((organization_id, _, Some(sequence), period)) = numbering {
+            release_invoice_number(&mut tx, NumberSeries::Estimate, &organization_id, sequence, period.as_deref()).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Moves sent estimates past their last valid day to expired. Returns how
+    // many were.
+    pub async fn expire_estimates(&self) -> Result<u64, sqlx::Error> {
+        let today = start_of_day(Local::now()).timestamp_millis();
+        let result = sqlx::query(
+            r#"
+            UPDATE estimates
+            SET state = ?
+            WHERE state = ? AND invoiceId IS NULL AND validUntil IS NOT NULL AND validUntil < ?
+            "#,
+        )
+        .bind(EstimateState::Expired)
+        .bind(EstimateState::Sent)
+        .bind(today)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Copies an estimate into a new draft invoice numbered from the invoice
+    // series, with the estimate's tax mode, and marks the estimate as accepted.
+    pub async fn convert_estimate_to_invoice(&self, estimate_id: &str) -> Result<Invoice, sqlx::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let estimate = fetch_estimate(&mut tx, estimate_id).await?;
+        if estimate.invoice_id.is_some() {
+            return Err(converted_estimate_error(&estimate));
+        }
+        let now = Local::now();
+        if matches!(estimate.state, EstimateState::Declined | EstimateState::Expired) {
+            return Err(sqlx::Error::Protocol(format!(
+                "A {} estimate can't be converted to an invoice",
+                estimate.state.as_str()
+            )));
+        }
+        if estimate.is_expired(now) {
+            return Err(sqlx::Error::Protocol(format!(
+                "Estimate {} is no longer valid and can't be converted to an invoice",
+                estimate.number
+            )));
+        }
+
+        let line_items = fetch_estimate_line_items(&mut tx, estimate_id).await?;
+
+        let organization = sqlx::query_as::<_, (Option<i64>, Option<f64>)>(
+            "SELECT due_days, overdueCharge FROM organizations WHERE id = ?"
+        )
+        .bind(&estimate.organization_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let (due_days, overdue_charge) = organization;
+
+        let today = start_of_day(now);
+
+        let invoice = CreateInvoiceRequest {
+            id: nanoid::nanoid!(),
+            organization_id: estimate.organization_id.clone(),
+            state: InvoiceState::Draft,
+            client_id: estimate.client_id.clone(),
+            date: today.timestamp_millis(),
+            due_date: due_days.map(|days| (today + chrono::Duration::days(days)).timestamp_millis()),
+            currency: estimate.currency.clone(),
+            customer_notes: estimate.customer_notes.clone(),
+            overdue_charge,
+            prices_include_tax: Some(estimate.prices_include_tax),
+            tax_rounding: Some(estimate.tax_rounding),
+            discount: None,
+            discount_kind: DiscountKind::default(),
+            line_items: line_items
+                .into_iter()
+                .map(|line_item| CreateInvoiceLineItemRequest {
+                    description: line_item.description,
+                    quantity: line_item.quantity,
+                    unit_code: line_item.unit_code,
+                    unit_price: line_item.unit_price.amount_minor,
+                    discount: line_item.discount,
+                    discount_kind: line_item.discount_kind,
+                    tax_rate: line_item.tax_rate,
+                    taxes: line_item.taxes,
+                    item_id: line_item.item_id,
+                })
+                .collect(),
+        };
+
+        insert_invoice(&mut tx, &invoice, InvoiceKind::Invoice, None).await?;
+
+        sqlx::query("UPDATE estimates SET invoiceId = ?, state = ? WHERE id = ?")
+            .bind(&invoice.id)
+            .bind(EstimateState::Accepted)
+            .bind(estimate_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.get_invoice(&invoice.id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+}
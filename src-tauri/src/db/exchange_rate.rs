@@ -0,0 +1,259 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+
+use super::Database;
+use crate::ecb::{self, parse_reference_rates};
+use crate::money::rate_quotient;
+
+// Units of `currency` for one unit of `base_currency` on a day
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExchangeRate {
+    pub id: String,
+    #[serde(rename = "baseCurrency")]
+    #[sqlx(rename = "baseCurrency")]
+    pub base_currency: String,
+    pub currency: String,
+    pub date: String,  // YYYY-MM-DD
+    pub rate: String,  // Decimal as published, e.g. "1.0921"
+    pub source: String,
+    #[serde(rename = "createdAt")]
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<String>,
+}
+
+// Units of an invoice's currency for one unit of the organization's, and the
+// day the rate is from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionRate {
+    pub rate: String,
+    pub date: String,
+}
+
+pub(crate) fn invoice_date(timestamp_ms: i64) -> Result<NaiveDate, sqlx::Error> {
+    Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .map(|date| date.date_naive())
+        .ok_or_else(|| sqlx::Error::Protocol(format!("Invalid invoice date: {}", timestamp_ms)))
+}
+
+// ECB rates are published on working days, the longest gap between them
+// (Easter, Christmas) is four days
+const MAX_RATE_AGE_DAYS: i64 = 5;
+
+pub(crate) fn missing_rate_error(currency: &str, base_currency: &str, date: NaiveDate) -> sqlx::Error {
+    sqlx::Error::Protocol(format!(
+        "No exchange rate from {} to {} within {} days before {}. Import reference rates first.",
+        currency, base_currency, MAX_RATE_AGE_DAYS, date
+    ))
+}
+
+// Latest rate on or up to MAX_RATE_AGE_DAYS before `date`: a published pair,
+// the inverse of one, or crossed through a currency both are quoted against
+// (EUR for ECB rates)
+pub(crate) async fn find_exchange_rate(
+    conn: &mut SqliteConnection,
+    base_currency: &str,
+    currency: &str,
+    date: NaiveDate,
+) -> Result<Option<ConversionRate>, sqlx::Error> {
+    let earliest = (date - Duration::days(MAX_RATE_AGE_DAYS)).format("%Y-%m-%d").to_string();
+    let date = date.format("%Y-%m-%d").to_string();
+    if base_currency.eq_ignore_ascii_case(currency) {
+        return Ok(Some(ConversionRate { rate: "1".into(), date }));
+    }
+
+    let published = |base: &str, quote: &str| {
+        sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT rate, date FROM exchangeRates
+            WHERE baseCurrency = ? AND currency = ? AND date BETWEEN ? AND ?
+            ORDER BY date DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(base.to_string())
+        .bind(quote.to_string())
+        .bind(earliest.clone())
+        .bind(date.clone())
+    };
+
+    let invalid = || sqlx::Error::Protocol(format!("Invalid exchange rate from {} to {}", currency, base_currency));
+
+    if let Some((rate, date)) = published(base_currency, currency).fetch_optional(&mut *conn).await? {
+        return Ok(Some(ConversionRate { rate, date }));
+    }
+    if let Some((rate, date)) = published(currency, base_currency).fetch_optional(&mut *conn).await? {
+        let rate = rate_quotient("1", &rate).ok_or_else(invalid)?;
+        return Ok(Some(ConversionRate { rate, date }));
+    }
+
+    let crossed = sqlx::query_as::<_, (String, String, String)>(
+        r#"
+        SELECT quote.rate, base.rate, quote.date
+        FROM exchangeRates quote
+        INNER JOIN exchangeRates base
+            ON base.baseCurrency = quote.baseCurrency AND base.date = quote.date
+        WHERE quote.currency = ? AND base.currency = ? AND quote.date BETWEEN ? AND ?
+        ORDER BY quote.date DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(currency)
+    .bind(base_currency)
+    .bind(&earliest)
+    .bind(&date)
+    .fetch_optional(&mut *conn)
+    .await?;
+    match crossed {
+        Some((quote_rate, base_rate, date)) => {
+            let rate = rate_quotient(&quote_rate, &base_rate).ok_or_else(invalid)?;
+            Ok(Some(ConversionRate { rate, date }))
+        }
+        None => Ok(None),
+    }
+}
+
+// Rate for an invoice's amounts. Credit notes take the rate of the invoice
+// they credit and cancel it out exactly.
+async fn conversion_rate(
+    conn: &mut SqliteConnection,
+    base_currency: &str,
+    currency: &str,
+    date: i64,
+    credited_invoice_id: Option<&str>,
+) -> Result<Option<ConversionRate>, sqlx::Error> {
+    let credited = match credited_invoice_id {
+        Some(credited_invoice_id) => sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT exchangeRate, exchangeRateDate FROM invoices
+            WHERE id = ? AND baseCurrency = ? AND exchangeRate IS NOT NULL AND exchangeRateDate IS NOT NULL
+            "#,
+        )
+        .bind(credited_invoice_id)
+        .bind(base_currency)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|(rate, date)| ConversionRate { rate, date }),
+        None => None,
+    };
+
+    match credited {
+        Some(conversion) => Ok(Some(conversion)),
+        None => find_exchange_rate(conn, base_currency, currency, invoice_date(date)?).await,
+    }
+}
+
+// Records the rate to the organization's currency on an invoice being issued,
+// so reports keep converting it the same way. Without a rate imported for its
+// date the rate is left empty until one is, see `backfill_exchange_rates`.
+pub(crate) async fn snapshot_exchange_rate(conn: &mut SqliteConnection, invoice_id: &str) -> Result<(), sqlx::Error> {
+    let (organization_id, currency, date, credited_invoice_id) =
+        sqlx::query_as::<_, (String, String, i64, Option<String>)>(
+            "SELECT organizationId, currency, date, creditedInvoiceId FROM invoices WHERE id = ?"
+        )
+        .bind(invoice_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    let base_currency = sqlx::query_scalar::<_, Option<String>>("SELECT currency FROM organizations WHERE id = ?")
+        .bind(&organization_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten()
+        .unwrap_or_else(|| "EUR".to_string());
+
+    let conversion =
+        conversion_rate(&mut *conn, &base_currency, &currency, date, credited_invoice_id.as_deref()).await?;
+
+    sqlx::query("UPDATE invoices SET baseCurrency = ?, exchangeRate = ?, exchangeRateDate = ? WHERE id = ?")
+        .bind(&base_currency)
+        .bind(conversion.as_ref().map(|conversion| &conversion.rate))
+        .bind(conversion.as_ref().map(|conversion| &conversion.date))
+        .bind(invoice_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+// Records the rate on invoices issued before one was imported for their date.
+// Once recorded it is fixed like any other snapshot. Credit notes go last so
+// they can take the rate just recorded on the invoice they credit.
+async fn backfill_exchange_rates(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let pending = sqlx::query_as::<_, (String, String, String, i64, Option<String>)>(
+        r#"
+        SELECT id, baseCurrency, currency, date, creditedInvoiceId FROM invoices
+        WHERE baseCurrency IS NOT NULL AND exchangeRate IS NULL
+        ORDER BY creditedInvoiceId IS NOT NULL, date ASC
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (invoice_id, base_currency, currency, date, credited_invoice_id) in pending {
+        let Some(conversion) =
+            conversion_rate(&mut *conn, &base_currency, &currency, date, credited_invoice_id.as_deref()).await?
+        else {
+            continue;
+        };
+        sqlx::query("UPDATE invoices SET exchangeRate = ?, exchangeRateDate = ? WHERE id = ?")
+            .bind(&conversion.rate)
+            .bind(&conversion.date)
+            .bind(&invoice_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+impl Database {
+    // Latest rate of every currency
+    pub async fn get_exchange_rates(&self) -> Result<Vec<ExchangeRate>, sqlx::Error> {
+        sqlx::query_as::<_, ExchangeRate>(
+            r#"
+            SELECT * FROM exchangeRates
+            WHERE date = (
+                SELECT MAX(latest.date) FROM exchangeRates latest
+                WHERE latest.baseCurrency = exchangeRates.baseCurrency
+                    AND latest.currency = exchangeRates.currency
+            )
+            ORDER BY baseCurrency ASC, currency ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // ECB reference rates from an XML or CSV file. Days already imported are
+    // replaced, invoices still waiting for a rate get theirs. Returns the
+    // number of rates read.
+    pub async fn import_exchange_rates(&self, contents: &str) -> Result<u64, sqlx::Error> {
+        let rates = parse_reference_rates(contents).map_err(sqlx::Error::Protocol)?;
+
+        let mut tx = self.pool.begin().await?;
+        for rate in &rates {
+            sqlx::query(
+                r#"
+                INSERT INTO exchangeRates (id, baseCurrency, currency, date, rate, source)
+                VALUES (?, ?, ?, ?, ?, 'ecb')
+                ON CONFLICT (baseCurrency, currency, date) DO UPDATE SET rate = excluded.rate, source = excluded.source
+                "#,
+            )
+            .bind(nanoid::nanoid!())
+            .bind(ecb::BASE_CURRENCY)
+            .bind(&rate.currency)
+            .bind(rate.date.format("%Y-%m-%d").to_string())
+            .bind(&rate.rate)
+            .execute(&mut *tx)
+            .await?;
+        }
+        backfill_exchange_rates(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(rates.len() as u64)
+    }
+}
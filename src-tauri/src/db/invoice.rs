@@ -1,63 +1,317 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqliteConnection};
+
+use super::{
+    allocate_invoice_number, current_user, line_item_description, record_invoice_state, release_invoice_number,
+    snapshot_exchange_rate, sync_invoice_payment_state, transition_invoice_state, Database, InvoiceState, NumberSeries,
+    TaxCategory, TaxRate,
+};
+use crate::countries::{country_code, is_eu_member_state};
+use crate::money::Money;
+use crate::reference::{normalize_reference, PaymentReferenceFormat};
+use crate::units::validate_unit_code;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum InvoiceKind {
+    Invoice,
+    CreditNote,
+}
 
-use super::Database;
+// How tax is rounded when an invoice is totalled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TaxRounding {
+    // Once per tax on the sum of its lines
+    #[default]
+    PerTotal,
+    // On every line, then summed
+    PerLine,
+}
+
+// How a discount's value is read: a percentage, or an amount in minor units
+// of the document's currency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum DiscountKind {
+    #[default]
+    Percentage,
+    Amount,
+}
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discount {
+    Percentage(f64),
+    Amount(i64),  // Minor units
+}
+
+impl Discount {
+    // No discount when there is no value or it is zero. Amounts come in as
+    // minor units and are rounded to whole ones.
+    pub fn new(value: Option<f64>, kind: DiscountKind) -> Option<Self> {
+        value.filter(|value| *value != 0.0).map(|value| match kind {
+            DiscountKind::Percentage => Self::Percentage(value),
+            DiscountKind::Amount => Self::Amount(round_minor(value)),
+        })
+    }
+
+    pub fn kind(&self) -> DiscountKind {
+        match self {
+            Self::Percentage(_) => DiscountKind::Percentage,
+            Self::Amount(_) => DiscountKind::Amount,
+        }
+    }
+
+    // As requests and the frontend carry it, along with its kind
+    pub fn value(&self) -> f64 {
+        match self {
+            Self::Percentage(percentage) => *percentage,
+            Self::Amount(amount) => *amount as f64,
+        }
+    }
+
+    // The part of `amount` taken off, with the same sign as it
+    pub(crate) fn amount_off(&self, amount: i64) -> Result<i64, String> {
+        match *self {
+            Self::Percentage(percentage) => {
+                if !(0.0..=100.0).contains(&percentage) {
+                    return Err(format!("{}% is not a valid discount", percentage));
+                }
+                Ok(round_minor(amount as f64 * percentage / 100.0))
+            }
+            Self::Amount(off) => {
+                if off < 0 {
+                    return Err("a discount can't be negative".into());
+                }
+                if off > amount.abs() {
+                    return Err(format!("the discount of {} is more than the amount of {}", off, amount.abs()));
+                }
+                Ok(amount.signum() * off)
+            }
+        }
+    }
+}
+
+// Percentages are stored in `discount` and amounts in minor units in
+// `discountAmount`, the other one is left empty
+pub(crate) fn discount_columns(discount: Option<Discount>) -> (Option<f64>, Option<i64>) {
+    match discount {
+        Some(Discount::Percentage(percentage)) => (Some(percentage), None),
+        Some(Discount::Amount(amount)) => (None, Some(amount)),
+        None => (None, None),
+    }
+}
+
+// A discount's value read back from whichever column its kind is stored in
+pub(crate) fn discount_from_row(row: &SqliteRow, kind: DiscountKind) -> Result<Option<f64>, sqlx::Error> {
+    match kind {
+        DiscountKind::Percentage => row.try_get("discount"),
+        DiscountKind::Amount => row
+            .try_get::<Option<i64>, _>("discountAmount")
+            .map(|amount| amount.map(|amount| amount as f64)),
+    }
+}
+
+// How an invoice's unit prices are read and its tax rounded. Set per invoice,
+// new invoices take the organization's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaxMode {
+    pub prices_include_tax: bool,
+    pub rounding: TaxRounding,
+}
+
+impl InvoiceKind {
+    pub fn number_series(&self) -> NumberSeries {
+        match self {
+            Self::Invoice => NumberSeries::Invoice,
+            Self::CreditNote => NumberSeries::CreditNote,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Invoice {
     pub id: String,
     #[serde(rename = "organizationId")]
-    #[sqlx(rename = "organizationId")]
     pub organization_id: String,
+    pub kind: InvoiceKind,
+    #[serde(rename = "creditedInvoiceId")]
+    pub credited_invoice_id: Option<String>,
+    #[serde(rename = "recurringInvoiceId")]
+    pub recurring_invoice_id: Option<String>,
     pub number: String,
-    pub state: String,
+    pub state: InvoiceState,
     #[serde(rename = "clientId")]
-    #[sqlx(rename = "clientId")]
     pub client_id: String,
     pub date: i64,
     #[serde(rename = "dueDate")]
-    #[sqlx(rename = "dueDate")]
     pub due_date: Option<i64>,
     pub currency: String,
     #[serde(rename = "customerNotes")]
-    #[sqlx(rename = "customerNotes")]
     pub customer_notes: Option<String>,
     #[serde(rename = "overdueCharge")]
-    #[sqlx(rename = "overdueCharge")]
     pub overdue_charge: Option<f64>,
-    pub total: i64,  // Stored as cents
+    // Derived from the number in the organization's reference format
+    #[serde(rename = "paymentReference")]
+    pub payment_reference: Option<String>,
+    // Unit prices are gross, net amounts and tax are backed out of them
+    #[serde(rename = "pricesIncludeTax")]
+    pub prices_include_tax: bool,
+    #[serde(rename = "taxRounding")]
+    pub tax_rounding: TaxRounding,
+    // Taken off the whole invoice, after the lines' own discounts
+    pub discount: Option<f64>,
+    #[serde(rename = "discountKind")]
+    pub discount_kind: DiscountKind,
+    // Line and invoice discounts together, in the same terms as the prices
+    #[serde(rename = "discountTotal")]
+    pub discount_total: Money,
+    // Kept back by the client, already taken off the total
+    #[serde(rename = "withholdingTotal")]
+    pub withholding_total: Money,
+    pub total: Money,
     #[serde(rename = "taxTotal")]
-    #[sqlx(rename = "taxTotal")]
-    pub tax_total: i64,  // Stored as cents
+    pub tax_total: Money,
     #[serde(rename = "subTotal")]
-    #[sqlx(rename = "subTotal")]
-    pub sub_total: i64,  // Stored as cents
+    pub sub_total: Money,
+    // Units of the currency for one unit of the organization's currency,
+    // recorded when the invoice is issued. Reports convert with it.
+    #[serde(rename = "baseCurrency")]
+    pub base_currency: Option<String>,
+    #[serde(rename = "exchangeRate")]
+    pub exchange_rate: Option<String>,
+    #[serde(rename = "exchangeRateDate")]
+    pub exchange_rate_date: Option<String>,  // YYYY-MM-DD
     #[serde(rename = "createdAt")]
-    #[sqlx(rename = "createdAt")]
     pub created_at: Option<String>,
     // Only for joined queries
     #[serde(rename = "clientName")]
-    #[sqlx(rename = "clientName")]
     pub client_name: Option<String>,
+    // Computed from payments and issued credit notes, see INVOICE_BALANCE_COLUMNS
+    #[serde(rename = "amountPaid")]
+    pub amount_paid: Money,
+    #[serde(rename = "balanceDue")]
+    pub balance_due: Money,
+}
+
+// Amounts are stored as minor units of the invoice's currency
+impl<'r> FromRow<'r, SqliteRow> for Invoice {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        let money = |column: &str| row.try_get(column).map(|amount_minor| Money::new(amount_minor, &currency));
+        let discount_kind = row.try_get("discountKind")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            organization_id: row.try_get("organizationId")?,
+            kind: row.try_get("kind")?,
+            credited_invoice_id: row.try_get("creditedInvoiceId")?,
+            recurring_invoice_id: row.try_get("recurringInvoiceId")?,
+            number: row.try_get("number")?,
+            state: row.try_get("state")?,
+            client_id: row.try_get("clientId")?,
+            date: row.try_get("date")?,
+            due_date: row.try_get("dueDate")?,
+            customer_notes: row.try_get("customerNotes")?,
+            overdue_charge: row.try_get("overdueCharge")?,
+            payment_reference: row.try_get("paymentReference")?,
+            prices_include_tax: row.try_get("pricesIncludeTax")?,
+            tax_rounding: row.try_get("taxRounding")?,
+            discount: discount_from_row(row, discount_kind)?,
+            discount_kind,
+            discount_total: money("discountTotal")?,
+            withholding_total: money("withholdingTotal")?,
+            total: money("total")?,
+            tax_total: money("taxTotal")?,
+            sub_total: money("subTotal")?,
+            base_currency: row.try_get("baseCurrency")?,
+            exchange_rate: row.try_get("exchangeRate")?,
+            exchange_rate_date: row.try_get("exchangeRateDate")?,
+            created_at: row.try_get("createdAt")?,
+            client_name: row.try_get("clientName")?,
+            amount_paid: money("amountPaid")?,
+            balance_due: money("balanceDue")?,
+            currency,
+        })
+    }
+}
+
+impl Invoice {
+    pub fn tax_mode(&self) -> TaxMode {
+        TaxMode {
+            prices_include_tax: self.prices_include_tax,
+            rounding: self.tax_rounding,
+        }
+    }
+
+    pub fn discount(&self) -> Option<Discount> {
+        Discount::new(self.discount, self.discount_kind)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize)]
 pub struct InvoiceLineItem {
     pub id: String,
     #[serde(rename = "invoiceId")]
-    #[sqlx(rename = "invoiceId")]
     pub invoice_id: String,
     pub description: Option<String>,
     pub quantity: f64,
+    // UN/ECE Recommendation 20 code, e.g. HUR for hours
+    #[serde(rename = "unitCode")]
+    pub unit_code: Option<String>,
     #[serde(rename = "unitPrice")]
-    #[sqlx(rename = "unitPrice")]
-    pub unit_price: i64,  // Stored as cents
+    pub unit_price: Money,  // In the invoice's currency
+    // Percentage, or minor units off the line amount
+    pub discount: Option<f64>,
+    #[serde(rename = "discountKind")]
+    pub discount_kind: DiscountKind,
+    // First of the line's taxes
     #[serde(rename = "taxRate")]
-    #[sqlx(rename = "taxRate")]
     pub tax_rate: Option<String>,
+    // Catalog item the line was filled from. Description and price are copies.
+    #[serde(rename = "itemId")]
+    pub item_id: Option<String>,
     #[serde(rename = "createdAt")]
-    #[sqlx(rename = "createdAt")]
     pub created_at: Option<String>,
+    // Loaded from lineItemTaxes
+    pub taxes: Vec<LineItemTax>,
+}
+
+// Read with the invoice's currency joined in
+impl<'r> FromRow<'r, SqliteRow> for InvoiceLineItem {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        let discount_kind = row.try_get("discountKind")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            invoice_id: row.try_get("invoiceId")?,
+            description: row.try_get("description")?,
+            quantity: row.try_get("quantity")?,
+            unit_code: row.try_get("unitCode")?,
+            unit_price: Money::new(row.try_get("unitPrice")?, &currency),
+            discount: discount_from_row(row, discount_kind)?,
+            discount_kind,
+            tax_rate: row.try_get("taxRate")?,
+            item_id: row.try_get("itemId")?,
+            created_at: row.try_get("createdAt")?,
+            taxes: Vec::new(),
+        })
+    }
+}
+
+// One of a line's taxes, in the order they are charged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct LineItemTax {
+    #[serde(rename = "taxRate")]
+    #[sqlx(rename = "taxRateId")]
+    pub tax_rate: String,
+    // Charged on the line amount plus the taxes before it
+    #[serde(default)]
+    pub compound: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,8 +319,7 @@ pub struct CreateInvoiceRequest {
     pub id: String,
     #[serde(rename = "organizationId")]
     pub organization_id: String,
-    pub number: String,
-    pub state: String,
+    pub state: InvoiceState,
     #[serde(rename = "clientId")]
     pub client_id: String,
     pub date: i64,
@@ -77,29 +330,457 @@ pub struct CreateInvoiceRequest {
     pub customer_notes: Option<String>,
     #[serde(rename = "overdueCharge")]
     pub overdue_charge: Option<f64>,
-    pub total: i64,  // Stored as cents
-    #[serde(rename = "taxTotal")]
-    pub tax_total: i64,  // Stored as cents
-    #[serde(rename = "subTotal")]
-    pub sub_total: i64,  // Stored as cents
+    // Default to the organization's settings
+    #[serde(rename = "pricesIncludeTax")]
+    pub prices_include_tax: Option<bool>,
+    #[serde(rename = "taxRounding")]
+    pub tax_rounding: Option<TaxRounding>,
+    pub discount: Option<f64>,
+    #[serde(default, rename = "discountKind")]
+    pub discount_kind: DiscountKind,
     #[serde(rename = "lineItems")]
     pub line_items: Vec<CreateInvoiceLineItemRequest>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct CreateInvoiceLineItemRequest {
     pub description: Option<String>,
     pub quantity: f64,
+    #[serde(default, rename = "unitCode")]
+    pub unit_code: Option<String>,
     #[serde(rename = "unitPrice")]
-    pub unit_price: f64,
+    pub unit_price: i64,  // Minor units
+    pub discount: Option<f64>,
+    #[serde(default, rename = "discountKind")]
+    pub discount_kind: DiscountKind,
     #[serde(rename = "taxRate")]
     pub tax_rate: Option<String>,
+    // Several taxes in order, `taxRate` alone stands for a single one
+    #[serde(default)]
+    pub taxes: Vec<LineItemTax>,
+    #[serde(default, rename = "itemId")]
+    pub item_id: Option<String>,
+}
+
+impl CreateInvoiceLineItemRequest {
+    pub fn discount(&self) -> Option<Discount> {
+        Discount::new(self.discount, self.discount_kind)
+    }
+
+    pub fn line_taxes(&self) -> Vec<LineItemTax> {
+        if !self.taxes.is_empty() {
+            return self.taxes.clone();
+        }
+        self.tax_rate
+            .iter()
+            .map(|tax_rate| LineItemTax { tax_rate: tax_rate.clone(), compound: false })
+            .collect()
+    }
+}
+
+impl From<InvoiceLineItem> for CreateInvoiceLineItemRequest {
+    fn from(line_item: InvoiceLineItem) -> Self {
+        Self {
+            description: line_item.description,
+            quantity: line_item.quantity,
+            unit_code: line_item.unit_code,
+            unit_price: line_item.unit_price.amount_minor,
+            discount: line_item.discount,
+            discount_kind: line_item.discount_kind,
+            tax_rate: line_item.tax_rate,
+            taxes: line_item.taxes,
+            item_id: line_item.item_id,
+        }
+    }
+}
+
+// Totals are always computed here from the line items and the organization's
+// tax rates. Any totals sent by the frontend are ignored.
+#[derive(Debug, Serialize)]
+pub struct InvoiceTotals {
+    #[serde(rename = "subTotal")]
+    pub sub_total: Money,
+    #[serde(rename = "taxTotal")]
+    pub tax_total: Money,
+    // What the client pays, after withholding
+    pub total: Money,
+    pub taxes: Vec<InvoiceTaxTotal>,
+    #[serde(rename = "withholdingTotal")]
+    pub withholding_total: Money,
+    pub withholdings: Vec<InvoiceTaxTotal>,
+    // Line and invoice discounts together, in the same terms as the prices
+    #[serde(rename = "discountTotal")]
+    pub discount_total: Money,
+    #[serde(rename = "invoiceDiscount")]
+    pub invoice_discount: Money,
+    pub lines: Vec<LineTotal>,
+}
+
+// A line's amounts excluding tax, `amount - discount - invoiceDiscount = net`
+#[derive(Debug, Clone, Serialize)]
+pub struct LineTotal {
+    pub amount: i64,  // Minor units
+    pub discount: i64,  // Minor units
+    // The line's share of the invoice discount
+    #[serde(rename = "invoiceDiscount")]
+    pub invoice_discount: i64,  // Minor units
+    pub net: i64,  // Minor units
+    // After the line's own discount in the same terms as the prices, what the
+    // line shows on the invoice
+    pub total: i64,  // Minor units
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceTaxTotal {
+    #[serde(rename = "taxRateId")]
+    pub tax_rate_id: String,
+    pub name: String,
+    pub percentage: f64,
+    pub compound: bool,
+    pub category: TaxCategory,
+    #[serde(rename = "exemptionReason")]
+    pub exemption_reason: Option<String>,
+    #[serde(rename = "taxableAmount")]
+    pub taxable_amount: i64,  // Minor units
+    pub amount: i64,  // Minor units
+}
+
+// Rounding policy: each line's quantity times unit price is calculated exactly
+// and rounded to the currency's minor unit (half away from zero), so to whole
+// cents for EUR and whole yen for JPY.
+// Per total, tax is calculated once per tax rate on the sum of its rounded
+// lines, which matches how the invoice form groups taxes. Per line, every
+// line's tax is rounded and then summed. A compound tax is charged on the line
+// plus the line's earlier taxes, rounded per line, and is totalled apart from
+// the same rate charged on its own.
+//
+// Discounts come off before tax. A line's discount is rounded to the minor unit,
+// the invoice discount is then shared out over the lines by their discounted
+// amounts, so each tax rate's base goes down by its part of it.
+//
+// With tax-inclusive prices the rounded gross is split into net and tax: the
+// net is the gross divided by the taxes' combined factor, the taxes are
+// charged on it and any minor unit left over goes to the last tax that charges
+// anything, so net and tax always add up to the gross. Per total, lines with
+// the same taxes are split together and the net is shared back out to them.
+//
+// Withholding rates are charged on the line's net like any other rate but
+// taken off the total instead of added to it. They don't take part in the
+// split of tax-inclusive prices or in compound taxes.
+fn round_minor(value: f64) -> i64 {
+    value.round() as i64
+}
+
+// Quantities are decimals with at most this many places
+pub const MAX_QUANTITY_PRECISION: u32 = 4;
+
+// Quantity in units of the smallest step. Any decimal quantity with up to
+// MAX_QUANTITY_PRECISION places comes out exact, whatever float it arrived as.
+fn scaled_quantity(quantity: f64) -> i128 {
+    (quantity * 10_f64.powi(MAX_QUANTITY_PRECISION as i32)).round() as i128
+}
+
+// Division rounded half away from zero
+fn divide_rounded(value: i128, divisor: i128) -> i128 {
+    let quotient = (value.abs() + divisor / 2) / divisor;
+    if value < 0 { -quotient } else { quotient }
+}
+
+// Quantity times unit price in minor units, in decimal rather than float
+// arithmetic so that e.g. 1.005 × 1.00 comes to 1.01 and not 1.00
+pub fn line_amount(quantity: f64, unit_price: i64) -> i64 {
+    let scale = 10_i128.pow(MAX_QUANTITY_PRECISION);
+    divide_rounded(scaled_quantity(quantity) * unit_price as i128, scale) as i64
+}
+
+// Quantity rounded half away from zero to `precision` decimals
+pub fn round_quantity(quantity: f64, precision: u32) -> f64 {
+    let step = 10_i128.pow(MAX_QUANTITY_PRECISION - precision.min(MAX_QUANTITY_PRECISION));
+    let rounded = divide_rounded(scaled_quantity(quantity), step) * step;
+    rounded as f64 / 10_f64.powi(MAX_QUANTITY_PRECISION as i32)
+}
+
+// A line's tax, resolved against the organization's tax rates
+struct LineTax<'a> {
+    tax_rate: &'a TaxRate,
+    compound: bool,
+}
+
+fn resolve_line_taxes<'a>(
+    line_item: &CreateInvoiceLineItemRequest,
+    tax_rates: &'a [TaxRate],
+) -> Result<Vec<LineTax<'a>>, sqlx::Error> {
+    let line_taxes = line_item.line_taxes();
+    let mut resolved = Vec::with_capacity(line_taxes.len());
+    for (i, line_tax) in line_taxes.iter().enumerate() {
+        let tax_rate_id = &line_tax.tax_rate;
+        if line_taxes[..i].iter().any(|earlier| &earlier.tax_rate == tax_rate_id) {
+            return Err(sqlx::Error::Protocol(format!("Tax rate {} is added to a line more than once", tax_rate_id)));
+        }
+
+        let tax_rate = tax_rates
+            .iter()
+            .find(|tax_rate| &tax_rate.id == tax_rate_id)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("Unknown tax rate: {}", tax_rate_id)))?;
+        resolved.push(LineTax { tax_rate, compound: line_tax.compound });
+    }
+    Ok(resolved)
+}
+
+fn same_taxes(a: &[LineTax], b: &[LineTax]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| a.tax_rate.id == b.tax_rate.id && a.compound == b.compound)
+}
+
+// Gross amount per unit of net amount
+fn gross_factor(line_taxes: &[LineTax]) -> f64 {
+    let mut charged = 0.0;
+    for line_tax in line_taxes {
+        let base = if line_tax.compound { 1.0 + charged } else { 1.0 };
+        charged += base * line_tax.tax_rate.percentage / 100.0;
+    }
+    1.0 + charged
+}
+
+// Taxable amount and unrounded tax of each of the line's taxes on a net amount
+fn charge_taxes(net: i64, line_taxes: &[LineTax]) -> Vec<(i64, f64)> {
+    // Tax charged on the line so far
+    let mut charged = 0.0;
+    line_taxes
+        .iter()
+        .map(|line_tax| {
+            let taxable_amount = if line_tax.compound {
+                round_minor(net as f64 + charged)
+            } else {
+                net
+            };
+            let tax = taxable_amount as f64 * line_tax.tax_rate.percentage / 100.0;
+            charged += tax;
+            (taxable_amount, tax)
+        })
+        .collect()
+}
+
+fn add_tax(taxes: &mut Vec<InvoiceTaxTotal>, line_tax: &LineTax, taxable_amount: i64, amount: i64) {
+    match taxes
+        .iter_mut()
+        .find(|tax| tax.tax_rate_id == line_tax.tax_rate.id && tax.compound == line_tax.compound)
+    {
+        Some(tax) => {
+            tax.taxable_amount += taxable_amount;
+            tax.amount += amount;
+        }
+        None => taxes.push(InvoiceTaxTotal {
+            tax_rate_id: line_tax.tax_rate.id.clone(),
+            name: line_tax.tax_rate.name.clone(),
+            percentage: line_tax.tax_rate.percentage,
+            compound: line_tax.compound,
+            category: line_tax.tax_rate.category,
+            exemption_reason: line_tax.tax_rate.exemption_reason.clone(),
+            taxable_amount,
+            amount,
+        }),
+    }
+}
+
+// Splits `total` over the weights in proportion, the last weighted one gets what is left
+fn share_out(total: i64, weights: &[i64]) -> Vec<i64> {
+    let mut shares = vec![0; weights.len()];
+    let sum = weights.iter().sum::<i64>();
+    let Some(last) = weights.iter().rposition(|weight| *weight != 0) else {
+        return shares;
+    };
+    if sum == 0 {
+        shares[last] = total;
+        return shares;
+    }
+    for (i, weight) in weights.iter().enumerate().take(last) {
+        shares[i] = round_minor(total as f64 * *weight as f64 / sum as f64);
+    }
+    shares[last] = total - shares.iter().sum::<i64>();
+    shares
+}
+
+pub fn calculate_invoice_totals(
+    line_items: &[CreateInvoiceLineItemRequest],
+    tax_rates: &[TaxRate],
+    mode: TaxMode,
+    discount: Option<Discount>,
+    currency: &str,
+) -> Result<InvoiceTotals, sqlx::Error> {
+    let mut amounts = Vec::with_capacity(line_items.len());
+    let mut line_discounts = Vec::with_capacity(line_items.len());
+    for (i, line_item) in line_items.iter().enumerate() {
+        let amount = line_amount(line_item.quantity, line_item.unit_price);
+        let line_discount = match line_item.discount() {
+            Some(discount) => discount
+                .amount_off(amount)
+                .map_err(|e| sqlx::Error::Protocol(format!("Line item {}: {}", i + 1, e)))?,
+            None => 0,
+        };
+        amounts.push(amount);
+        line_discounts.push(line_discount);
+    }
+
+    let discounted = amounts.iter().zip(&line_discounts).map(|(amount, discount)| amount - discount).collect::<Vec<_>>();
+    let invoice_discount = match discount {
+        Some(discount) => discount
+            .amount_off(discounted.iter().sum())
+            .map_err(|e| sqlx::Error::Protocol(format!("Invoice discount: {}", e)))?,
+        None => 0,
+    };
+    let shares = share_out(invoice_discount, &discounted);
+
+    // Lines after all discounts, what tax is charged on
+    let mut lines = Vec::with_capacity(line_items.len());
+    let mut withheld = Vec::with_capacity(line_items.len());
+    for (i, line_item) in line_items.iter().enumerate() {
+        let (line_withholding, line_taxes): (Vec<_>, Vec<_>) = resolve_line_taxes(line_item, tax_rates)?
+            .into_iter()
+            .partition(|line_tax| line_tax.tax_rate.withholding);
+        if let Some(line_tax) = line_withholding.iter().find(|line_tax| line_tax.compound) {
+            return Err(sqlx::Error::Protocol(format!(
+                "Line item {}: withholding rate {} can't be compound",
+                i + 1,
+                line_tax.tax_rate.name
+            )));
+        }
+        lines.push((discounted[i] - shares[i], line_taxes));
+        withheld.push(line_withholding);
+    }
+
+    let mut line_nets = vec![0; lines.len()];
+    let mut taxes: Vec<InvoiceTaxTotal> = Vec::new();
+
+    if !mode.prices_include_tax {
+        for (i, (line_total, line_taxes)) in lines.iter().enumerate() {
+            line_nets[i] = *line_total;
+            for (line_tax, (taxable_amount, tax)) in line_taxes.iter().zip(charge_taxes(*line_total, line_taxes)) {
+                let amount = match mode.rounding {
+                    TaxRounding::PerLine => round_minor(tax),
+                    TaxRounding::PerTotal => 0,
+                };
+                add_tax(&mut taxes, line_tax, taxable_amount, amount);
+            }
+        }
+        if mode.rounding == TaxRounding::PerTotal {
+            for tax in &mut taxes {
+                tax.amount = round_minor(tax.taxable_amount as f64 * tax.percentage / 100.0);
+            }
+        }
+    } else {
+        // Lines whose gross is split into net and tax together
+        let mut splits: Vec<Vec<usize>> = Vec::new();
+        for (i, (_, line_taxes)) in lines.iter().enumerate() {
+            let split = match mode.rounding {
+                TaxRounding::PerTotal => splits.iter_mut().find(|split| same_taxes(&lines[split[0]].1, line_taxes)),
+                TaxRounding::PerLine => None,
+            };
+            match split {
+                Some(split) => split.push(i),
+                None => splits.push(vec![i]),
+            }
+        }
+
+        for split in splits {
+            let line_taxes = &lines[split[0]].1;
+            let factor = gross_factor(line_taxes);
+            let gross = split.iter().map(|&i| lines[i].0).sum::<i64>();
+            let net = round_minor(gross as f64 / factor);
+
+            let mut charged = charge_taxes(net, line_taxes)
+                .into_iter()
+                .map(|(taxable_amount, tax)| (taxable_amount, round_minor(tax)))
+                .collect::<Vec<_>>();
+            let difference = gross - net - charged.iter().map(|(_, amount)| amount).sum::<i64>();
+            if let Some((_, amount)) = charged
+                .iter_mut()
+                .zip(line_taxes)
+                .rev()
+                .find(|(_, line_tax)| line_tax.tax_rate.percentage != 0.0)
+                .map(|(charged, _)| charged)
+            {
+                *amount += difference;
+            }
+            for (line_tax, (taxable_amount, amount)) in line_taxes.iter().zip(charged) {
+                add_tax(&mut taxes, line_tax, taxable_amount, amount);
+            }
+
+            // Each line gets its share of the net, the last one what is left
+            let mut allocated = 0;
+            for (n, &i) in split.iter().enumerate() {
+                line_nets[i] = if n + 1 == split.len() {
+                    net - allocated
+                } else {
+                    round_minor(lines[i].0 as f64 / factor)
+                };
+                allocated += line_nets[i];
+            }
+        }
+    }
+
+    let mut withholdings: Vec<InvoiceTaxTotal> = Vec::new();
+    for (i, line_withholding) in withheld.iter().enumerate() {
+        for line_tax in line_withholding {
+            let amount = match mode.rounding {
+                TaxRounding::PerLine => round_minor(line_nets[i] as f64 * line_tax.tax_rate.percentage / 100.0),
+                TaxRounding::PerTotal => 0,
+            };
+            add_tax(&mut withholdings, line_tax, line_nets[i], amount);
+        }
+    }
+    if mode.rounding == TaxRounding::PerTotal {
+        for withholding in &mut withholdings {
+            withholding.amount = round_minor(withholding.taxable_amount as f64 * withholding.percentage / 100.0);
+        }
+    }
+
+    // Discounts excluding tax. Backed out of gross amounts only where there is one,
+    // so rounding doesn't turn up as a discount of a minor unit.
+    let lines = lines
+        .iter()
+        .enumerate()
+        .map(|(i, (_, line_taxes))| {
+            let net = line_nets[i];
+            let (amount, discounted) = if mode.prices_include_tax {
+                let factor = gross_factor(line_taxes);
+                let discounted = if shares[i] == 0 { net } else { round_minor(discounted[i] as f64 / factor) };
+                let amount = if line_discounts[i] == 0 { discounted } else { round_minor(amounts[i] as f64 / factor) };
+                (amount, discounted)
+            } else {
+                (amounts[i], discounted[i])
+            };
+            LineTotal {
+                amount,
+                discount: amount - discounted,
+                invoice_discount: discounted - net,
+                net,
+                total: amounts[i] - line_discounts[i],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let sub_total = line_nets.iter().sum::<i64>();
+    let tax_total = taxes.iter().map(|tax| tax.amount).sum::<i64>();
+    let withholding_total = withholdings.iter().map(|withholding| withholding.amount).sum::<i64>();
+
+    Ok(InvoiceTotals {
+        sub_total: Money::new(sub_total, currency),
+        tax_total: Money::new(tax_total, currency),
+        total: Money::new(sub_total + tax_total - withholding_total, currency),
+        taxes,
+        withholding_total: Money::new(withholding_total, currency),
+        withholdings,
+        discount_total: Money::new(line_discounts.iter().sum::<i64>() + invoice_discount, currency),
+        invoice_discount: Money::new(invoice_discount, currency),
+        lines,
+    })
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateInvoiceRequest {
     pub number: Option<String>,
-    pub state: Option<String>,
+    pub state: Option<InvoiceState>,
     #[serde(rename = "clientId")]
     pub client_id: Option<String>,
     pub date: Option<i64>,
@@ -110,22 +791,461 @@ pub struct UpdateInvoiceRequest {
     pub customer_notes: Option<String>,
     #[serde(rename = "overdueCharge")]
     pub overdue_charge: Option<f64>,
-    pub total: Option<i64>,  // Stored as cents
-    #[serde(rename = "taxTotal")]
-    pub tax_total: Option<i64>,  // Stored as cents
-    #[serde(rename = "subTotal")]
-    pub sub_total: Option<i64>,  // Stored as cents
+    #[serde(rename = "pricesIncludeTax")]
+    pub prices_include_tax: Option<bool>,
+    #[serde(rename = "taxRounding")]
+    pub tax_rounding: Option<TaxRounding>,
+    // Zero removes the discount
+    pub discount: Option<f64>,
+    #[serde(rename = "discountKind")]
+    pub discount_kind: Option<DiscountKind>,
+    #[serde(rename = "lineItems")]
+    pub line_items: Option<Vec<CreateInvoiceLineItemRequest>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCreditNoteRequest {
+    pub id: String,
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: String,
+    pub date: i64,
+    #[serde(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    // Lines as they appear on the credit note, i.e. with negative quantities.
+    // When omitted every line of the original invoice is credited in full.
     #[serde(rename = "lineItems")]
     pub line_items: Option<Vec<CreateInvoiceLineItemRequest>>,
 }
 
+// Selected alongside `invoices.*` wherever an Invoice is loaded
+pub(crate) const INVOICE_BALANCE_COLUMNS: &str = r#"
+    COALESCE((SELECT SUM(amount) FROM payments WHERE payments.invoiceId = invoices.id), 0) AS amountPaid,
+    invoices.total
+        + COALESCE((
+            SELECT SUM(creditNotes.total)
+            FROM invoices AS creditNotes
+            WHERE creditNotes.creditedInvoiceId = invoices.id AND creditNotes.state NOT IN ('draft', 'void')
+        ), 0)
+        - COALESCE((SELECT SUM(amount) FROM payments WHERE payments.invoiceId = invoices.id), 0) AS balanceDue
+"#;
+
+fn locked_invoice_error(field: &str) -> sqlx::Error {
+    sqlx::Error::Protocol(format!(
+        "Invoice has been issued and its {} can no longer be changed. Create a credit note instead.",
+        field
+    ))
+}
+
+fn same_line_items(current: &[InvoiceLineItem], updates: &[CreateInvoiceLineItemRequest]) -> bool {
+    current.len() == updates.len()
+        && current.iter().zip(updates).all(|(current, update)| {
+            current.description == update.description
+                && current.quantity == update.quantity
+                && current.unit_code == update.unit_code
+                && current.unit_price.amount_minor == update.unit_price
+                && Discount::new(current.discount, current.discount_kind) == update.discount()
+                && current.taxes == update.line_taxes()
+                && current.item_id == update.item_id
+        })
+}
+
+pub(crate) async fn fetch_invoice(conn: &mut SqliteConnection, invoice_id: &str) -> Result<Invoice, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>(&format!(
+        "SELECT invoices.*, NULL AS clientName, {} FROM invoices WHERE invoices.id = ?",
+        INVOICE_BALANCE_COLUMNS
+    ))
+    .bind(invoice_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(sqlx::Error::RowNotFound)
+}
+
+async fn fetch_line_items(conn: &mut SqliteConnection, invoice_id: &str) -> Result<Vec<InvoiceLineItem>, sqlx::Error> {
+    let mut line_items = sqlx::query_as::<_, InvoiceLineItem>(
+        r#"
+        SELECT invoiceLineItems.*, invoices.currency
+        FROM invoiceLineItems
+        INNER JOIN invoices ON invoices.id = invoiceLineItems.invoiceId
+        WHERE invoiceLineItems.invoiceId = ?
+        ORDER BY invoiceLineItems.createdAt ASC, invoiceLineItems.rowid ASC
+        "#,
+    )
+    .bind(invoice_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut taxes = fetch_line_item_taxes(conn, "invoiceLineItems", "invoiceId", invoice_id).await?;
+    for line_item in &mut line_items {
+        line_item.taxes = taxes.remove(&line_item.id).unwrap_or_default();
+    }
+
+    Ok(line_items)
+}
+
+// lineItemTaxes holds the taxes of invoice, estimate and recurring invoice lines.
+// These take the line item table and the column of its parent, keyed by line item id.
+pub(crate) async fn fetch_line_item_taxes(
+    conn: &mut SqliteConnection,
+    table: &str,
+    parent_column: &str,
+    parent_id: &str,
+) -> Result<HashMap<String, Vec<LineItemTax>>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String, String, bool)>(&format!(
+        r#"
+        SELECT lineItemTaxes.lineItemId, lineItemTaxes.taxRateId, lineItemTaxes.compound
+        FROM lineItemTaxes
+        INNER JOIN {table} ON {table}.id = lineItemTaxes.lineItemId
+        WHERE {table}.{parent_column} = ?
+        ORDER BY lineItemTaxes.position ASC
+        "#,
+        table = table,
+        parent_column = parent_column,
+    ))
+    .bind(parent_id)
+    .fetch_all(conn)
+    .await?;
+
+    let mut taxes: HashMap<String, Vec<LineItemTax>> = HashMap::new();
+    for (line_item_id, tax_rate, compound) in rows {
+        taxes.entry(line_item_id).or_default().push(LineItemTax { tax_rate, compound });
+    }
+    Ok(taxes)
+}
+
+pub(crate) async fn insert_line_item_taxes(
+    conn: &mut SqliteConnection,
+    line_item_id: &str,
+    taxes: &[LineItemTax],
+) -> Result<(), sqlx::Error> {
+    for (position, tax) in taxes.iter().enumerate() {
+        // Keeps the rate as it is now, documents are rendered from this copy
+        sqlx::query(
+            r#"
+            INSERT INTO lineItemTaxes (
+                id, lineItemId, taxRateId, compound, position,
+                name, percentage, category, exemptionReason, withholding
+            )
+            SELECT ?, ?, ?, ?, ?,
+                taxRates.name, taxRates.percentage, taxRates.category, taxRates.exemptionReason, taxRates.withholding
+            FROM (SELECT 1)
+            LEFT JOIN taxRates ON taxRates.id = ?
+            "#,
+        )
+        .bind(nanoid::nanoid!())
+        .bind(line_item_id)
+        .bind(&tax.tax_rate)
+        .bind(tax.compound)
+        .bind(position as i64)
+        .bind(&tax.tax_rate)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn delete_line_item_taxes(
+    conn: &mut SqliteConnection,
+    table: &str,
+    parent_column: &str,
+    parent_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!(
+        "DELETE FROM lineItemTaxes WHERE lineItemId IN (SELECT id FROM {} WHERE {} = ?)",
+        table, parent_column
+    ))
+    .bind(parent_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn delete_line_items(conn: &mut SqliteConnection, invoice_id: &str) -> Result<(), sqlx::Error> {
+    delete_line_item_taxes(&mut *conn, "invoiceLineItems", "invoiceId", invoice_id).await?;
+    sqlx::query("DELETE FROM invoiceLineItems WHERE invoiceId = ?")
+        .bind(invoice_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn insert_line_items(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    invoice_id: &str,
+    line_items: &[CreateInvoiceLineItemRequest],
+) -> Result<(), sqlx::Error> {
+    for line_item in line_items {
+        let line_item_id = nanoid::nanoid!();
+        let taxes = line_item.line_taxes();
+        let description = line_item_description(&mut *conn, organization_id, line_item).await?;
+        let (discount, discount_amount) = discount_columns(line_item.discount());
+        sqlx::query(
+            r#"
+            INSERT INTO invoiceLineItems (
+                id, invoiceId, description, quantity, unitCode, unitPrice, discount, discountAmount, discountKind,
+                taxRate, itemId
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&line_item_id)
+        .bind(invoice_id)
+        .bind(&description)
+        .bind(&line_item.quantity)
+        .bind(&line_item.unit_code)
+        .bind(&line_item.unit_price)
+        .bind(discount)
+        .bind(discount_amount)
+        .bind(line_item.discount_kind)
+        .bind(taxes.first().map(|tax| &tax.tax_rate))
+        .bind(&line_item.item_id)
+        .execute(&mut *conn)
+        .await?;
+
+        insert_line_item_taxes(&mut *conn, &line_item_id, &taxes).await?;
+    }
+
+    Ok(())
+}
+
+// Payment reference for an invoice number in the organization's format.
+// Credit notes aren't paid, so they don't get one.
+async fn derive_payment_reference(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    kind: InvoiceKind,
+    number: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    if kind == InvoiceKind::CreditNote {
+        return Ok(None);
+    }
+
+    let format = sqlx::query_scalar::<_, Option<String>>("SELECT payment_reference_format FROM organizations WHERE id = ?")
+        .bind(organization_id)
+        .fetch_optional(conn)
+        .await?
+        .flatten();
+
+    PaymentReferenceFormat::parse(format.as_deref())?
+        .derive(number)
+        .map_err(|e| sqlx::Error::Protocol(format!("Payment reference can't be derived: {}", e)))
+}
+
+// Supplies to a business in another EU country are invoiced with reverse
+// charge. The client's VAT number must have been verified in VIES.
+async fn applies_reverse_charge(conn: &mut SqliteConnection, invoice: &CreateInvoiceRequest) -> Result<bool, sqlx::Error> {
+    let organization_country = sqlx::query_scalar::<_, Option<String>>("SELECT country FROM organizations WHERE id = ?")
+        .bind(&invoice.organization_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten();
+    let client = sqlx::query_as::<_, (Option<String>, Option<bool>)>("SELECT country, vatin_valid FROM clients WHERE id = ?")
+        .bind(&invoice.client_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some((client_country, vatin_valid)) = client else {
+        return Ok(false);
+    };
+
+    let organization_country = organization_country.as_deref().and_then(country_code);
+    let client_country = client_country.as_deref().and_then(country_code);
+    Ok(match (organization_country, client_country) {
+        (Some(seller), Some(buyer)) => {
+            seller != buyer && is_eu_member_state(seller) && is_eu_member_state(buyer) && vatin_valid == Some(true)
+        }
+        _ => false,
+    })
+}
+
+// The organization's reverse charge rate, its default one when there are several
+fn reverse_charge_tax_rate(tax_rates: &[TaxRate]) -> Result<&TaxRate, sqlx::Error> {
+    tax_rates
+        .iter()
+        .filter(|tax_rate| tax_rate.category == TaxCategory::ReverseCharge)
+        .max_by_key(|tax_rate| tax_rate.is_default.unwrap_or(0))
+        .ok_or_else(|| {
+            sqlx::Error::Protocol(
+                "This client is invoiced with reverse charge, add a tax rate in the reverse charge category first".into(),
+            )
+        })
+}
+
+// Rounds quantities to the organization's precision and checks unit codes,
+// before anything is totalled or stored
+pub(crate) async fn normalize_line_items(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    line_items: &mut [CreateInvoiceLineItemRequest],
+) -> Result<(), sqlx::Error> {
+    let precision = sqlx::query_scalar::<_, i64>("SELECT quantity_precision FROM organizations WHERE id = ?")
+        .bind(organization_id)
+        .fetch_optional(conn)
+        .await?
+        .unwrap_or(2);
+
+    for (i, line_item) in line_items.iter_mut().enumerate() {
+        line_item.quantity = round_quantity(line_item.quantity, precision.max(0) as u32);
+        line_item.unit_code = match line_item.unit_code.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(code) => Some(
+                validate_unit_code(code)
+                    .map_err(|e| sqlx::Error::Protocol(format!("Line item {}: {}", i + 1, e)))?,
+            ),
+        };
+    }
+
+    Ok(())
+}
+
+// The organization's defaults, overridden by whatever is set
+pub(crate) async fn resolve_tax_mode(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    prices_include_tax: Option<bool>,
+    tax_rounding: Option<TaxRounding>,
+) -> Result<TaxMode, sqlx::Error> {
+    let (default_prices_include_tax, default_tax_rounding) = sqlx::query_as::<_, (bool, TaxRounding)>(
+        "SELECT prices_include_tax, tax_rounding FROM organizations WHERE id = ?"
+    )
+    .bind(organization_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .unwrap_or_default();
+
+    Ok(TaxMode {
+        prices_include_tax: prices_include_tax.unwrap_or(default_prices_include_tax),
+        rounding: tax_rounding.unwrap_or(default_tax_rounding),
+    })
+}
+
+// Numbers, totals and inserts an invoice or credit note with its line items.
+// Must run inside an immediate transaction, see `allocate_invoice_number`.
+pub(crate) async fn insert_invoice(
+    conn: &mut SqliteConnection,
+    invoice: &CreateInvoiceRequest,
+    kind: InvoiceKind,
+    credited_invoice_id: Option<&str>,
+) -> Result<InvoiceTotals, sqlx::Error> {
+    // Later states are reached through `transition_invoice_state`, e.g. paid from its payments
+    if !matches!(invoice.state, InvoiceState::Draft | InvoiceState::Sent) {
+        return Err(sqlx::Error::Protocol(format!(
+            "Invoices are created as draft or sent, not {}",
+            invoice.state.as_str()
+        )));
+    }
+
+    let tax_rates = sqlx::query_as::<_, TaxRate>("SELECT * FROM taxRates WHERE organizationId = ?")
+        .bind(&invoice.organization_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    // Taxed lines switch to reverse charge, exempt and out of scope ones stay as they are.
+    // Credit notes keep the treatment of the invoice they credit.
+    let mut line_items = invoice.line_items.clone();
+    normalize_line_items(&mut *conn, &invoice.organization_id, &mut line_items).await?;
+    if kind == InvoiceKind::Invoice && applies_reverse_charge(&mut *conn, invoice).await? {
+        let reverse_charge = reverse_charge_tax_rate(&tax_rates)?;
+        for line_item in &mut line_items {
+            let taxed = line_item.line_taxes().iter().all(|tax| {
+                let category = tax_rates
+                    .iter()
+                    .find(|tax_rate| tax_rate.id == tax.tax_rate)
+                    .map(|tax_rate| tax_rate.category);
+                matches!(category, None | Some(TaxCategory::Standard) | Some(TaxCategory::ZeroRated))
+            });
+            if taxed {
+                // Withholding is income tax and stays on the line
+                let mut taxes = vec![LineItemTax { tax_rate: reverse_charge.id.clone(), compound: false }];
+                taxes.extend(line_item.line_taxes().into_iter().filter(|tax| {
+                    tax_rates.iter().any(|tax_rate| tax_rate.id == tax.tax_rate && tax_rate.withholding)
+                }));
+                line_item.tax_rate = Some(reverse_charge.id.clone());
+                line_item.taxes = if taxes.len() > 1 { taxes } else { Vec::new() };
+            }
+        }
+    }
+    let mode = resolve_tax_mode(
+        &mut *conn,
+        &invoice.organization_id,
+        invoice.prices_include_tax,
+        invoice.tax_rounding,
+    )
+    .await?;
+    let discount = Discount::new(invoice.discount, invoice.discount_kind);
+    let totals = calculate_invoice_totals(&line_items, &tax_rates, mode, discount, &invoice.currency)?;
+
+    let number = allocate_invoice_number(
+        &mut *conn,
+        kind.number_series(),
+        &invoice.organization_id,
+        &invoice.client_id,
+        invoice.date,
+    )
+    .await?;
+    let payment_reference =
+        derive_payment_reference(&mut *conn, &invoice.organization_id, kind, &number.number).await?;
+
+    // Insert invoice
+    sqlx::query(
+        r#"
+        INSERT INTO invoices (
+            id, organizationId, kind, creditedInvoiceId, number, state, clientId, date, dueDate,
+            currency, customerNotes, overdueCharge, paymentReference, pricesIncludeTax, taxRounding,
+            discount, discountAmount, discountKind, discountTotal, withholdingTotal, total, taxTotal, subTotal,
+            numberSequence, numberPeriod
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&invoice.id)
+    .bind(&invoice.organization_id)
+    .bind(kind)
+    .bind(credited_invoice_id)
+    .bind(&number.number)
+    .bind(&invoice.state)
+    .bind(&invoice.client_id)
+    .bind(&invoice.date)
+    .bind(&invoice.due_date)
+    .bind(&invoice.currency)
+    .bind(&invoice.customer_notes)
+    .bind(&invoice.overdue_charge)
+    .bind(&payment_reference)
+    .bind(mode.prices_include_tax)
+    .bind(mode.rounding)
+    .bind(discount_columns(discount).0)
+    .bind(discount_columns(discount).1)
+    .bind(invoice.discount_kind)
+    .bind(totals.discount_total.amount_minor)
+    .bind(totals.withholding_total.amount_minor)
+    .bind(totals.total.amount_minor)
+    .bind(totals.tax_total.amount_minor)
+    .bind(totals.sub_total.amount_minor)
+    .bind(number.sequence)
+    .bind(&number.period)
+    .execute(&mut *conn)
+    .await?;
+
+    insert_line_items(&mut *conn, &invoice.organization_id, &invoice.id, &line_items).await?;
+
+    if invoice.state == InvoiceState::Sent {
+        snapshot_exchange_rate(&mut *conn, &invoice.id).await?;
+    }
+
+    record_invoice_state(conn, &invoice.id, None, invoice.state, &current_user(), None).await?;
+
+    Ok(totals)
+}
+
 impl Database {
     pub async fn get_invoices(&self, organization_id: &str) -> Result<Vec<Invoice>, sqlx::Error> {
-        sqlx::query_as::<_, Invoice>(
+        sqlx::query_as::<_, Invoice>(&format!(
             r#"
             SELECT
                 invoices.*,
-                clients.name AS clientName
+                clients.name AS clientName,
+                {}
             FROM
                 invoices
             INNER JOIN
@@ -135,18 +1255,20 @@ impl Database {
             ORDER BY
                 invoices.date DESC
             "#,
-        )
+            INVOICE_BALANCE_COLUMNS
+        ))
         .bind(organization_id)
         .fetch_all(&self.pool)
         .await
     }
 
     pub async fn get_invoice(&self, invoice_id: &str) -> Result<Option<Invoice>, sqlx::Error> {
-        sqlx::query_as::<_, Invoice>(
+        sqlx::query_as::<_, Invoice>(&format!(
             r#"
             SELECT
                 invoices.*,
-                clients.name AS clientName
+                clients.name AS clientName,
+                {}
             FROM
                 invoices
             INNER JOIN
@@ -155,83 +1277,151 @@ impl Database {
                 invoices.id = ?
             LIMIT 1
             "#,
-        )
+            INVOICE_BALANCE_COLUMNS
+        ))
         .bind(invoice_id)
         .fetch_optional(&self.pool)
         .await
     }
 
-    pub async fn get_invoice_line_items(&self, invoice_id: &str) -> Result<Vec<InvoiceLineItem>, sqlx::Error> {
-        sqlx::query_as::<_, InvoiceLineItem>(
+    // Invoice an incoming payment's reference points to, ignoring spacing and case
+    pub async fn find_invoice_by_payment_reference(
+        &self,
+        organization_id: &str,
+        reference: &str,
+    ) -> Result<Option<Invoice>, sqlx::Error> {
+        let reference = normalize_reference(reference);
+        if reference.is_empty() {
+            return Ok(None);
+        }
+
+        sqlx::query_as::<_, Invoice>(&format!(
             r#"
-            SELECT *
-            FROM invoiceLineItems
-            WHERE invoiceId = ?
-            ORDER BY createdAt ASC
+            SELECT
+                invoices.*,
+                clients.name AS clientName,
+                {}
+            FROM
+                invoices
+            INNER JOIN
+                clients ON invoices.clientId = clients.id
+            WHERE
+                invoices.organizationId = ? AND invoices.paymentReference = ?
+            ORDER BY
+                invoices.date DESC
+            LIMIT 1
             "#,
-        )
-        .bind(invoice_id)
-        .fetch_all(&self.pool)
+            INVOICE_BALANCE_COLUMNS
+        ))
+        .bind(organization_id)
+        .bind(&reference)
+        .fetch_optional(&self.pool)
         .await
     }
 
+    pub async fn get_invoice_line_items(&self, invoice_id: &str) -> Result<Vec<InvoiceLineItem>, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_line_items(&mut conn, invoice_id).await
+    }
+
+    pub async fn get_invoice_totals(
+        &self,
+        organization_id: &str,
+        line_items: &[CreateInvoiceLineItemRequest],
+        prices_include_tax: Option<bool>,
+        tax_rounding: Option<TaxRounding>,
+        discount: Option<Discount>,
+        currency: &str,
+    ) -> Result<InvoiceTotals, sqlx::Error> {
+        let tax_rates = self.get_tax_rates(organization_id).await?;
+        let mut conn = self.pool.acquire().await?;
+        let mut line_items = line_items.to_vec();
+        normalize_line_items(&mut conn, organization_id, &mut line_items).await?;
+        let mode = resolve_tax_mode(&mut conn, organization_id, prices_include_tax, tax_rounding).await?;
+        calculate_invoice_totals(&line_items, &tax_rates, mode, discount, currency)
+    }
+
     pub async fn create_invoice(&self, invoice: CreateInvoiceRequest) -> Result<Invoice, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
+        // Take the write lock up front so concurrent invoices are numbered one after another
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
 
-        // Insert invoice
-        sqlx::query(
-            r#"
-            INSERT INTO invoices (
-                id, organizationId, number, state, clientId, date, dueDate, 
-                currency, customerNotes, overdueCharge, total, taxTotal, subTotal
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&invoice.id)
-        .bind(&invoice.organization_id)
-        .bind(&invoice.number)
-        .bind(&invoice.state)
-        .bind(&invoice.client_id)
-        .bind(&invoice.date)
-        .bind(&invoice.due_date)
-        .bind(&invoice.currency)
-        .bind(&invoice.customer_notes)
-        .bind(&invoice.overdue_charge)
-        .bind(&invoice.total)
-        .bind(&invoice.tax_total)
-        .bind(&invoice.sub_total)
-        .execute(&mut *tx)
-        .await?;
+        insert_invoice(&mut tx, &invoice, InvoiceKind::Invoice, None).await?;
 
-        // Insert line items
-        for line_item in &invoice.line_items {
-            let line_item_id = nanoid::nanoid!();
-            sqlx::query(
-                r#"
-                INSERT INTO invoiceLineItems (id, invoiceId, description, quantity, unitPrice, taxRate)
-                VALUES (?, ?, ?, ?, ?, ?)
-                "#,
-            )
-            .bind(&line_item_id)
-            .bind(&invoice.id)
-            .bind(&line_item.description)
-            .bind(&line_item.quantity)
-            .bind(&line_item.unit_price)
-            .bind(&line_item.tax_rate)
-            .execute(&mut *tx)
-            .await?;
+        tx.commit().await?;
+
+        self.get_invoice(&invoice.id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn create_credit_note(&self, credit_note: CreateCreditNoteRequest) -> Result<Invoice, sqlx::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let original = fetch_invoice(&mut tx, &credit_note.invoice_id).await?;
+        if original.kind != InvoiceKind::Invoice {
+            return Err(sqlx::Error::Protocol("Only invoices can be credited".into()));
+        }
+        if matches!(original.state, InvoiceState::Draft | InvoiceState::Void) {
+            return Err(sqlx::Error::Protocol(
+                "Only issued invoices can be credited. Edit or delete the draft instead.".into(),
+            ));
         }
 
-        // Increment the invoice counter for the organization
-        sqlx::query("UPDATE organizations SET invoice_number_counter = invoice_number_counter + 1 WHERE id = ?")
-            .bind(&invoice.organization_id)
-            .execute(&mut *tx)
-            .await?;
+        // A fixed invoice discount only carries over when everything is credited
+        let discount = match (&credit_note.line_items, original.discount()) {
+            (Some(_), Some(Discount::Amount(_))) => None,
+            (_, discount) => discount,
+        };
+        let line_items = match credit_note.line_items {
+            Some(line_items) => line_items,
+            None => fetch_line_items(&mut tx, &original.id)
+                .await?
+                .into_iter()
+                .map(|line_item| CreateInvoiceLineItemRequest {
+                    quantity: -line_item.quantity,
+                    ..line_item.into()
+                })
+                .collect(),
+        };
+
+        let request = CreateInvoiceRequest {
+            id: credit_note.id,
+            organization_id: original.organization_id.clone(),
+            state: InvoiceState::Draft,
+            client_id: original.client_id.clone(),
+            date: credit_note.date,
+            due_date: None,
+            currency: original.currency.clone(),
+            customer_notes: credit_note.customer_notes,
+            overdue_charge: None,
+            prices_include_tax: Some(original.prices_include_tax),
+            tax_rounding: Some(original.tax_rounding),
+            discount: discount.map(|discount| discount.value()),
+            discount_kind: original.discount_kind,
+            line_items,
+        };
+
+        let totals = insert_invoice(&mut tx, &request, InvoiceKind::CreditNote, Some(&original.id)).await?;
+        if totals.total.amount_minor >= 0 {
+            return Err(sqlx::Error::Protocol("Credit note total must be negative".into()));
+        }
+
+        // Credit notes may not add up to more than the invoice they correct
+        let credited_total = sqlx::query_scalar::<_, i64>(
+            "SELECT COALESCE(SUM(total), 0) FROM invoices WHERE creditedInvoiceId = ? AND state != 'void'"
+        )
+        .bind(&original.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if -credited_total > original.total.amount_minor {
+            return Err(sqlx::Error::Protocol(format!(
+                "Credit notes would total {} but invoice {} is only {}",
+                Money::new(-credited_total, &original.currency), original.number, original.total
+            )));
+        }
 
         tx.commit().await?;
 
-        self.get_invoice(&invoice.id).await?
+        self.get_invoice(&request.id).await?
             .ok_or_else(|| sqlx::Error::RowNotFound)
     }
 
@@ -240,67 +1430,149 @@ impl Database {
         invoice_id: &str,
         updates: UpdateInvoiceRequest,
     ) -> Result<Invoice, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
+        // Checks the current state before it writes, see `update_invoice_state`
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let current = fetch_invoice(&mut tx, invoice_id).await?;
+        let mut line_items = updates.line_items;
+        let mode = TaxMode {
+            prices_include_tax: updates.prices_include_tax.unwrap_or(current.prices_include_tax),
+            rounding: updates.tax_rounding.unwrap_or(current.tax_rounding),
+        };
+        let discount = Discount::new(
+            updates.discount.or(current.discount),
+            updates.discount_kind.unwrap_or(current.discount_kind),
+        );
+
+        // Numbers come from the organization's series, a draft keeps the one it was given
+        if updates.number.as_ref().is_some_and(|number| *number != current.number) {
+            return Err(sqlx::Error::Protocol(
+                "Invoice numbers are assigned from the numbering series and can't be changed".into(),
+            ));
+        }
+
+        // Issued invoices are immutable apart from their notes and state
+        if current.state != InvoiceState::Draft {
+            if updates.client_id.as_ref().is_some_and(|client_id| *client_id != current.client_id) {
+                return Err(locked_invoice_error("client"));
+            }
+            if updates.date.is_some_and(|date| date != current.date) {
+                return Err(locked_invoice_error("date"));
+            }
+            if updates.due_date.is_some() && updates.due_date != current.due_date {
+                return Err(locked_invoice_error("due date"));
+            }
+            if updates.currency.as_ref().is_some_and(|currency| *currency != current.currency) {
+                return Err(locked_invoice_error("currency"));
+            }
+            if mode != current.tax_mode() {
+                return Err(locked_invoice_error("tax settings"));
+            }
+            if discount != current.discount() {
+                return Err(locked_invoice_error("discount"));
+            }
+            if let Some(updated_line_items) = &line_items {
+                if !same_line_items(&fetch_line_items(&mut tx, invoice_id).await?, updated_line_items) {
+                    return Err(locked_invoice_error("line items"));
+                }
+                // Unchanged, keep the stored line items and totals as they are
+                line_items = None;
+            }
+        }
+        if let Some(line_items) = &mut line_items {
+            normalize_line_items(&mut tx, &current.organization_id, line_items).await?;
+        }
+
+        // Recalculate totals when the line items are replaced, their prices read differently
+        // or the discount changes
+        let retotalled_line_items = match &line_items {
+            Some(line_items) => Some(line_items.clone()),
+            None if mode != current.tax_mode() || discount != current.discount() => Some(
+                fetch_line_items(&mut tx, invoice_id)
+                    .await?
+                    .into_iter()
+                    .map(CreateInvoiceLineItemRequest::from)
+                    .collect::<Vec<_>>(),
+            ),
+            None => None,
+        };
+        let totals = match &retotalled_line_items {
+            Some(line_items) => {
+                let tax_rates = sqlx::query_as::<_, TaxRate>(
+                    r#"
+                    SELECT taxRates.*
+                    FROM taxRates
+                    INNER JOIN invoices ON invoices.organizationId = taxRates.organizationId
+                    WHERE invoices.id = ?
+                    "#,
+                )
+                .bind(invoice_id)
+                .fetch_all(&mut *tx)
+                .await?;
+                let currency = updates.currency.as_deref().unwrap_or(&current.currency);
+                Some(calculate_invoice_totals(line_items, &tax_rates, mode, discount, currency)?)
+            }
+            None => None,
+        };
+
+        if let Some(state) = updates.state {
+            transition_invoice_state(&mut tx, invoice_id, state, &current_user(), None).await?;
+
+            // Issuing or voiding a credit note changes what is owed on the credited invoice
+            if let Some(credited_invoice_id) = &current.credited_invoice_id {
+                sync_invoice_payment_state(&mut tx, credited_invoice_id).await?;
+            }
+        }
 
         // Update invoice fields (using all fields with Option checks)
         sqlx::query(
             r#"
             UPDATE invoices
-            SET number = COALESCE(?, number),
-                state = COALESCE(?, state),
-                clientId = COALESCE(?, clientId),
+            SET clientId = COALESCE(?, clientId),
                 date = COALESCE(?, date),
                 dueDate = COALESCE(?, dueDate),
                 currency = COALESCE(?, currency),
                 customerNotes = COALESCE(?, customerNotes),
                 overdueCharge = COALESCE(?, overdueCharge),
+                pricesIncludeTax = COALESCE(?, pricesIncludeTax),
+                taxRounding = COALESCE(?, taxRounding),
+                discount = ?,
+                discountAmount = ?,
+                discountKind = ?,
+                discountTotal = COALESCE(?, discountTotal),
+                withholdingTotal = COALESCE(?, withholdingTotal),
                 total = COALESCE(?, total),
                 taxTotal = COALESCE(?, taxTotal),
                 subTotal = COALESCE(?, subTotal)
             WHERE id = ?
             "#,
         )
-        .bind(&updates.number)
-        .bind(&updates.state)
         .bind(&updates.client_id)
         .bind(&updates.date)
         .bind(&updates.due_date)
         .bind(&updates.currency)
         .bind(&updates.customer_notes)
         .bind(&updates.overdue_charge)
-        .bind(&updates.total)
-        .bind(&updates.tax_total)
-        .bind(&updates.sub_total)
+        .bind(updates.prices_include_tax)
+        .bind(updates.tax_rounding)
+        .bind(discount_columns(discount).0)
+        .bind(discount_columns(discount).1)
+        .bind(discount.map_or(current.discount_kind, |discount| discount.kind()))
+        .bind(totals.as_ref().map(|totals| totals.discount_total.amount_minor))
+        .bind(totals.as_ref().map(|totals| totals.withholding_total.amount_minor))
+        .bind(totals.as_ref().map(|totals| totals.total.amount_minor))
+        .bind(totals.as_ref().map(|totals| totals.tax_total.amount_minor))
+        .bind(totals.as_ref().map(|totals| totals.sub_total.amount_minor))
         .bind(invoice_id)
         .execute(&mut *tx)
         .await?;
 
         // Update line items if provided
-        if let Some(line_items) = updates.line_items {
+        if let Some(line_items) = line_items {
             // Delete existing line items
-            sqlx::query("DELETE FROM invoiceLineItems WHERE invoiceId = ?")
-                .bind(invoice_id)
-                .execute(&mut *tx)
-                .await?;
+            delete_line_items(&mut tx, invoice_id).await?;
 
-            // Insert new line items
-            for line_item in &line_items {
-                let line_item_id = nanoid::nanoid!();
-                sqlx::query(
-                    r#"
-                    INSERT INTO invoiceLineItems (id, invoiceId, description, quantity, unitPrice, taxRate)
-                    VALUES (?, ?, ?, ?, ?, ?)
-                    "#,
-                )
-                .bind(&line_item_id)
-                .bind(invoice_id)
-                .bind(&line_item.description)
-                .bind(&line_item.quantity)
-                .bind(&line_item.unit_price)
-                .bind(&line_item.tax_rate)
-                .execute(&mut *tx)
-                .await?;
-            }
+            insert_line_items(&mut tx, &current.organization_id, invoice_id, &line_items).await?;
         }
 
         tx.commit().await?;
@@ -312,20 +1584,22 @@ impl Database {
     pub async fn update_invoice_state(
         &self,
         invoice_id: &str,
-        state: &str,
+        state: InvoiceState,
+        note: Option<&str>,
     ) -> Result<Invoice, sqlx::Error> {
-        // Update only the state field
-        sqlx::query(
-            r#"
-            UPDATE invoices
-            SET state = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(state)
-        .bind(invoice_id)
-        .execute(&self.pool)
-        .await?;
+        // Reads before it writes, so take the write lock up front and wait for
+        // it rather than fail when another write is still finishing
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        transition_invoice_state(&mut tx, invoice_id, state, &current_user(), note).await?;
+
+        // Issuing or voiding a credit note changes what is owed on the credited invoice
+        let invoice = fetch_invoice(&mut tx, invoice_id).await?;
+        if let Some(credited_invoice_id) = &invoice.credited_invoice_id {
+            sync_invoice_payment_state(&mut tx, credited_invoice_id).await?;
+        }
+
+        tx.commit().await?;
 
         // Return the updated invoice
         self.get_invoice(invoice_id).await?
@@ -333,13 +1607,36 @@ impl Database {
     }
 
     pub async fn delete_invoice(&self, invoice_id: &str) -> Result<bool, sqlx::Error> {
-        let mut tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let numbering = sqlx::query_as::<_, (String, InvoiceKind, InvoiceState, Option<i64>, Option<String>)>(
+            "SELECT organizationId, kind, state, numberSequence, numberPeriod FROM invoices WHERE id = ?"
+        )
+        .bind(invoice_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // Issued invoices must stay on record, they can only be voided or credited
+        if numbering.as_ref().is_some_and(|(_, _, state, _, _)| *state != InvoiceState::Draft) {
+            return Err(sqlx::Error::Protocol(
+                "Only draft invoices can be deleted. Void the invoice or create a credit note instead.".into(),
+            ));
+        }
+
+        // Hand the number back. Only the latest number can go back to the series,
+        // deleting an earlier draft would leave a gap.
+        if let Some((organization_id, kind, _, Some(sequence), period)) = numbering {
+            if !release_invoice_number(&mut tx, kind.number_series(), &organization_id, sequence, period.as_deref())
+                .await?
+            {
+                return Err(sqlx::Error::Protocol(
+                    "Only the latest numbered draft can be deleted, deleting this one would leave a gap in the numbering. Reuse the draft instead.".into(),
+                ));
+            }
+        }
 
         // Delete line items first (foreign key constraint)
-        sqlx::query("DELETE FROM invoiceLineItems WHERE invoiceId = ?")
-            .bind(invoice_id)
-            .execute(&mut *tx)
-            .await?;
+        delete_line_items(&mut tx, invoice_id).await?;
 
         // Delete invoice
         let result = sqlx::query("DELETE FROM invoices WHERE id = ?")
@@ -351,4 +1648,145 @@ impl Database {
 
         Ok(result.rows_affected() > 0)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tax_rate(id: &str, percentage: f64, withholding: bool) -> TaxRate {
+        TaxRate {
+            id: id.to_string(),
+            organization_id: "org".to_string(),
+            name: id.to_uppercase(),
+            description: None,
+            percentage,
+            is_default: None,
+            category: TaxCategory::Standard,
+            exemption_reason: None,
+            compound: false,
+            withholding,
+        }
+    }
+
+    fn line(quantity: f64, unit_price: i64, taxes: &[(&str, bool)]) -> CreateInvoiceLineItemRequest {
+        CreateInvoiceLineItemRequest {
+            description: None,
+            quantity,
+            unit_code: None,
+            unit_price,
+            discount: None,
+            discount_kind: DiscountKind::Percentage,
+            tax_rate: None,
+            taxes: taxes
+                .iter()
+                .map(|(tax_rate, compound)| LineItemTax { tax_rate: tax_rate.to_string(), compound: *compound })
+                .collect(),
+            item_id: None,
+        }
+    }
+
+    fn mode(prices_include_tax: bool, rounding: TaxRounding) -> TaxMode {
+        TaxMode { prices_include_tax, rounding }
+    }
+
+    #[test]
+    fn totals_lines_by_tax_rate() {
+        let tax_rates = [tax_rate("vat", 22.0, false), tax_rate("low", 9.0, false)];
+        let lines = [line(2.0, 1250, &[("vat", false)]), line(1.5, 333, &[("low", false)]), line(1.0, 1000, &[])];
+
+        let totals = calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerTotal), None, "EUR").unwrap();
+        assert_eq!((totals.sub_total.amount_minor, totals.tax_total.amount_minor, totals.total.amount_minor), (4000, 595, 4595));
+        let taxes = totals
+            .taxes
+            .iter()
+            .map(|tax| (tax.tax_rate_id.as_str(), tax.taxable_amount, tax.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(taxes, [("vat", 2500, 550), ("low", 500, 45)]);
+        assert_eq!(totals.lines.iter().map(|line| line.net).collect::<Vec<_>>(), [2500, 500, 1000]);
+    }
+
+    #[test]
+    fn charges_compound_taxes_on_earlier_taxes() {
+        let tax_rates = [tax_rate("gst", 5.0, false), tax_rate("qst", 9.975, false)];
+        let lines = [line(1.0, 10000, &[("gst", false), ("qst", true)]), line(1.0, 2000, &[("qst", false)])];
+
+        let totals = calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerTotal), None, "EUR").unwrap();
+        assert_eq!((totals.sub_total.amount_minor, totals.tax_total.amount_minor, totals.total.amount_minor), (12000, 1747, 13747));
+        let taxes = totals
+            .taxes
+            .iter()
+            .map(|tax| (tax.tax_rate_id.as_str(), tax.compound, tax.taxable_amount, tax.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(taxes, [("gst", false, 10000, 500), ("qst", true, 10500, 1047), ("qst", false, 2000, 200)]);
+    }
+
+    #[test]
+    fn rounds_tax_per_line_or_per_total() {
+        let tax_rates = [tax_rate("vat", 22.0, false)];
+        let lines = vec![line(1.0, 333, &[("vat", false)]); 3];
+
+        let per_line = calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerLine), None, "EUR").unwrap();
+        assert_eq!((per_line.sub_total.amount_minor, per_line.tax_total.amount_minor, per_line.total.amount_minor), (999, 219, 1218));
+
+        let per_total = calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerTotal), None, "EUR").unwrap();
+        assert_eq!((per_total.sub_total.amount_minor, per_total.tax_total.amount_minor, per_total.total.amount_minor), (999, 220, 1219));
+        assert_eq!(per_total.taxes.len(), 1);
+        assert_eq!(per_total.taxes[0].taxable_amount, 999);
+    }
+
+    #[test]
+    fn backs_compound_taxes_out_of_inclusive_prices() {
+        let tax_rates = [tax_rate("gst", 5.0, false), tax_rate("qst", 9.975, false)];
+        let lines = [line(1.0, 11500, &[("gst", false), ("qst", true)])];
+
+        let totals = calculate_invoice_totals(&lines, &tax_rates, mode(true, TaxRounding::PerTotal), None, "EUR").unwrap();
+        assert_eq!((totals.sub_total.amount_minor, totals.tax_total.amount_minor, totals.total.amount_minor), (9959, 1541, 11500));
+        let taxes = totals
+            .taxes
+            .iter()
+            .map(|tax| (tax.tax_rate_id.as_str(), tax.compound, tax.taxable_amount, tax.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(taxes, [("gst", false, 9959, 498), ("qst", true, 10457, 1043)]);
+        assert_eq!(totals.lines[0].net, 9959);
+    }
+
+    #[test]
+    fn takes_discounts_off_before_tax() {
+        let tax_rates = [tax_rate("vat", 20.0, false)];
+        let mut first = line(2.0, 5000, &[("vat", false)]);
+        first.discount = Some(10.0);
+        let mut second = line(1.0, 1000, &[("vat", false)]);
+        second.discount = Some(100.0);
+        second.discount_kind = DiscountKind::Amount;
+        let lines = [first, second];
+
+        for discount in [
+            Discount::new(Some(10.0), DiscountKind::Percentage),
+            Discount::new(Some(990.0), DiscountKind::Amount),
+        ] {
+            let totals = calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerTotal), discount, "EUR").unwrap();
+            assert_eq!((totals.sub_total.amount_minor, totals.tax_total.amount_minor, totals.total.amount_minor), (8910, 1782, 10692));
+            assert_eq!((totals.discount_total.amount_minor, totals.invoice_discount.amount_minor), (2090, 990));
+            let nets = totals.lines.iter().map(|line| (line.discount, line.invoice_discount, line.net)).collect::<Vec<_>>();
+            assert_eq!(nets, [(1000, 900, 8100), (100, 90, 810)]);
+        }
+
+        let too_much = Discount::new(Some(101.0), DiscountKind::Percentage);
+        assert!(calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerTotal), too_much, "EUR").is_err());
+    }
+
+    #[test]
+    fn withholds_income_tax_from_the_total() {
+        let tax_rates = [tax_rate("vat", 21.0, false), tax_rate("irpf", 15.0, true)];
+        let lines = [line(1.0, 100000, &[("vat", false), ("irpf", false)])];
+
+        let totals = calculate_invoice_totals(&lines, &tax_rates, mode(false, TaxRounding::PerTotal), None, "EUR").unwrap();
+        assert_eq!((totals.sub_total.amount_minor, totals.tax_total.amount_minor, totals.withholding_total.amount_minor), (100000, 21000, 15000));
+        assert_eq!(totals.total.amount_minor, 106000);
+        assert_eq!(totals.withholdings.len(), 1);
+
+        let compound = [line(1.0, 100000, &[("vat", false), ("irpf", true)])];
+        assert!(calculate_invoice_totals(&compound, &tax_rates, mode(false, TaxRounding::PerTotal), None, "EUR").is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::{DateTime, Datelike, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+
+use super::Database;
+
+// Placeholders supported in `organizations.invoice_number_format`.
+// `{number}` also accepts a zero-padding width, e.g. `{number:5}` -> 00042.
+const NUMBER_VARIABLES: [&str; 7] = ["number", "year", "y", "month", "m", "day", "clientCode"];
+const MONTH_ABBREVIATIONS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Each document series has its own format, counter and period columns on
+// `organizations`. The reset policy is shared by all series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberSeries {
+    Invoice,
+    CreditNote,
+    Estimate,
+}
+
+impl NumberSeries {
+    fn column_prefix(&self) -> &'static str {
+        match self {
+            Self::Invoice => "invoice_number",
+            Self::CreditNote => "credit_note_number",
+            Self::Estimate => "estimate_number",
+        }
+    }
+
+    // Where numbers of the series are kept, unique per organization
+    fn table(&self) -> &'static str {
+        match self {
+            Self::Invoice | Self::CreditNote => "invoices",
+            Self::Estimate => "estimates",
+        }
+    }
+
+    fn default_format(&self) -> &'static str {
+        match self {
+            Self::Invoice => "INV-{year}-{number}",
+            Self::CreditNote => "CN-{year}-{number}",
+            Self::Estimate => "EST-{year}-{number}",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceNumberReset {
+    Never,
+    Yearly,
+    Monthly,
+}
+
+impl InvoiceNumberReset {
+    pub fn parse(value: Option<&str>) -> Result<Self, sqlx::Error> {
+        match value.unwrap_or("never") {
+            "never" => Ok(Self::Never),
+            "yearly" => Ok(Self::Yearly),
+            "monthly" => Ok(Self::Monthly),
+            other => Err(sqlx::Error::Protocol(format!("Invalid invoice number reset: {}", other))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Never => "never",
+            Self::Yearly => "yearly",
+            Self::Monthly => "monthly",
+        }
+    }
+
+    // Key of the numbering period an invoice date falls into, e.g. "2025" or "2025-03"
+    pub fn period(&self, date: &DateTime<Local>) -> Option<String> {
+        match self {
+            Self::Never => None,
+            Self::Yearly => Some(date.format("%Y").to_string()),
+            Self::Monthly => Some(date.format("%Y-%m").to_string()),
+        }
+    }
+}
+
+// An allocated number together with the counter state it was taken from
+#[derive(Debug)]
+pub struct InvoiceNumber {
+    pub number: String,
+    pub sequence: i64,
+    pub period: Option<String>,
+}
+
+#[derive(Debug, FromRow)]
+struct NumberingRow {
+    format: Option<String>,
+    counter: Option<i64>,
+    reset: Option<String>,
+    period: Option<String>,
+}
+
+fn local_date(timestamp_ms: i64) -> Result<DateTime<Local>, sqlx::Error> {
+    Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .ok_or_else(|| sqlx::Error::Protocol(format!("Invalid invoice date: {}", timestamp_ms)))
+}
+
+pub fn validate_invoice_number_format(format: &str) -> Result<(), sqlx::Error> {
+    render_invoice_number(format, 1, 0, "").map(|_| ())
+}
+
+pub fn render_invoice_number(
+    format: &str,
+    sequence: i64,
+    date_ms: i64,
+    client_code: &str,
+) -> Result<String, sqlx::Error> {
+    if format.trim().is_empty() {
+        return Err(sqlx::Error::Protocol("Invoice number format is required".into()));
+    }
+    if !format.contains("{number") {
+        return Err(sqlx::Error::Protocol("Invoice number format must contain {number}".into()));
+    }
+
+    let date = local_date(date_ms)?;
+    let mut result = String::with_capacity(format.len());
+    let mut rest = format;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| sqlx::Error::Protocol(format!("Unclosed variable in invoice number format: {}", format)))?;
+
+        let variable = &rest[start + 1..end];
+        let (name, width) = match variable.split_once(':') {
+            Some((name, width)) => (name, Some(width)),
+            None => (variable, None),
+        };
+        if !NUMBER_VARIABLES.contains(&name) || (width.is_some() && name != "number") {
+            return Err(sqlx::Error::Protocol(format!(
+                "Invalid variable: {{{}}}. Valid variables are: {{{}}}",
+                variable,
+                NUMBER_VARIABLES.join("}, {")
+            )));
+        }
+
+        match name {
+            "number" => {
+                let width = match width {
+                    Some(width) => width
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|width| *width <= 20)
+                        .ok_or_else(|| sqlx::Error::Protocol(format!("Invalid number padding: {}", width)))?,
+                    None => 0,
+                };
+                result.push_str(&format!("{:0width$}", sequence, width = width));
+            }
+            "year" => result.push_str(&date.year().to_string()),
+            "y" => result.push_str(&format!("{:02}", date.year() % 100)),
+            "month" => result.push_str(&format!("{:02}", date.month())),
+            "m" => result.push_str(MONTH_ABBREVIATIONS[date.month0() as usize]),
+            "day" => result.push_str(&format!("{:02}", date.day())),
+            "clientCode" => result.push_str(client_code),
+            _ => unreachable!(),
+        }
+
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+// Works out the next sequence for an invoice date without touching the counter.
+// Returns the sequence and the period it belongs to.
+fn next_sequence(row: &NumberingRow, date_ms: i64) -> Result<(i64, Option<String>), sqlx::Error> {
+    let reset = InvoiceNumberReset::parse(row.reset.as_deref())?;
+    let period = reset.period(&local_date(date_ms)?);
+    let counter = row.counter.unwrap_or(0);
+
+    match (&period, &row.period) {
+        (Some(period), Some(current)) if period < current => Err(sqlx::Error::Protocol(format!(
+            "Invoice date falls in numbering period {} which is already closed (current period is {})",
+            period, current
+        ))),
+        (Some(period), Some(current)) if period == current => Ok((counter + 1, Some(period.clone()))),
+        (Some(period), _) => Ok((1, Some(period.clone()))),
+        (None, _) => Ok((counter + 1, None)),
+    }
+}
+
+// Renders the number for `sequence`, moving past numbers already in use. The
+// counter can fall behind them when the reset policy or format changes, e.g.
+// a yearly reset starting at 1 after years of never resetting.
+async fn next_free_number(
+    conn: &mut SqliteConnection,
+    series: NumberSeries,
+    organization_id: &str,
+    format: &str,
+    mut sequence: i64,
+    date_ms: i64,
+    client_code: &str,
+) -> Result<(i64, String), sqlx::Error> {
+    loop {
+        let number = render_invoice_number(format, sequence, date_ms, client_code)?;
+        let taken = sqlx::query_scalar::<_, bool>(&format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE organizationId = ? AND number = ?)",
+            series.table()
+        ))
+        .bind(organization_id)
+        .bind(&number)
+        .fetch_one(&mut *conn)
+        .await?;
+        if !taken {
+            return Ok((sequence, number));
+        }
+        sequence += 1;
+    }
+}
+
+async fn client_code(conn: &mut SqliteConnection, client_id: &str) -> Result<String, sqlx::Error> {
+    let code = sqlx::query_scalar::<_, Option<String>>("SELECT code FROM clients WHERE id = ?")
+        .bind(client_id)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(code.flatten().unwrap_or_default())
+}
+
+async fn numbering_row(
+    conn: &mut SqliteConnection,
+    series: NumberSeries,
+    organization_id: &str,
+) -> Result<NumberingRow, sqlx::Error> {
+    let prefix = series.column_prefix();
+    sqlx::query_as::<_, NumberingRow>(&format!(
+        r#"
+        SELECT {prefix}_format AS format, {prefix}_counter AS counter,
+            invoice_number_reset AS reset, {prefix}_period AS period
+        FROM organizations
+        WHERE id = ?
+        "#,
+    ))
+    .bind(organization_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(sqlx::Error::RowNotFound)
+}
+
+// Takes the next number from the organization's counter. Must be called inside
+// an immediate transaction so concurrent invoices can't be given the same number.
+pub async fn allocate_invoice_number(
+    conn: &mut SqliteConnection,
+    series: NumberSeries,
+    organization_id: &str,
+    client_id: &str,
+    date_ms: i64,
+) -> Result<InvoiceNumber, sqlx::Error> {
+    let row = numbering_row(&mut *conn, series, organization_id).await?;
+    let format = row.format.clone().unwrap_or_else(|| series.default_format().into());
+    let (sequence, period) = next_sequence(&row, date_ms)?;
+    let code = client_code(&mut *conn, client_id).await?;
+    let (sequence, number) =
+        next_free_number(&mut *conn, series, organization_id, &format, sequence, date_ms, &code).await?;
+
+    let prefix = series.column_prefix();
+    sqlx::query(&format!(
+        r#"
+        UPDATE organizations
+        SET {prefix}_counter = ?,
+            {prefix}_period = COALESCE(?, {prefix}_period)
+        WHERE id = ?
+        "#,
+    ))
+    .bind(sequence)
+    .bind(&period)
+    .bind(organization_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(InvoiceNumber { number, sequence, period })
+}
+
+// Gives back a number when the invoice holding it was the last one allocated
+// in its period, so deleting a mistaken invoice doesn't leave a gap. Returns
+// whether the number was given back.
+pub async fn release_invoice_number(
+    conn: &mut SqliteConnection,
+    series: NumberSeries,
+    organization_id: &str,
+    sequence: i64,
+    period: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let prefix = series.column_prefix();
+    let result = sqlx::query(&format!(
+        r#"
+        UPDATE organizations
+        SET {prefix}_counter = {prefix}_counter - 1
+        WHERE id = ?
+            AND {prefix}_counter = ?
+            AND (? IS NULL OR {prefix}_period = ?)
+        "#,
+    ))
+    .bind(organization_id)
+    .bind(sequence)
+    .bind(period)
+    .bind(period)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+impl Database {
+    pub async fn preview_invoice_number(
+        &self,
+        series: NumberSeries,
+        organization_id: &str,
+        client_id: Option<&str>,
+        date_ms: i64,
+    ) -> Result<String, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let row = numbering_row(&mut conn, series, organization_id).await?;
+        let format = row.format.clone().unwrap_or_else(|| series.default_format().into());
+        let (sequence, _) = next_sequence(&row, date_ms)?;
+        let code = match client_id {
+            Some(client_id) => client_code(&mut conn, client_id).await?,
+            None => String::new(),
+        };
+
+        next_free_number(&mut conn, series, organization_id, &format, sequence, date_ms, &code)
+            .await
+            .map(|(_, number)| number)
+    }
+}
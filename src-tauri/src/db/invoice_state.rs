@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+
+use super::{snapshot_exchange_rate, Database};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum InvoiceState {
+    Draft,
+    Sent,
+    PartiallyPaid,
+    Overdue,
+    Paid,
+    Void,
+}
+
+impl InvoiceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Sent => "sent",
+            Self::PartiallyPaid => "partially_paid",
+            Self::Overdue => "overdue",
+            Self::Paid => "paid",
+            Self::Void => "void",
+        }
+    }
+
+    // Allowed transitions. Once an invoice has left draft it can never go back,
+    // and paid or void invoices are final.
+    pub fn can_transition_to(&self, to: InvoiceState) -> bool {
+        use InvoiceState::*;
+
+        matches!(
+            (self, to),
+            (Draft, Sent | Paid | Void)
+                | (Sent, PartiallyPaid | Overdue | Paid | Void)
+                | (PartiallyPaid, Overdue | Paid | Void)
+                | (Overdue, PartiallyPaid | Paid | Void)
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct InvoiceStateChange {
+    pub id: String,
+    #[serde(rename = "invoiceId")]
+    #[sqlx(rename = "invoiceId")]
+    pub invoice_id: String,
+    #[serde(rename = "fromState")]
+    #[sqlx(rename = "fromState")]
+    pub from_state: Option<InvoiceState>,
+    #[serde(rename = "toState")]
+    #[sqlx(rename = "toState")]
+    pub to_state: InvoiceState,
+    #[serde(rename = "changedBy")]
+    #[sqlx(rename = "changedBy")]
+    pub changed_by: String,
+    pub note: Option<String>,
+    #[serde(rename = "createdAt")]
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<String>,
+}
+
+// The desktop app has no accounts, so changes are attributed to the OS user
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub async fn record_invoice_state(
+    conn: &mut SqliteConnection,
+    invoice_id: &str,
+    from: Option<InvoiceState>,
+    to: InvoiceState,
+    changed_by: &str,
+    note: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO invoiceStateHistory (id, invoiceId, fromState, toState, changedBy, note)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(nanoid::nanoid!())
+    .bind(invoice_id)
+    .bind(from)
+    .bind(to)
+    .bind(changed_by)
+    .bind(note)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Moves an invoice to a new state if the transition is allowed and records it.
+// Setting the state it's already in is a no-op.
+pub async fn transition_invoice_state(
+    conn: &mut SqliteConnection,
+    invoice_id: &str,
+    to: InvoiceState,
+    changed_by: &str,
+    note: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let from = sqlx::query_scalar::<_, InvoiceState>("SELECT state FROM invoices WHERE id = ?")
+        .bind(invoice_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    if from == to {
+        return Ok(());
+    }
+    if !from.can_transition_to(to) {
+        return Err(sqlx::Error::Protocol(format!(
+            "Invoice cannot be moved from {} to {}",
+            from.as_str(),
+            to.as_str()
+        )));
+    }
+
+    sqlx::query("UPDATE invoices SET state = ? WHERE id = ?")
+        .bind(to)
+        .bind(invoice_id)
+        .execute(&mut *conn)
+        .await?;
+
+    // Issued now, its amounts are fixed and so is their value in the
+    // organization's currency
+    if from == InvoiceState::Draft && to != InvoiceState::Void {
+        snapshot_exchange_rate(&mut *conn, invoice_id).await?;
+    }
+
+    record_invoice_state(conn, invoice_id, Some(from), to, changed_by, note).await
+}
+
+impl Database {
+    pub async fn get_invoice_state_history(&self, invoice_id: &str) -> Result<Vec<InvoiceStateChange>, sqlx::Error> {
+        sqlx::query_as::<_, InvoiceStateChange>(
+            r#"
+            SELECT *
+            FROM invoiceStateHistory
+            WHERE invoiceId = ?
+            ORDER BY createdAt ASC, rowid ASC
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+
+use super::{CreateInvoiceLineItemRequest, Database};
+use crate::units::validate_unit_code;
+
+// A product or service in the organization's catalog. Lines copy what they
+// need from it, so editing an item never changes existing documents.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Item {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    #[sqlx(rename = "organizationId")]
+    pub organization_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    // UN/ECE Recommendation 20 code, e.g. HUR for hours
+    #[serde(rename = "unitCode")]
+    #[sqlx(rename = "unitCode")]
+    pub unit_code: Option<String>,
+    // Default price in minor units of the organization's currency
+    #[serde(rename = "unitPrice")]
+    #[sqlx(rename = "unitPrice")]
+    pub unit_price: i64,
+    #[serde(rename = "taxRateId")]
+    #[sqlx(rename = "taxRateId")]
+    pub tax_rate_id: Option<String>,
+    pub sku: Option<String>,
+    #[serde(rename = "createdAt")]
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateItemRequest {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    pub organization_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "unitCode")]
+    pub unit_code: Option<String>,
+    #[serde(rename = "unitPrice", default)]
+    pub unit_price: i64,
+    #[serde(rename = "taxRateId")]
+    pub tax_rate_id: Option<String>,
+    pub sku: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateItemRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "unitCode")]
+    pub unit_code: Option<String>,
+    #[serde(rename = "unitPrice")]
+    pub unit_price: Option<i64>,
+    #[serde(rename = "taxRateId")]
+    pub tax_rate_id: Option<String>,
+    pub sku: Option<String>,
+}
+
+// Description stored on a line. Lines from the catalog keep what was sent,
+// or copy the item's description or name when it was left empty.
+pub(crate) async fn line_item_description(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    line_item: &CreateInvoiceLineItemRequest,
+) -> Result<Option<String>, sqlx::Error> {
+    let Some(item_id) = &line_item.item_id else {
+        return Ok(line_item.description.clone());
+    };
+
+    let item = sqlx::query_as::<_, Item>("SELECT * FROM items WHERE id = ? AND organizationId = ?")
+        .bind(item_id)
+        .bind(organization_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| sqlx::Error::Protocol("A line item refers to an item that isn't in the catalog".into()))?;
+
+    Ok(line_item
+        .description
+        .clone()
+        .filter(|description| !description.trim().is_empty())
+        .or(item.description)
+        .or(Some(item.name)))
+}
+
+fn item_unit_code(unit_code: Option<&str>) -> Result<Option<String>, sqlx::Error> {
+    match unit_code.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(unit_code) => validate_unit_code(unit_code).map(Some).map_err(sqlx::Error::Protocol),
+    }
+}
+
+impl Database {
+    pub async fn get_items(&self, organization_id: &str) -> Result<Vec<Item>, sqlx::Error> {
+        sqlx::query_as::<_, Item>(
+            r#"
+            SELECT *
+            FROM items
+            WHERE organizationId = ?
+            ORDER BY name ASC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_item(&self, item_id: &str) -> Result<Option<Item>, sqlx::Error> {
+        sqlx::query_as::<_, Item>(
+            r#"
+            SELECT *
+            FROM items
+            WHERE id = ?
+            LIMIT 1
+            "#,
+        )
+        .bind(item_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn validate_item(
+        &self,
+        organization_id: &str,
+        name: &str,
+        tax_rate_id: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        if name.trim().is_empty() {
+            return Err(sqlx::Error::Protocol("Items need a name".into()));
+        }
+        if let Some(tax_rate_id) = tax_rate_id {
+            let tax_rate = self.get_tax_rate(tax_rate_id).await?;
+            if tax_rate.map(|t| t.organization_id) != Some(organization_id.to_string()) {
+                return Err(sqlx::Error::Protocol(
+                    "The item's tax rate doesn't belong to the organization".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn create_item(&self, item: CreateItemRequest) -> Result<Item, sqlx::Error> {
+        self.validate_item(&item.organization_id, &item.name, item.tax_rate_id.as_deref())
+            .await?;
+        let unit_code = item_unit_code(item.unit_code.as_deref())?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, organizationId, name, description, unitCode, unitPrice, taxRateId, sku)
+            VALUES (?, ?, ?, ?, ?, ?, ?, NULLIF(?, ''))
+            "#,
+        )
+        .bind(&item.id)
+        .bind(&item.organization_id)
+        .bind(item.name.trim())
+        .bind(&item.description)
+        .bind(&unit_code)
+        .bind(item.unit_price)
+        .bind(&item.tax_rate_id)
+        .bind(&item.sku)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_item(&item.id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn update_item(&self, item_id: &str, updates: UpdateItemRequest) -> Result<Item, sqlx::Error> {
+        let item = self.get_item(item_id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)?;
+
+        self.validate_item(
+            &item.organization_id,
+            updates.name.as_deref().unwrap_or(&item.name),
+            updates.tax_rate_id.as_deref(),
+        )
+        .await?;
+        let unit_code = item_unit_code(updates.unit_code.as_deref())?;
+
+        // An empty SKU clears it
+        sqlx::query(
+            r#"
+            UPDATE items
+            SET name = COALESCE(?, name),
+                description = COALESCE(?, description),
+                unitCode = COALESCE(?, unitCode),
+                unitPrice = COALESCE(?, unitPrice),
+                taxRateId = COALESCE(?, taxRateId),
+                sku = CASE WHEN ? IS NULL THEN sku ELSE NULLIF(?, '') END
+            WHERE id = ?
+            "#,
+        )
+        .bind(updates.name.as_deref().map(str::trim))
+        .bind(&updates.description)
+        .bind(&unit_code)
+        .bind(updates.unit_price)
+        .bind(&updates.tax_rate_id)
+        .bind(&updates.sku)
+        .bind(&updates.sku)
+        .bind(item_id)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_item(item_id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn delete_item(&self, item_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM items WHERE id = ?")
+            .bind(item_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-use super::Database;
+use super::{validate_invoice_number_format, Database, InvoiceNumberReset, TaxRounding, MAX_QUANTITY_PRECISION};
+use crate::money::minor_unit_exponent;
+use crate::reference::PaymentReferenceFormat;
+use crate::validation::{validate_bic, validate_iban, validate_vatin, FieldError, FieldErrors};
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Organization {
@@ -16,6 +19,7 @@ pub struct Organization {
     pub vatin: Option<String>,
     pub bank_name: Option<String>,
     pub iban: Option<String>,
+    pub bic: Option<String>,
     pub currency: Option<String>,
     pub minimum_fraction_digits: Option<i64>,
     pub due_days: Option<i64>,
@@ -35,7 +39,45 @@ pub struct Organization {
     #[serde(rename = "invoiceNumberCounter")]
     #[sqlx(rename = "invoice_number_counter")]
     pub invoice_number_counter: Option<i64>,
+    #[serde(rename = "invoiceNumberReset")]
+    #[sqlx(rename = "invoice_number_reset")]
+    pub invoice_number_reset: Option<String>,
+    #[serde(rename = "invoiceNumberPeriod")]
+    #[sqlx(rename = "invoice_number_period")]
+    pub invoice_number_period: Option<String>,
+    #[serde(rename = "creditNoteNumberFormat")]
+    #[sqlx(rename = "credit_note_number_format")]
+    pub credit_note_number_format: Option<String>,
+    #[serde(rename = "creditNoteNumberCounter")]
+    #[sqlx(rename = "credit_note_number_counter")]
+    pub credit_note_number_counter: Option<i64>,
+    #[serde(rename = "creditNoteNumberPeriod")]
+    #[sqlx(rename = "credit_note_number_period")]
+    pub credit_note_number_period: Option<String>,
+    #[serde(rename = "estimateNumberFormat")]
+    #[sqlx(rename = "estimate_number_format")]
+    pub estimate_number_format: Option<String>,
+    #[serde(rename = "estimateNumberCounter")]
+    #[sqlx(rename = "estimate_number_counter")]
+    pub estimate_number_counter: Option<i64>,
+    #[serde(rename = "estimateNumberPeriod")]
+    #[sqlx(rename = "estimate_number_period")]
+    pub estimate_number_period: Option<String>,
     pub date_format: Option<String>,
+    #[serde(rename = "paymentReferenceFormat")]
+    #[sqlx(rename = "payment_reference_format")]
+    pub payment_reference_format: Option<String>,
+    // Defaults for new invoices
+    #[serde(rename = "pricesIncludeTax")]
+    #[sqlx(rename = "prices_include_tax")]
+    pub prices_include_tax: bool,
+    #[serde(rename = "taxRounding")]
+    #[sqlx(rename = "tax_rounding")]
+    pub tax_rounding: TaxRounding,
+    // Decimals line item quantities are rounded to
+    #[serde(rename = "quantityPrecision")]
+    #[sqlx(rename = "quantity_precision")]
+    pub quantity_precision: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,6 +93,7 @@ pub struct CreateOrganizationRequest {
     pub vatin: Option<String>,
     pub bank_name: Option<String>,
     pub iban: Option<String>,
+    pub bic: Option<String>,
     pub currency: Option<String>,
     pub minimum_fraction_digits: Option<i64>,
     pub due_days: Option<i64>,
@@ -61,7 +104,21 @@ pub struct CreateOrganizationRequest {
     pub logo: Option<Vec<u8>>,
     #[serde(rename = "invoiceNumberFormat")]
     pub invoice_number_format: Option<String>,
+    #[serde(rename = "invoiceNumberReset")]
+    pub invoice_number_reset: Option<InvoiceNumberReset>,
+    #[serde(rename = "creditNoteNumberFormat")]
+    pub credit_note_number_format: Option<String>,
+    #[serde(rename = "estimateNumberFormat")]
+    pub estimate_number_format: Option<String>,
     pub date_format: Option<String>,
+    #[serde(rename = "paymentReferenceFormat")]
+    pub payment_reference_format: Option<PaymentReferenceFormat>,
+    #[serde(rename = "pricesIncludeTax")]
+    pub prices_include_tax: Option<bool>,
+    #[serde(rename = "taxRounding")]
+    pub tax_rounding: Option<TaxRounding>,
+    #[serde(rename = "quantityPrecision")]
+    pub quantity_precision: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,6 +133,7 @@ pub struct UpdateOrganizationRequest {
     pub vatin: Option<String>,
     pub bank_name: Option<String>,
     pub iban: Option<String>,
+    pub bic: Option<String>,
     pub currency: Option<String>,
     pub minimum_fraction_digits: Option<i64>,
     pub due_days: Option<i64>,
@@ -88,7 +146,66 @@ pub struct UpdateOrganizationRequest {
     pub invoice_number_format: Option<String>,
     #[serde(rename = "invoiceNumberCounter")]
     pub invoice_number_counter: Option<i64>,
+    #[serde(rename = "invoiceNumberReset")]
+    pub invoice_number_reset: Option<InvoiceNumberReset>,
+    #[serde(rename = "creditNoteNumberFormat")]
+    pub credit_note_number_format: Option<String>,
+    #[serde(rename = "creditNoteNumberCounter")]
+    pub credit_note_number_counter: Option<i64>,
+    #[serde(rename = "estimateNumberFormat")]
+    pub estimate_number_format: Option<String>,
+    #[serde(rename = "estimateNumberCounter")]
+    pub estimate_number_counter: Option<i64>,
     pub date_format: Option<String>,
+    #[serde(rename = "paymentReferenceFormat")]
+    pub payment_reference_format: Option<PaymentReferenceFormat>,
+    #[serde(rename = "pricesIncludeTax")]
+    pub prices_include_tax: Option<bool>,
+    #[serde(rename = "taxRounding")]
+    pub tax_rounding: Option<TaxRounding>,
+    #[serde(rename = "quantityPrecision")]
+    pub quantity_precision: Option<i64>,
+}
+
+// Bank details and the VAT number end up on invoices, typos are caught on save
+fn validate_organization(
+    country: Option<&str>,
+    vatin: Option<&str>,
+    iban: Option<&str>,
+    bic: Option<&str>,
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = FieldErrors::default();
+    errors.check("vatin", vatin, |vatin| validate_vatin(vatin, country));
+    errors.check("iban", iban, validate_iban);
+    errors.check("bic", bic, validate_bic);
+    errors.into_result()
+}
+
+fn validate_quantity_precision(precision: Option<i64>) -> Result<(), sqlx::Error> {
+    match precision {
+        Some(precision) if !(0..=MAX_QUANTITY_PRECISION as i64).contains(&precision) => Err(sqlx::Error::Protocol(
+            format!("Quantities can have 0 to {} decimals", MAX_QUANTITY_PRECISION),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl Organization {
+    pub fn currency(&self) -> &str {
+        self.currency.as_deref().unwrap_or("EUR")
+    }
+}
+
+impl CreateOrganizationRequest {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        validate_organization(self.country.as_deref(), self.vatin.as_deref(), self.iban.as_deref(), self.bic.as_deref())
+    }
+}
+
+impl UpdateOrganizationRequest {
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        validate_organization(self.country.as_deref(), self.vatin.as_deref(), self.iban.as_deref(), self.bic.as_deref())
+    }
 }
 
 impl Database {
@@ -119,15 +236,29 @@ impl Database {
     }
 
     pub async fn create_organization(&self, organization: CreateOrganizationRequest) -> Result<Organization, sqlx::Error> {
+        let formats = [
+            &organization.invoice_number_format,
+            &organization.credit_note_number_format,
+            &organization.estimate_number_format,
+        ];
+        for format in formats.into_iter().flatten() {
+            validate_invoice_number_format(format)?;
+        }
+        validate_quantity_precision(organization.quantity_precision)?;
+
         sqlx::query(
             r#"
             INSERT INTO organizations (
                 id, name, country, address, email, phone, website, 
-                registration_number, vatin, bank_name, iban, currency,
+                registration_number, vatin, bank_name, iban, bic, currency,
                 minimum_fraction_digits, due_days, overdueCharge, 
-                customerNotes, logo, invoice_number_format, date_format
+                customerNotes, logo, invoice_number_format, invoice_number_reset,
+                credit_note_number_format, estimate_number_format, date_format, payment_reference_format,
+                prices_include_tax, tax_rounding, quantity_precision
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, 'never'),
+                COALESCE(?, 'CN-{year}-{number}'), COALESCE(?, 'EST-{year}-{number}'), ?, ?,
+                COALESCE(?, 0), COALESCE(?, 'per_total'), COALESCE(?, 2))
             "#,
         )
         .bind(&organization.id)
@@ -141,6 +272,7 @@ impl Database {
         .bind(&organization.vatin)
         .bind(&organization.bank_name)
         .bind(&organization.iban)
+        .bind(&organization.bic)
         .bind(&organization.currency)
         .bind(&organization.minimum_fraction_digits)
         .bind(&organization.due_days)
@@ -148,7 +280,14 @@ impl Database {
         .bind(&organization.customer_notes)
         .bind(&organization.logo)
         .bind(&organization.invoice_number_format)
+        .bind(organization.invoice_number_reset.map(|reset| reset.as_str()))
+        .bind(&organization.credit_note_number_format)
+        .bind(&organization.estimate_number_format)
         .bind(&organization.date_format)
+        .bind(organization.payment_reference_format.map(|format| format.as_str()))
+        .bind(organization.prices_include_tax)
+        .bind(organization.tax_rounding)
+        .bind(organization.quantity_precision)
         .execute(&self.pool)
         .await?;
 
@@ -161,6 +300,50 @@ impl Database {
         organization_id: &str,
         updates: UpdateOrganizationRequest,
     ) -> Result<Organization, sqlx::Error> {
+        let formats = [
+            &updates.invoice_number_format,
+            &updates.credit_note_number_format,
+            &updates.estimate_number_format,
+        ];
+        for format in formats.into_iter().flatten() {
+            validate_invoice_number_format(format)?;
+        }
+        validate_quantity_precision(updates.quantity_precision)?;
+
+        // Locked so items can't be priced between the check and the currency change
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let current_currency = sqlx::query_scalar::<_, Option<String>>("SELECT currency FROM organizations WHERE id = ?")
+            .bind(organization_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)?
+            .unwrap_or_else(|| "EUR".to_string());
+
+        // Item prices and hourly rates are minor units of the currency, they
+        // would change value with its number of decimals
+        if let Some(currency) = updates.currency.as_deref() {
+            if minor_unit_exponent(currency) != minor_unit_exponent(&current_currency) {
+                let priced = sqlx::query_scalar::<_, bool>(
+                    r#"
+                    SELECT EXISTS (SELECT 1 FROM items WHERE organizationId = ? AND unitPrice != 0)
+                        OR EXISTS (SELECT 1 FROM timeEntries WHERE organizationId = ? AND hourlyRate != 0)
+                    "#,
+                )
+                .bind(organization_id)
+                .bind(organization_id)
+                .fetch_one(&mut *tx)
+                .await?;
+                if priced {
+                    return Err(sqlx::Error::Protocol(format!(
+                        "Items and time entries are priced in {}, which has a different number of decimals than {}. \
+                         Clear their prices before changing the currency.",
+                        current_currency,
+                        currency.trim().to_ascii_uppercase()
+                    )));
+                }
+            }
+        }
+
         sqlx::query(
             r#"
             UPDATE organizations
@@ -174,6 +357,7 @@ impl Database {
                 vatin = COALESCE(?, vatin),
                 bank_name = COALESCE(?, bank_name),
                 iban = COALESCE(?, iban),
+                bic = COALESCE(?, bic),
                 currency = COALESCE(?, currency),
                 minimum_fraction_digits = COALESCE(?, minimum_fraction_digits),
                 due_days = COALESCE(?, due_days),
@@ -182,7 +366,16 @@ impl Database {
                 logo = COALESCE(?, logo),
                 invoice_number_format = COALESCE(?, invoice_number_format),
                 invoice_number_counter = COALESCE(?, invoice_number_counter),
-                date_format = COALESCE(?, date_format)
+                invoice_number_reset = COALESCE(?, invoice_number_reset),
+                credit_note_number_format = COALESCE(?, credit_note_number_format),
+                credit_note_number_counter = COALESCE(?, credit_note_number_counter),
+                estimate_number_format = COALESCE(?, estimate_number_format),
+                estimate_number_counter = COALESCE(?, estimate_number_counter),
+                date_format = COALESCE(?, date_format),
+                payment_reference_format = COALESCE(?, payment_reference_format),
+                prices_include_tax = COALESCE(?, prices_include_tax),
+                tax_rounding = COALESCE(?, tax_rounding),
+                quantity_precision = COALESCE(?, quantity_precision)
             WHERE id = ?
             "#,
         )
@@ -196,6 +389,7 @@ impl Database {
         .bind(&updates.vatin)
         .bind(&updates.bank_name)
         .bind(&updates.iban)
+        .bind(&updates.bic)
         .bind(&updates.currency)
         .bind(&updates.minimum_fraction_digits)
         .bind(&updates.due_days)
@@ -204,10 +398,20 @@ impl Database {
         .bind(&updates.logo)
         .bind(&updates.invoice_number_format)
         .bind(&updates.invoice_number_counter)
+        .bind(updates.invoice_number_reset.map(|reset| reset.as_str()))
+        .bind(&updates.credit_note_number_format)
+        .bind(&updates.credit_note_number_counter)
+        .bind(&updates.estimate_number_format)
+        .bind(&updates.estimate_number_counter)
         .bind(&updates.date_format)
+        .bind(updates.payment_reference_format.map(|format| format.as_str()))
+        .bind(updates.prices_include_tax)
+        .bind(updates.tax_rounding)
+        .bind(updates.quantity_precision)
         .bind(organization_id)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
 
         self.get_organization(organization_id).await?
             .ok_or_else(|| sqlx::Error::RowNotFound)
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqliteConnection};
+
+use super::{fetch_invoice, record_invoice_state, Database, Invoice, InvoiceKind, InvoiceState};
+use crate::money::Money;
+
+#[derive(Debug, Serialize)]
+pub struct Payment {
+    pub id: String,
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: String,
+    pub amount: Money,
+    pub date: i64,
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+}
+
+// Read with the invoice's currency joined in, see PAYMENT_COLUMNS
+impl<'r> FromRow<'r, SqliteRow> for Payment {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            invoice_id: row.try_get("invoiceId")?,
+            amount: Money::new(row.try_get("amount")?, &currency),
+            date: row.try_get("date")?,
+            method: row.try_get("method")?,
+            reference: row.try_get("reference")?,
+            note: row.try_get("note")?,
+            created_at: row.try_get("createdAt")?,
+        })
+    }
+}
+
+const PAYMENT_COLUMNS: &str = r#"
+    payments.*,
+    invoices.currency
+    FROM payments
+    INNER JOIN invoices ON invoices.id = payments.invoiceId
+"#;
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePaymentRequest {
+    pub id: String,
+    #[serde(rename = "invoiceId")]
+    pub invoice_id: String,
+    pub amount: i64,  // Minor units
+    pub date: i64,
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePaymentRequest {
+    pub amount: Option<i64>,  // Minor units
+    pub date: Option<i64>,
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+}
+
+// Who moves invoice states as payments and credit notes come in
+const PAYMENTS_USER: &str = "payments";
+
+// State an issued invoice should be in given what has been paid and credited
+// against it. Drafts and voided invoices are never moved. Neither are paid
+// invoices without payments whose state was set by hand, e.g. marked paid
+// before payments were recorded.
+fn payment_state(
+    current: InvoiceState,
+    amount_paid: i64,
+    amount_credited: i64,
+    balance_due: i64,
+    overdue: bool,
+    set_by_payments: bool,
+) -> InvoiceState {
+    match current {
+        InvoiceState::Draft | InvoiceState::Void => current,
+        _ if amount_paid + amount_credited > 0 && balance_due <= 0 => InvoiceState::Paid,
+        _ if amount_paid > 0 => InvoiceState::PartiallyPaid,
+        InvoiceState::Paid | InvoiceState::PartiallyPaid if !set_by_payments => current,
+        InvoiceState::Paid | InvoiceState::PartiallyPaid if overdue => InvoiceState::Overdue,
+        InvoiceState::Paid | InvoiceState::PartiallyPaid => InvoiceState::Sent,
+        _ => current,
+    }
+}
+
+// Brings an invoice's state in line with its payments and issued credit notes.
+// Runs as part of every payment change, so these moves are recorded but not
+// checked against the manual transition rules (a removed payment may take a
+// paid invoice back).
+pub async fn sync_invoice_payment_state(conn: &mut SqliteConnection, invoice_id: &str) -> Result<(), sqlx::Error> {
+    let invoice = fetch_invoice(&mut *conn, invoice_id).await?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let overdue = invoice.due_date.is_some_and(|due_date| due_date < now);
+    // Credit notes are negative and bring the balance down along with payments
+    let amount_credited =
+        invoice.total.amount_minor - invoice.amount_paid.amount_minor - invoice.balance_due.amount_minor;
+    let set_by_payments = sqlx::query_scalar::<_, String>(
+        "SELECT changedBy FROM invoiceStateHistory WHERE invoiceId = ? ORDER BY rowid DESC LIMIT 1"
+    )
+    .bind(invoice_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .is_some_and(|changed_by| changed_by == PAYMENTS_USER);
+    let state = payment_state(
+        invoice.state,
+        invoice.amount_paid.amount_minor,
+        amount_credited,
+        invoice.balance_due.amount_minor,
+        overdue,
+        set_by_payments,
+    );
+
+    if state == invoice.state {
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE invoices SET state = ? WHERE id = ?")
+        .bind(state)
+        .bind(invoice_id)
+        .execute(&mut *conn)
+        .await?;
+
+    record_invoice_state(conn, invoice_id, Some(invoice.state), state, PAYMENTS_USER, None).await
+}
+
+fn validate_amount(amount: i64) -> Result<(), sqlx::Error> {
+    if amount <= 0 {
+        return Err(sqlx::Error::Protocol("Payment amount must be greater than zero".into()));
+    }
+    Ok(())
+}
+
+// A payment can settle what is outstanding but not go past it. `replaced` is
+// the amount of the payment being changed, which is outstanding again.
+fn validate_outstanding(invoice: &Invoice, amount: i64, replaced: i64) -> Result<(), sqlx::Error> {
+    let outstanding = Money::new(invoice.balance_due.amount_minor + replaced, &invoice.currency);
+    if amount > outstanding.amount_minor {
+        return Err(sqlx::Error::Protocol(format!(
+            "Payment of {} is more than the {} outstanding on invoice {}",
+            Money::new(amount, &invoice.currency),
+            outstanding,
+            invoice.number
+        )));
+    }
+    Ok(())
+}
+
+impl Database {
+    pub async fn get_payments(&self, invoice_id: &str) -> Result<Vec<Payment>, sqlx::Error> {
+        sqlx::query_as::<_, Payment>(&format!(
+            r#"
+            SELECT {PAYMENT_COLUMNS}
+            WHERE payments.invoiceId = ?
+            ORDER BY payments.date ASC, payments.createdAt ASC
+            "#,
+        ))
+        .bind(invoice_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_payment(&self, payment_id: &str) -> Result<Option<Payment>, sqlx::Error> {
+        sqlx::query_as::<_, Payment>(&format!(
+            r#"
+            SELECT {PAYMENT_COLUMNS}
+            WHERE payments.id = ?
+            LIMIT 1
+            "#,
+        ))
+        .bind(payment_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn create_payment(&self, payment: CreatePaymentRequest) -> Result<Payment, sqlx::Error> {
+        validate_amount(payment.amount)?;
+
+        // Checks the balance before it writes, so take the write lock up front
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let invoice = fetch_invoice(&mut tx, &payment.invoice_id).await?;
+        if invoice.kind != InvoiceKind::Invoice {
+            return Err(sqlx::Error::Protocol("Payments can only be recorded against invoices".into()));
+        }
+        if matches!(invoice.state, InvoiceState::Draft | InvoiceState::Void) {
+            return Err(sqlx::Error::Protocol(format!(
+                "Payments can't be recorded against a {} invoice",
+                invoice.state.as_str()
+            )));
+        }
+        validate_outstanding(&invoice, payment.amount, 0)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO payments (id, invoiceId, amount, date, method, reference, note)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&payment.id)
+        .bind(&payment.invoice_id)
+        .bind(&payment.amount)
+        .bind(&payment.date)
+        .bind(&payment.method)
+        .bind(&payment.reference)
+        .bind(&payment.note)
+        .execute(&mut *tx)
+        .await?;
+
+        sync_invoice_payment_state(&mut tx, &payment.invoice_id).await?;
+
+        tx.commit().await?;
+
+        self.get_payment(&payment.id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn update_payment(
+        &self,
+        payment_id: &str,
+        updates: UpdatePaymentRequest,
+    ) -> Result<Payment, sqlx::Error> {
+        if let Some(amount) = updates.amount {
+            validate_amount(amount)?;
+        }
+
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let (invoice_id, amount) =
+            sqlx::query_as::<_, (String, i64)>("SELECT invoiceId, amount FROM payments WHERE id = ?")
+                .bind(payment_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(sqlx::Error::RowNotFound)?;
+        if let Some(updated_amount) = updates.amount {
+            let invoice = fetch_invoice(&mut tx, &invoice_id).await?;
+            validate_outstanding(&invoice, updated_amount, amount)?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE payments
+            SET amount = COALESCE(?, amount),
+                date = COALESCE(?, date),
+                method = COALESCE(?, method),
+                reference = COALESCE(?, reference),
+                note = COALESCE(?, note)
+            WHERE id = ?
+            "#,
+        )
+        .bind(&updates.amount)
+        .bind(&updates.date)
+        .bind(&updates.method)
+        .bind(&updates.reference)
+        .bind(&updates.note)
+        .bind(payment_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sync_invoice_payment_state(&mut tx, &invoice_id).await?;
+
+        tx.commit().await?;
+
+        self.get_payment(payment_id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn delete_payment(&self, payment_id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let invoice_id = sqlx::query_scalar::<_, String>("SELECT invoiceId FROM payments WHERE id = ?")
+            .bind(payment_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        let result = sqlx::query("DELETE FROM payments WHERE id = ?")
+            .bind(payment_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if let Some(invoice_id) = invoice_id {
+            sync_invoice_payment_state(&mut tx, &invoice_id).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,614 @@
+use std::collections::HashSet;
+
+use chrono::{Datelike, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqliteConnection};
+
+use super::{
+    delete_line_item_taxes, discount_columns, discount_from_row, fetch_line_item_taxes, insert_invoice,
+    insert_line_item_taxes, line_item_description, normalize_line_items, transition_invoice_state,
+    CreateInvoiceLineItemRequest, CreateInvoiceRequest, Database, DiscountKind, Invoice, InvoiceKind, InvoiceState,
+    LineItemTax,
+};
+use crate::money::Money;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum RecurringInterval {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl RecurringInterval {
+    fn months(&self) -> u32 {
+        match self {
+            Self::Monthly => 1,
+            Self::Quarterly => 3,
+            Self::Yearly => 12,
+        }
+    }
+
+    // Key of the billing period an invoice date falls in, e.g. "2025-03",
+    // "2025-Q1" or "2025". Stays the same when the schedule's day or start
+    // date is edited, so periods already invoiced aren't invoiced again.
+    pub fn period(&self, date: NaiveDate) -> String {
+        match self {
+            Self::Monthly => date.format("%Y-%m").to_string(),
+            Self::Quarterly => format!("{}-Q{}", date.year(), date.month0() / 3 + 1),
+            Self::Yearly => date.year().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RecurringInvoice {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    #[sqlx(rename = "organizationId")]
+    pub organization_id: String,
+    #[serde(rename = "clientId")]
+    #[sqlx(rename = "clientId")]
+    pub client_id: String,
+    pub name: Option<String>,
+    pub currency: String,
+    #[serde(rename = "customerNotes")]
+    #[sqlx(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    #[serde(rename = "overdueCharge")]
+    #[sqlx(rename = "overdueCharge")]
+    pub overdue_charge: Option<f64>,
+    #[serde(rename = "dueDays")]
+    #[sqlx(rename = "dueDays")]
+    pub due_days: Option<i64>,
+    pub interval: RecurringInterval,
+    // Day of the month invoices are dated, clamped to the last day of shorter
+    // months. Defaults to the day of the start date.
+    #[serde(rename = "dayOfMonth")]
+    #[sqlx(rename = "dayOfMonth")]
+    pub day_of_month: Option<i64>,
+    #[serde(rename = "startDate")]
+    #[sqlx(rename = "startDate")]
+    pub start_date: i64,
+    #[serde(rename = "endDate")]
+    #[sqlx(rename = "endDate")]
+    pub end_date: Option<i64>,
+    #[serde(rename = "autoSend")]
+    #[sqlx(rename = "autoSend")]
+    pub auto_send: Option<i64>,
+    #[serde(rename = "isActive")]
+    #[sqlx(rename = "isActive")]
+    pub is_active: Option<i64>,
+    #[serde(rename = "createdAt")]
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<String>,
+    // Only for joined queries
+    #[serde(rename = "clientName")]
+    #[sqlx(rename = "clientName")]
+    pub client_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecurringInvoiceLineItem {
+    pub id: String,
+    #[serde(rename = "recurringInvoiceId")]
+    pub recurring_invoice_id: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    #[serde(rename = "unitCode")]
+    pub unit_code: Option<String>,
+    #[serde(rename = "unitPrice")]
+    pub unit_price: Money,
+    // Percentage, or minor units off the line amount
+    pub discount: Option<f64>,
+    #[serde(rename = "discountKind")]
+    pub discount_kind: DiscountKind,
+    #[serde(rename = "taxRate")]
+    pub tax_rate: Option<String>,
+    // Catalog item the line was filled from. Description and price are copies.
+    #[serde(rename = "itemId")]
+    pub item_id: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<String>,
+    // Loaded from lineItemTaxes
+    pub taxes: Vec<LineItemTax>,
+}
+
+// Read with the schedule's currency joined in
+impl<'r> FromRow<'r, SqliteRow> for RecurringInvoiceLineItem {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        let discount_kind = row.try_get("discountKind")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            recurring_invoice_id: row.try_get("recurringInvoiceId")?,
+            description: row.try_get("description")?,
+            quantity: row.try_get("quantity")?,
+            unit_code: row.try_get("unitCode")?,
+            unit_price: Money::new(row.try_get("unitPrice")?, &currency),
+            discount: discount_from_row(row, discount_kind)?,
+            discount_kind,
+            tax_rate: row.try_get("taxRate")?,
+            item_id: row.try_get("itemId")?,
+            created_at: row.try_get("createdAt")?,
+            taxes: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRecurringInvoiceRequest {
+    pub id: String,
+    #[serde(rename = "organizationId")]
+    pub organization_id: String,
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub name: Option<String>,
+    pub currency: String,
+    #[serde(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    #[serde(rename = "overdueCharge")]
+    pub overdue_charge: Option<f64>,
+    #[serde(rename = "dueDays")]
+    pub due_days: Option<i64>,
+    pub interval: RecurringInterval,
+    #[serde(rename = "dayOfMonth")]
+    pub day_of_month: Option<i64>,
+    #[serde(rename = "startDate")]
+    pub start_date: i64,
+    #[serde(rename = "endDate")]
+    pub end_date: Option<i64>,
+    #[serde(rename = "autoSend")]
+    pub auto_send: Option<i64>,
+    #[serde(rename = "isActive")]
+    pub is_active: Option<i64>,
+    #[serde(rename = "lineItems")]
+    pub line_items: Vec<CreateInvoiceLineItemRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRecurringInvoiceRequest {
+    #[serde(rename = "clientId")]
+    pub client_id: Option<String>,
+    pub name: Option<String>,
+    pub currency: Option<String>,
+    #[serde(rename = "customerNotes")]
+    pub customer_notes: Option<String>,
+    #[serde(rename = "overdueCharge")]
+    pub overdue_charge: Option<f64>,
+    #[serde(rename = "dueDays")]
+    pub due_days: Option<i64>,
+    pub interval: Option<RecurringInterval>,
+    #[serde(rename = "dayOfMonth")]
+    pub day_of_month: Option<i64>,
+    #[serde(rename = "startDate")]
+    pub start_date: Option<i64>,
+    #[serde(rename = "endDate")]
+    pub end_date: Option<i64>,
+    #[serde(rename = "autoSend")]
+    pub auto_send: Option<i64>,
+    #[serde(rename = "isActive")]
+    pub is_active: Option<i64>,
+    #[serde(rename = "lineItems")]
+    pub line_items: Option<Vec<CreateInvoiceLineItemRequest>>,
+}
+
+fn local_date(timestamp_ms: i64) -> Option<NaiveDate> {
+    Local.timestamp_millis_opt(timestamp_ms).single().map(|date| date.date_naive())
+}
+
+fn local_midnight_ms(date: NaiveDate) -> i64 {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|date| date.timestamp_millis())
+        .unwrap_or_default()
+}
+
+fn validate_day_of_month(day_of_month: Option<i64>) -> Result<(), sqlx::Error> {
+    match day_of_month {
+        Some(day) if !(1..=31).contains(&day) => {
+            Err(sqlx::Error::Protocol(format!("Invalid day of month: {}", day)))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Day `day` of the given month, or the month's last day if it is shorter
+fn clamped_date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1..=day.min(31))
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+impl RecurringInvoice {
+    // Invoice dates due from the start date up to and including `until`
+    pub fn occurrences(&self, until: NaiveDate) -> Vec<NaiveDate> {
+        let Some(start) = local_date(self.start_date) else {
+            return Vec::new();
+        };
+        let until = match self.end_date.and_then(local_date) {
+            Some(end) => end.min(until),
+            None => until,
+        };
+        let day = self.day_of_month.map(|day| day as u32).unwrap_or_else(|| start.day());
+
+        let mut dates = Vec::new();
+        let mut month_index = start.year() * 12 + start.month0() as i32;
+        while let Some(date) = clamped_date(month_index / 12, (month_index % 12) as u32 + 1, day) {
+            if date > until {
+                break;
+            }
+            if date >= start {
+                dates.push(date);
+            }
+            month_index += self.interval.months() as i32;
+        }
+
+        dates
+    }
+}
+
+async fn insert_recurring_line_items(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    recurring_invoice_id: &str,
+    line_items: &[CreateInvoiceLineItemRequest],
+) -> Result<(), sqlx::Error> {
+    for line_item in line_items {
+        let line_item_id = nanoid::nanoid!();
+        let taxes = line_item.line_taxes();
+        let description = line_item_description(&mut *conn, organization_id, line_item).await?;
+        let (discount, discount_amount) = discount_columns(line_item.discount());
+        sqlx::query(
+            r#"
+            INSERT INTO recurringInvoiceLineItems (
+                id, recurringInvoiceId, description, quantity, unitCode, unitPrice, discount, discountAmount,
+                discountKind, taxRate, itemId
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&line_item_id)
+        .bind(recurring_invoice_id)
+        .bind(&description)
+        .bind(&line_item.quantity)
+        .bind(&line_item.unit_code)
+        .bind(&line_item.unit_price)
+        .bind(discount)
+        .bind(discount_amount)
+        .bind(line_item.discount_kind)
+        .bind(taxes.first().map(|tax| &tax.tax_rate))
+        .bind(&line_item.item_id)
+        .execute(&mut *conn)
+        .await?;
+
+        insert_line_item_taxes(&mut *conn, &line_item_id, &taxes).await?;
+    }
+
+    Ok(())
+}
+
+impl Database {
+    pub async fn get_recurring_invoices(&self, organization_id: &str) -> Result<Vec<RecurringInvoice>, sqlx::Error> {
+        sqlx::query_as::<_, RecurringInvoice>(
+            r#"
+            SELECT
+                recurringInvoices.*,
+                clients.name AS clientName
+            FROM
+                recurringInvoices
+            INNER JOIN
+                clients ON recurringInvoices.clientId = clients.id
+            WHERE
+                recurringInvoices.organizationId = ?
+            ORDER BY
+                recurringInvoices.startDate DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_recurring_invoice(&self, recurring_invoice_id: &str) -> Result<Option<RecurringInvoice>, sqlx::Error> {
+        sqlx::query_as::<_, RecurringInvoice>(
+            r#"
+            SELECT
+                recurringInvoices.*,
+                clients.name AS clientName
+            FROM
+                recurringInvoices
+            INNER JOIN
+                clients ON recurringInvoices.clientId = clients.id
+            WHERE
+                recurringInvoices.id = ?
+            LIMIT 1
+            "#,
+        )
+        .bind(recurring_invoice_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn get_recurring_invoice_line_items(
+        &self,
+        recurring_invoice_id: &str,
+    ) -> Result<Vec<RecurringInvoiceLineItem>, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let mut line_items = sqlx::query_as::<_, RecurringInvoiceLineItem>(
+            r#"
+            SELECT recurringInvoiceLineItems.*, recurringInvoices.currency
+            FROM recurringInvoiceLineItems
+            INNER JOIN recurringInvoices ON recurringInvoices.id = recurringInvoiceLineItems.recurringInvoiceId
+            WHERE recurringInvoiceLineItems.recurringInvoiceId = ?
+            ORDER BY recurringInvoiceLineItems.createdAt ASC, recurringInvoiceLineItems.rowid ASC
+            "#,
+        )
+        .bind(recurring_invoice_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut taxes =
+            fetch_line_item_taxes(&mut conn, "recurringInvoiceLineItems", "recurringInvoiceId", recurring_invoice_id)
+                .await?;
+        for line_item in &mut line_items {
+            line_item.taxes = taxes.remove(&line_item.id).unwrap_or_default();
+        }
+
+        Ok(line_items)
+    }
+
+    pub async fn create_recurring_invoice(
+        &self,
+        mut recurring_invoice: CreateRecurringInvoiceRequest,
+    ) -> Result<RecurringInvoice, sqlx::Error> {
+        validate_day_of_month(recurring_invoice.day_of_month)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        normalize_line_items(&mut tx, &recurring_invoice.organization_id, &mut recurring_invoice.line_items).await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO recurringInvoices (
+                id, organizationId, clientId, name, currency, customerNotes, overdueCharge, dueDays,
+                interval, dayOfMonth, startDate, endDate, autoSend, isActive
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, 0), COALESCE(?, 1))
+            "#,
+        )
+        .bind(&recurring_invoice.id)
+        .bind(&recurring_invoice.organization_id)
+        .bind(&recurring_invoice.client_id)
+        .bind(&recurring_invoice.name)
+        .bind(&recurring_invoice.currency)
+        .bind(&recurring_invoice.customer_notes)
+        .bind(&recurring_invoice.overdue_charge)
+        .bind(&recurring_invoice.due_days)
+        .bind(recurring_invoice.interval)
+        .bind(&recurring_invoice.day_of_month)
+        .bind(&recurring_invoice.start_date)
+        .bind(&recurring_invoice.end_date)
+        .bind(&recurring_invoice.auto_send)
+        .bind(&recurring_invoice.is_active)
+        .execute(&mut *tx)
+        .await?;
+
+        insert_recurring_line_items(&mut tx, &recurring_invoice.organization_id, &recurring_invoice.id, &recurring_invoice.line_items).await?;
+
+        tx.commit().await?;
+
+        self.get_recurring_invoice(&recurring_invoice.id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn update_recurring_invoice(
+        &self,
+        recurring_invoice_id: &str,
+        updates: UpdateRecurringInvoiceRequest,
+    ) -> Result<RecurringInvoice, sqlx::Error> {
+        validate_day_of_month(updates.day_of_month)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE recurringInvoices
+            SET clientId = COALESCE(?, clientId),
+                name = COALESCE(?, name),
+                currency = COALESCE(?, currency),
+                customerNotes = COALESCE(?, customerNotes),
+                overdueCharge = COALESCE(?, overdueCharge),
+                dueDays = COALESCE(?, dueDays),
+                interval = COALESCE(?, interval),
+                dayOfMonth = COALESCE(?, dayOfMonth),
+                startDate = COALESCE(?, startDate),
+                endDate = COALESCE(?, endDate),
+                autoSend = COALESCE(?, autoSend),
+                isActive = COALESCE(?, isActive)
+            WHERE id = ?
+            "#,
+        )
+        .bind(&updates.client_id)
+        .bind(&updates.name)
+        .bind(&updates.currency)
+        .bind(&updates.customer_notes)
+        .bind(&updates.overdue_charge)
+        .bind(&updates.due_days)
+        .bind(updates.interval)
+        .bind(&updates.day_of_month)
+        .bind(&updates.start_date)
+        .bind(&updates.end_date)
+        .bind(&updates.auto_send)
+        .bind(&updates.is_active)
+        .bind(recurring_invoice_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Update line items if provided. Invoices already generated keep their own copies.
+        if let Some(mut line_items) = updates.line_items {
+            let organization_id =
+                sqlx::query_scalar::<_, String>("SELECT organizationId FROM recurringInvoices WHERE id = ?")
+                    .bind(recurring_invoice_id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            normalize_line_items(&mut tx, &organization_id, &mut line_items).await?;
+            delete_line_item_taxes(&mut tx, "recurringInvoiceLineItems", "recurringInvoiceId", recurring_invoice_id)
+                .await?;
+            sqlx::query("DELETE FROM recurringInvoiceLineItems WHERE recurringInvoiceId = ?")
+                .bind(recurring_invoice_id)
+                .execute(&mut *tx)
+                .await?;
+
+            insert_recurring_line_items(&mut tx, &organization_id, recurring_invoice_id, &line_items).await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_recurring_invoice(recurring_invoice_id).await?
+            .ok_or_else(|| sqlx::Error::RowNotFound)
+    }
+
+    pub async fn delete_recurring_invoice(&self, recurring_invoice_id: &str) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // The line items themselves go with the schedule
+        delete_line_item_taxes(&mut tx, "recurringInvoiceLineItems", "recurringInvoiceId", recurring_invoice_id)
+            .await?;
+        let result = sqlx::query("DELETE FROM recurringInvoices WHERE id = ?")
+            .bind(recurring_invoice_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Creates the invoice for one occurrence of a schedule unless it already exists.
+    async fn generate_recurring_invoice(
+        &self,
+        schedule: &RecurringInvoice,
+        line_items: &[RecurringInvoiceLineItem],
+        date: NaiveDate,
+    ) -> Result<Option<Invoice>, sqlx::Error> {
+        let period = schedule.interval.period(date);
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+
+        let exists = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM invoices WHERE recurringInvoiceId = ? AND recurringPeriod = ?"
+        )
+        .bind(&schedule.id)
+        .bind(&period)
+        .fetch_one(&mut *tx)
+        .await?;
+        if exists > 0 {
+            return Ok(None);
+        }
+
+        let due_days = match schedule.due_days {
+            Some(due_days) => Some(due_days),
+            None => sqlx::query_scalar::<_, Option<i64>>("SELECT due_days FROM organizations WHERE id = ?")
+                .bind(&schedule.organization_id)
+                .fetch_one(&mut *tx)
+                .await?,
+        };
+
+        let invoice = CreateInvoiceRequest {
+            id: nanoid::nanoid!(),
+            organization_id: schedule.organization_id.clone(),
+            state: InvoiceState::Draft,
+            client_id: schedule.client_id.clone(),
+            date: local_midnight_ms(date),
+            due_date: due_days.map(|days| local_midnight_ms(date + chrono::Days::new(days.max(0) as u64))),
+            currency: schedule.currency.clone(),
+            customer_notes: schedule.customer_notes.clone(),
+            overdue_charge: schedule.overdue_charge,
+            prices_include_tax: None,
+            tax_rounding: None,
+            discount: None,
+            discount_kind: DiscountKind::default(),
+            line_items: line_items
+                .iter()
+                .map(|line_item| CreateInvoiceLineItemRequest {
+                    description: line_item.description.clone(),
+                    quantity: line_item.quantity,
+                    unit_code: line_item.unit_code.clone(),
+                    unit_price: line_item.unit_price.amount_minor,
+                    discount: line_item.discount,
+                    discount_kind: line_item.discount_kind,
+                    tax_rate: line_item.tax_rate.clone(),
+                    taxes: line_item.taxes.clone(),
+                    item_id: line_item.item_id.clone(),
+                })
+                .collect(),
+        };
+
+        insert_invoice(&mut tx, &invoice, InvoiceKind::Invoice, None).await?;
+
+        sqlx::query("UPDATE invoices SET recurringInvoiceId = ?, recurringPeriod = ? WHERE id = ?")
+            .bind(&schedule.id)
+            .bind(&period)
+            .bind(&invoice.id)
+            .execute(&mut *tx)
+            .await?;
+
+        if schedule.auto_send == Some(1) {
+            transition_invoice_state(&mut tx, &invoice.id, InvoiceState::Sent, "recurring", None).await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_invoice(&invoice.id).await
+    }
+
+    // Generates every invoice that is due for active schedules, including any
+    // periods missed while the app was closed. Safe to run repeatedly.
+    pub async fn run_recurring_invoices(&self) -> Result<Vec<Invoice>, sqlx::Error> {
+        let schedules = sqlx::query_as::<_, RecurringInvoice>(
+            "SELECT recurringInvoices.*, NULL AS clientName FROM recurringInvoices WHERE isActive = 1"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let today = Local::now().date_naive();
+        let mut generated = Vec::new();
+
+        for schedule in schedules {
+            let existing: HashSet<String> = sqlx::query_scalar::<_, String>(
+                "SELECT recurringPeriod FROM invoices WHERE recurringInvoiceId = ? AND recurringPeriod IS NOT NULL"
+            )
+            .bind(&schedule.id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+            let due: Vec<NaiveDate> = schedule
+                .occurrences(today)
+                .into_iter()
+                .filter(|date| !existing.contains(&schedule.interval.period(*date)))
+                .collect();
+            if due.is_empty() {
+                continue;
+            }
+
+            let line_items = self.get_recurring_invoice_line_items(&schedule.id).await?;
+            for date in due {
+                match self.generate_recurring_invoice(&schedule, &line_items, date).await {
+                    Ok(Some(invoice)) => generated.push(invoice),
+                    Ok(None) => {}
+                    Err(e) => {
+                        // Leave the remaining periods for the next run so numbering stays in order
+                        eprintln!("Failed to generate recurring invoice {} for {}: {}", schedule.id, date, e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(generated)
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::{Datelike, Local, NaiveDate, TimeZone};
+use serde::Serialize;
+use sqlx::{FromRow, SqliteConnection};
+
+use super::{find_exchange_rate, invoice_date, missing_rate_error, Database};
+use crate::money::Money;
+
+// Income tax withheld by one client over a year, for reconciling with the
+// certificates clients send at year end
+#[derive(Debug, Serialize)]
+pub struct WithholdingReportRow {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientName")]
+    pub client_name: Option<String>,
+    pub vatin: Option<String>,
+    // The organization's currency, every amount is converted to it
+    pub currency: String,
+    #[serde(rename = "invoiceCount")]
+    pub invoice_count: i64,
+    // Net amount of the invoices tax was withheld on
+    #[serde(rename = "subTotal")]
+    pub sub_total: i64,  // Minor units
+    #[serde(rename = "withholdingTotal")]
+    pub withholding_total: i64,  // Minor units
+}
+
+// Revenue and output tax of one month, in the organization's currency
+#[derive(Debug, Serialize)]
+pub struct RevenueReportRow {
+    pub month: u32,
+    pub currency: String,
+    #[serde(rename = "invoiceCount")]
+    pub invoice_count: i64,
+    #[serde(rename = "subTotal")]
+    pub sub_total: i64,  // Minor units
+    #[serde(rename = "taxTotal")]
+    pub tax_total: i64,  // Minor units
+    #[serde(rename = "withholdingTotal")]
+    pub withholding_total: i64,  // Minor units
+    pub total: i64,  // Minor units
+}
+
+// An issued invoice or credit note with what's needed to convert its amounts
+#[derive(Debug, FromRow)]
+struct ReportInvoice {
+    #[sqlx(rename = "clientId")]
+    client_id: String,
+    #[sqlx(rename = "clientName")]
+    client_name: Option<String>,
+    vatin: Option<String>,
+    date: i64,
+    currency: String,
+    #[sqlx(rename = "subTotal")]
+    sub_total: i64,
+    #[sqlx(rename = "taxTotal")]
+    tax_total: i64,
+    #[sqlx(rename = "withholdingTotal")]
+    withholding_total: i64,
+    total: i64,
+    #[sqlx(rename = "baseCurrency")]
+    base_currency: Option<String>,
+    #[sqlx(rename = "exchangeRate")]
+    exchange_rate: Option<String>,
+}
+
+impl ReportInvoice {
+    // At the rate recorded when it was issued. Invoices issued before rates
+    // were kept, or while the organization had another currency, use the
+    // rate on their date. Ones still waiting for a rate to be imported can't
+    // be converted yet.
+    async fn converter(&self, conn: &mut SqliteConnection, base_currency: &str) -> Result<Converter, sqlx::Error> {
+        let rate = match (&self.base_currency, &self.exchange_rate) {
+            (Some(currency), Some(rate)) if currency == base_currency => rate.clone(),
+            (Some(currency), None) if currency == base_currency => {
+                return Err(missing_rate_error(&self.currency, base_currency, invoice_date(self.date)?));
+            }
+            _ => {
+                let date = invoice_date(self.date)?;
+                find_exchange_rate(conn, base_currency, &self.currency, date)
+                    .await?
+                    .ok_or_else(|| missing_rate_error(&self.currency, base_currency, date))?
+                    .rate
+            }
+        };
+        Ok(Converter { currency: self.currency.clone(), base_currency: base_currency.to_string(), rate })
+    }
+}
+
+struct Converter {
+    currency: String,
+    base_currency: String,
+    rate: String,
+}
+
+impl Converter {
+    fn convert(&self, amount_minor: i64) -> Result<i64, sqlx::Error> {
+        Money::new(amount_minor, &self.currency)
+            .convert(&self.rate, &self.base_currency)
+            .map(|money| money.amount_minor)
+            .ok_or_else(|| {
+                sqlx::Error::Protocol(format!(
+                    "Invalid exchange rate from {} to {}: {}",
+                    self.currency, self.base_currency, self.rate
+                ))
+            })
+    }
+}
+
+fn local_year_start_ms(year: i32) -> Result<i64, sqlx::Error> {
+    NaiveDate::from_ymd_opt(year, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|date| Local.from_local_datetime(&date).earliest())
+        .map(|date| date.timestamp_millis())
+        .ok_or_else(|| sqlx::Error::Protocol(format!("Invalid year: {}", year)))
+}
+
+// Issued invoices and credit notes dated in the year, by client name
+async fn fetch_report_invoices(
+    conn: &mut SqliteConnection,
+    organization_id: &str,
+    year: i32,
+) -> Result<(String, Vec<ReportInvoice>), sqlx::Error> {
+    let start = local_year_start_ms(year)?;
+    let end = local_year_start_ms(year + 1)?;
+
+    let base_currency = sqlx::query_scalar::<_, Option<String>>("SELECT currency FROM organizations WHERE id = ?")
+        .bind(organization_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten()
+        .unwrap_or_else(|| "EUR".to_string());
+
+    let invoices = sqlx::query_as::<_, ReportInvoice>(
+        r#"
+        SELECT
+            invoices.clientId,
+            clients.name AS clientName,
+            clients.vatin,
+            invoices.date,
+            invoices.currency,
+            invoices.subTotal,
+            invoices.taxTotal,
+            invoices.withholdingTotal,
+            invoices.total,
+            invoices.baseCurrency,
+            invoices.exchangeRate
+        FROM invoices
+        LEFT JOIN clients ON clients.id = invoices.clientId
+        WHERE invoices.organizationId = ?
+            AND invoices.state NOT IN ('draft', 'void')
+            AND invoices.date >= ? AND invoices.date < ?
+        ORDER BY clients.name ASC, invoices.clientId ASC, invoices.date ASC
+        "#,
+    )
+    .bind(organization_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok((base_currency, invoices))
+}
+
+impl Database {
+    // Per client, converted to the organization's currency
+    pub async fn get_withholding_report(
+        &self,
+        organization_id: &str,
+        year: i32,
+    ) -> Result<Vec<WithholdingReportRow>, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let (base_currency, invoices) = fetch_report_invoices(&mut conn, organization_id, year).await?;
+
+        let mut rows: Vec<WithholdingReportRow> = Vec::new();
+        for invoice in invoices.iter().filter(|invoice| invoice.withholding_total != 0) {
+            let converter = invoice.converter(&mut conn, &base_currency).await?;
+            let sub_total = converter.convert(invoice.sub_total)?;
+            let withholding_total = converter.convert(invoice.withholding_total)?;
+
+            match rows.last_mut() {
+                Some(row) if row.client_id == invoice.client_id => {
+                    row.invoice_count += 1;
+                    row.sub_total += sub_total;
+                    row.withholding_total += withholding_total;
+                }
+                _ => rows.push(WithholdingReportRow {
+                    client_id: invoice.client_id.clone(),
+                    client_name: invoice.client_name.clone(),
+                    vatin: invoice.vatin.clone(),
+                    currency: base_currency.clone(),
+                    invoice_count: 1,
+                    sub_total,
+                    withholding_total,
+                }),
+            }
+        }
+        Ok(rows)
+    }
+
+    // Per month of the year, converted to the organization's currency. Months
+    // without issued invoices are left out.
+    pub async fn get_revenue_report(
+        &self,
+        organization_id: &str,
+        year: i32,
+    ) -> Result<Vec<RevenueReportRow>, sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let (base_currency, invoices) = fetch_report_invoices(&mut conn, organization_id, year).await?;
+
+        let mut rows: Vec<RevenueReportRow> = (1..=12)
+            .map(|month| RevenueReportRow {
+                month,
+                currency: base_currency.clone(),
+                invoice_count: 0,
+                sub_total: 0,
+                tax_total: 0,
+                withholding_total: 0,
+                total: 0,
+            })
+            .collect();
+        for invoice in &invoices {
+            let converter = invoice.converter(&mut conn, &base_currency).await?;
+            let row = &mut rows[invoice_date(invoice.date)?.month0() as usize];
+            row.invoice_count += 1;
+            row.sub_total += converter.convert(invoice.sub_total)?;
+            row.tax_total += converter.convert(invoice.tax_total)?;
+            row.withholding_total += converter.convert(invoice.withholding_total)?;
+            row.total += converter.convert(invoice.total)?;
+        }
+        rows.retain(|row| row.invoice_count > 0);
+        Ok(rows)
+    }
+}
@@ -3,7 +3,22 @@ use sqlx::FromRow;
 
 use super::Database;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+// How a tax rate treats a supply. Only standard rates charge tax, the others
+// are 0% and state their legal reason on the invoice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TaxCategory {
+    #[default]
+    Standard,
+    ZeroRated,
+    Exempt,
+    // The buyer accounts for the tax, e.g. services to businesses in other EU countries
+    ReverseCharge,
+    OutsideScope,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct TaxRate {
     pub id: String,
     #[serde(rename = "organizationId")]
@@ -15,6 +30,16 @@ pub struct TaxRate {
     #[serde(rename = "isDefault")]
     #[sqlx(rename = "isDefault")]
     pub is_default: Option<i64>,
+    pub category: TaxCategory,
+    // Legal note printed on invoices, e.g. the article a supply is exempt under
+    #[serde(rename = "exemptionReason")]
+    #[sqlx(rename = "exemptionReason")]
+    pub exemption_reason: Option<String>,
+    // Charged on top of a line's other taxes when added to a line, e.g. QST on GST
+    pub compound: bool,
+    // Income tax the client withholds, e.g. IRPF. Taken off the amount due
+    // instead of added to it.
+    pub withholding: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +52,14 @@ pub struct CreateTaxRateRequest {
     pub percentage: f64,
     #[serde(rename = "isDefault")]
     pub is_default: Option<i64>,
+    #[serde(default)]
+    pub category: TaxCategory,
+    #[serde(rename = "exemptionReason")]
+    pub exemption_reason: Option<String>,
+    #[serde(default)]
+    pub compound: bool,
+    #[serde(default)]
+    pub withholding: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,6 +69,25 @@ pub struct UpdateTaxRateRequest {
     pub percentage: Option<f64>,
     #[serde(rename = "isDefault")]
     pub is_default: Option<i64>,
+    pub category: Option<TaxCategory>,
+    #[serde(rename = "exemptionReason")]
+    pub exemption_reason: Option<String>,
+    pub compound: Option<bool>,
+    pub withholding: Option<bool>,
+}
+
+fn validate_tax_rate(category: TaxCategory, percentage: f64, compound: bool, withholding: bool) -> Result<(), sqlx::Error> {
+    if category != TaxCategory::Standard && percentage != 0.0 {
+        return Err(sqlx::Error::Protocol(
+            "Only standard tax rates can have a percentage, other categories are 0%".into(),
+        ));
+    }
+    if withholding && (category != TaxCategory::Standard || compound) {
+        return Err(sqlx::Error::Protocol(
+            "Withholding rates must be standard rates and can't be compound".into(),
+        ));
+    }
+    Ok(())
 }
 
 impl Database {
@@ -53,6 +105,35 @@ impl Database {
         .await
     }
 
+    // Rates on an invoice's lines as they were when the lines were saved, so
+    // its documents keep the amounts it was issued with
+    pub async fn get_invoice_tax_rates(&self, invoice_id: &str) -> Result<Vec<TaxRate>, sqlx::Error> {
+        sqlx::query_as::<_, TaxRate>(
+            r#"
+            SELECT
+                lineItemTaxes.taxRateId AS id,
+                taxRates.organizationId,
+                COALESCE(lineItemTaxes.name, taxRates.name) AS name,
+                taxRates.description,
+                COALESCE(lineItemTaxes.percentage, taxRates.percentage) AS percentage,
+                taxRates.isDefault,
+                COALESCE(lineItemTaxes.category, taxRates.category) AS category,
+                COALESCE(lineItemTaxes.exemptionReason, taxRates.exemptionReason) AS exemptionReason,
+                taxRates.compound,
+                COALESCE(lineItemTaxes.withholding, taxRates.withholding) AS withholding
+            FROM lineItemTaxes
+            INNER JOIN invoiceLineItems ON invoiceLineItems.id = lineItemTaxes.lineItemId
+            INNER JOIN taxRates ON taxRates.id = lineItemTaxes.taxRateId
+            WHERE invoiceLineItems.invoiceId = ?
+            GROUP BY lineItemTaxes.taxRateId
+            ORDER BY name ASC
+            "#,
+        )
+        .bind(invoice_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     pub async fn get_tax_rate(&self, tax_rate_id: &str) -> Result<Option<TaxRate>, sqlx::Error> {
         sqlx::query_as::<_, TaxRate>(
             r#"
@@ -68,6 +149,8 @@ impl Database {
     }
 
     pub async fn create_tax_rate(&self, tax_rate: CreateTaxRateRequest) -> Result<TaxRate, sqlx::Error> {
+        validate_tax_rate(tax_rate.category, tax_rate.percentage, tax_rate.compound, tax_rate.withholding)?;
+
         let mut tx = self.pool.begin().await?;
 
         // If this tax rate is being set as default, unset all others for this organization
@@ -86,8 +169,8 @@ impl Database {
 
         sqlx::query(
             r#"
-            INSERT INTO taxRates (id, organizationId, name, description, percentage, isDefault)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO taxRates (id, organizationId, name, description, percentage, isDefault, category, exemptionReason, compound, withholding)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&tax_rate.id)
@@ -96,6 +179,10 @@ impl Database {
         .bind(&tax_rate.description)
         .bind(&tax_rate.percentage)
         .bind(&tax_rate.is_default)
+        .bind(tax_rate.category)
+        .bind(&tax_rate.exemption_reason)
+        .bind(tax_rate.compound)
+        .bind(tax_rate.withholding)
         .execute(&mut *tx)
         .await?;
 
@@ -112,18 +199,23 @@ impl Database {
     ) -> Result<TaxRate, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
 
-        // If this tax rate is being set as default, first get its organization ID
-        // and unset all others for this organization
+        let tax_rate_row = sqlx::query_as::<_, TaxRate>(
+            "SELECT * FROM taxRates WHERE id = ? LIMIT 1"
+        )
+        .bind(tax_rate_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| sqlx::Error::RowNotFound)?;
+
+        validate_tax_rate(
+            updates.category.unwrap_or(tax_rate_row.category),
+            updates.percentage.unwrap_or(tax_rate_row.percentage),
+            updates.compound.unwrap_or(tax_rate_row.compound),
+            updates.withholding.unwrap_or(tax_rate_row.withholding),
+        )?;
+
+        // If this tax rate is being set as default, unset all others for this organization
         if updates.is_default == Some(1) {
-            // Get the organization ID for this tax rate
-            let tax_rate_row = sqlx::query_as::<_, TaxRate>(
-                "SELECT * FROM taxRates WHERE id = ? LIMIT 1"
-            )
-            .bind(tax_rate_id)
-            .fetch_optional(&mut *tx)
-            .await?
-            .ok_or_else(|| sqlx::Error::RowNotFound)?;
-            
             sqlx::query(
                 r#"
                 UPDATE taxRates
@@ -143,7 +235,11 @@ impl Database {
             SET name = COALESCE(?, name),
                 description = COALESCE(?, description),
                 percentage = COALESCE(?, percentage),
-                isDefault = COALESCE(?, isDefault)
+                isDefault = COALESCE(?, isDefault),
+                category = COALESCE(?, category),
+                exemptionReason = COALESCE(?, exemptionReason),
+                compound = COALESCE(?, compound),
+                withholding = COALESCE(?, withholding)
             WHERE id = ?
             "#,
         )
@@ -151,6 +247,10 @@ impl Database {
         .bind(&updates.description)
         .bind(&updates.percentage)
         .bind(&updates.is_default)
+        .bind(updates.category)
+        .bind(&updates.exemption_reason)
+        .bind(updates.compound)
+        .bind(updates.withholding)
         .bind(tax_rate_id)
         .execute(&mut *tx)
         .await?;
@@ -162,6 +262,26 @@ impl Database {
     }
 
     pub async fn delete_tax_rate(&self, tax_rate_id: &str) -> Result<bool, sqlx::Error> {
+        // Line taxes go with the rate, which would change issued invoices
+        let issued = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM lineItemTaxes
+                INNER JOIN invoiceLineItems ON invoiceLineItems.id = lineItemTaxes.lineItemId
+                INNER JOIN invoices ON invoices.id = invoiceLineItems.invoiceId
+                WHERE lineItemTaxes.taxRateId = ? AND invoices.state != 'draft'
+            )
+            "#,
+        )
+        .bind(tax_rate_id)
+        .fetch_one(&self.pool)
+        .await?;
+        if issued {
+            return Err(sqlx::Error::Protocol(
+                "Tax rate is used on issued invoices and can't be deleted".into(),
+            ));
+        }
+
         let result = sqlx::query("DELETE FROM taxRates WHERE id = ?")
             .bind(tax_rate_id)
             .execute(&self.pool)
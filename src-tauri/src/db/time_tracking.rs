@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
 
 use super::Database;
+use crate::money::Money;
 
 // Tags
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -33,39 +34,54 @@ pub struct UpdateTagRequest {
 }
 
 // Time Entries
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize)]
 pub struct TimeEntry {
     pub id: String,
     #[serde(rename = "organizationId")]
-    #[sqlx(rename = "organizationId")]
     pub organization_id: String,
     #[serde(rename = "clientId")]
-    #[sqlx(rename = "clientId")]
     pub client_id: Option<String>,
     pub description: Option<String>,
     #[serde(rename = "startTime")]
-    #[sqlx(rename = "startTime")]
     pub start_time: i64,
     #[serde(rename = "endTime")]
-    #[sqlx(rename = "endTime")]
     pub end_time: Option<i64>,
     pub duration: i64,
     pub tags: Option<String>, // JSON array of tag names
     #[serde(rename = "isBillable")]
-    #[sqlx(rename = "isBillable")]
     pub is_billable: i64,
+    // In the organization's currency
     #[serde(rename = "hourlyRate")]
-    #[sqlx(rename = "hourlyRate")]
-    pub hourly_rate: Option<f64>,
+    pub hourly_rate: Option<Money>,
     #[serde(rename = "createdAt")]
-    #[sqlx(rename = "createdAt")]
     pub created_at: Option<String>,
     // Only for joined queries
     #[serde(rename = "clientName")]
-    #[sqlx(rename = "clientName")]
     pub client_name: Option<String>,
 }
 
+// Read with the organization's currency joined in
+impl<'r> FromRow<'r, SqliteRow> for TimeEntry {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let currency: String = row.try_get("currency")?;
+        let hourly_rate: Option<i64> = row.try_get("hourlyRate")?;
+        Ok(Self {
+            id: row.try_get("id")?,
+            organization_id: row.try_get("organizationId")?,
+            client_id: row.try_get("clientId")?,
+            description: row.try_get("description")?,
+            start_time: row.try_get("startTime")?,
+            end_time: row.try_get("endTime")?,
+            duration: row.try_get("duration")?,
+            tags: row.try_get("tags")?,
+            is_billable: row.try_get("isBillable")?,
+            hourly_rate: hourly_rate.map(|amount_minor| Money::new(amount_minor, &currency)),
+            created_at: row.try_get("createdAt")?,
+            client_name: row.try_get("clientName")?,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateTimeEntryRequest {
     pub id: String,
@@ -83,7 +99,7 @@ pub struct CreateTimeEntryRequest {
     #[serde(rename = "isBillable")]
     pub is_billable: i64,
     #[serde(rename = "hourlyRate")]
-    pub hourly_rate: Option<f64>,
+    pub hourly_rate: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -100,7 +116,7 @@ pub struct UpdateTimeEntryRequest {
     #[serde(rename = "isBillable")]
     pub is_billable: Option<i64>,
     #[serde(rename = "hourlyRate")]
-    pub hourly_rate: Option<f64>,
+    pub hourly_rate: Option<i64>,
 }
 
 impl Database {
@@ -160,9 +176,10 @@ impl Database {
     // Time Entry methods
     pub async fn get_time_entries(&self, organization_id: &str) -> Result<Vec<TimeEntry>, sqlx::Error> {
         sqlx::query_as::<_, TimeEntry>(
-            "SELECT t.*, c.name as clientName 
+            "SELECT t.*, c.name as clientName, COALESCE(o.currency, 'EUR') as currency
              FROM timeEntries t 
              LEFT JOIN clients c ON t.clientId = c.id 
+             LEFT JOIN organizations o ON t.organizationId = o.id 
              WHERE t.organizationId = ? 
              ORDER BY t.startTime DESC"
         )
@@ -173,9 +190,10 @@ impl Database {
 
     pub async fn get_time_entry(&self, time_entry_id: &str) -> Result<Option<TimeEntry>, sqlx::Error> {
         sqlx::query_as::<_, TimeEntry>(
-            "SELECT t.*, c.name as clientName 
+            "SELECT t.*, c.name as clientName, COALESCE(o.currency, 'EUR') as currency
              FROM timeEntries t 
              LEFT JOIN clients c ON t.clientId = c.id 
+             LEFT JOIN organizations o ON t.organizationId = o.id 
              WHERE t.id = ?"
         )
         .bind(time_entry_id)
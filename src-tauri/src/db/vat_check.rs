@@ -0,0 +1,261 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+use super::{Client, Database};
+use crate::validation::{is_eu_vat_prefix, validate_vatin};
+use crate::vat_registry::VatRegistry;
+
+// Registrations rarely change within a day, earlier answers are reused until then
+const VAT_CHECK_MAX_AGE: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct VatCheck {
+    pub id: String,
+    #[serde(rename = "countryCode")]
+    #[sqlx(rename = "countryCode")]
+    pub country_code: String,
+    #[serde(rename = "vatNumber")]
+    #[sqlx(rename = "vatNumber")]
+    pub vat_number: String,
+    pub valid: bool,
+    pub name: Option<String>,
+    pub address: Option<String>,
+    // Own VAT number the check was made with
+    pub requester: Option<String>,
+    #[serde(rename = "consultationNumber")]
+    #[sqlx(rename = "consultationNumber")]
+    pub consultation_number: Option<String>,
+    #[serde(rename = "checkedAt")]
+    #[sqlx(rename = "checkedAt")]
+    pub checked_at: i64,
+}
+
+// Splits an EU VAT number into its prefix and national number
+fn split_vatin(vatin: &str, country: Option<&str>) -> Result<(String, String), String> {
+    let vatin = validate_vatin(vatin, country)?;
+    if vatin.len() < 3 || !is_eu_vat_prefix(&vatin[..2]) {
+        return Err(format!("VAT number {} is not from an EU member state, VIES can't check it", vatin));
+    }
+    Ok((vatin[..2].to_string(), vatin[2..].to_string()))
+}
+
+impl Database {
+    // Looks the number up in the registry, or reuses a recent answer for the
+    // same requester unless `refresh` is set. Registry outages are errors and
+    // aren't recorded.
+    pub async fn check_vatin<R: VatRegistry>(
+        &self,
+        registry: &R,
+        vatin: &str,
+        country: Option<&str>,
+        requester: Option<&str>,
+        refresh: bool,
+    ) -> Result<VatCheck, sqlx::Error> {
+        let (country_code, number) = split_vatin(vatin, country).map_err(sqlx::Error::Protocol)?;
+        // A malformed own number only costs the consultation number
+        let requester = requester.and_then(|requester| split_vatin(requester, None).ok());
+        let requester_vatin = requester.as_ref().map(|(country_code, number)| format!("{}{}", country_code, number));
+        let now = chrono::Utc::now().timestamp_millis();
+
+        if !refresh {
+            let cached = sqlx::query_as::<_, VatCheck>(
+                r#"
+                SELECT *
+                FROM vatChecks
+                WHERE countryCode = ? AND vatNumber = ? AND requester IS ? AND checkedAt > ?
+                ORDER BY checkedAt DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(&country_code)
+            .bind(&number)
+            .bind(&requester_vatin)
+            .bind(now - VAT_CHECK_MAX_AGE)
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(cached) = cached {
+                return Ok(cached);
+            }
+        }
+
+        let lookup = registry
+            .lookup(
+                &country_code,
+                &number,
+                requester.as_ref().map(|(country_code, number)| (country_code.as_str(), number.as_str())),
+            )
+            .await
+            .map_err(sqlx::Error::Protocol)?;
+
+        let check = VatCheck {
+            id: nanoid::nanoid!(),
+            country_code,
+            vat_number: number,
+            valid: lookup.valid,
+            name: lookup.name,
+            address: lookup.address,
+            requester: requester_vatin,
+            consultation_number: lookup.consultation_number,
+            checked_at: now,
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO vatChecks (id, countryCode, vatNumber, valid, name, address, requester, consultationNumber, checkedAt)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&check.id)
+        .bind(&check.country_code)
+        .bind(&check.vat_number)
+        .bind(check.valid)
+        .bind(&check.name)
+        .bind(&check.address)
+        .bind(&check.requester)
+        .bind(&check.consultation_number)
+        .bind(check.checked_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(check)
+    }
+
+    // Checks the client's VAT number again and stores the result on the client
+    pub async fn verify_client_vatin<R: VatRegistry>(&self, registry: &R, client_id: &str) -> Result<Client, sqlx::Error> {
+        let client = self.get_client(client_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let vatin = client
+            .vatin
+            .as_deref()
+            .filter(|vatin| !vatin.trim().is_empty())
+            .ok_or_else(|| sqlx::Error::Protocol("The client has no VAT number to verify".into()))?;
+        let organization = self.get_organization(&client.organization_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let requester = organization.vatin.as_deref().and_then(|vatin| validate_vatin(vatin, organization.country.as_deref()).ok());
+
+        let check = self
+            .check_vatin(registry, vatin, client.country.as_deref(), requester.as_deref(), true)
+            .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE clients
+            SET vatin_valid = ?, vatin_checked_at = ?, vatin_consultation_number = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(check.valid)
+        .bind(check.checked_at)
+        .bind(&check.consultation_number)
+        .bind(client_id)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_client(client_id).await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vat_registry::MockVatRegistry;
+
+    async fn database() -> Database {
+        let path = std::env::temp_dir().join(format!("upcount-test-{}.db", nanoid::nanoid!()));
+        Database::new(&format!("sqlite://{}", path.display())).await.unwrap()
+    }
+
+    async fn client(db: &Database, vatin: &str) {
+        db.create_organization(
+            serde_json::from_value(serde_json::json!({"id": "org", "name": "Seller GmbH", "country": "Germany", "vatin": "DE136695976"}))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        db.create_client(
+            serde_json::from_value(serde_json::json!({"id": "client", "organizationId": "org", "name": "Acme OÜ", "country": "Estonia", "vatin": vatin}))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reuses_a_recent_answer() {
+        let db = database().await;
+        let registry = MockVatRegistry::default().with_registered("EE100931558", "Acme OÜ");
+
+        let check = db.check_vatin(&registry, "EE100931558", None, Some("DE136695976"), false).await.unwrap();
+        assert!(check.valid);
+        assert_eq!(check.name.as_deref(), Some("Acme OÜ"));
+        assert_eq!(check.consultation_number.as_deref(), Some("MOCKEE100931558"));
+
+        // Answered from the cache even though the registry is down
+        let unavailable = MockVatRegistry::default().unavailable();
+        let cached = db.check_vatin(&unavailable, "EE 100931558", None, Some("DE136695976"), false).await.unwrap();
+        assert_eq!(cached.id, check.id);
+
+        // Not for another requester, or when asked to refresh
+        assert!(db.check_vatin(&unavailable, "EE100931558", None, None, false).await.is_err());
+        assert!(db.check_vatin(&unavailable, "EE100931558", None, Some("DE136695976"), true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn looks_up_again_after_a_day() {
+        let db = database().await;
+        let registry = MockVatRegistry::default().with_registered("EE100931558", "Acme OÜ");
+        let check = db.check_vatin(&registry, "EE100931558", None, None, false).await.unwrap();
+        sqlx::query("UPDATE vatChecks SET checkedAt = checkedAt - ? WHERE id = ?")
+            .bind(VAT_CHECK_MAX_AGE + 1)
+            .bind(&check.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let unavailable = MockVatRegistry::default().unavailable();
+        assert!(db.check_vatin(&unavailable, "EE100931558", None, None, false).await.is_err());
+        let fresh = db.check_vatin(&registry, "EE100931558", None, None, false).await.unwrap();
+        assert_ne!(fresh.id, check.id);
+    }
+
+    #[tokio::test]
+    async fn records_nothing_when_the_registry_is_unavailable() {
+        let db = database().await;
+        let unavailable = MockVatRegistry::default().unavailable();
+        assert!(db.check_vatin(&unavailable, "EE100931558", None, None, false).await.is_err());
+
+        let checks = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM vatChecks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(checks, 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_numbers_vies_cant_check() {
+        let db = database().await;
+        let registry = MockVatRegistry::default();
+        assert!(db.check_vatin(&registry, "NO123456789MVA", None, None, false).await.is_err());
+        assert!(db.check_vatin(&registry, "EE100931559", None, None, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn stores_the_result_on_the_client() {
+        let db = database().await;
+        client(&db, "100931558").await;
+
+        let registered = MockVatRegistry::default().with_registered("EE100931558", "Acme OÜ");
+        let client = db.verify_client_vatin(&registered, "client").await.unwrap();
+        assert_eq!(client.vatin_valid, Some(true));
+        assert!(client.vatin_checked_at.is_some());
+        assert_eq!(client.vatin_consultation_number.as_deref(), Some("MOCKEE100931558"));
+
+        // An outage leaves the last result in place
+        let unavailable = MockVatRegistry::default().unavailable();
+        assert!(db.verify_client_vatin(&unavailable, "client").await.is_err());
+        assert_eq!(db.get_client("client").await.unwrap().unwrap().vatin_valid, Some(true));
+
+        let client = db.verify_client_vatin(&MockVatRegistry::default(), "client").await.unwrap();
+        assert_eq!(client.vatin_valid, Some(false));
+    }
+}
@@ -0,0 +1,172 @@
+// Euro foreign exchange reference rates published by the European Central
+// Bank, as the XML or CSV files offered for download (daily, last 90 days or
+// the full history)
+
+use chrono::NaiveDate;
+
+use crate::money::is_valid_rate;
+
+// Every ECB rate is units of a currency for one euro
+pub const BASE_CURRENCY: &str = "EUR";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceRate {
+    pub date: NaiveDate,
+    pub currency: String,
+    // Decimal as published, e.g. "1.0921"
+    pub rate: String,
+}
+
+// Reads either format, telling them apart by the XML declaration or root
+pub fn parse_reference_rates(contents: &str) -> Result<Vec<ReferenceRate>, String> {
+    let contents = contents.trim_start_matches('\u{feff}').trim();
+    let rates = if contents.starts_with('<') {
+        parse_xml(contents)?
+    } else {
+        parse_csv(contents)?
+    };
+
+    if rates.is_empty() {
+        return Err("No exchange rates found in the file".into());
+    }
+    Ok(rates)
+}
+
+// <Cube time="2024-01-05"><Cube currency="USD" rate="1.0921"/>...</Cube>
+fn parse_xml(contents: &str) -> Result<Vec<ReferenceRate>, String> {
+    let document = roxmltree::Document::parse(contents)
+        .map_err(|e| format!("Invalid exchange rate XML: {}", e))?;
+
+    let mut rates = Vec::new();
+    for day in document
+        .descendants()
+        .filter(|node| node.has_tag_name("Cube") && node.has_attribute("time"))
+    {
+        let date = parse_date(day.attribute("time").unwrap_or_default())?;
+        for rate in day.children().filter(|node| node.has_tag_name("Cube")) {
+            let (Some(currency), Some(value)) = (rate.attribute("currency"), rate.attribute("rate")) else {
+                continue;
+            };
+            rates.push(reference_rate(date, currency, value)?);
+        }
+    }
+    Ok(rates)
+}
+
+// A header row of currencies after "Date", then one row per day. Currencies
+// the ECB stopped publishing are "N/A" or empty on later days.
+fn parse_csv(contents: &str) -> Result<Vec<ReferenceRate>, String> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .map(|line| line.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    if !header.first().is_some_and(|column| column.eq_ignore_ascii_case("Date")) {
+        return Err("Exchange rate CSV must start with a Date column".into());
+    }
+
+    let mut rates = Vec::new();
+    for line in lines {
+        let mut columns = line.split(',').map(str::trim);
+        let date = parse_date(columns.next().unwrap_or_default())?;
+        for (currency, value) in header.iter().skip(1).zip(columns) {
+            if currency.is_empty() || value.is_empty() || value.eq_ignore_ascii_case("N/A") {
+                continue;
+            }
+            rates.push(reference_rate(date, currency, value)?);
+        }
+    }
+    Ok(rates)
+}
+
+fn reference_rate(date: NaiveDate, currency: &str, rate: &str) -> Result<ReferenceRate, String> {
+    let currency = currency.trim().to_ascii_uppercase();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("Invalid currency in exchange rates: {}", currency));
+    }
+    if !is_valid_rate(rate) {
+        return Err(format!("Invalid {} exchange rate on {}: {}", currency, date, rate));
+    }
+    Ok(ReferenceRate { date, currency, rate: rate.trim().to_string() })
+}
+
+// ISO dates, or "5 January 2024" as in the daily CSV
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%d %B %Y"))
+        .map_err(|_| format!("Invalid date in exchange rates: {}", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::rate_quotient;
+
+    const EUROFXREF_DAILY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+	<gesmes:subject>Reference rates</gesmes:subject>
+	<gesmes:Sender>
+		<gesmes:name>European Central Bank</gesmes:name>
+	</gesmes:Sender>
+	<Cube>
+		<Cube time='2024-01-05'>
+			<Cube currency='USD' rate='1.0921'/>
+			<Cube currency='JPY' rate='158.08'/>
+			<Cube currency='GBP' rate='0.86045'/>
+		</Cube>
+	</Cube>
+</gesmes:Envelope>"#;
+
+    fn rate(date: &str, currency: &str, rate: &str) -> ReferenceRate {
+        ReferenceRate {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            currency: currency.to_string(),
+            rate: rate.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_the_daily_xml() {
+        assert_eq!(
+            parse_reference_rates(EUROFXREF_DAILY).unwrap(),
+            vec![
+                rate("2024-01-05", "USD", "1.0921"),
+                rate("2024-01-05", "JPY", "158.08"),
+                rate("2024-01-05", "GBP", "0.86045"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_the_csv_and_skips_discontinued_currencies() {
+        let csv = "Date, USD, JPY, CYP, \n05 January 2024, 1.0921, 158.08, N/A, \n2024-01-04,1.0953,157.93,,\n";
+        assert_eq!(
+            parse_reference_rates(csv).unwrap(),
+            vec![
+                rate("2024-01-05", "USD", "1.0921"),
+                rate("2024-01-05", "JPY", "158.08"),
+                rate("2024-01-04", "USD", "1.0953"),
+                rate("2024-01-04", "JPY", "157.93"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_valid_rates() {
+        assert!(parse_reference_rates("<Envelope/>").is_err());
+        assert!(parse_reference_rates("Currency,USD\n2024-01-05,1.0921\n").is_err());
+        assert!(parse_reference_rates("Date,USD\n2024-01-05,abc\n").is_err());
+        assert!(parse_reference_rates("Date,USD\n2024-01-05,0\n").is_err());
+        assert!(parse_reference_rates("Date,US\n2024-01-05,1.0921\n").is_err());
+    }
+
+    #[test]
+    fn inverts_and_crosses_rates() {
+        // USD to EUR from EUR to USD
+        assert_eq!(rate_quotient("1", "1.0921").as_deref(), Some("0.9156670635"));
+        assert_eq!(rate_quotient("1", "0.5").as_deref(), Some("2"));
+        // USD to JPY through EUR
+        assert_eq!(rate_quotient("158.08", "1.0921").as_deref(), Some("144.7486493911"));
+        assert_eq!(rate_quotient("1", "0"), None);
+    }
+}
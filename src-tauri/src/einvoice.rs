@@ -0,0 +1,717 @@
+// Structured e-invoices (EN 16931). Invoices are first mapped onto the
+// semantic model below, which is where the business rules are checked, and
+// then written out in a concrete syntax.
+
+pub mod cii;
+pub mod facturx;
+pub mod import;
+pub mod ubl;
+pub mod xml;
+
+use chrono::{Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::countries::country_code;
+use cii::FacturXProfile;
+use crate::db::{
+    calculate_invoice_totals, line_amount, Client, CreateInvoiceLineItemRequest, Database, Invoice, InvoiceKind,
+    InvoiceLineItem, InvoiceTotals, Organization, TaxRate,
+};
+use crate::db::TaxCategory as RateCategory;
+use crate::money::Money;
+use crate::units::DEFAULT_UNIT_CODE;
+
+// A rule the invoice doesn't meet, tied to the field that needs fixing
+#[derive(Debug, Clone, Serialize)]
+pub struct EInvoiceError {
+    pub field: String,
+    pub message: String,
+}
+
+impl EInvoiceError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        Self { field: field.to_string(), message: message.into() }
+    }
+}
+
+// Syntax, and profile where there is a choice, an e-invoice is exported in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EInvoiceFormat {
+    // Peppol BIS Billing 3.0 in UBL
+    #[default]
+    Peppol,
+    // CII embedded in a PDF/A-3
+    FacturX(FacturXProfile),
+}
+
+fn validation_error(errors: &[EInvoiceError]) -> sqlx::Error {
+    let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+    sqlx::Error::Protocol(format!("The invoice can't be exported as an e-invoice:\n{}", messages.join("\n")))
+}
+
+#[derive(Debug, Clone)]
+pub struct Address {
+    pub lines: Vec<String>,
+    pub country_code: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Party {
+    pub name: String,
+    // Electronic address (scheme, identifier), e.g. ("9931", "EE100000000")
+    pub endpoint: Option<(String, String)>,
+    pub vat_id: Option<String>,
+    pub legal_id: Option<String>,
+    pub address: Address,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+// VAT category code (UNCL5305) with its rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxCategory {
+    pub code: &'static str,
+    pub percent: f64,
+}
+
+impl TaxCategory {
+    pub fn for_rate(percent: f64) -> Self {
+        if percent > 0.0 {
+            Self { code: "S", percent }
+        } else {
+            Self { code: "Z", percent: 0.0 }
+        }
+    }
+
+    pub fn for_tax_rate(category: RateCategory, percent: f64) -> Self {
+        let code = match category {
+            RateCategory::Standard => return Self::for_rate(percent),
+            RateCategory::ZeroRated => "Z",
+            RateCategory::Exempt => "E",
+            RateCategory::ReverseCharge => "AE",
+            RateCategory::OutsideScope => "O",
+        };
+        Self { code, percent: 0.0 }
+    }
+
+    // Categories that don't charge tax have to say why (BT-120 or BT-121).
+    // Returns the VATEX code and the text used when the rate has none.
+    pub fn exemption(&self) -> Option<(Option<&'static str>, &'static str)> {
+        match self.code {
+            "E" => Some((None, "Exempt from VAT")),
+            "AE" => Some((Some("VATEX-EU-AE"), "Reverse charge")),
+            "K" => Some((Some("VATEX-EU-IC"), "Intra-community supply")),
+            "G" => Some((Some("VATEX-EU-G"), "Export outside the EU")),
+            "O" => Some((Some("VATEX-EU-O"), "Not subject to VAT")),
+            _ => None,
+        }
+    }
+
+    // For received documents, which may use any of the EN 16931 categories
+    pub fn from_code(code: &str, percent: f64) -> Option<Self> {
+        let code = match code {
+            "S" => "S",
+            "Z" => "Z",
+            "E" => "E",
+            "AE" => "AE",
+            "K" => "K",
+            "G" => "G",
+            "O" => "O",
+            "L" => "L",
+            "M" => "M",
+            "B" => "B",
+            _ => return None,
+        };
+        Some(Self { code, percent })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub id: String,
+    pub name: String,
+    pub quantity: f64,
+    // UN/ECE Recommendation 20 unit code
+    pub unit_code: String,
+    pub price: i64,  // Minor units
+    // Quantity the price is given for when it isn't per unit. Received
+    // documents have their prices read per unit.
+    pub base_quantity: Option<f64>,
+    pub net: i64,  // Minor units
+    // Discount already taken off `net`. Not read from received documents.
+    pub allowance: i64,  // Minor units
+    pub tax: TaxCategory,
+}
+
+// Document level discount on the lines of one tax category
+#[derive(Debug, Clone)]
+pub struct Allowance {
+    pub category: TaxCategory,
+    pub amount: i64,  // Minor units
+}
+
+#[derive(Debug, Clone)]
+pub struct TaxBreakdown {
+    pub category: TaxCategory,
+    pub taxable: i64,  // Minor units
+    pub amount: i64,  // Minor units
+    pub exemption_reason: Option<String>,
+    pub exemption_reason_code: Option<&'static str>,
+}
+
+impl TaxBreakdown {
+    fn new(category: TaxCategory, taxable: i64, amount: i64, reason: Option<&str>) -> Self {
+        let exemption = category.exemption();
+        let exemption_reason = exemption.map(|(_, default)| {
+            reason.map(str::trim).filter(|reason| !reason.is_empty()).unwrap_or(default).to_string()
+        });
+        Self {
+            exemption_reason_code: exemption.and_then(|(code, _)| code),
+            category,
+            taxable,
+            amount,
+            exemption_reason,
+        }
+    }
+}
+
+// EN 16931 semantic invoice. Credit notes are stored with negative amounts
+// but carry positive amounts here, as the syntaxes expect.
+#[derive(Debug, Clone)]
+pub struct EInvoice {
+    pub kind: InvoiceKind,
+    pub number: String,
+    pub issue_date: NaiveDate,
+    pub due_date: Option<NaiveDate>,
+    pub currency: String,
+    pub note: Option<String>,
+    pub buyer_reference: String,
+    pub preceding_invoice: Option<String>,
+    pub seller: Party,
+    pub buyer: Party,
+    pub payment_reference: Option<String>,
+    pub iban: Option<String>,
+    pub bank_name: Option<String>,
+    pub lines: Vec<Line>,
+    // Not read from received documents
+    pub allowances: Vec<Allowance>,
+    pub taxes: Vec<TaxBreakdown>,
+    // Total without tax, after allowances. Read documents may include
+    // document level charges in it.
+    pub line_total: i64,  // Minor units
+    pub tax_total: i64,  // Minor units
+    pub total: i64,  // Minor units
+    pub prepaid: i64,  // Minor units
+    pub rounding: i64,  // Minor units
+}
+
+impl EInvoice {
+    pub fn payable(&self) -> i64 {
+        self.total - self.prepaid + self.rounding
+    }
+
+    pub fn allowance_total(&self) -> i64 {
+        self.allowances.iter().map(|allowance| allowance.amount).sum()
+    }
+
+    // UNCL1001 document type
+    pub fn type_code(&self) -> &'static str {
+        match self.kind {
+            InvoiceKind::Invoice => "380",
+            InvoiceKind::CreditNote => "381",
+        }
+    }
+}
+
+// Everything the mapping reads, loaded up front
+pub struct EInvoiceSource {
+    pub invoice: Invoice,
+    pub line_items: Vec<InvoiceLineItem>,
+    pub client: Client,
+    pub organization: Organization,
+    pub totals: InvoiceTotals,
+    pub tax_rates: Vec<TaxRate>,
+    pub credited_invoice_number: Option<String>,
+}
+
+// Peppol electronic address scheme (EAS) for VAT numbers, by country
+fn vat_endpoint_scheme(country_code: &str) -> Option<&'static str> {
+    Some(match country_code {
+        "AD" => "9922",
+        "AL" => "9923",
+        "AT" => "9914",
+        "BA" => "9924",
+        "BE" => "9925",
+        "BG" => "9926",
+        "CH" => "9927",
+        "CY" => "9928",
+        "CZ" => "9929",
+        "DE" => "9930",
+        "EE" => "9931",
+        "ES" => "9920",
+        "FI" => "0213",
+        "FR" => "9957",
+        "GB" => "9932",
+        "GR" => "9933",
+        "HR" => "9934",
+        "HU" => "9910",
+        "IE" => "9935",
+        "IT" => "9906",
+        "LI" => "9936",
+        "LT" => "9937",
+        "LU" => "9938",
+        "LV" => "9939",
+        "MC" => "9940",
+        "ME" => "9941",
+        "MK" => "9942",
+        "MT" => "9943",
+        "NL" => "9944",
+        "PL" => "9945",
+        "PT" => "9946",
+        "RO" => "9947",
+        "RS" => "9948",
+        "SI" => "9949",
+        "SK" => "9950",
+        "SM" => "9951",
+        "TR" => "9952",
+        "VA" => "9953",
+        _ => return None,
+    })
+}
+
+// VAT identifiers must start with the country prefix (EL for Greece)
+fn vat_id(vatin: &str, country_code: &str) -> String {
+    let vatin: String = vatin.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+    if vatin.len() > 2 && vatin[..2].chars().all(|c| c.is_ascii_alphabetic()) {
+        vatin
+    } else {
+        let prefix = if country_code == "GR" { "EL" } else { country_code };
+        format!("{}{}", prefix, vatin)
+    }
+}
+
+fn local_date(timestamp: i64) -> Option<NaiveDate> {
+    Local.timestamp_millis_opt(timestamp).single().map(|date| date.date_naive())
+}
+
+fn local_midnight(date: NaiveDate) -> i64 {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|date| date.timestamp_millis())
+        .unwrap_or_default()
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+struct PartyFields<'a> {
+    prefix: &'static str,
+    label: &'static str,
+    name: &'a Option<String>,
+    country: &'a Option<String>,
+    address: &'a Option<String>,
+    vatin: &'a Option<String>,
+    vat_required: bool,
+    endpoint_required: bool,
+    registration_number: &'a Option<String>,
+    email: Option<String>,
+    phone: &'a Option<String>,
+}
+
+fn party(fields: PartyFields, errors: &mut Vec<EInvoiceError>) -> Option<Party> {
+    let PartyFields { prefix, label, .. } = fields;
+    let name = non_empty(fields.name);
+    if name.is_none() {
+        errors.push(EInvoiceError::new(&format!("{}.name", prefix), format!("{} name is missing", label)));
+    }
+
+    let country = match non_empty(fields.country) {
+        None => {
+            errors.push(EInvoiceError::new(&format!("{}.country", prefix), format!("{} country is missing", label)));
+            None
+        }
+        Some(country) => {
+            let code = country_code(&country);
+            if code.is_none() {
+                errors.push(EInvoiceError::new(
+                    &format!("{}.country", prefix),
+                    format!("{} country \"{}\" is not recognized", label, country),
+                ));
+            }
+            code
+        }
+    };
+
+    let vat_id = non_empty(fields.vatin).zip(country).map(|(vatin, country)| vat_id(&vatin, country));
+    if fields.vat_required && non_empty(fields.vatin).is_none() {
+        errors.push(EInvoiceError::new(&format!("{}.vatin", prefix), format!("{} VAT number is missing", label)));
+    }
+
+    // The VAT number doubles as the electronic address
+    let endpoint = match (&vat_id, country) {
+        (Some(vat_id), Some(country)) => match vat_endpoint_scheme(country) {
+            Some(scheme) => Some((scheme.to_string(), vat_id.clone())),
+            None => {
+                if fields.endpoint_required {
+                    errors.push(EInvoiceError::new(
+                        &format!("{}.country", prefix),
+                        format!("{} electronic address can't be derived for country {}", label, country),
+                    ));
+                }
+                None
+            }
+        },
+        _ => None,
+    };
+
+    Some(Party {
+        name: name?,
+        endpoint,
+        vat_id,
+        legal_id: non_empty(fields.registration_number),
+        address: Address {
+            lines: non_empty(fields.address)
+                .map(|address| address.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            country_code: country?.to_string(),
+        },
+        email: fields.email,
+        phone: non_empty(fields.phone),
+    })
+}
+
+impl EInvoiceSource {
+    // Maps the invoice onto the semantic model, collecting every rule it
+    // fails instead of stopping at the first one. Peppol also needs an
+    // electronic address for both parties, which is taken from the VAT number.
+    pub fn to_einvoice(&self, format: EInvoiceFormat) -> Result<EInvoice, Vec<EInvoiceError>> {
+        let invoice = &self.invoice;
+        let organization = &self.organization;
+        let client = &self.client;
+        let peppol = format == EInvoiceFormat::Peppol;
+        let mut errors = Vec::new();
+
+        let client_email = client
+            .emails
+            .as_deref()
+            .and_then(|emails| serde_json::from_str::<Vec<String>>(emails).ok())
+            .and_then(|emails| emails.into_iter().find(|email| !email.trim().is_empty()));
+
+        let seller = party(
+            PartyFields {
+                prefix: "organization",
+                label: "Seller",
+                name: &organization.name,
+                country: &organization.country,
+                address: &organization.address,
+                vatin: &organization.vatin,
+                // Every VAT category in use requires the seller's VAT number
+                vat_required: true,
+                endpoint_required: peppol,
+                registration_number: &organization.registration_number,
+                email: non_empty(&organization.email),
+                phone: &organization.phone,
+            },
+            &mut errors,
+        );
+        let buyer = party(
+            PartyFields {
+                prefix: "client",
+                label: "Buyer",
+                name: &client.name,
+                country: &client.country,
+                address: &client.address,
+                vatin: &client.vatin,
+                vat_required: peppol,
+                endpoint_required: peppol,
+                registration_number: &client.registration_number,
+                email: client_email,
+                phone: &client.phone,
+            },
+            &mut errors,
+        );
+
+        if self.line_items.is_empty() {
+            errors.push(EInvoiceError::new("lineItems", "The invoice has no line items"));
+        }
+        // EN 16931 has no way to take withheld income tax off the amount due
+        if self.totals.withholding_total.amount_minor != 0 {
+            errors.push(EInvoiceError::new(
+                "lineItems",
+                "The invoice has withholding tax, which e-invoices can't carry",
+            ));
+        }
+        if invoice.currency.len() != 3 || !invoice.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            errors.push(EInvoiceError::new("currency", format!("Currency \"{}\" is not an ISO 4217 code", invoice.currency)));
+        }
+
+        // Credit notes carry positive amounts in the e-invoice
+        let sign = if invoice.kind == InvoiceKind::CreditNote { -1 } else { 1 };
+
+        let mut lines = Vec::with_capacity(self.line_items.len());
+        let mut allowances: Vec<Allowance> = Vec::new();
+        for (i, line_item) in self.line_items.iter().enumerate() {
+            let name = non_empty(&line_item.description);
+            if name.is_none() {
+                errors.push(EInvoiceError::new(
+                    &format!("lineItems.{}.description", i),
+                    format!("Line item {} has no description", i + 1),
+                ));
+            }
+
+            // EN 16931 allows a single VAT category and rate per line
+            if line_item.taxes.len() > 1 {
+                errors.push(EInvoiceError::new(
+                    &format!("lineItems.{}.taxes", i),
+                    format!("Line item {} has several taxes, e-invoices allow only one per line", i + 1),
+                ));
+            }
+
+            let tax = line_item
+                .tax_rate
+                .as_ref()
+                .and_then(|id| self.tax_rates.iter().find(|tax_rate| &tax_rate.id == id))
+                .map(|tax_rate| TaxCategory::for_tax_rate(tax_rate.category, tax_rate.percentage))
+                .unwrap_or_else(|| TaxCategory::for_rate(0.0));
+
+            // E-invoices carry net prices, tax-inclusive ones are backed out per unit.
+            // When that price doesn't multiply back out to the line amount it is given
+            // for the whole quantity instead, so the line net matches exactly (BR-LIN-04).
+            let line_total = &self.totals.lines[i];
+            let (unit_price, base_quantity) = if invoice.prices_include_tax && line_item.quantity != 0.0 {
+                let unit_price = (line_total.amount as f64 / line_item.quantity).round() as i64;
+                if line_amount(line_item.quantity, unit_price) == line_total.amount {
+                    (unit_price, None)
+                } else {
+                    (line_total.amount * line_item.quantity.signum() as i64, Some(line_item.quantity.abs()))
+                }
+            } else {
+                (line_item.unit_price.amount_minor, None)
+            };
+
+            // The line's own discount is a line allowance, its share of the
+            // invoice discount goes into a document allowance for its category
+            if line_total.invoice_discount != 0 {
+                match allowances.iter_mut().find(|allowance| allowance.category == tax) {
+                    Some(allowance) => allowance.amount += sign * line_total.invoice_discount,
+                    None => allowances.push(Allowance {
+                        category: tax.clone(),
+                        amount: sign * line_total.invoice_discount,
+                    }),
+                }
+            }
+
+            // Prices can't be negative, a credited line negates its quantity instead
+            let net = sign * (line_total.net + line_total.invoice_discount);
+            let (quantity, price) = if unit_price < 0 {
+                (-(sign as f64) * line_item.quantity, -unit_price)
+            } else {
+                (sign as f64 * line_item.quantity, unit_price)
+            };
+
+            lines.push(Line {
+                id: (i + 1).to_string(),
+                name: name.unwrap_or_default(),
+                quantity,
+                unit_code: line_item.unit_code.clone().unwrap_or_else(|| DEFAULT_UNIT_CODE.to_string()),
+                price,
+                base_quantity,
+                net,
+                allowance: sign * line_total.discount,
+                tax,
+            });
+        }
+
+        // One breakdown per category and rate
+        let mut taxes: Vec<TaxBreakdown> = Vec::new();
+        for tax in &self.totals.taxes {
+            let category = TaxCategory::for_tax_rate(tax.category, tax.percentage);
+            match taxes.iter_mut().find(|breakdown| breakdown.category == category) {
+                Some(breakdown) => {
+                    breakdown.taxable += sign * tax.taxable_amount;
+                    breakdown.amount += sign * tax.amount;
+                }
+                None => taxes.push(TaxBreakdown::new(
+                    category,
+                    sign * tax.taxable_amount,
+                    sign * tax.amount,
+                    tax.exemption_reason.as_deref(),
+                )),
+            }
+        }
+        let untaxed: i64 = self
+            .totals
+            .lines
+            .iter()
+            .zip(&self.line_items)
+            .filter(|(_, line_item)| line_item.tax_rate.is_none())
+            .map(|(line, _)| sign * line.net)
+            .sum();
+        if self.line_items.iter().any(|line_item| line_item.tax_rate.is_none()) {
+            let category = TaxCategory::for_rate(0.0);
+            match taxes.iter_mut().find(|breakdown| breakdown.category == category) {
+                Some(breakdown) => breakdown.taxable += untaxed,
+                None => taxes.push(TaxBreakdown::new(category, untaxed, 0, None)),
+            }
+        }
+
+        let total = sign * self.totals.total.amount_minor;
+        let prepaid = if invoice.kind == InvoiceKind::Invoice { invoice.amount_paid.amount_minor } else { 0 };
+        let due_date = invoice.due_date.and_then(local_date);
+        if invoice.kind == InvoiceKind::Invoice && due_date.is_none() && total - prepaid > 0 {
+            errors.push(EInvoiceError::new("dueDate", "A due date is required when an amount is due"));
+        }
+
+        let issue_date = local_date(invoice.date);
+        if issue_date.is_none() {
+            errors.push(EInvoiceError::new("date", "The invoice date is not valid"));
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let (Some(seller), Some(buyer), Some(issue_date)) = (seller, buyer, issue_date) else {
+            return Err(errors);
+        };
+
+        Ok(EInvoice {
+            kind: invoice.kind,
+            number: invoice.number.clone(),
+            issue_date,
+            due_date,
+            currency: invoice.currency.clone(),
+            note: non_empty(&invoice.customer_notes),
+            // Required by Peppol, the client code is what buyers usually route on
+            buyer_reference: non_empty(&client.code).unwrap_or_else(|| invoice.number.clone()),
+            preceding_invoice: self.credited_invoice_number.clone(),
+            // Invoices without a structured reference are paid by their number
+            payment_reference: Some(invoice.payment_reference.clone().unwrap_or_else(|| invoice.number.clone())),
+            seller,
+            buyer,
+            iban: non_empty(&organization.iban).map(|iban| iban.replace(' ', "").to_uppercase()),
+            bank_name: non_empty(&organization.bank_name),
+            lines,
+            allowances,
+            taxes,
+            line_total: sign * self.totals.sub_total.amount_minor,
+            tax_total: sign * self.totals.tax_total.amount_minor,
+            total,
+            prepaid,
+            rounding: 0,
+        })
+    }
+}
+
+// Amount in minor units as a decimal amount with the currency's fraction
+// digits
+pub fn format_amount(amount_minor: i64, currency: &str) -> String {
+    Money::new(amount_minor, currency).to_decimal_string()
+}
+
+// Decimal amount in minor units of the currency, rounding any further
+// fraction digits half away from zero
+pub fn parse_amount(amount: &str, currency: &str) -> Option<i64> {
+    Money::parse(amount, currency).map(|money| money.amount_minor)
+}
+
+// Reading helpers for received documents, with errors naming the element
+
+fn read_amount(node: roxmltree::Node, path: &[&str], currency: &str) -> Result<Option<i64>, String> {
+    xml::text(node, path)
+        .map(|text| {
+            parse_amount(&text, currency).ok_or_else(|| format!("{} \"{}\" is not a valid amount", path.join("/"), text))
+        })
+        .transpose()
+}
+
+fn read_number(node: roxmltree::Node, path: &[&str]) -> Result<Option<f64>, String> {
+    xml::text(node, path)
+        .map(|text| text.parse::<f64>().map_err(|_| format!("{} \"{}\" is not a valid number", path.join("/"), text)))
+        .transpose()
+}
+
+fn read_date(node: roxmltree::Node, path: &[&str], format: &str) -> Result<Option<NaiveDate>, String> {
+    xml::text(node, path)
+        .map(|text| {
+            NaiveDate::parse_from_str(&text, format)
+                .map_err(|_| format!("{} \"{}\" is not a valid date", path.join("/"), text))
+        })
+        .transpose()
+}
+
+fn required<T>(value: Option<T>, path: &[&str]) -> Result<T, String> {
+    value.ok_or_else(|| format!("{} is missing", path.join("/")))
+}
+
+fn read_tax_category(node: roxmltree::Node, code_path: &[&str], percent_path: &[&str]) -> Result<TaxCategory, String> {
+    let code = required(xml::text(node, code_path), code_path)?;
+    let percent = read_number(node, percent_path)?.unwrap_or(0.0);
+    TaxCategory::from_code(&code, percent).ok_or_else(|| format!("Unknown tax category \"{}\"", code))
+}
+
+pub fn format_quantity(quantity: f64) -> String {
+    let formatted = format!("{:.4}", quantity);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+impl Database {
+    pub async fn get_einvoice_source(&self, invoice_id: &str) -> Result<EInvoiceSource, sqlx::Error> {
+        let invoice = self.get_invoice(invoice_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let line_items = self.get_invoice_line_items(invoice_id).await?;
+        let client = self.get_client(&invoice.client_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let organization = self.get_organization(&invoice.organization_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let tax_rates = self.get_invoice_tax_rates(invoice_id).await?;
+
+        let requests: Vec<CreateInvoiceLineItemRequest> = line_items
+            .iter()
+            .map(|line_item| CreateInvoiceLineItemRequest {
+                description: line_item.description.clone(),
+                quantity: line_item.quantity,
+                unit_code: line_item.unit_code.clone(),
+                unit_price: line_item.unit_price.amount_minor,
+                discount: line_item.discount,
+                discount_kind: line_item.discount_kind,
+                tax_rate: line_item.tax_rate.clone(),
+                taxes: line_item.taxes.clone(),
+                item_id: line_item.item_id.clone(),
+            })
+            .collect();
+        let totals = calculate_invoice_totals(&requests, &tax_rates, invoice.tax_mode(), invoice.discount(), &invoice.currency)?;
+
+        let credited_invoice_number = match &invoice.credited_invoice_id {
+            Some(credited_invoice_id) => self.get_invoice(credited_invoice_id).await?.map(|credited| credited.number),
+            None => None,
+        };
+
+        Ok(EInvoiceSource {
+            invoice,
+            line_items,
+            client,
+            organization,
+            totals,
+            tax_rates,
+            credited_invoice_number,
+        })
+    }
+
+    pub async fn validate_einvoice(
+        &self,
+        invoice_id: &str,
+        format: EInvoiceFormat,
+    ) -> Result<Vec<EInvoiceError>, sqlx::Error> {
+        let source = self.get_einvoice_source(invoice_id).await?;
+        Ok(source.to_einvoice(format).err().unwrap_or_default())
+    }
+
+    pub async fn export_ubl_invoice(&self, invoice_id: &str) -> Result<String, sqlx::Error> {
+        let source = self.get_einvoice_source(invoice_id).await?;
+        let einvoice = source.to_einvoice(EInvoiceFormat::Peppol).map_err(|errors| validation_error(&errors))?;
+        Ok(ubl::write(&einvoice))
+    }
+}
@@ -0,0 +1,430 @@
+// UN/CEFACT Cross Industry Invoice (D16B) as profiled by Factur-X 1.0 and
+// ZUGFeRD 2.x. Amounts carry no currency except the tax total.
+
+use roxmltree::Node;
+use serde::Deserialize;
+
+use super::xml::{child, children, text, XmlWriter};
+use super::{
+    format_amount, format_quantity, read_amount, read_date, read_number, read_tax_category, required, Address,
+    EInvoice, Line, Party, TaxBreakdown, TaxCategory,
+};
+use crate::db::InvoiceKind;
+use crate::units::DEFAULT_UNIT_CODE;
+
+pub const RSM: &str = "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100";
+const RAM: &str = "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100";
+const QDT: &str = "urn:un:unece:uncefact:data:standard:QualifiedDataType:100";
+const UDT: &str = "urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FacturXProfile {
+    // Header totals only, not a valid invoice in itself
+    Minimum,
+    // Line items without the optional EN 16931 details
+    Basic,
+    #[serde(rename = "en16931")]
+    En16931,
+}
+
+impl FacturXProfile {
+    pub fn guideline_id(&self) -> &'static str {
+        match self {
+            Self::Minimum => "urn:factur-x.eu:1p0:minimum",
+            Self::Basic => "urn:cen.eu:en16931:2017#compliant#urn:factur-x.eu:1p0:basic",
+            Self::En16931 => "urn:cen.eu:en16931:2017",
+        }
+    }
+
+    // Conformance level as written in the XMP metadata
+    pub fn conformance_level(&self) -> &'static str {
+        match self {
+            Self::Minimum => "MINIMUM",
+            Self::Basic => "BASIC",
+            Self::En16931 => "EN 16931",
+        }
+    }
+}
+
+fn date(xml: &mut XmlWriter, name: &str, date: chrono::NaiveDate) {
+    xml.start(name, &[])
+        .leaf_with("udt:DateTimeString", &[("format", "102")], &date.format("%Y%m%d").to_string())
+        .end();
+}
+
+fn amount(xml: &mut XmlWriter, name: &str, amount_minor: i64, currency: &str) {
+    xml.leaf(name, &format_amount(amount_minor, currency));
+}
+
+// Lines only carry the category, the breakdown also amounts and exemption reasons
+fn trade_tax(xml: &mut XmlWriter, category: &TaxCategory, breakdown: Option<&TaxBreakdown>, currency: &str) {
+    xml.start("ram:ApplicableTradeTax", &[]);
+    if let Some(breakdown) = breakdown {
+        amount(xml, "ram:CalculatedAmount", breakdown.amount, currency);
+    }
+    xml.leaf("ram:TypeCode", "VAT");
+    if let Some(breakdown) = breakdown {
+        xml.optional("ram:ExemptionReason", breakdown.exemption_reason.as_deref());
+        amount(xml, "ram:BasisAmount", breakdown.taxable, currency);
+    }
+    xml.leaf("ram:CategoryCode", category.code);
+    if let Some(breakdown) = breakdown {
+        xml.optional("ram:ExemptionReasonCode", breakdown.exemption_reason_code);
+    }
+    xml.leaf("ram:RateApplicablePercent", &format_quantity(category.percent))
+        .end();
+}
+
+// Discounts are allowances with reason code 95. Document level ones name the
+// tax category they reduce.
+fn allowance(xml: &mut XmlWriter, amount_minor: i64, currency: &str, category: Option<&TaxCategory>) {
+    xml.start("ram:SpecifiedTradeAllowanceCharge", &[])
+        .start("ram:ChargeIndicator", &[])
+        .leaf("udt:Indicator", "false")
+        .end();
+    amount(xml, "ram:ActualAmount", amount_minor, currency);
+    xml.leaf("ram:ReasonCode", "95").leaf("ram:Reason", "Discount");
+    if let Some(category) = category {
+        xml.start("ram:CategoryTradeTax", &[])
+            .leaf("ram:TypeCode", "VAT")
+            .leaf("ram:CategoryCode", category.code)
+            .leaf("ram:RateApplicablePercent", &format_quantity(category.percent))
+            .end();
+    }
+    xml.end();
+}
+
+fn party(xml: &mut XmlWriter, name: &str, party: &Party, profile: FacturXProfile, is_seller: bool) {
+    xml.start(name, &[]).leaf("ram:Name", &party.name);
+
+    if let Some(legal_id) = &party.legal_id {
+        xml.start("ram:SpecifiedLegalOrganization", &[]).leaf("ram:ID", legal_id).end();
+    }
+
+    if profile == FacturXProfile::En16931 && (party.email.is_some() || party.phone.is_some()) {
+        xml.start("ram:DefinedTradeContact", &[]);
+        if let Some(phone) = &party.phone {
+            xml.start("ram:TelephoneUniversalCommunication", &[])
+                .leaf("ram:CompleteNumber", phone)
+                .end();
+        }
+        if let Some(email) = &party.email {
+            xml.start("ram:EmailURIUniversalCommunication", &[]).leaf("ram:URIID", email).end();
+        }
+        xml.end();
+    }
+
+    // MINIMUM only has the seller's country and VAT number
+    if profile > FacturXProfile::Minimum || is_seller {
+        let lines = &party.address.lines;
+        xml.start("ram:PostalTradeAddress", &[]);
+        if profile > FacturXProfile::Minimum {
+            xml.optional("ram:LineOne", lines.first().map(String::as_str))
+                .optional("ram:LineTwo", lines.get(1).map(String::as_str));
+            if lines.len() > 2 {
+                xml.leaf("ram:LineThree", &lines[2..].join(", "));
+            }
+        }
+        xml.leaf("ram:CountryID", &party.address.country_code).end();
+    }
+
+    if profile > FacturXProfile::Minimum {
+        if let Some((scheme, id)) = &party.endpoint {
+            xml.start("ram:URIUniversalCommunication", &[])
+                .leaf_with("ram:URIID", &[("schemeID", scheme)], id)
+                .end();
+        }
+    }
+
+    let vat_id = party.vat_id.as_ref().filter(|_| profile > FacturXProfile::Minimum || is_seller);
+    if let Some(vat_id) = vat_id {
+        xml.start("ram:SpecifiedTaxRegistration", &[])
+            .leaf_with("ram:ID", &[("schemeID", "VA")], vat_id)
+            .end();
+    }
+
+    xml.end();
+}
+
+pub fn write(invoice: &EInvoice, profile: FacturXProfile) -> String {
+    let detailed = profile > FacturXProfile::Minimum;
+    let currency = invoice.currency.as_str();
+
+    let mut xml = XmlWriter::new();
+    xml.start(
+        "rsm:CrossIndustryInvoice",
+        &[("xmlns:rsm", RSM), ("xmlns:ram", RAM), ("xmlns:qdt", QDT), ("xmlns:udt", UDT)],
+    );
+
+    xml.start("rsm:ExchangedDocumentContext", &[])
+        .start("ram:GuidelineSpecifiedDocumentContextParameter", &[])
+        .leaf("ram:ID", profile.guideline_id())
+        .end()
+        .end();
+
+    xml.start("rsm:ExchangedDocument", &[])
+        .leaf("ram:ID", &invoice.number)
+        .leaf("ram:TypeCode", invoice.type_code());
+    date(&mut xml, "ram:IssueDateTime", invoice.issue_date);
+    if detailed {
+        if let Some(note) = &invoice.note {
+            xml.start("ram:IncludedNote", &[]).leaf("ram:Content", note).end();
+        }
+    }
+    xml.end();
+
+    xml.start("rsm:SupplyChainTradeTransaction", &[]);
+
+    if detailed {
+        for line in &invoice.lines {
+            xml.start("ram:IncludedSupplyChainTradeLineItem", &[])
+                .start("ram:AssociatedDocumentLineDocument", &[])
+                .leaf("ram:LineID", &line.id)
+                .end()
+                .start("ram:SpecifiedTradeProduct", &[])
+                .leaf("ram:Name", &line.name)
+                .end()
+                .start("ram:SpecifiedLineTradeAgreement", &[])
+                .start("ram:NetPriceProductTradePrice", &[]);
+            amount(&mut xml, "ram:ChargeAmount", line.price, currency);
+            if let Some(base_quantity) = line.base_quantity {
+                xml.leaf_with("ram:BasisQuantity", &[("unitCode", &line.unit_code)], &format_quantity(base_quantity));
+            }
+            xml.end()
+                .end()
+                .start("ram:SpecifiedLineTradeDelivery", &[])
+                .leaf_with("ram:BilledQuantity", &[("unitCode", &line.unit_code)], &format_quantity(line.quantity))
+                .end()
+                .start("ram:SpecifiedLineTradeSettlement", &[]);
+            trade_tax(&mut xml, &line.tax, None, currency);
+            if line.allowance != 0 {
+                allowance(&mut xml, line.allowance, currency, None);
+            }
+            xml.start("ram:SpecifiedTradeSettlementLineMonetarySummation", &[]);
+            amount(&mut xml, "ram:LineTotalAmount", line.net, currency);
+            xml.end().end().end();
+        }
+    }
+
+    xml.start("ram:ApplicableHeaderTradeAgreement", &[])
+        .leaf("ram:BuyerReference", &invoice.buyer_reference);
+    party(&mut xml, "ram:SellerTradeParty", &invoice.seller, profile, true);
+    party(&mut xml, "ram:BuyerTradeParty", &invoice.buyer, profile, false);
+    xml.end();
+
+    // No delivery details are kept, the element is still required
+    xml.start("ram:ApplicableHeaderTradeDelivery", &[]).end();
+
+    xml.start("ram:ApplicableHeaderTradeSettlement", &[]);
+    if detailed {
+        xml.optional("ram:PaymentReference", invoice.payment_reference.as_deref());
+    }
+    xml.leaf("ram:InvoiceCurrencyCode", &invoice.currency);
+
+    if detailed {
+        if let Some(iban) = &invoice.iban {
+            // 58 = SEPA credit transfer
+            xml.start("ram:SpecifiedTradeSettlementPaymentMeans", &[])
+                .leaf("ram:TypeCode", "58")
+                .start("ram:PayeePartyCreditorFinancialAccount", &[])
+                .leaf("ram:IBANID", iban)
+                .optional("ram:AccountName", invoice.bank_name.as_deref())
+                .end()
+                .end();
+        }
+
+        for breakdown in &invoice.taxes {
+            trade_tax(&mut xml, &breakdown.category, Some(breakdown), currency);
+        }
+
+        for discount in &invoice.allowances {
+            allowance(&mut xml, discount.amount, currency, Some(&discount.category));
+        }
+
+        if let Some(due_date) = invoice.due_date {
+            xml.start("ram:SpecifiedTradePaymentTerms", &[]);
+            date(&mut xml, "ram:DueDateDateTime", due_date);
+            xml.end();
+        }
+    }
+
+    xml.start("ram:SpecifiedTradeSettlementHeaderMonetarySummation", &[]);
+    if detailed {
+        let allowance_total = invoice.allowance_total();
+        amount(&mut xml, "ram:LineTotalAmount", invoice.line_total + allowance_total, currency);
+        if allowance_total != 0 {
+            amount(&mut xml, "ram:AllowanceTotalAmount", allowance_total, currency);
+        }
+    }
+    amount(&mut xml, "ram:TaxBasisTotalAmount", invoice.line_total, currency);
+    xml.leaf_with("ram:TaxTotalAmount", &[("currencyID", currency)], &format_amount(invoice.tax_total, currency));
+    amount(&mut xml, "ram:GrandTotalAmount", invoice.total, currency);
+    if detailed && invoice.prepaid != 0 {
+        amount(&mut xml, "ram:TotalPrepaidAmount", invoice.prepaid, currency);
+    }
+    amount(&mut xml, "ram:DuePayableAmount", invoice.payable(), currency);
+    xml.end();
+
+    if detailed {
+        if let Some(preceding_invoice) = &invoice.preceding_invoice {
+            xml.start("ram:InvoiceReferencedDocument", &[])
+                .leaf("ram:IssuerAssignedID", preceding_invoice)
+                .end();
+        }
+    }
+
+    xml.end().end().end();
+    xml.finish()
+}
+
+fn read_date_time(node: Node, path: &[&str]) -> Result<Option<chrono::NaiveDate>, String> {
+    let path = [path, &["DateTimeString"]].concat();
+    read_date(node, &path, "%Y%m%d")
+}
+
+fn read_party(node: Node) -> Result<Party, String> {
+    let address = child(node, &["PostalTradeAddress"]);
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(address) = address {
+        lines.extend(["LineOne", "LineTwo", "LineThree"].iter().filter_map(|line| text(address, &[line])));
+        let city = [text(address, &["PostcodeCode"]), text(address, &["CityName"])]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !city.is_empty() {
+            lines.push(city);
+        }
+    }
+
+    let vat_id = children(node, "SpecifiedTaxRegistration")
+        .filter_map(|registration| child(registration, &["ID"]))
+        .find(|id| id.attribute("schemeID") == Some("VA"))
+        .and_then(|id| text(id, &[]));
+
+    Ok(Party {
+        name: required(text(node, &["Name"]), &["Name"])?,
+        endpoint: child(node, &["URIUniversalCommunication", "URIID"]).and_then(|endpoint| {
+            Some((endpoint.attribute("schemeID")?.to_string(), text(endpoint, &[])?))
+        }),
+        vat_id,
+        legal_id: text(node, &["SpecifiedLegalOrganization", "ID"]),
+        address: Address {
+            lines,
+            country_code: address.and_then(|address| text(address, &["CountryID"])).unwrap_or_default(),
+        },
+        email: text(node, &["DefinedTradeContact", "EmailURIUniversalCommunication", "URIID"]),
+        phone: text(node, &["DefinedTradeContact", "TelephoneUniversalCommunication", "CompleteNumber"]),
+    })
+}
+
+// Reads a CrossIndustryInvoice document in any of the profiles
+pub fn read(root: Node) -> Result<EInvoice, String> {
+    let document = &["ExchangedDocument"];
+    let document = required(child(root, document), document)?;
+    let transaction = &["SupplyChainTradeTransaction"];
+    let transaction = required(child(root, transaction), transaction)?;
+    let agreement = &["ApplicableHeaderTradeAgreement"];
+    let agreement = required(child(transaction, agreement), agreement)?;
+    let settlement = &["ApplicableHeaderTradeSettlement"];
+    let settlement = required(child(transaction, settlement), settlement)?;
+    let summation = &["SpecifiedTradeSettlementHeaderMonetarySummation"];
+    let summation = required(child(settlement, summation), summation)?;
+
+    // Credit notes have positive amounts, same as in UBL
+    let kind = match text(document, &["TypeCode"]).as_deref() {
+        Some("381") => InvoiceKind::CreditNote,
+        _ => InvoiceKind::Invoice,
+    };
+    let currency = required(text(settlement, &["InvoiceCurrencyCode"]), &["InvoiceCurrencyCode"])?;
+
+    let mut lines = Vec::new();
+    for line in children(transaction, "IncludedSupplyChainTradeLineItem") {
+        let price = &["SpecifiedLineTradeAgreement", "NetPriceProductTradePrice"];
+        let base_quantity = read_number(line, &[price.as_slice(), &["BasisQuantity"]].concat())?
+            .filter(|quantity| *quantity != 0.0);
+        let price = read_amount(line, &[price.as_slice(), &["ChargeAmount"]].concat(), &currency)?.unwrap_or(0);
+        let quantity = &["SpecifiedLineTradeDelivery", "BilledQuantity"];
+        let net = &["SpecifiedLineTradeSettlement", "SpecifiedTradeSettlementLineMonetarySummation", "LineTotalAmount"];
+
+        lines.push(Line {
+            id: text(line, &["AssociatedDocumentLineDocument", "LineID"]).unwrap_or_default(),
+            name: text(line, &["SpecifiedTradeProduct", "Name"]).unwrap_or_default(),
+            quantity: read_number(line, quantity)?.unwrap_or(1.0),
+            unit_code: child(line, quantity)
+                .and_then(|quantity| quantity.attribute("unitCode"))
+                .unwrap_or(DEFAULT_UNIT_CODE)
+                .to_string(),
+            price: match base_quantity {
+                Some(base_quantity) => (price as f64 / base_quantity).round() as i64,
+                None => price,
+            },
+            base_quantity: None,
+            net: required(read_amount(line, net, &currency)?, net)?,
+            allowance: 0,
+            tax: read_tax_category(
+                line,
+                &["SpecifiedLineTradeSettlement", "ApplicableTradeTax", "CategoryCode"],
+                &["SpecifiedLineTradeSettlement", "ApplicableTradeTax", "RateApplicablePercent"],
+            )?,
+        });
+    }
+
+    let mut taxes = Vec::new();
+    for tax in children(settlement, "ApplicableTradeTax") {
+        taxes.push(TaxBreakdown {
+            category: read_tax_category(tax, &["CategoryCode"], &["RateApplicablePercent"])?,
+            taxable: read_amount(tax, &["BasisAmount"], &currency)?.unwrap_or(0),
+            amount: read_amount(tax, &["CalculatedAmount"], &currency)?.unwrap_or(0),
+            exemption_reason: text(tax, &["ExemptionReason"]),
+            exemption_reason_code: None,
+        });
+    }
+
+    // The tax total may be repeated in the tax currency
+    let tax_total = children(summation, "TaxTotalAmount")
+        .find(|amount| amount.attribute("currencyID").map_or(true, |id| id == currency))
+        .and_then(|amount| text(amount, &[]));
+    let tax_total = match tax_total {
+        Some(tax_total) => super::parse_amount(&tax_total, &currency)
+            .ok_or_else(|| format!("TaxTotalAmount \"{}\" is not a valid amount", tax_total))?,
+        None => 0,
+    };
+
+    let payment_means = children(settlement, "SpecifiedTradeSettlementPaymentMeans")
+        .find(|means| child(*means, &["PayeePartyCreditorFinancialAccount"]).is_some());
+
+    Ok(EInvoice {
+        kind,
+        number: required(text(document, &["ID"]), &["ExchangedDocument", "ID"])?,
+        issue_date: required(read_date_time(document, &["IssueDateTime"])?, &["IssueDateTime"])?,
+        due_date: child(settlement, &["SpecifiedTradePaymentTerms"])
+            .map(|terms| read_date_time(terms, &["DueDateDateTime"]))
+            .transpose()?
+            .flatten(),
+        currency: currency.clone(),
+        note: Some(
+            children(document, "IncludedNote")
+                .filter_map(|note| text(note, &["Content"]))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .filter(|note| !note.is_empty()),
+        buyer_reference: text(agreement, &["BuyerReference"]).unwrap_or_default(),
+        preceding_invoice: text(settlement, &["InvoiceReferencedDocument", "IssuerAssignedID"]),
+        seller: read_party(required(child(agreement, &["SellerTradeParty"]), &["SellerTradeParty"])?)?,
+        buyer: read_party(required(child(agreement, &["BuyerTradeParty"]), &["BuyerTradeParty"])?)?,
+        payment_reference: text(settlement, &["PaymentReference"]),
+        iban: payment_means.and_then(|means| text(means, &["PayeePartyCreditorFinancialAccount", "IBANID"])),
+        bank_name: payment_means.and_then(|means| text(means, &["PayeePartyCreditorFinancialAccount", "AccountName"])),
+        lines,
+        allowances: Vec::new(),
+        taxes,
+        line_total: read_amount(summation, &["TaxBasisTotalAmount"], &currency)?.unwrap_or(0),
+        tax_total,
+        total: read_amount(summation, &["GrandTotalAmount"], &currency)?.unwrap_or(0),
+        prepaid: read_amount(summation, &["TotalPrepaidAmount"], &currency)?.unwrap_or(0),
+        rounding: read_amount(summation, &["RoundingAmount"], &currency)?.unwrap_or(0),
+    })
+}
@@ -0,0 +1,109 @@
+// Factur-X / ZUGFeRD hybrid invoices: the regular invoice PDF as a PDF/A-3
+// document with the CII XML attached as factur-x.xml
+
+use super::cii::{self, FacturXProfile};
+use super::{validation_error, EInvoiceFormat};
+use crate::db::Database;
+use crate::pdf::{Archival, Attachment, InvoiceDocument, InvoiceTemplate};
+
+const FILE_NAME: &str = "factur-x.xml";
+const NAMESPACE: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+
+// The fx properties and the extension schema declaring them, which PDF/A
+// requires for any namespace outside of the predefined XMP schemas
+fn metadata(profile: FacturXProfile) -> String {
+    let property = |name: &str, description: &str| {
+        format!(
+            r#"                <rdf:li rdf:parseType="Resource">
+                  <pdfaProperty:name>{}</pdfaProperty:name>
+                  <pdfaProperty:valueType>Text</pdfaProperty:valueType>
+                  <pdfaProperty:category>external</pdfaProperty:category>
+                  <pdfaProperty:description>{}</pdfaProperty:description>
+                </rdf:li>
+"#,
+            name, description
+        )
+    };
+    let properties = [
+        property("DocumentFileName", "The name of the embedded XML document"),
+        property("DocumentType", "The type of the hybrid document in capital letters, e.g. INVOICE or ORDER"),
+        property("Version", "The actual version of the standard applying to the embedded XML document"),
+        property("ConformanceLevel", "The conformance level of the embedded XML document"),
+    ]
+    .concat();
+
+    format!(
+        r#"    <rdf:Description rdf:about="" xmlns:fx="{namespace}">
+      <fx:DocumentType>INVOICE</fx:DocumentType>
+      <fx:DocumentFileName>{file_name}</fx:DocumentFileName>
+      <fx:Version>1.0</fx:Version>
+      <fx:ConformanceLevel>{conformance_level}</fx:ConformanceLevel>
+    </rdf:Description>
+    <rdf:Description rdf:about=""
+        xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/"
+        xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#"
+        xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+      <pdfaExtension:schemas>
+        <rdf:Bag>
+          <rdf:li rdf:parseType="Resource">
+            <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>
+            <pdfaSchema:namespaceURI>{namespace}</pdfaSchema:namespaceURI>
+            <pdfaSchema:prefix>fx</pdfaSchema:prefix>
+            <pdfaSchema:property>
+              <rdf:Seq>
+{properties}              </rdf:Seq>
+            </pdfaSchema:property>
+          </rdf:li>
+        </rdf:Bag>
+      </pdfaExtension:schemas>
+    </rdf:Description>
+"#,
+        namespace = NAMESPACE,
+        file_name = FILE_NAME,
+        conformance_level = profile.conformance_level(),
+        properties = properties,
+    )
+}
+
+impl Database {
+    pub async fn export_factur_x(
+        &self,
+        invoice_id: &str,
+        profile: FacturXProfile,
+        template: InvoiceTemplate,
+        locale: &str,
+    ) -> Result<Vec<u8>, sqlx::Error> {
+        let source = self.get_einvoice_source(invoice_id).await?;
+        let einvoice = source
+            .to_einvoice(EInvoiceFormat::FacturX(profile))
+            .map_err(|errors| validation_error(&errors))?;
+
+        let attachment = Attachment {
+            name: FILE_NAME.to_string(),
+            description: format!("Factur-X {} invoice", profile.conformance_level()),
+            mime_type: "text/xml".to_string(),
+            // MINIMUM doesn't carry enough to replace the PDF, so it's only data
+            relationship: match profile {
+                FacturXProfile::Minimum => "Data",
+                FacturXProfile::Basic | FacturXProfile::En16931 => "Alternative",
+            }
+            .to_string(),
+            data: cii::write(&einvoice, profile).into_bytes(),
+        };
+
+        let document = InvoiceDocument {
+            invoice: &source.invoice,
+            line_items: &source.line_items,
+            client: &source.client,
+            organization: &source.organization,
+            tax_rates: &source.tax_rates,
+            totals: &source.totals,
+        };
+
+        Ok(template.render(
+            &document,
+            locale,
+            Some(Archival { attachments: vec![attachment], metadata: metadata(profile) }),
+        ))
+    }
+}
@@ -0,0 +1,144 @@
+// Received UBL and CII invoices are stored as bills, with the supplier
+// matched to an existing contact by VAT number or added as a new one
+
+use super::{cii, local_midnight, ubl, vat_id, EInvoice, Party};
+use crate::countries::{country_code, country_name};
+use crate::db::{
+    Bill, Client, CreateBillLineItemRequest, CreateBillRequest, CreateBillTaxRequest, CreateClientRequest, Database,
+    InvoiceKind,
+};
+
+fn import_error(message: impl std::fmt::Display) -> sqlx::Error {
+    sqlx::Error::Protocol(format!("The e-invoice can't be imported: {}", message))
+}
+
+// Returns the syntax ("ubl" or "cii") along with the invoice
+pub fn parse(xml: &str) -> Result<(&'static str, EInvoice), String> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| format!("the file is not valid XML ({})", e))?;
+    let root = document.root_element();
+
+    match (root.tag_name().namespace(), root.tag_name().name()) {
+        (Some(ubl::INVOICE_NAMESPACE), "Invoice") | (Some(ubl::CREDIT_NOTE_NAMESPACE), "CreditNote") => {
+            Ok(("ubl", ubl::read(root)?))
+        }
+        (Some(cii::RSM), "CrossIndustryInvoice") => Ok(("cii", cii::read(root)?)),
+        _ => Err(format!("<{}> is not a UBL or CII invoice", root.tag_name().name())),
+    }
+}
+
+impl Database {
+    // The supplier's contact id, along with the contact to add with the bill
+    // when there is none yet
+    async fn find_supplier(
+        &self,
+        organization_id: &str,
+        seller: &Party,
+    ) -> Result<(String, Option<CreateClientRequest>), sqlx::Error> {
+        let existing = match &seller.vat_id {
+            Some(vat_id) => self.find_client_by_vatin(organization_id, &seller.address.country_code, vat_id).await?,
+            // Without a VAT number the name is all there is to go on
+            None => self
+                .get_clients(organization_id)
+                .await?
+                .into_iter()
+                .find(|client| client.name.as_deref().is_some_and(|name| name.trim().eq_ignore_ascii_case(&seller.name))),
+        };
+        if let Some(client) = existing {
+            return Ok((client.id, None));
+        }
+
+        let country = &seller.address.country_code;
+        let country = country_name(country).map(str::to_string).or_else(|| Some(country.clone()));
+        let supplier = CreateClientRequest {
+            id: nanoid::nanoid!(),
+            organization_id: organization_id.to_string(),
+            name: Some(seller.name.clone()),
+            code: None,
+            address: Some(seller.address.lines.join("\n")).filter(|address| !address.is_empty()),
+            country: country.filter(|country| !country.is_empty()),
+            emails: seller.email.as_ref().map(|email| serde_json::json!([email]).to_string()),
+            phone: seller.phone.clone(),
+            website: None,
+            registration_number: seller.legal_id.clone(),
+            vatin: seller.vat_id.clone(),
+        };
+        Ok((supplier.id.clone(), Some(supplier)))
+    }
+
+    pub async fn import_bill(&self, organization_id: &str, xml: &str) -> Result<Bill, sqlx::Error> {
+        let (format, einvoice) = parse(xml).map_err(import_error)?;
+        let organization = self.get_organization(organization_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        // Catch invoices meant for another organization when both sides have a VAT number
+        let organization_vat_id = organization
+            .vatin
+            .as_deref()
+            .zip(organization.country.as_deref().and_then(country_code))
+            .map(|(vatin, country)| vat_id(vatin, country));
+        let buyer_vat_id = einvoice
+            .buyer
+            .vat_id
+            .as_deref()
+            .map(|vatin| vat_id(vatin, &einvoice.buyer.address.country_code));
+        if let (Some(organization_vat_id), Some(buyer_vat_id)) = (organization_vat_id, buyer_vat_id) {
+            if organization_vat_id != buyer_vat_id {
+                return Err(import_error(format!(
+                    "it's addressed to {} ({}), not {}",
+                    einvoice.buyer.name,
+                    buyer_vat_id,
+                    organization.name.as_deref().unwrap_or(&organization_vat_id),
+                )));
+            }
+        }
+
+        let (supplier_id, new_supplier) = self.find_supplier(organization_id, &einvoice.seller).await?;
+
+        // Stored with the same signs as issued credit notes
+        let sign = if einvoice.kind == InvoiceKind::CreditNote { -1 } else { 1 };
+
+        let bill = CreateBillRequest {
+            id: nanoid::nanoid!(),
+            organization_id: organization_id.to_string(),
+            supplier_id,
+            kind: einvoice.kind,
+            number: einvoice.number.clone(),
+            date: local_midnight(einvoice.issue_date),
+            due_date: einvoice.due_date.map(local_midnight),
+            currency: einvoice.currency.clone(),
+            notes: einvoice.note.clone(),
+            payment_reference: einvoice.payment_reference.clone(),
+            iban: einvoice.iban.clone(),
+            total: sign * einvoice.total,
+            tax_total: sign * einvoice.tax_total,
+            sub_total: sign * einvoice.line_total,
+            amount_due: sign * einvoice.payable(),
+            format: format.to_string(),
+            xml: xml.to_string(),
+            line_items: einvoice
+                .lines
+                .iter()
+                .map(|line| CreateBillLineItemRequest {
+                    description: Some(line.name.clone()).filter(|name| !name.is_empty()),
+                    quantity: sign as f64 * line.quantity,
+                    unit_code: Some(line.unit_code.clone()),
+                    unit_price: line.price,
+                    total: sign * line.net,
+                    tax_category: line.tax.code.to_string(),
+                    tax_percentage: line.tax.percent,
+                })
+                .collect(),
+            taxes: einvoice
+                .taxes
+                .iter()
+                .map(|tax| CreateBillTaxRequest {
+                    category: tax.category.code.to_string(),
+                    percentage: tax.category.percent,
+                    taxable_amount: sign * tax.taxable,
+                    amount: sign * tax.amount,
+                })
+                .collect(),
+        };
+        self.create_bill(bill, new_supplier).await
+    }
+}
@@ -0,0 +1,356 @@
+// Peppol BIS Billing 3.0 in the UBL 2.1 syntax
+
+use roxmltree::Node;
+
+use super::xml::{child, children, text, XmlWriter};
+use super::{
+    format_amount, format_quantity, read_amount, read_date, read_number, read_tax_category, required, Address,
+    EInvoice, Line, Party, TaxBreakdown, TaxCategory,
+};
+use crate::db::InvoiceKind;
+use crate::units::DEFAULT_UNIT_CODE;
+
+const CUSTOMIZATION_ID: &str = "urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0";
+const PROFILE_ID: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+
+pub const INVOICE_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2";
+pub const CREDIT_NOTE_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2";
+const CAC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+const CBC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+
+fn amount(xml: &mut XmlWriter, name: &str, amount_minor: i64, currency: &str) {
+    xml.leaf_with(name, &[("currencyID", currency)], &format_amount(amount_minor, currency));
+}
+
+fn tax_scheme(xml: &mut XmlWriter) {
+    xml.start("cac:TaxScheme", &[]).leaf("cbc:ID", "VAT").end();
+}
+
+// Exemption reasons are only given in the tax breakdown, not on lines
+fn tax_category(xml: &mut XmlWriter, name: &str, category: &TaxCategory, breakdown: Option<&TaxBreakdown>) {
+    xml.start(name, &[])
+        .leaf("cbc:ID", category.code)
+        .leaf("cbc:Percent", &format_quantity(category.percent));
+    if let Some(breakdown) = breakdown {
+        xml.optional("cbc:TaxExemptionReasonCode", breakdown.exemption_reason_code)
+            .optional("cbc:TaxExemptionReason", breakdown.exemption_reason.as_deref());
+    }
+    tax_scheme(xml);
+    xml.end();
+}
+
+// Discounts are allowances with reason code 95. Document level ones name the
+// tax category they reduce.
+fn allowance(xml: &mut XmlWriter, amount_minor: i64, currency: &str, category: Option<&TaxCategory>) {
+    xml.start("cac:AllowanceCharge", &[])
+        .leaf("cbc:ChargeIndicator", "false")
+        .leaf("cbc:AllowanceChargeReasonCode", "95")
+        .leaf("cbc:AllowanceChargeReason", "Discount");
+    amount(xml, "cbc:Amount", amount_minor, currency);
+    if let Some(category) = category {
+        tax_category(xml, "cac:TaxCategory", category, None);
+    }
+    xml.end();
+}
+
+fn party(xml: &mut XmlWriter, wrapper: &str, party: &Party) {
+    xml.start(wrapper, &[]).start("cac:Party", &[]);
+
+    if let Some((scheme, id)) = &party.endpoint {
+        xml.leaf_with("cbc:EndpointID", &[("schemeID", scheme)], id);
+    }
+
+    xml.start("cac:PartyName", &[]).leaf("cbc:Name", &party.name).end();
+
+    // Free-form addresses: the first two lines are the street, the rest is
+    // kept as an additional address line
+    let lines = &party.address.lines;
+    xml.start("cac:PostalAddress", &[])
+        .optional("cbc:StreetName", lines.first().map(String::as_str))
+        .optional("cbc:AdditionalStreetName", lines.get(1).map(String::as_str));
+    if lines.len() > 2 {
+        xml.start("cac:AddressLine", &[]).leaf("cbc:Line", &lines[2..].join(", ")).end();
+    }
+    xml.start("cac:Country", &[])
+        .leaf("cbc:IdentificationCode", &party.address.country_code)
+        .end()
+        .end();
+
+    if let Some(vat_id) = &party.vat_id {
+        xml.start("cac:PartyTaxScheme", &[]).leaf("cbc:CompanyID", vat_id);
+        tax_scheme(xml);
+        xml.end();
+    }
+
+    xml.start("cac:PartyLegalEntity", &[])
+        .leaf("cbc:RegistrationName", &party.name)
+        .optional("cbc:CompanyID", party.legal_id.as_deref())
+        .end();
+
+    if party.email.is_some() || party.phone.is_some() {
+        xml.start("cac:Contact", &[])
+            .optional("cbc:Telephone", party.phone.as_deref())
+            .optional("cbc:ElectronicMail", party.email.as_deref())
+            .end();
+    }
+
+    xml.end().end();
+}
+
+pub fn write(invoice: &EInvoice) -> String {
+    let (root, namespace, type_code, line_name, quantity_name) = match invoice.kind {
+        InvoiceKind::Invoice => (
+            "Invoice",
+            INVOICE_NAMESPACE,
+            "cbc:InvoiceTypeCode",
+            "cac:InvoiceLine",
+            "cbc:InvoicedQuantity",
+        ),
+        InvoiceKind::CreditNote => (
+            "CreditNote",
+            CREDIT_NOTE_NAMESPACE,
+            "cbc:CreditNoteTypeCode",
+            "cac:CreditNoteLine",
+            "cbc:CreditedQuantity",
+        ),
+    };
+    let currency = invoice.currency.as_str();
+    let date = |date: chrono::NaiveDate| date.format("%Y-%m-%d").to_string();
+
+    let mut xml = XmlWriter::new();
+    xml.start(root, &[("xmlns", namespace), ("xmlns:cac", CAC), ("xmlns:cbc", CBC)])
+        .leaf("cbc:CustomizationID", CUSTOMIZATION_ID)
+        .leaf("cbc:ProfileID", PROFILE_ID)
+        .leaf("cbc:ID", &invoice.number)
+        .leaf("cbc:IssueDate", &date(invoice.issue_date));
+    // Credit notes have no document level due date in UBL
+    if invoice.kind == InvoiceKind::Invoice {
+        xml.optional("cbc:DueDate", invoice.due_date.map(date).as_deref());
+    }
+    xml.leaf(type_code, invoice.type_code())
+        .optional("cbc:Note", invoice.note.as_deref())
+        .leaf("cbc:DocumentCurrencyCode", currency)
+        .leaf("cbc:BuyerReference", &invoice.buyer_reference);
+
+    if let Some(preceding_invoice) = &invoice.preceding_invoice {
+        xml.start("cac:BillingReference", &[])
+            .start("cac:InvoiceDocumentReference", &[])
+            .leaf("cbc:ID", preceding_invoice)
+            .end()
+            .end();
+    }
+
+    party(&mut xml, "cac:AccountingSupplierParty", &invoice.seller);
+    party(&mut xml, "cac:AccountingCustomerParty", &invoice.buyer);
+
+    if let Some(iban) = &invoice.iban {
+        // 58 = SEPA credit transfer
+        xml.start("cac:PaymentMeans", &[]).leaf("cbc:PaymentMeansCode", "58");
+        if invoice.kind == InvoiceKind::CreditNote {
+            xml.optional("cbc:PaymentDueDate", invoice.due_date.map(date).as_deref());
+        }
+        xml.optional("cbc:PaymentID", invoice.payment_reference.as_deref())
+            .start("cac:PayeeFinancialAccount", &[])
+            .leaf("cbc:ID", iban)
+            .optional("cbc:Name", invoice.bank_name.as_deref())
+            .end()
+            .end();
+    }
+
+    for discount in &invoice.allowances {
+        allowance(&mut xml, discount.amount, currency, Some(&discount.category));
+    }
+
+    xml.start("cac:TaxTotal", &[]);
+    amount(&mut xml, "cbc:TaxAmount", invoice.tax_total, currency);
+    for breakdown in &invoice.taxes {
+        xml.start("cac:TaxSubtotal", &[]);
+        amount(&mut xml, "cbc:TaxableAmount", breakdown.taxable, currency);
+        amount(&mut xml, "cbc:TaxAmount", breakdown.amount, currency);
+        tax_category(&mut xml, "cac:TaxCategory", &breakdown.category, Some(breakdown));
+        xml.end();
+    }
+    xml.end();
+
+    xml.start("cac:LegalMonetaryTotal", &[]);
+    let allowance_total = invoice.allowance_total();
+    amount(&mut xml, "cbc:LineExtensionAmount", invoice.line_total + allowance_total, currency);
+    amount(&mut xml, "cbc:TaxExclusiveAmount", invoice.line_total, currency);
+    amount(&mut xml, "cbc:TaxInclusiveAmount", invoice.total, currency);
+    if allowance_total != 0 {
+        amount(&mut xml, "cbc:AllowanceTotalAmount", allowance_total, currency);
+    }
+    if invoice.prepaid != 0 {
+        amount(&mut xml, "cbc:PrepaidAmount", invoice.prepaid, currency);
+    }
+    amount(&mut xml, "cbc:PayableAmount", invoice.payable(), currency);
+    xml.end();
+
+    for line in &invoice.lines {
+        xml.start(line_name, &[])
+            .leaf("cbc:ID", &line.id)
+            .leaf_with(quantity_name, &[("unitCode", &line.unit_code)], &format_quantity(line.quantity));
+        amount(&mut xml, "cbc:LineExtensionAmount", line.net, currency);
+        if line.allowance != 0 {
+            allowance(&mut xml, line.allowance, currency, None);
+        }
+        xml.start("cac:Item", &[]).leaf("cbc:Name", &line.name);
+        tax_category(&mut xml, "cac:ClassifiedTaxCategory", &line.tax, None);
+        xml.end().start("cac:Price", &[]);
+        amount(&mut xml, "cbc:PriceAmount", line.price, currency);
+        if let Some(base_quantity) = line.base_quantity {
+            xml.leaf_with("cbc:BaseQuantity", &[("unitCode", &line.unit_code)], &format_quantity(base_quantity));
+        }
+        xml.end().end();
+    }
+
+    xml.end();
+    xml.finish()
+}
+
+fn read_party(node: Node) -> Result<Party, String> {
+    let name = text(node, &["PartyLegalEntity", "RegistrationName"]).or_else(|| text(node, &["PartyName", "Name"]));
+
+    let address = child(node, &["PostalAddress"]);
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(address) = address {
+        lines.extend(text(address, &["StreetName"]));
+        lines.extend(text(address, &["AdditionalStreetName"]));
+        lines.extend(children(address, "AddressLine").filter_map(|line| text(line, &["Line"])));
+        let city = [text(address, &["PostalZone"]), text(address, &["CityName"])]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !city.is_empty() {
+            lines.push(city);
+        }
+    }
+
+    let vat_id = children(node, "PartyTaxScheme")
+        .find(|scheme| text(*scheme, &["TaxScheme", "ID"]).as_deref() == Some("VAT"))
+        .and_then(|scheme| text(scheme, &["CompanyID"]));
+
+    Ok(Party {
+        name: required(name, &["PartyName", "Name"])?,
+        endpoint: child(node, &["EndpointID"]).and_then(|endpoint| {
+            Some((endpoint.attribute("schemeID")?.to_string(), text(endpoint, &[])?))
+        }),
+        vat_id,
+        legal_id: text(node, &["PartyLegalEntity", "CompanyID"]),
+        address: Address {
+            lines,
+            country_code: address
+                .and_then(|address| text(address, &["Country", "IdentificationCode"]))
+                .unwrap_or_default(),
+        },
+        email: text(node, &["Contact", "ElectronicMail"]),
+        phone: text(node, &["Contact", "Telephone"]),
+    })
+}
+
+// Reads an Invoice or CreditNote document
+pub fn read(root: Node) -> Result<EInvoice, String> {
+    let credit_note = root.tag_name().name() == "CreditNote";
+    let (line_name, quantity_name) = if credit_note {
+        ("CreditNoteLine", "CreditedQuantity")
+    } else {
+        ("InvoiceLine", "InvoicedQuantity")
+    };
+    let type_code = text(root, &["InvoiceTypeCode"]).or_else(|| text(root, &["CreditNoteTypeCode"]));
+    let kind = if credit_note || type_code.as_deref() == Some("381") {
+        InvoiceKind::CreditNote
+    } else {
+        InvoiceKind::Invoice
+    };
+
+    let currency = required(text(root, &["DocumentCurrencyCode"]), &["DocumentCurrencyCode"])?;
+    let payment_means = children(root, "PaymentMeans").find(|means| child(*means, &["PayeeFinancialAccount"]).is_some());
+
+    let mut lines = Vec::new();
+    for line in children(root, line_name) {
+        let base_quantity = read_number(line, &["Price", "BaseQuantity"])?.filter(|quantity| *quantity != 0.0);
+        let price = read_amount(line, &["Price", "PriceAmount"], &currency)?.unwrap_or(0);
+        lines.push(Line {
+            id: text(line, &["ID"]).unwrap_or_default(),
+            name: text(line, &["Item", "Name"]).or_else(|| text(line, &["Item", "Description"])).unwrap_or_default(),
+            quantity: read_number(line, &[quantity_name])?.unwrap_or(1.0),
+            unit_code: child(line, &[quantity_name])
+                .and_then(|quantity| quantity.attribute("unitCode"))
+                .unwrap_or(DEFAULT_UNIT_CODE)
+                .to_string(),
+            price: match base_quantity {
+                Some(base_quantity) => (price as f64 / base_quantity).round() as i64,
+                None => price,
+            },
+            base_quantity: None,
+            net: required(read_amount(line, &["LineExtensionAmount"], &currency)?, &[line_name, "LineExtensionAmount"])?,
+            allowance: 0,
+            tax: read_tax_category(
+                line,
+                &["Item", "ClassifiedTaxCategory", "ID"],
+                &["Item", "ClassifiedTaxCategory", "Percent"],
+            )?,
+        });
+    }
+
+    // The first tax total is in the document currency, a second one may
+    // repeat it in the accounting currency
+    let tax_total = children(root, "TaxTotal").next();
+    let mut taxes = Vec::new();
+    if let Some(tax_total) = tax_total {
+        for subtotal in children(tax_total, "TaxSubtotal") {
+            taxes.push(TaxBreakdown {
+                category: read_tax_category(subtotal, &["TaxCategory", "ID"], &["TaxCategory", "Percent"])?,
+                taxable: read_amount(subtotal, &["TaxableAmount"], &currency)?.unwrap_or(0),
+                amount: read_amount(subtotal, &["TaxAmount"], &currency)?.unwrap_or(0),
+                exemption_reason: text(subtotal, &["TaxCategory", "TaxExemptionReason"]),
+                exemption_reason_code: None,
+            });
+        }
+    }
+
+    let totals = &["LegalMonetaryTotal"];
+    let totals = required(child(root, totals), totals)?;
+
+    Ok(EInvoice {
+        kind,
+        number: required(text(root, &["ID"]), &["ID"])?,
+        issue_date: required(read_date(root, &["IssueDate"], "%Y-%m-%d")?, &["IssueDate"])?,
+        due_date: match read_date(root, &["DueDate"], "%Y-%m-%d")? {
+            Some(due_date) => Some(due_date),
+            None => payment_means
+                .map(|means| read_date(means, &["PaymentDueDate"], "%Y-%m-%d"))
+                .transpose()?
+                .flatten(),
+        },
+        currency: currency.clone(),
+        note: Some(children(root, "Note").filter_map(|note| text(note, &[])).collect::<Vec<_>>().join("\n"))
+            .filter(|note| !note.is_empty()),
+        buyer_reference: text(root, &["BuyerReference"]).unwrap_or_default(),
+        preceding_invoice: text(root, &["BillingReference", "InvoiceDocumentReference", "ID"]),
+        seller: read_party(required(
+            child(root, &["AccountingSupplierParty", "Party"]),
+            &["AccountingSupplierParty", "Party"],
+        )?)?,
+        buyer: read_party(required(
+            child(root, &["AccountingCustomerParty", "Party"]),
+            &["AccountingCustomerParty", "Party"],
+        )?)?,
+        payment_reference: payment_means.and_then(|means| text(means, &["PaymentID"])),
+        iban: payment_means.and_then(|means| text(means, &["PayeeFinancialAccount", "ID"])),
+        bank_name: payment_means.and_then(|means| text(means, &["PayeeFinancialAccount", "Name"])),
+        lines,
+        allowances: Vec::new(),
+        taxes,
+        line_total: read_amount(totals, &["TaxExclusiveAmount"], &currency)?.unwrap_or(0),
+        tax_total: tax_total
+            .map(|tax_total| read_amount(tax_total, &["TaxAmount"], &currency))
+            .transpose()?
+            .flatten()
+            .unwrap_or(0),
+        total: read_amount(totals, &["TaxInclusiveAmount"], &currency)?.unwrap_or(0),
+        prepaid: read_amount(totals, &["PrepaidAmount"], &currency)?.unwrap_or(0),
+        rounding: read_amount(totals, &["PayableRoundingAmount"], &currency)?.unwrap_or(0),
+    })
+}
@@ -0,0 +1,114 @@
+// Small streaming XML builder for the e-invoice syntaxes. Elements are written
+// in call order, which is what the UBL and CII schemas require.
+pub struct XmlWriter {
+    out: String,
+    open: Vec<String>,
+}
+
+impl XmlWriter {
+    pub fn new() -> Self {
+        Self {
+            out: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            open: Vec::new(),
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.open.len() {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[(&str, &str)]) {
+        for (name, value) in attributes {
+            self.out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+    }
+
+    pub fn start(&mut self, name: &str, attributes: &[(&str, &str)]) -> &mut Self {
+        self.indent();
+        self.out.push('<');
+        self.out.push_str(name);
+        self.attributes(attributes);
+        self.out.push_str(">\n");
+        self.open.push(name.to_string());
+        self
+    }
+
+    pub fn end(&mut self) -> &mut Self {
+        let name = self.open.pop().expect("end() without a matching start()");
+        self.indent();
+        self.out.push_str(&format!("</{}>\n", name));
+        self
+    }
+
+    pub fn leaf_with(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) -> &mut Self {
+        self.indent();
+        self.out.push('<');
+        self.out.push_str(name);
+        self.attributes(attributes);
+        self.out.push_str(&format!(">{}</{}>\n", escape(text), name));
+        self
+    }
+
+    pub fn leaf(&mut self, name: &str, text: &str) -> &mut Self {
+        self.leaf_with(name, &[], text)
+    }
+
+    // Skips the element entirely when there is no value
+    pub fn optional(&mut self, name: &str, text: Option<&str>) -> &mut Self {
+        if let Some(text) = text.filter(|text| !text.is_empty()) {
+            self.leaf(name, text);
+        }
+        self
+    }
+
+    pub fn finish(self) -> String {
+        debug_assert!(self.open.is_empty(), "unclosed elements: {:?}", self.open);
+        self.out
+    }
+}
+
+impl Default for XmlWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Reading goes by local element names, so it doesn't matter which prefixes
+// the sender bound the namespaces to
+
+pub fn children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+pub fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, path: &[&'a str]) -> Option<roxmltree::Node<'a, 'input>> {
+    path.iter().try_fold(node, |node, name| children(node, name).next())
+}
+
+// Trimmed text of the element at `path`, if it has any
+pub fn text(node: roxmltree::Node, path: &[&str]) -> Option<String> {
+    child(node, path)
+        .and_then(|node| node.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
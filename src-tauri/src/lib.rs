@@ -2,10 +2,25 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod countries;
 mod db;
+mod ecb;
+mod einvoice;
+mod money;
+mod pdf;
+mod qr;
+mod reference;
+mod units;
+mod validation;
+mod vat_registry;
 
+use std::time::Duration;
 use tauri::Manager;
 
+// How often recurring invoice schedules and estimate validity are checked
+// while the app is open
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
@@ -43,7 +58,31 @@ pub fn run() {
       })?;
       
       app.manage(db);
+      app.manage(vat_registry::ViesRegistry::default());
       println!("Database initialized successfully");
+
+      // Generate due recurring invoices and expire estimates at startup and then periodically
+      let app_handle = app.handle().clone();
+      tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_INTERVAL);
+        loop {
+          interval.tick().await;
+          let db = app_handle.state::<db::Database>();
+          match db.run_recurring_invoices().await {
+            Ok(invoices) if !invoices.is_empty() => {
+              println!("Generated {} recurring invoice(s)", invoices.len());
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Failed to run recurring invoices: {}", e),
+          }
+          match db.expire_estimates().await {
+            Ok(expired) if expired > 0 => println!("Expired {} estimate(s)", expired),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: Failed to expire estimates: {}", e),
+          }
+        }
+      });
+
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
@@ -53,13 +92,57 @@ pub fn run() {
       commands::update_client,
       commands::delete_client,
       commands::get_client_invoice_count,
+      commands::verify_client_vatin,
       commands::get_invoices,
       commands::get_invoice,
+      commands::find_invoice_by_payment_reference,
       commands::get_invoice_line_items,
+      commands::calculate_invoice_totals,
+      commands::preview_invoice_number,
       commands::create_invoice,
+      commands::create_credit_note,
       commands::update_invoice,
       commands::update_invoice_state,
+      commands::get_invoice_state_history,
       commands::delete_invoice,
+      commands::get_withholding_report,
+      commands::get_revenue_report,
+      commands::get_exchange_rates,
+      commands::import_exchange_rates,
+      commands::get_payments,
+      commands::get_payment,
+      commands::create_payment,
+      commands::update_payment,
+      commands::delete_payment,
+      commands::get_recurring_invoices,
+      commands::get_recurring_invoice,
+      commands::get_recurring_invoice_line_items,
+      commands::create_recurring_invoice,
+      commands::update_recurring_invoice,
+      commands::delete_recurring_invoice,
+      commands::run_recurring_invoices,
+      commands::get_estimates,
+      commands::get_estimate,
+      commands::get_estimate_line_items,
+      commands::create_estimate,
+      commands::update_estimate,
+      commands::update_estimate_state,
+      commands::delete_estimate,
+      commands::convert_estimate_to_invoice,
+      commands::render_invoice_pdf,
+      commands::export_invoice_pdf,
+      commands::validate_einvoice,
+      commands::export_ubl_invoice,
+      commands::export_factur_x,
+      commands::get_epc_qr_code,
+      commands::get_swiss_qr_bill,
+      commands::get_bills,
+      commands::get_bill,
+      commands::get_bill_line_items,
+      commands::get_bill_taxes,
+      commands::get_bill_xml,
+      commands::import_bill,
+      commands::delete_bill,
       commands::get_organizations,
       commands::get_organization,
       commands::create_organization,
@@ -70,6 +153,11 @@ pub fn run() {
       commands::create_tax_rate,
       commands::update_tax_rate,
       commands::delete_tax_rate,
+      commands::get_items,
+      commands::get_item,
+      commands::create_item,
+      commands::update_item,
+      commands::delete_item,
       commands::backup_database,
       commands::restore_database,
       commands::get_tags,
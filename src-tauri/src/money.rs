@@ -0,0 +1,190 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+// Amounts are whole numbers of the currency's minor unit: cents for EUR, yen
+// for JPY, fils for BHD. Currencies not listed here have two decimals, as
+// most do (ISO 4217). Mirrored in src/utils/currency.ts.
+const MINOR_UNIT_EXPONENTS: &[(&str, u32)] = &[
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("ISK", 0),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("UGX", 0),
+    ("UYI", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+    ("BHD", 3),
+    ("IQD", 3),
+    ("JOD", 3),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("TND", 3),
+    ("CLF", 4),
+    ("UYW", 4),
+];
+
+// Number of decimals of the currency's minor unit
+pub fn minor_unit_exponent(currency: &str) -> u32 {
+    MINOR_UNIT_EXPONENTS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(currency.trim()))
+        .map(|(_, exponent)| *exponent)
+        .unwrap_or(2)
+}
+
+// Decimals kept on exchange rates worked out from other rates
+const RATE_DECIMALS: u32 = 10;
+
+// Positive decimal such as "1.0921" as its digits and number of decimals
+fn parse_rate(rate: &str) -> Option<(i128, u32)> {
+    let (whole, fraction) = rate.trim().split_once('.').unwrap_or((rate.trim(), ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        || whole.len() + fraction.len() > 30
+    {
+        return None;
+    }
+    let digits = format!("{}{}", whole, fraction).parse::<i128>().ok()?;
+    (digits > 0).then_some((digits, fraction.len() as u32))
+}
+
+// Division rounding half away from zero, the divisor is positive
+fn div_round(dividend: i128, divisor: i128) -> i128 {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder.abs() * 2 >= divisor {
+        quotient + dividend.signum()
+    } else {
+        quotient
+    }
+}
+
+// Whether an exchange rate is a positive decimal
+pub fn is_valid_rate(rate: &str) -> bool {
+    parse_rate(rate).is_some()
+}
+
+// `numerator / denominator` of two rates against the same currency, e.g. the
+// USD to JPY rate from the EUR to JPY and EUR to USD ones
+pub fn rate_quotient(numerator: &str, denominator: &str) -> Option<String> {
+    let (numerator, numerator_decimals) = parse_rate(numerator)?;
+    let (denominator, denominator_decimals) = parse_rate(denominator)?;
+    let digits = div_round(
+        numerator.checked_mul(10_i128.checked_pow(RATE_DECIMALS + denominator_decimals)?)?,
+        denominator.checked_mul(10_i128.pow(numerator_decimals))?,
+    );
+    if digits == 0 {
+        return None;
+    }
+
+    let scale = 10_i128.pow(RATE_DECIMALS);
+    let fraction = format!("{:0width$}", digits % scale, width = RATE_DECIMALS as usize);
+    let fraction = fraction.trim_end_matches('0');
+    Some(if fraction.is_empty() {
+        (digits / scale).to_string()
+    } else {
+        format!("{}.{}", digits / scale, fraction)
+    })
+}
+
+// An amount with its currency. Rows keep their currency in a column of its
+// own, so amounts are sent to the frontend as just their minor units.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub amount_minor: i64,
+    pub currency: String,
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.amount_minor)
+    }
+}
+
+impl Money {
+    pub fn new(amount_minor: i64, currency: &str) -> Self {
+        Self { amount_minor, currency: currency.trim().to_ascii_uppercase() }
+    }
+
+    pub fn exponent(&self) -> u32 {
+        minor_unit_exponent(&self.currency)
+    }
+
+    // Decimal amount such as "-12.5", rounding any fraction digits beyond the
+    // currency's minor unit half away from zero
+    pub fn parse(amount: &str, currency: &str) -> Option<Self> {
+        let amount = amount.trim();
+        let (negative, digits) = match amount.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, amount.strip_prefix('+').unwrap_or(amount)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let exponent = minor_unit_exponent(currency);
+        let whole = if whole.is_empty() { 0 } else { whole.parse::<i64>().ok()? };
+        let mut amount_minor = whole.checked_mul(10_i64.pow(exponent))?;
+        let mut fraction_digits = fraction.chars().map(|c| c as i64 - '0' as i64);
+        for place in (0..exponent).rev() {
+            amount_minor = amount_minor.checked_add(fraction_digits.next().unwrap_or(0) * 10_i64.pow(place))?;
+        }
+        if fraction_digits.next().unwrap_or(0) >= 5 {
+            amount_minor = amount_minor.checked_add(1)?;
+        }
+
+        Some(Self::new(if negative { -amount_minor } else { amount_minor }, currency))
+    }
+
+    // Decimal amount with exactly the currency's fraction digits, as
+    // e-invoices and payment codes carry it, e.g. "1250.00", "1250", "1.250"
+    pub fn to_decimal_string(&self) -> String {
+        let exponent = self.exponent();
+        let sign = if self.amount_minor < 0 { "-" } else { "" };
+        let amount = self.amount_minor.unsigned_abs();
+        if exponent == 0 {
+            return format!("{}{}", sign, amount);
+        }
+        let scale = 10_u64.pow(exponent);
+        format!("{}{}.{:0width$}", sign, amount / scale, amount % scale, width = exponent as usize)
+    }
+
+    // Amount in major units as a float, for display only
+    pub fn to_major(&self) -> f64 {
+        self.amount_minor as f64 / 10_f64.powi(self.exponent() as i32)
+    }
+
+    // Into `currency` at `rate` units of this currency for one unit of it, as
+    // rates are quoted: USD converts to EUR at "1 EUR = 1.0921 USD". Rounded to
+    // the minor unit half away from zero, None for a malformed rate.
+    pub fn convert(&self, rate: &str, currency: &str) -> Option<Money> {
+        let (rate, rate_decimals) = parse_rate(rate)?;
+        let amount_minor = div_round(
+            (self.amount_minor as i128)
+                .checked_mul(10_i128.pow(rate_decimals))?
+                .checked_mul(10_i128.pow(minor_unit_exponent(currency)))?,
+            rate.checked_mul(10_i128.pow(self.exponent()))?,
+        );
+        Some(Self::new(i64::try_from(amount_minor).ok()?, currency))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.to_decimal_string(), self.currency)
+    }
+}
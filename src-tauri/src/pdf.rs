@@ -0,0 +1,77 @@
+// Native PDF rendering, so invoices can be exported and attached without the
+// webview (src/components/invoices/pdf.tsx is the on-screen counterpart)
+
+pub mod document;
+pub mod font;
+pub mod invoice;
+pub mod locale;
+pub mod logo;
+
+use serde::Deserialize;
+
+use crate::db::{calculate_invoice_totals, CreateInvoiceLineItemRequest, Database};
+
+pub use document::{Archival, Attachment};
+pub use invoice::InvoiceDocument;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceTemplate {
+    // Mirrors the webview invoice PDF
+    #[default]
+    Default,
+}
+
+impl InvoiceTemplate {
+    pub fn render(&self, document: &InvoiceDocument, locale: &str, archival: Option<Archival>) -> Vec<u8> {
+        match self {
+            Self::Default => invoice::render(document, locale, archival),
+        }
+    }
+}
+
+impl Database {
+    pub async fn render_invoice_pdf(
+        &self,
+        invoice_id: &str,
+        template: InvoiceTemplate,
+        locale: &str,
+    ) -> Result<Vec<u8>, sqlx::Error> {
+        let invoice = self.get_invoice(invoice_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let line_items = self.get_invoice_line_items(invoice_id).await?;
+        let client = self.get_client(&invoice.client_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let organization = self.get_organization(&invoice.organization_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let tax_rates = self.get_invoice_tax_rates(invoice_id).await?;
+
+        // The tax breakdown is recalculated with the same rules used to store the totals
+        let requests: Vec<CreateInvoiceLineItemRequest> = line_items
+            .iter()
+            .map(|line_item| CreateInvoiceLineItemRequest {
+                description: line_item.description.clone(),
+                quantity: line_item.quantity,
+                unit_code: line_item.unit_code.clone(),
+                unit_price: line_item.unit_price.amount_minor,
+                discount: line_item.discount,
+                discount_kind: line_item.discount_kind,
+                tax_rate: line_item.tax_rate.clone(),
+                taxes: line_item.taxes.clone(),
+                item_id: line_item.item_id.clone(),
+            })
+            .collect();
+        let totals = calculate_invoice_totals(&requests, &tax_rates, invoice.tax_mode(), invoice.discount(), &invoice.currency)?;
+
+        let document = InvoiceDocument {
+            invoice: &invoice,
+            line_items: &line_items,
+            client: &client,
+            organization: &organization,
+            tax_rates: &tax_rates,
+            totals: &totals,
+        };
+
+        Ok(template.render(&document, locale, None))
+    }
+}
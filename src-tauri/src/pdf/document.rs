@@ -0,0 +1,359 @@
+use chrono::{DateTime, Datelike, Local, Timelike};
+use pdf_writer::{Content, Date, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use qrcode::QrCode;
+
+use super::font::{Font, FontWeight};
+use super::logo::Logo;
+use crate::qr::{dark_modules, QUIET_ZONE};
+
+// A4 in points
+pub const PAGE_WIDTH: f32 = 595.28;
+pub const PAGE_HEIGHT: f32 = 841.89;
+
+// Gray used for rules, same as the webview PDF
+const RULE_GRAY: f32 = 0.525;
+
+// Output intent of archival (PDF/A) documents
+const SRGB_PROFILE: &[u8] = include_bytes!("sRGB-v4.icc");
+
+// A file carried inside the PDF, e.g. the XML of a hybrid e-invoice
+pub struct Attachment {
+    pub name: String,
+    pub description: String,
+    pub mime_type: String,
+    // Relationship to the document (AFRelationship), e.g. "Alternative"
+    pub relationship: String,
+    pub data: Vec<u8>,
+}
+
+// Makes the document a PDF/A-3b file. `metadata` holds extra rdf:Description
+// elements for the XMP packet, such as extension schemas the attachments need.
+pub struct Archival {
+    pub attachments: Vec<Attachment>,
+    pub metadata: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// Page-by-page drawing surface. Coordinates are measured from the top-left
+// corner of the page, as in the webview layout, and flipped when written.
+pub struct Document {
+    fonts: Vec<Font>,
+    logo: Option<Logo>,
+    pages: Vec<Vec<u8>>,
+    content: Content,
+}
+
+impl Document {
+    pub fn new(logo: Option<Logo>) -> Self {
+        Self {
+            fonts: [FontWeight::Regular, FontWeight::Medium, FontWeight::SemiBold]
+                .into_iter()
+                .map(Font::new)
+                .collect(),
+            logo,
+            pages: Vec::new(),
+            content: Content::new(),
+        }
+    }
+
+    fn font(&self, weight: FontWeight) -> &Font {
+        self.fonts.iter().find(|font| font.weight == weight).expect("all weights are loaded")
+    }
+
+    fn font_mut(&mut self, weight: FontWeight) -> &mut Font {
+        self.fonts.iter_mut().find(|font| font.weight == weight).expect("all weights are loaded")
+    }
+
+    pub fn logo_size(&self) -> Option<(u32, u32)> {
+        self.logo.as_ref().map(|logo| (logo.width, logo.height))
+    }
+
+    pub fn new_page(&mut self) {
+        let content = std::mem::replace(&mut self.content, Content::new());
+        self.pages.push(content.finish());
+    }
+
+    pub fn text_width(&self, text: &str, weight: FontWeight, size: f32) -> f32 {
+        self.font(weight).width(text, size)
+    }
+
+    // Draws a single line of text with its top at `top`. For right and center
+    // alignment `x` is the right edge or the center respectively.
+    pub fn text(&mut self, x: f32, top: f32, text: &str, weight: FontWeight, size: f32, align: Align) {
+        if text.is_empty() {
+            return;
+        }
+
+        let width = self.text_width(text, weight, size);
+        let x = match align {
+            Align::Left => x,
+            Align::Center => x - width / 2.0,
+            Align::Right => x - width,
+        };
+        let baseline = PAGE_HEIGHT - top - size * 0.95;
+
+        let encoded = self.font_mut(weight).encode(text);
+        self.content
+            .begin_text()
+            .set_font(weight.resource_name(), size)
+            .next_line(x, baseline)
+            .show(Str(&encoded))
+            .end_text();
+    }
+
+    // Splits text into lines no wider than `max_width`, keeping the text's own
+    // line breaks. Words longer than a line are broken between characters.
+    pub fn wrap(&self, text: &str, weight: FontWeight, size: f32, max_width: f32) -> Vec<String> {
+        let font = self.font(weight);
+        let mut lines = Vec::new();
+
+        for paragraph in text.lines() {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+                if font.width(&candidate, size) <= max_width {
+                    line = candidate;
+                    continue;
+                }
+
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                for c in word.chars() {
+                    line.push(c);
+                    if font.width(&line, size) > max_width && line.chars().count() > 1 {
+                        line.pop();
+                        lines.push(std::mem::take(&mut line));
+                        line.push(c);
+                    }
+                }
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    pub fn rule(&mut self, x1: f32, x2: f32, top: f32, width: f32) {
+        let y = PAGE_HEIGHT - top - width / 2.0;
+        self.content
+            .save_state()
+            .set_stroke_gray(RULE_GRAY)
+            .set_line_width(width)
+            .move_to(x1, y)
+            .line_to(x2, y)
+            .stroke()
+            .restore_state();
+    }
+
+    pub fn logo(&mut self, x: f32, top: f32, width: f32, height: f32) {
+        if self.logo.is_none() {
+            return;
+        }
+        self.content
+            .save_state()
+            .transform([width, 0.0, 0.0, height, x, PAGE_HEIGHT - top - height])
+            .x_object(Name(b"Im1"))
+            .restore_state();
+    }
+
+    // Draws the code as vector squares, `size` includes the quiet zone
+    pub fn qr_code(&mut self, x: f32, top: f32, size: f32, code: &QrCode) {
+        let module = size / (code.width() + 2 * QUIET_ZONE) as f32;
+        self.content.save_state().set_fill_gray(0.0);
+        for (column, row) in dark_modules(code) {
+            let left = x + (column + QUIET_ZONE) as f32 * module;
+            let bottom = PAGE_HEIGHT - top - (row + QUIET_ZONE + 1) as f32 * module;
+            self.content.rect(left, bottom, module, module);
+        }
+        self.content.fill_nonzero().restore_state();
+    }
+
+    pub fn finish(mut self, title: &str, archival: Option<Archival>) -> Vec<u8> {
+        self.new_page();
+
+        let mut next = 0;
+        let mut next_id = || {
+            next += 1;
+            Ref::new(next)
+        };
+
+        let catalog_id = next_id();
+        let page_tree_id = next_id();
+        let info_id = next_id();
+        let font_ids: Vec<Ref> = self.fonts.iter().map(|_| next_id()).collect();
+        let logo_id = next_id();
+        let page_ids: Vec<(Ref, Ref)> = self.pages.iter().map(|_| (next_id(), next_id())).collect();
+
+        let mut pdf = Pdf::new();
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().map(|(page_id, _)| *page_id))
+            .count(page_ids.len() as i32);
+        let mut info = pdf.document_info(info_id);
+        info.title(TextStr(title)).producer(TextStr(PRODUCER));
+        // The XMP dates of archival documents have to match the info dictionary
+        let now = Local::now();
+        if archival.is_some() {
+            info.creation_date(pdf_date(&now)).modified_date(pdf_date(&now));
+        }
+        info.finish();
+
+        for ((page_id, content_id), content) in page_ids.iter().zip(&self.pages) {
+            let mut page = pdf.page(*page_id);
+            page.parent(page_tree_id)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(*content_id);
+            let mut resources = page.resources();
+            let mut fonts = resources.fonts();
+            for (font, font_id) in self.fonts.iter().zip(&font_ids) {
+                if font.is_used() {
+                    fonts.pair(font.weight.resource_name(), *font_id);
+                }
+            }
+            fonts.finish();
+            if self.logo.is_some() {
+                resources.x_objects().pair(Name(b"Im1"), logo_id);
+            }
+            resources.finish();
+            page.finish();
+
+            let compressed = miniz_oxide::deflate::compress_to_vec_zlib(content, 6);
+            pdf.stream(*content_id, &compressed).filter(Filter::FlateDecode);
+        }
+
+        for (font, font_id) in self.fonts.iter().zip(&font_ids) {
+            if font.is_used() {
+                font.write(&mut pdf, *font_id, &mut next_id);
+            }
+        }
+        if let Some(logo) = &self.logo {
+            logo.write(&mut pdf, logo_id, &mut next_id);
+        }
+
+        if let Some(archival) = archival {
+            let metadata_id = next_id();
+            let profile_id = next_id();
+            let file_ids: Vec<(Ref, Ref)> = archival.attachments.iter().map(|_| (next_id(), next_id())).collect();
+
+            let mut catalog = pdf.catalog(catalog_id);
+            catalog.pages(page_tree_id).metadata(metadata_id);
+            catalog
+                .output_intents()
+                .push()
+                .pair(Name(b"Type"), Name(b"OutputIntent"))
+                .pair(Name(b"S"), Name(b"GTS_PDFA1"))
+                .pair(Name(b"OutputConditionIdentifier"), TextStr("sRGB"))
+                .pair(Name(b"Info"), TextStr("sRGB IEC61966-2.1"))
+                .pair(Name(b"DestOutputProfile"), profile_id);
+            if !file_ids.is_empty() {
+                let mut names = catalog.names();
+                let mut embedded_files = names.embedded_files();
+                let mut entries = embedded_files.names();
+                for (attachment, (spec_id, _)) in archival.attachments.iter().zip(&file_ids) {
+                    entries.insert(Str(attachment.name.as_bytes()), *spec_id);
+                }
+                entries.finish();
+                embedded_files.finish();
+                names.finish();
+                catalog
+                    .insert(Name(b"AF"))
+                    .array()
+                    .items(file_ids.iter().map(|(spec_id, _)| *spec_id));
+            }
+            catalog.finish();
+
+            let xmp = xmp_packet(title, &now, &archival.metadata);
+            pdf.metadata(metadata_id, xmp.as_bytes());
+            let compressed = miniz_oxide::deflate::compress_to_vec_zlib(SRGB_PROFILE, 6);
+            pdf.icc_profile(profile_id, &compressed)
+                .n(3)
+                .filter(Filter::FlateDecode);
+
+            for (attachment, (spec_id, file_id)) in archival.attachments.iter().zip(&file_ids) {
+                let mut spec = pdf.file_spec(*spec_id);
+                spec.path(Str(attachment.name.as_bytes()))
+                    .unic_file(TextStr(&attachment.name))
+                    .description(TextStr(&attachment.description));
+                spec.insert(Name(b"EF"))
+                    .dict()
+                    .pair(Name(b"F"), *file_id)
+                    .pair(Name(b"UF"), *file_id);
+                spec.pair(Name(b"AFRelationship"), Name(attachment.relationship.as_bytes()));
+                spec.finish();
+
+                let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&attachment.data, 6);
+                let mut file = pdf.embedded_file(*file_id, &compressed);
+                file.subtype(Name(attachment.mime_type.as_bytes()))
+                    .filter(Filter::FlateDecode);
+                file.params()
+                    .size(attachment.data.len() as i32)
+                    .modification_date(pdf_date(&now));
+            }
+
+            // PDF/A requires a file identifier
+            let id = nanoid::nanoid!().into_bytes();
+            pdf.set_file_id((id.clone(), id));
+        } else {
+            pdf.catalog(catalog_id).pages(page_tree_id);
+        }
+
+        pdf.finish()
+    }
+}
+
+const PRODUCER: &str = "Upcount";
+
+fn pdf_date(date: &DateTime<Local>) -> Date {
+    let offset = date.offset().local_minus_utc() / 60;
+    Date::new(date.year() as u16)
+        .month(date.month() as u8)
+        .day(date.day() as u8)
+        .hour(date.hour() as u8)
+        .minute(date.minute() as u8)
+        .second(date.second() as u8)
+        .utc_offset_hour((offset / 60) as i8)
+        .utc_offset_minute((offset % 60).unsigned_abs() as u8)
+}
+
+// XMP packet identifying the document as PDF/A-3b, with the title, producer
+// and dates mirrored from the info dictionary
+fn xmp_packet(title: &str, date: &DateTime<Local>, metadata: &str) -> String {
+    let date = date.format("%Y-%m-%dT%H:%M:%S%:z");
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+      <pdfaid:part>3</pdfaid:part>
+      <pdfaid:conformance>B</pdfaid:conformance>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">{title}</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:pdf="http://ns.adobe.com/pdf/1.3/">
+      <pdf:Producer>{producer}</pdf:Producer>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+      <xmp:CreateDate>{date}</xmp:CreateDate>
+      <xmp:ModifyDate>{date}</xmp:ModifyDate>
+    </rdf:Description>
+{metadata}  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+        title = crate::einvoice::xml::escape(title),
+        producer = PRODUCER,
+        date = date,
+        metadata = metadata,
+    )
+}
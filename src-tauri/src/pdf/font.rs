@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::{Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use ttf_parser::{Face, GlyphId};
+
+// Same family as the webview PDF (src/components/invoices/pdf.tsx)
+const MONTSERRAT_REGULAR: &[u8] = include_bytes!("../../../public/fonts/montserrat/Montserrat-Regular.ttf");
+const MONTSERRAT_MEDIUM: &[u8] = include_bytes!("../../../public/fonts/montserrat/Montserrat-Medium.ttf");
+const MONTSERRAT_SEMIBOLD: &[u8] = include_bytes!("../../../public/fonts/montserrat/Montserrat-SemiBold.ttf");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontWeight {
+    Regular,
+    Medium,
+    SemiBold,
+}
+
+impl FontWeight {
+    fn data(&self) -> &'static [u8] {
+        match self {
+            Self::Regular => MONTSERRAT_REGULAR,
+            Self::Medium => MONTSERRAT_MEDIUM,
+            Self::SemiBold => MONTSERRAT_SEMIBOLD,
+        }
+    }
+
+    fn postscript_name(&self) -> &'static str {
+        match self {
+            Self::Regular => "Montserrat-Regular",
+            Self::Medium => "Montserrat-Medium",
+            Self::SemiBold => "Montserrat-SemiBold",
+        }
+    }
+
+    fn weight(&self) -> u16 {
+        match self {
+            Self::Regular => 400,
+            Self::Medium => 500,
+            Self::SemiBold => 600,
+        }
+    }
+
+    // Resource name used in content streams
+    pub fn resource_name(&self) -> Name<'static> {
+        match self {
+            Self::Regular => Name(b"F1"),
+            Self::Medium => Name(b"F2"),
+            Self::SemiBold => Name(b"F3"),
+        }
+    }
+}
+
+// An embedded TrueType font addressed by glyph id (Identity-H), so any
+// character the font covers can be shown regardless of PDF encodings.
+// Glyphs are recorded as they are used to build the widths and ToUnicode map.
+pub struct Font {
+    pub weight: FontWeight,
+    face: Face<'static>,
+    used: BTreeMap<u16, char>,
+}
+
+impl Font {
+    pub fn new(weight: FontWeight) -> Self {
+        let face = Face::parse(weight.data(), 0).expect("bundled font is a valid TrueType font");
+        Self { weight, face, used: BTreeMap::new() }
+    }
+
+    fn units_per_em(&self) -> f32 {
+        self.face.units_per_em() as f32
+    }
+
+    fn glyph(&self, c: char) -> GlyphId {
+        self.face.glyph_index(c).unwrap_or(GlyphId(0))
+    }
+
+    fn advance(&self, glyph: GlyphId) -> f32 {
+        self.face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * 1000.0 / self.units_per_em()
+    }
+
+    // Width of the text in points at the given font size
+    pub fn width(&self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.advance(self.glyph(c))).sum::<f32>() * size / 1000.0
+    }
+
+    // Glyph ids for a show-text operator
+    pub fn encode(&mut self, text: &str) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(text.len() * 2);
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let glyph = self.glyph(c);
+            self.used.entry(glyph.0).or_insert(c);
+            encoded.extend_from_slice(&glyph.0.to_be_bytes());
+        }
+        encoded
+    }
+
+    pub fn is_used(&self) -> bool {
+        !self.used.is_empty()
+    }
+
+    // Writes the Type0 font and its descendant CID font, descriptor, font file
+    // and ToUnicode map. `next_id` hands out the indirect references.
+    pub fn write(&self, pdf: &mut Pdf, font_id: Ref, next_id: &mut impl FnMut() -> Ref) {
+        let cid_id = next_id();
+        let descriptor_id = next_id();
+        let file_id = next_id();
+        let cmap_id = next_id();
+
+        let base_font = Name(self.weight.postscript_name().as_bytes());
+        let system_info = SystemInfo {
+            registry: Str(b"Adobe"),
+            ordering: Str(b"Identity"),
+            supplement: 0,
+        };
+
+        pdf.type0_font(font_id)
+            .base_font(base_font)
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid_id)
+            .to_unicode(cmap_id);
+
+        let mut cid = pdf.cid_font(cid_id);
+        cid.subtype(CidFontType::Type2)
+            .base_font(base_font)
+            .system_info(system_info)
+            .font_descriptor(descriptor_id)
+            .default_width(0.0)
+            .cid_to_gid_map_predefined(Name(b"Identity"));
+        let mut widths = cid.widths();
+        for &glyph in self.used.keys() {
+            widths.consecutive(glyph, [self.advance(GlyphId(glyph))]);
+        }
+        widths.finish();
+        cid.finish();
+
+        let scale = 1000.0 / self.units_per_em();
+        let bbox = self.face.global_bounding_box();
+        pdf.font_descriptor(descriptor_id)
+            .name(base_font)
+            .family(Str(b"Montserrat"))
+            .weight(self.weight.weight())
+            .flags(FontFlags::NON_SYMBOLIC)
+            .bbox(Rect::new(
+                bbox.x_min as f32 * scale,
+                bbox.y_min as f32 * scale,
+                bbox.x_max as f32 * scale,
+                bbox.y_max as f32 * scale,
+            ))
+            .italic_angle(0.0)
+            .ascent(self.face.ascender() as f32 * scale)
+            .descent(self.face.descender() as f32 * scale)
+            .cap_height(self.face.capital_height().unwrap_or(self.face.ascender()) as f32 * scale)
+            .stem_v(80.0)
+            .font_file2(file_id);
+
+        let data = self.weight.data();
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(data, 6);
+        pdf.stream(file_id, &compressed)
+            .filter(Filter::FlateDecode)
+            .pair(Name(b"Length1"), data.len() as i32);
+
+        let mut cmap = UnicodeCmap::new(Name(b"Upcount-UTF16"), system_info);
+        for (&glyph, &c) in &self.used {
+            cmap.pair(glyph, c);
+        }
+        pdf.cmap(cmap_id, &cmap.finish());
+    }
+}
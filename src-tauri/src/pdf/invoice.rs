@@ -0,0 +1,425 @@
+use crate::db::{
+    line_amount, Client, Discount, Invoice, InvoiceKind, InvoiceLineItem, InvoiceTotals, Organization,
+    TaxRate,
+};
+use crate::money::Money;
+use crate::units::{unit_label, DEFAULT_UNIT_CODE};
+
+use super::document::{Align, Archival, Document, PAGE_HEIGHT, PAGE_WIDTH};
+use super::font::FontWeight;
+use super::locale::Locale;
+use super::logo::Logo;
+use crate::qr::epc::EpcPayment;
+
+const MARGIN: f32 = 50.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const RIGHT: f32 = PAGE_WIDTH - MARGIN;
+// Content stops above the footer
+const CONTENT_BOTTOM: f32 = PAGE_HEIGHT - 70.0;
+
+const ROW_GAP: f32 = 36.0;
+const CELL_PADDING: f32 = 8.0;
+const SMALL: f32 = 8.0;
+const LINE_HEIGHT: f32 = 1.25;
+// Payment QR code including its quiet zone
+const QR_SIZE: f32 = 90.0;
+
+// Line item columns as fractions of the content width:
+// #, description, quantity, unit price, tax and total
+const COLUMNS: [f32; 6] = [0.04, 0.56, 0.10, 0.10, 0.10, 0.10];
+
+pub struct InvoiceDocument<'a> {
+    pub invoice: &'a Invoice,
+    pub line_items: &'a [InvoiceLineItem],
+    pub client: &'a Client,
+    pub organization: &'a Organization,
+    pub tax_rates: &'a [TaxRate],
+    // Breakdown by line and tax rate. The subtotal and total printed are the
+    // invoice's stored ones, as on its e-invoice and payment code.
+    pub totals: &'a InvoiceTotals,
+}
+
+struct Renderer<'a> {
+    doc: Document,
+    data: &'a InvoiceDocument<'a>,
+    locale: Locale,
+    y: f32,
+}
+
+// Lays out an invoice the same way as the webview PDF
+// (src/components/invoices/pdf.tsx), continuing onto extra pages when the
+// line items don't fit. Archival output is used for hybrid e-invoices.
+pub fn render(data: &InvoiceDocument, locale: &str, archival: Option<Archival>) -> Vec<u8> {
+    let logo = data.organization.logo.as_deref().and_then(Logo::from_organization);
+    let mut renderer = Renderer {
+        doc: Document::new(logo),
+        data,
+        locale: Locale::new(locale),
+        y: MARGIN,
+    };
+
+    renderer.header();
+    renderer.parties();
+    renderer.dates();
+    renderer.line_items();
+    renderer.summary();
+    renderer.payment_code();
+    renderer.footer();
+
+    let title = renderer.title();
+    renderer.doc.finish(&title, archival)
+}
+
+impl Renderer<'_> {
+    fn title(&self) -> String {
+        let number = Some(self.data.invoice.number.as_str());
+        match self.data.invoice.kind {
+            InvoiceKind::Invoice => self.locale.t("Invoice {0}", number),
+            InvoiceKind::CreditNote => self.locale.t("Credit note {0}", number),
+        }
+    }
+
+    fn discount(&self, discount: &Discount) -> String {
+        let value = match *discount {
+            Discount::Percentage(percentage) => format!("{}%", self.locale.format_quantity(percentage)),
+            Discount::Amount(amount) => self.money(amount),
+        };
+        self.locale.t("Discount {0}", Some(&value))
+    }
+
+    // Quantity followed by its unit, e.g. "2.5 h". Plain units aren't named.
+    fn quantity(&self, line_item: &InvoiceLineItem) -> String {
+        let quantity = self.locale.format_quantity(line_item.quantity);
+        match line_item.unit_code.as_deref().filter(|code| *code != DEFAULT_UNIT_CODE) {
+            Some(code) => {
+                let unit = unit_label(code).map(|label| self.locale.t(label, None)).unwrap_or_else(|| code.to_string());
+                format!("{} {}", quantity, unit)
+            }
+            None => quantity,
+        }
+    }
+
+    fn money(&self, amount_minor: i64) -> String {
+        self.locale.format_money(&Money::new(amount_minor, &self.data.invoice.currency), self.data.organization.minimum_fraction_digits)
+    }
+
+    fn date(&self, timestamp: i64) -> String {
+        self.locale.format_date(timestamp, self.data.organization.date_format.as_deref())
+    }
+
+    // Starts a new page when `height` doesn't fit on the current one
+    fn ensure_space(&mut self, height: f32) -> bool {
+        if self.y + height <= CONTENT_BOTTOM {
+            return false;
+        }
+        self.footer();
+        self.doc.new_page();
+        self.y = MARGIN;
+        true
+    }
+
+    // Wrapped lines of small text, returns the height used
+    fn paragraph(&mut self, x: f32, top: f32, width: f32, text: &str, align: Align) -> f32 {
+        let lines = self.doc.wrap(text, FontWeight::Regular, SMALL, width);
+        let x = if align == Align::Right { x + width } else { x };
+        for (i, line) in lines.iter().enumerate() {
+            let line_top = top + i as f32 * SMALL * LINE_HEIGHT;
+            self.doc.text(x, line_top, line, FontWeight::Regular, SMALL, align);
+        }
+        lines.len() as f32 * SMALL * LINE_HEIGHT
+    }
+
+    fn header(&mut self) {
+        let title = self.title();
+        self.doc.text(MARGIN, self.y, &title, FontWeight::SemiBold, 24.0, Align::Left);
+        let mut height: f32 = 24.0 * LINE_HEIGHT;
+
+        if let Some((width, height_px)) = self.doc.logo_size() {
+            // Fit into the same 120pt wide box as the webview, without upscaling
+            let scale = (120.0 / width as f32).min(60.0 / height_px as f32).min(0.75);
+            let (logo_width, logo_height) = (width as f32 * scale, height_px as f32 * scale);
+            self.doc.logo(RIGHT - logo_width, self.y, logo_width, logo_height);
+            height = height.max(logo_height);
+        }
+
+        self.y += height + ROW_GAP;
+    }
+
+    fn parties(&mut self) {
+        let client = self.data.client;
+        let organization = self.data.organization;
+        let column = CONTENT_WIDTH / 2.0;
+
+        // Emails are stored as a JSON array, only the first one is printed
+        let email = client
+            .emails
+            .as_deref()
+            .and_then(|emails| serde_json::from_str::<Vec<String>>(emails).ok())
+            .and_then(|emails| emails.into_iter().next());
+
+        let client_lines = [client.address.as_deref(), email.as_deref(), client.website.as_deref()];
+        let organization_lines = [
+            organization.address.as_deref(),
+            organization.email.as_deref(),
+            organization.website.as_deref(),
+        ];
+
+        let mut heights = [0.0f32; 2];
+        for (i, (name, lines, x, align)) in [
+            (client.name.as_deref(), client_lines, MARGIN, Align::Left),
+            (organization.name.as_deref(), organization_lines, MARGIN + column, Align::Right),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let name_x = if align == Align::Right { RIGHT } else { x };
+            self.doc.text(name_x, self.y, name.unwrap_or_default(), FontWeight::Regular, 12.0, align);
+            let mut top = self.y + 12.0 * LINE_HEIGHT + 8.0;
+            for line in lines.into_iter().flatten() {
+                top += self.paragraph(x, top, column, line, align) + 2.0;
+            }
+            heights[i] = top - self.y;
+        }
+
+        self.y += heights[0].max(heights[1]) + ROW_GAP;
+    }
+
+    fn dates(&mut self) {
+        let invoice = self.data.invoice;
+        let mut rows = vec![(self.locale.t("Date", None), self.date(invoice.date))];
+        if let Some(due_date) = invoice.due_date {
+            rows.push((self.locale.t("Due date", None), self.date(due_date)));
+        }
+        if let Some(payment_reference) = &invoice.payment_reference {
+            rows.push((self.locale.t("Reference", None), payment_reference.clone()));
+        }
+        if let Some(overdue_charge) = invoice.overdue_charge.filter(|charge| *charge != 0.0) {
+            rows.push((self.locale.t("Overdue charge", None), format!("{}%", overdue_charge)));
+        }
+
+        let label_width = CONTENT_WIDTH * 0.2;
+        for (label, value) in rows {
+            self.doc.text(MARGIN, self.y, &label, FontWeight::Regular, SMALL, Align::Left);
+            self.doc.text(MARGIN + label_width, self.y, &value, FontWeight::Regular, SMALL, Align::Left);
+            self.y += SMALL * LINE_HEIGHT + 8.0;
+        }
+
+        self.y += ROW_GAP - 8.0;
+    }
+
+    // Left edge and inner width of each table column
+    fn columns() -> [(f32, f32); 6] {
+        let mut x = MARGIN;
+        COLUMNS.map(|fraction| {
+            let width = CONTENT_WIDTH * fraction;
+            let column = (x, width);
+            x += width;
+            column
+        })
+    }
+
+    fn table_row(&mut self, cells: [String; 6], weight: FontWeight, bottom_rule: f32) {
+        let columns = Self::columns();
+        let mut wrapped: Vec<Vec<String>> = Vec::with_capacity(6);
+        for (i, cell) in cells.iter().enumerate() {
+            let padding_left = if i == 0 { 0.0 } else { CELL_PADDING };
+            let padding_right = if i == 5 { 0.0 } else { CELL_PADDING };
+            let width = columns[i].1 - padding_left - padding_right;
+            wrapped.push(self.doc.wrap(cell, weight, SMALL, width));
+        }
+
+        let lines = wrapped.iter().map(Vec::len).max().unwrap_or(1).max(1);
+        let height = lines as f32 * SMALL * LINE_HEIGHT + 2.0 * CELL_PADDING + bottom_rule;
+        if self.ensure_space(height) && weight == FontWeight::Regular {
+            self.table_header();
+        }
+
+        for (i, lines) in wrapped.iter().enumerate() {
+            let (x, width) = columns[i];
+            let (x, align) = match i {
+                0 => (x, Align::Left),
+                5 => (x + width, Align::Right),
+                _ => (x + CELL_PADDING, Align::Left),
+            };
+            for (j, line) in lines.iter().enumerate() {
+                let top = self.y + CELL_PADDING + j as f32 * SMALL * LINE_HEIGHT;
+                self.doc.text(x, top, line, weight, SMALL, align);
+            }
+        }
+
+        self.y += height - bottom_rule;
+        self.doc.rule(MARGIN, RIGHT, self.y, bottom_rule);
+        self.y += bottom_rule;
+    }
+
+    fn table_header(&mut self) {
+        let cells = [
+            "#".to_string(),
+            self.locale.t("Description", None),
+            self.locale.t("Qty.", None),
+            self.locale.t("Price", None),
+            self.locale.t("Tax %", None),
+            self.locale.t("Total", None),
+        ];
+        self.table_row(cells, FontWeight::Medium, 2.0);
+    }
+
+    fn line_items(&mut self) {
+        self.table_header();
+
+        let count = self.data.line_items.len();
+        for (i, line_item) in self.data.line_items.iter().enumerate() {
+            // Withheld rates are shown as taken off, e.g. "21% - 15%"
+            let mut taxes = String::new();
+            for tax_rate in line_item
+                .taxes
+                .iter()
+                .filter_map(|tax| self.data.tax_rates.iter().find(|tax_rate| tax_rate.id == tax.tax_rate))
+            {
+                let sign = match (tax_rate.withholding, taxes.is_empty()) {
+                    (true, true) => "-",
+                    (true, false) => " - ",
+                    (false, true) => "",
+                    (false, false) => " + ",
+                };
+                taxes.push_str(&format!("{}{}%", sign, tax_rate.percentage));
+            }
+            let mut line_total = line_amount(line_item.quantity, line_item.unit_price.amount_minor);
+            let mut description = line_item.description.clone().unwrap_or_default();
+            // The total is after the line's own discount, which is noted under the description
+            if let Some(discount) = Discount::new(line_item.discount, line_item.discount_kind) {
+                line_total -= discount.amount_off(line_total).unwrap_or(0);
+                if !description.is_empty() {
+                    description.push('\n');
+                }
+                description.push_str(&self.discount(&discount));
+            }
+
+            let cells = [
+                (i + 1).to_string(),
+                description,
+                self.quantity(line_item),
+                self.locale.format_money(&line_item.unit_price, self.data.organization.minimum_fraction_digits),
+                taxes,
+                self.money(line_total),
+            ];
+            let bottom_rule = if i + 1 == count { 2.0 } else { 0.5 };
+            self.table_row(cells, FontWeight::Regular, bottom_rule);
+        }
+    }
+
+    fn summary(&mut self) {
+        let invoice = self.data.invoice;
+        let totals = self.data.totals;
+        // Line amounts include tax, the subtotal is what is left after backing it out
+        let subtotal = if self.data.invoice.prices_include_tax { "Subtotal excl. tax" } else { "Subtotal" };
+        let mut rows: Vec<(String, String)> = Vec::new();
+        if let Some(discount) = self.data.invoice.discount().filter(|_| totals.invoice_discount.amount_minor != 0) {
+            rows.push((self.discount(&discount), self.money(-totals.invoice_discount.amount_minor)));
+        }
+        rows.push((self.locale.t(subtotal, None), self.money(invoice.sub_total.amount_minor)));
+        for tax in &totals.taxes {
+            let mut label = format!("{} {}%", tax.name, tax.percentage);
+            if tax.compound {
+                label = self.locale.t("{0} (compound)", Some(&label));
+            }
+            rows.push((label, self.money(tax.amount)));
+        }
+        if self.data.line_items.iter().any(|line_item| line_item.tax_rate.is_none()) {
+            rows.push((self.locale.t("Tax 0%", None), self.money(0)));
+        }
+        // Withheld by the client, the total is what is left to pay
+        for withholding in &totals.withholdings {
+            rows.push((format!("{} {}%", withholding.name, withholding.percentage), self.money(-withholding.amount)));
+        }
+
+        let notes_width = CONTENT_WIDTH * 0.6 - 20.0;
+        let mut notes = self.data.invoice.customer_notes.clone().unwrap_or_default();
+        // Exempt and reverse charge supplies must state why no VAT is charged
+        let mut reasons: Vec<&str> = Vec::new();
+        for reason in totals.taxes.iter().filter_map(|tax| tax.exemption_reason.as_deref()) {
+            if !reason.trim().is_empty() && !reasons.contains(&reason) {
+                reasons.push(reason);
+            }
+        }
+        for reason in reasons {
+            if !notes.is_empty() {
+                notes.push_str("\n\n");
+            }
+            notes.push_str(reason);
+        }
+        let notes_lines = self.doc.wrap(&notes, FontWeight::Regular, SMALL, notes_width).len();
+        let notes_height = notes_lines as f32 * SMALL * LINE_HEIGHT;
+        let total_rows = if invoice.balance_due != invoice.total { 2 } else { 1 };
+        let totals_height = (rows.len() + total_rows) as f32 * (SMALL * LINE_HEIGHT + 8.0) + 10.0;
+
+        self.y += 20.0;
+        self.ensure_space(notes_height.max(totals_height));
+
+        self.paragraph(MARGIN, self.y, notes_width, &notes, Align::Left);
+
+        let left = MARGIN + CONTENT_WIDTH * 0.6;
+        let mut top = self.y;
+        for (label, value) in rows {
+            self.doc.text(left, top + 4.0, &label, FontWeight::Regular, SMALL, Align::Left);
+            self.doc.text(RIGHT, top + 4.0, &value, FontWeight::Regular, SMALL, Align::Right);
+            top += SMALL * LINE_HEIGHT + 8.0;
+        }
+
+        top += 8.0;
+        self.doc.rule(left, RIGHT, top, 2.0);
+        top += 2.0 + 8.0;
+        let total = self.locale.t("Total", None);
+        let amount = self.money(invoice.total.amount_minor);
+        self.doc.text(left, top, &total, FontWeight::SemiBold, SMALL, Align::Left);
+        self.doc.text(RIGHT, top, &amount, FontWeight::SemiBold, SMALL, Align::Right);
+        // Part paid or credited, what the payment code asks for
+        if invoice.balance_due != invoice.total {
+            top += SMALL * LINE_HEIGHT + 8.0;
+            let balance_due = self.locale.t("Balance due", None);
+            let amount = self.money(invoice.balance_due.amount_minor);
+            self.doc.text(left, top, &balance_due, FontWeight::Regular, SMALL, Align::Left);
+            self.doc.text(RIGHT, top, &amount, FontWeight::Regular, SMALL, Align::Right);
+        }
+
+        self.y = (self.y + notes_height).max(top + SMALL * LINE_HEIGHT) + ROW_GAP;
+    }
+
+    // EPC QR code for the outstanding balance. Left out when the invoice can't
+    // be paid that way, e.g. it isn't in EUR or there's no IBAN.
+    fn payment_code(&mut self) {
+        let code = EpcPayment::from_invoice(self.data.invoice, self.data.organization).and_then(|payment| payment.qr_code());
+        let Ok(code) = code else {
+            return;
+        };
+
+        let height = QR_SIZE + SMALL * LINE_HEIGHT;
+        self.ensure_space(height);
+        self.doc.qr_code(MARGIN, self.y, QR_SIZE, &code);
+        let label = self.locale.t("Scan to pay", None);
+        self.doc.text(MARGIN + QR_SIZE / 2.0, self.y + QR_SIZE, &label, FontWeight::Regular, SMALL, Align::Center);
+        self.y += height + ROW_GAP;
+    }
+
+    fn footer(&mut self) {
+        let organization = self.data.organization;
+        let top = PAGE_HEIGHT - 30.0 - 10.0 * LINE_HEIGHT - 8.0;
+        self.doc.rule(MARGIN, RIGHT, top, 0.5);
+
+        let bank = [organization.bank_name.as_deref(), organization.iban.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let top = top + 8.5;
+        self.doc.text(MARGIN, top, &bank, FontWeight::Regular, 10.0, Align::Left);
+        if let Some(registration_number) = organization.registration_number.as_deref().filter(|s| !s.is_empty()) {
+            let text = format!("{} {}", self.locale.t("Registration number", None), registration_number);
+            self.doc.text(PAGE_WIDTH / 2.0, top, &text, FontWeight::Regular, 10.0, Align::Center);
+        }
+        if let Some(vatin) = organization.vatin.as_deref().filter(|s| !s.is_empty()) {
+            let text = format!("{} {}", self.locale.t("VATIN", None), vatin);
+            self.doc.text(RIGHT, top, &text, FontWeight::Regular, 10.0, Align::Right);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use chrono::{Local, TimeZone};
+
+use crate::money::Money;
+
+// The app's own catalogs, so the PDF reads the same as the invoice screen
+const CATALOGS: &[(&str, &str)] = &[
+    ("de", include_str!("../../../src/locales/de.po")),
+    ("el", include_str!("../../../src/locales/el.po")),
+    ("en", include_str!("../../../src/locales/en.po")),
+    ("en-GB", include_str!("../../../src/locales/en-GB.po")),
+    ("et", include_str!("../../../src/locales/et.po")),
+    ("fi", include_str!("../../../src/locales/fi.po")),
+    ("fr", include_str!("../../../src/locales/fr.po")),
+    ("nl", include_str!("../../../src/locales/nl.po")),
+    ("pt", include_str!("../../../src/locales/pt.po")),
+    ("sv", include_str!("../../../src/locales/sv.po")),
+];
+
+pub struct Locale {
+    code: &'static str,
+    catalog: &'static str,
+}
+
+impl Locale {
+    // Picks the exact locale, then its language, then English
+    pub fn new(locale: &str) -> Self {
+        let language = locale.split(['-', '_']).next().unwrap_or_default();
+        let (code, catalog) = CATALOGS
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(&locale.replace('_', "-")))
+            .or_else(|| CATALOGS.iter().find(|(code, _)| code.eq_ignore_ascii_case(language)))
+            .or_else(|| CATALOGS.iter().find(|(code, _)| *code == "en"))
+            .copied()
+            .unwrap_or(("en", ""));
+
+        Self { code, catalog }
+    }
+
+    fn language(&self) -> &'static str {
+        self.code.split('-').next().unwrap_or(self.code)
+    }
+
+    // Looks up a message in the .po catalog, falling back to the message id.
+    // `{0}` is replaced with the argument.
+    pub fn t(&self, msgid: &str, arg: Option<&str>) -> String {
+        let message = translate(self.catalog, msgid).unwrap_or_else(|| msgid.to_string());
+        match arg {
+            Some(arg) => message.replace("{0}", arg),
+            None => message,
+        }
+    }
+
+    // Amount as a currency string, similar to Intl.NumberFormat with style
+    // "currency" and the organization's minimum fraction digits. Never shows
+    // more decimals than the currency has, none for JPY and three for BHD.
+    pub fn format_money(&self, money: &Money, minimum_fraction_digits: Option<i64>) -> String {
+        let fraction_digits = money.exponent() as usize;
+        let minimum_fraction_digits = (minimum_fraction_digits.unwrap_or(2).max(0) as usize).min(fraction_digits);
+        let number = self.format_decimal(money.to_major().abs(), fraction_digits, minimum_fraction_digits);
+        let sign = if money.amount_minor < 0 { "-" } else { "" };
+        let symbol = currency_symbol(&money.currency);
+
+        match self.language() {
+            "en" => format!("{}{}{}", sign, symbol, number),
+            "nl" => format!("{} {}{}", symbol, sign, number),
+            _ => format!("{}{}\u{a0}{}", sign, number, symbol),
+        }
+    }
+
+    pub fn format_quantity(&self, quantity: f64) -> String {
+        let sign = if quantity < 0.0 { "-" } else { "" };
+        format!("{}{}", sign, self.format_decimal(quantity.abs(), 4, 0))
+    }
+
+    fn format_decimal(&self, value: f64, fraction_digits: usize, minimum_fraction_digits: usize) -> String {
+        let (group, decimal) = match self.language() {
+            "en" => (",", "."),
+            "de" | "nl" | "pt" | "el" => (".", ","),
+            _ => ("\u{a0}", ","),
+        };
+
+        let formatted = format!("{:.*}", fraction_digits, value);
+        let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+        let mut fraction = fraction;
+        while fraction.len() > minimum_fraction_digits && fraction.ends_with('0') {
+            fraction = &fraction[..fraction.len() - 1];
+        }
+
+        let mut grouped = String::new();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                grouped.push_str(group);
+            }
+            grouped.push(digit);
+        }
+
+        if fraction.is_empty() {
+            grouped
+        } else {
+            format!("{}{}{}", grouped, decimal, fraction)
+        }
+    }
+
+    // Timestamp in milliseconds formatted with the organization's date format
+    // (DD.MM.YYYY style tokens) or the locale's default
+    pub fn format_date(&self, timestamp: i64, date_format: Option<&str>) -> String {
+        let Some(date) = Local.timestamp_millis_opt(timestamp).single() else {
+            return String::new();
+        };
+
+        let format = match date_format {
+            Some(format) if !format.is_empty() && format != "AUTO" => format,
+            _ => match self.code {
+                "en" => "MM/DD/YYYY",
+                "en-GB" | "fr" | "pt" | "el" => "DD/MM/YYYY",
+                "nl" => "DD-MM-YYYY",
+                "sv" => "YYYY-MM-DD",
+                _ => "DD.MM.YYYY",
+            },
+        };
+
+        let pattern = format.replace("YYYY", "%Y").replace("MM", "%m").replace("DD", "%d");
+        date.format(&pattern).to_string()
+    }
+}
+
+fn currency_symbol(currency: &str) -> &str {
+    match currency {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "INR" => "₹",
+        _ => currency,
+    }
+}
+
+fn unquote(line: &str) -> Option<String> {
+    let inner = line.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some(inner.replace("\\\"", "\"").replace("\\n", "\n").replace("\\\\", "\\"))
+}
+
+// Minimal .po reader: finds the msgid and joins its (possibly multi-line)
+// msgstr. Untranslated entries return None.
+fn translate(catalog: &str, msgid: &str) -> Option<String> {
+    let mut lines = catalog.lines();
+    while let Some(line) = lines.next() {
+        let Some(id) = line.strip_prefix("msgid ").and_then(unquote) else {
+            continue;
+        };
+        if id != msgid {
+            continue;
+        }
+
+        let mut message = lines.next()?.strip_prefix("msgstr ").and_then(unquote)?;
+        for line in lines.by_ref() {
+            match unquote(line) {
+                Some(continuation) => message.push_str(&continuation),
+                None => break,
+            }
+        }
+
+        return (!message.is_empty()).then_some(message);
+    }
+    None
+}
@@ -0,0 +1,149 @@
+use base64::Engine;
+use pdf_writer::{Filter, Name, Pdf, Ref};
+
+enum LogoData {
+    // Already DCT encoded, embedded as is
+    Jpeg { data: Vec<u8>, components: u8 },
+    // Decoded 8-bit samples with an optional alpha channel
+    Raw { color: Vec<u8>, alpha: Option<Vec<u8>>, components: u8 },
+}
+
+pub struct Logo {
+    pub width: u32,
+    pub height: u32,
+    data: LogoData,
+}
+
+impl Logo {
+    // The organization logo is stored as the bytes of a data URL
+    // (data:image/png;base64,...) as uploaded in the invoice settings.
+    // Formats other than PNG and JPEG are left out of the PDF.
+    pub fn from_organization(logo: &[u8]) -> Option<Self> {
+        let url = std::str::from_utf8(logo).ok()?;
+        let (header, payload) = url.strip_prefix("data:")?.split_once(',')?;
+        if !header.ends_with(";base64") {
+            return None;
+        }
+        let bytes = base64::engine::general_purpose::STANDARD.decode(payload.trim()).ok()?;
+
+        match header.trim_end_matches(";base64") {
+            "image/png" => decode_png(&bytes),
+            "image/jpeg" | "image/jpg" => decode_jpeg(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn write(&self, pdf: &mut Pdf, image_id: Ref, next_id: &mut impl FnMut() -> Ref) {
+        let color_space = |components: u8| match components {
+            1 => Name(b"DeviceGray"),
+            4 => Name(b"DeviceCMYK"),
+            _ => Name(b"DeviceRGB"),
+        };
+
+        match &self.data {
+            LogoData::Jpeg { data, components } => {
+                let mut image = pdf.image_xobject(image_id, data);
+                image.filter(Filter::DctDecode);
+                image.width(self.width as i32)
+                    .height(self.height as i32)
+                    .color_space_name(color_space(*components))
+                    .bits_per_component(8);
+                // Adobe CMYK JPEGs are stored inverted
+                if *components == 4 {
+                    image.decode([1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+                }
+            }
+            LogoData::Raw { color, alpha, components } => {
+                let mask_id = alpha.as_ref().map(|alpha| {
+                    let mask_id = next_id();
+                    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(alpha, 6);
+                    let mut mask = pdf.image_xobject(mask_id, &compressed);
+                    mask.filter(Filter::FlateDecode);
+                    mask.width(self.width as i32)
+                        .height(self.height as i32)
+                        .color_space_name(Name(b"DeviceGray"))
+                        .bits_per_component(8);
+                    mask_id
+                });
+
+                let compressed = miniz_oxide::deflate::compress_to_vec_zlib(color, 6);
+                let mut image = pdf.image_xobject(image_id, &compressed);
+                image.filter(Filter::FlateDecode);
+                image.width(self.width as i32)
+                    .height(self.height as i32)
+                    .color_space_name(color_space(*components))
+                    .bits_per_component(8);
+                if let Some(mask_id) = mask_id {
+                    image.s_mask(mask_id);
+                }
+            }
+        }
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Option<Logo> {
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).ok()?;
+    let samples = &buffer[..info.buffer_size()];
+
+    let (components, has_alpha) = match info.color_type {
+        png::ColorType::Grayscale => (1, false),
+        png::ColorType::GrayscaleAlpha => (1, true),
+        png::ColorType::Rgb => (3, false),
+        png::ColorType::Rgba => (3, true),
+        // Palettes are expanded by normalize_to_color8
+        png::ColorType::Indexed => return None,
+    };
+
+    let (color, alpha) = if has_alpha {
+        let stride = components + 1;
+        let mut color = Vec::with_capacity(samples.len() / stride * components);
+        let mut alpha = Vec::with_capacity(samples.len() / stride);
+        for pixel in samples.chunks_exact(stride) {
+            color.extend_from_slice(&pixel[..components]);
+            alpha.push(pixel[components]);
+        }
+        (color, Some(alpha))
+    } else {
+        (samples.to_vec(), None)
+    };
+
+    Some(Logo {
+        width: info.width,
+        height: info.height,
+        data: LogoData::Raw { color, alpha, components: components as u8 },
+    })
+}
+
+// Reads the frame header for the dimensions, the image data itself is
+// passed through to the PDF
+fn decode_jpeg(bytes: Vec<u8>) -> Option<Logo> {
+    let mut i = 2;
+    if bytes.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    while i + 4 <= bytes.len() {
+        if bytes[i] != 0xFF {
+            return None;
+        }
+        let marker = bytes[i + 1];
+        let length = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+
+        // SOF0..SOF15 except DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let frame = bytes.get(i + 4..i + 2 + length)?;
+            let height = u16::from_be_bytes([*frame.get(1)?, *frame.get(2)?]) as u32;
+            let width = u16::from_be_bytes([*frame.get(3)?, *frame.get(4)?]) as u32;
+            let components = *frame.get(5)?;
+            return Some(Logo { width, height, data: LogoData::Jpeg { data: bytes, components } });
+        }
+
+        i += 2 + length;
+    }
+
+    None
+}
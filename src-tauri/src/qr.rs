@@ -0,0 +1,86 @@
+// Payment QR codes for invoices, as SVG and PNG images for the webview and
+// drawn straight into the native PDF
+
+pub mod epc;
+pub mod swiss;
+
+use base64::Engine;
+use qrcode::{Color, QrCode};
+use serde::{Deserialize, Serialize};
+
+// Light margin around the code, in modules
+pub const QUIET_ZONE: usize = 4;
+// PNG pixels per module
+const PNG_SCALE: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QrImageFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QrImage {
+    // The encoded text, for display next to the code
+    pub payload: String,
+    // SVG markup or a PNG data URL
+    pub image: String,
+}
+
+// Positions of the dark modules, without the quiet zone
+pub fn dark_modules(code: &QrCode) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let width = code.width();
+    (0..width * width)
+        .map(move |i| (i % width, i / width))
+        .filter(move |&(x, y)| code[(x, y)] == Color::Dark)
+}
+
+// Path data with one unit per module and the quiet zone included
+pub fn svg_path(code: &QrCode) -> String {
+    dark_modules(code)
+        .map(|(x, y)| format!("M{} {}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE))
+        .collect()
+}
+
+pub fn svg(code: &QrCode) -> String {
+    let size = code.width() + 2 * QUIET_ZONE;
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {size} {size}" shape-rendering="crispEdges"><rect width="{size}" height="{size}" fill="#fff"/><path fill="#000" d="{path}"/></svg>"##,
+        size = size,
+        path = svg_path(code),
+    )
+}
+
+pub fn png(code: &QrCode) -> Vec<u8> {
+    let size = (code.width() + 2 * QUIET_ZONE) * PNG_SCALE;
+    let mut pixels = vec![0xff; size * size];
+    for (x, y) in dark_modules(code) {
+        for row in 0..PNG_SCALE {
+            let start = ((y + QUIET_ZONE) * PNG_SCALE + row) * size + (x + QUIET_ZONE) * PNG_SCALE;
+            pixels[start..start + PNG_SCALE].fill(0);
+        }
+    }
+
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .expect("writing to memory doesn't fail");
+    data
+}
+
+pub fn image(payload: String, code: &QrCode, format: QrImageFormat) -> QrImage {
+    let image = match format {
+        QrImageFormat::Svg => svg(code),
+        QrImageFormat::Png => format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png(code))
+        ),
+    };
+    QrImage { payload, image }
+}
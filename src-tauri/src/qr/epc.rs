@@ -0,0 +1,126 @@
+// EPC069-12 "GiroCode" QR codes for SEPA credit transfers. Banking apps that
+// scan the code prefill a payment of the outstanding balance to the
+// organization's account.
+
+use qrcode::{EcLevel, QrCode};
+
+use super::{image, QrImage, QrImageFormat};
+use crate::db::{Database, Invoice, InvoiceKind, Organization};
+use crate::reference::is_creditor_reference;
+use crate::validation::{validate_bic, validate_iban};
+
+// Field and payload limits from the guidelines
+const MAX_NAME: usize = 70;
+const MAX_REMITTANCE: usize = 140;
+const MAX_PAYLOAD: usize = 331;
+const MAX_AMOUNT: i64 = 99_999_999_999;  // Cents
+
+#[derive(Debug)]
+pub struct EpcPayment {
+    pub bic: Option<String>,
+    pub name: String,
+    pub iban: String,
+    pub amount: i64,  // Cents
+    // Structured creditor reference, which excludes the remittance text
+    pub reference: Option<String>,
+    pub remittance: Option<String>,
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    text.trim().chars().take(max).collect()
+}
+
+impl EpcPayment {
+    // Payment of the invoice's outstanding balance to the organization
+    pub fn from_invoice(invoice: &Invoice, organization: &Organization) -> Result<Self, String> {
+        if invoice.kind == InvoiceKind::CreditNote {
+            return Err("EPC QR codes can't be used for credit notes".to_string());
+        }
+        if invoice.currency != "EUR" {
+            return Err(format!("EPC QR codes are only for EUR invoices, this invoice is in {}", invoice.currency));
+        }
+        if invoice.balance_due.amount_minor <= 0 {
+            return Err(format!("Invoice {} has no outstanding balance", invoice.number));
+        }
+        if invoice.balance_due.amount_minor > MAX_AMOUNT {
+            return Err("EPC QR codes are limited to amounts up to 999,999,999.99 EUR".to_string());
+        }
+
+        let name = organization
+            .name
+            .as_deref()
+            .filter(|name| !name.trim().is_empty())
+            .ok_or("The organization name is required for EPC QR codes")?;
+        let iban = organization
+            .iban
+            .as_deref()
+            .filter(|iban| !iban.trim().is_empty())
+            .ok_or("The organization IBAN is required for EPC QR codes")?;
+        let bic = organization
+            .bic
+            .as_deref()
+            .filter(|bic| !bic.trim().is_empty())
+            .map(validate_bic)
+            .transpose()?;
+
+        Ok(Self {
+            bic,
+            name: truncate(name, MAX_NAME),
+            iban: validate_iban(iban)?,
+            amount: invoice.balance_due.amount_minor,
+            // Only RF references go in the structured field, national ones are sent as text
+            reference: invoice.payment_reference.clone().filter(|reference| is_creditor_reference(reference)),
+            remittance: Some(truncate(
+                invoice.payment_reference.as_deref().unwrap_or(&invoice.number),
+                MAX_REMITTANCE,
+            )),
+        })
+    }
+
+    // Version 002 with UTF-8 text. Trailing empty fields are left out.
+    pub fn payload(&self) -> String {
+        let amount = format!("EUR{}.{:02}", self.amount / 100, self.amount % 100);
+        let fields = [
+            "BCD",
+            "002",
+            "1",
+            "SCT",
+            self.bic.as_deref().unwrap_or_default(),
+            &self.name,
+            &self.iban,
+            &amount,
+            // Purpose code
+            "",
+            self.reference.as_deref().unwrap_or_default(),
+            // Only one of the reference and remittance text may be given
+            if self.reference.is_some() { "" } else { self.remittance.as_deref().unwrap_or_default() },
+        ];
+        fields.join("\n").trim_end_matches('\n').to_string()
+    }
+
+    pub fn qr_code(&self) -> Result<QrCode, String> {
+        let payload = self.payload();
+        if payload.len() > MAX_PAYLOAD {
+            return Err(format!("The EPC QR code data is {} bytes, over the limit of {}", payload.len(), MAX_PAYLOAD));
+        }
+        QrCode::with_error_correction_level(payload, EcLevel::M)
+            .map_err(|e| format!("The EPC QR code can't be generated: {}", e))
+    }
+}
+
+impl Database {
+    pub async fn get_epc_payment(&self, invoice_id: &str) -> Result<EpcPayment, sqlx::Error> {
+        let invoice = self.get_invoice(invoice_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let organization = self.get_organization(&invoice.organization_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        EpcPayment::from_invoice(&invoice, &organization).map_err(sqlx::Error::Protocol)
+    }
+
+    pub async fn get_epc_qr_code(&self, invoice_id: &str, format: QrImageFormat) -> Result<QrImage, sqlx::Error> {
+        let payment = self.get_epc_payment(invoice_id).await?;
+        let code = payment.qr_code().map_err(sqlx::Error::Protocol)?;
+        Ok(image(payment.payload(), &code, format))
+    }
+}
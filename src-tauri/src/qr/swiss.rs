@@ -0,0 +1,491 @@
+// Swiss QR-bill (Swiss Payment Standards 2.3): the "SPC" payload and the
+// payment part with its receipt as an A6 landscape SVG, ready to be placed
+// at the bottom of an A4 invoice
+
+use qrcode::{EcLevel, QrCode};
+
+use super::{svg_path, QrImage, QUIET_ZONE};
+use crate::countries::country_code;
+use crate::db::{Client, Database, Invoice, InvoiceKind, Organization};
+use crate::einvoice::xml::escape;
+use crate::reference::{creditor_reference, is_creditor_reference};
+use crate::validation::validate_iban;
+
+const MAX_PAYLOAD: usize = 997;
+const MAX_AMOUNT: i64 = 99_999_999_999;  // Cents
+
+// Payment part geometry in millimetres
+const WIDTH: f32 = 210.0;
+const HEIGHT: f32 = 105.0;
+const RECEIPT_WIDTH: f32 = 62.0;
+const MARGIN: f32 = 5.0;
+const QR_X: f32 = 67.0;
+const QR_Y: f32 = 17.0;
+const QR_SIZE: f32 = 46.0;
+const CROSS_SIZE: f32 = 7.0;
+const INFORMATION_X: f32 = 118.0;
+const AMOUNT_Y: f32 = 68.0;
+
+struct Labels {
+    receipt: &'static str,
+    payment_part: &'static str,
+    account: &'static str,
+    reference: &'static str,
+    additional_information: &'static str,
+    payable_by: &'static str,
+    currency: &'static str,
+    amount: &'static str,
+    acceptance_point: &'static str,
+}
+
+// The payment part may only be printed in one of these languages
+const LABELS: &[(&str, Labels)] = &[
+    ("en", Labels {
+        receipt: "Receipt",
+        payment_part: "Payment part",
+        account: "Account / Payable to",
+        reference: "Reference",
+        additional_information: "Additional information",
+        payable_by: "Payable by",
+        currency: "Currency",
+        amount: "Amount",
+        acceptance_point: "Acceptance point",
+    }),
+    ("de", Labels {
+        receipt: "Empfangsschein",
+        payment_part: "Zahlteil",
+        account: "Konto / Zahlbar an",
+        reference: "Referenz",
+        additional_information: "Zusätzliche Informationen",
+        payable_by: "Zahlbar durch",
+        currency: "Währung",
+        amount: "Betrag",
+        acceptance_point: "Annahmestelle",
+    }),
+    ("fr", Labels {
+        receipt: "Récépissé",
+        payment_part: "Section paiement",
+        account: "Compte / Payable à",
+        reference: "Référence",
+        additional_information: "Informations supplémentaires",
+        payable_by: "Payable par",
+        currency: "Monnaie",
+        amount: "Montant",
+        acceptance_point: "Point de dépôt",
+    }),
+    ("it", Labels {
+        receipt: "Ricevuta",
+        payment_part: "Sezione pagamento",
+        account: "Conto / Pagabile a",
+        reference: "Riferimento",
+        additional_information: "Informazioni supplementari",
+        payable_by: "Pagabile da",
+        currency: "Valuta",
+        amount: "Importo",
+        acceptance_point: "Punto di accettazione",
+    }),
+];
+
+fn labels(locale: &str) -> &'static Labels {
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    LABELS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map(|(_, labels)| labels)
+        .unwrap_or(&LABELS[0].1)
+}
+
+#[derive(Debug, Clone)]
+pub struct StructuredAddress {
+    pub name: String,
+    pub street: String,
+    pub building_number: String,
+    pub postal_code: String,
+    pub town: String,
+    pub country_code: String,
+}
+
+impl StructuredAddress {
+    // Addresses are free text, so the street and building number are taken from
+    // the first line and the postal code and town from the last one
+    fn parse(party: &str, name: Option<&str>, address: Option<&str>, country: Option<&str>) -> Result<Self, String> {
+        let name = name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| format!("The {} name is required for QR-bills", party))?;
+        let country_code = country
+            .and_then(country_code)
+            .ok_or_else(|| format!("The {} country is required for QR-bills", party))?;
+
+        let unstructured = || {
+            format!(
+                "The {} address must have the street and building number on the first line and the postal code and town on the last line",
+                party
+            )
+        };
+        let lines: Vec<&str> = address.unwrap_or_default().lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        if lines.len() < 2 {
+            return Err(unstructured());
+        }
+        let (first, last) = (lines[0], lines[lines.len() - 1]);
+
+        // "8001 Zürich" or "CH-8001 Zürich"
+        let (postal_code, town) = last.split_once(char::is_whitespace).ok_or_else(unstructured)?;
+        let postal_code = postal_code.trim_start_matches(&format!("{}-", country_code));
+        if !postal_code.chars().any(|c| c.is_ascii_digit()) {
+            return Err(unstructured());
+        }
+
+        let (street, building_number) = match first.rsplit_once(' ') {
+            Some((street, number)) if number.starts_with(|c: char| c.is_ascii_digit()) => (street.trim(), number),
+            _ => (first, ""),
+        };
+
+        let address = Self {
+            name: name.to_string(),
+            street: street.to_string(),
+            building_number: building_number.to_string(),
+            postal_code: postal_code.to_string(),
+            town: town.trim().to_string(),
+            country_code: country_code.to_string(),
+        };
+        for (field, value, max) in [
+            ("name", &address.name, 70),
+            ("street", &address.street, 70),
+            ("building number", &address.building_number, 16),
+            ("postal code", &address.postal_code, 16),
+            ("town", &address.town, 35),
+        ] {
+            if value.chars().count() > max {
+                return Err(format!("The {} {} is longer than the {} characters allowed on QR-bills", party, field, max));
+            }
+        }
+
+        Ok(address)
+    }
+
+    fn fields(&self) -> [&str; 7] {
+        [
+            "S",
+            &self.name,
+            &self.street,
+            &self.building_number,
+            &self.postal_code,
+            &self.town,
+            &self.country_code,
+        ]
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let street = format!("{} {}", self.street, self.building_number);
+        let town = format!("{} {}", self.postal_code, self.town);
+        vec![self.name.clone(), street.trim().to_string(), town]
+    }
+}
+
+// QR-IBANs have an institution ID between 30000 and 31999 and can only be
+// used with QR references
+fn is_qr_iban(iban: &str) -> bool {
+    iban.get(4..9)
+        .and_then(|id| id.parse::<u32>().ok())
+        .is_some_and(|id| (30000..=31999).contains(&id))
+}
+
+fn mod10_recursive(digits: &str) -> u32 {
+    const TABLE: [u32; 10] = [0, 9, 4, 6, 8, 2, 7, 1, 3, 5];
+    let carry = digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .fold(0, |carry, digit| TABLE[((carry + digit) % 10) as usize]);
+    (10 - carry) % 10
+}
+
+// The invoice number's digits padded to 26, followed by a check digit
+fn qr_reference(number: &str) -> Result<String, String> {
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    if digits.trim_start_matches('0').is_empty() || digits.len() > 26 {
+        return Err(format!("Invoice number {} needs between 1 and 26 digits for a QR reference", number));
+    }
+    let reference = format!("{:0>26}", digits);
+    Ok(format!("{}{}", reference, mod10_recursive(&reference)))
+}
+
+// Splits into blocks of `size` characters, counting from the end when
+// `from_end` is set (as QR references are printed)
+fn blocks(text: &str, size: usize, from_end: bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let offset = if from_end { chars.len() % size } else { 0 };
+    let mut blocks: Vec<String> = Vec::new();
+    if offset > 0 {
+        blocks.push(chars[..offset].iter().collect());
+    }
+    blocks.extend(chars[offset..].chunks(size).map(|block| block.iter().collect()));
+    blocks.join(" ")
+}
+
+fn format_amount(cents: i64) -> String {
+    let units = blocks(&(cents / 100).to_string(), 3, true);
+    format!("{}.{:02}", units, cents % 100)
+}
+
+fn collect<T>(errors: &mut Vec<String>, result: Result<T, String>) -> Option<T> {
+    result.map_err(|error| errors.push(error)).ok()
+}
+
+#[derive(Debug)]
+pub struct SwissQrBill {
+    pub iban: String,
+    pub creditor: StructuredAddress,
+    pub amount: i64,  // Cents
+    pub currency: String,
+    pub debtor: StructuredAddress,
+    // "QRR" or "SCOR"
+    pub reference_type: &'static str,
+    pub reference: String,
+    pub message: String,
+}
+
+impl SwissQrBill {
+    // Bill for the invoice's outstanding balance, or every reason it can't be made
+    pub fn from_invoice(invoice: &Invoice, organization: &Organization, client: &Client) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+
+        if invoice.kind == InvoiceKind::CreditNote {
+            errors.push("QR-bills can't be used for credit notes".to_string());
+        }
+        if invoice.currency != "CHF" && invoice.currency != "EUR" {
+            errors.push(format!("QR-bills are only for CHF and EUR invoices, this invoice is in {}", invoice.currency));
+        }
+        if invoice.balance_due.amount_minor <= 0 {
+            errors.push(format!("Invoice {} has no outstanding balance", invoice.number));
+        } else if invoice.balance_due.amount_minor > MAX_AMOUNT {
+            errors.push("QR-bills are limited to amounts up to 999,999,999.99".to_string());
+        }
+
+        let iban = organization
+            .iban
+            .as_deref()
+            .filter(|iban| !iban.trim().is_empty())
+            .ok_or_else(|| "The organization IBAN is required for QR-bills".to_string())
+            .and_then(validate_iban)
+            .and_then(|iban| match &iban[..2] {
+                "CH" | "LI" => Ok(iban),
+                _ => Err(format!("QR-bills need a Swiss or Liechtenstein IBAN, {} is not one", iban)),
+            });
+        let iban = collect(&mut errors, iban);
+
+        let creditor = StructuredAddress::parse(
+            "organization",
+            organization.name.as_deref(),
+            organization.address.as_deref(),
+            organization.country.as_deref(),
+        );
+        let creditor = collect(&mut errors, creditor);
+        let debtor = StructuredAddress::parse("client", client.name.as_deref(), client.address.as_deref(), client.country.as_deref());
+        let debtor = collect(&mut errors, debtor);
+
+        let reference = iban.as_deref().and_then(|iban| {
+            let reference = if is_qr_iban(iban) {
+                qr_reference(&invoice.number).map(|reference| ("QRR", reference))
+            } else {
+                // The invoice's own reference when it's already an RF one
+                match invoice.payment_reference.as_deref().filter(|reference| is_creditor_reference(reference)) {
+                    Some(reference) => Ok(reference.to_string()),
+                    None => creditor_reference(&invoice.number),
+                }
+                .map(|reference| ("SCOR", reference))
+            };
+            collect(&mut errors, reference)
+        });
+
+        match (iban, creditor, debtor, reference) {
+            (Some(iban), Some(creditor), Some(debtor), Some((reference_type, reference))) if errors.is_empty() => Ok(Self {
+                iban,
+                creditor,
+                amount: invoice.balance_due.amount_minor,
+                currency: invoice.currency.clone(),
+                debtor,
+                reference_type,
+                reference,
+                message: invoice.number.chars().take(140).collect(),
+            }),
+            _ => Err(errors),
+        }
+    }
+
+    pub fn payload(&self) -> String {
+        let amount = format!("{}.{:02}", self.amount / 100, self.amount % 100);
+        let mut fields: Vec<&str> = vec!["SPC", "0200", "1", &self.iban];
+        fields.extend(self.creditor.fields());
+        // Ultimate creditor, reserved for future use
+        fields.extend([""; 7]);
+        fields.extend([amount.as_str(), &self.currency]);
+        fields.extend(self.debtor.fields());
+        fields.extend([self.reference_type, &self.reference, &self.message, "EPD"]);
+        fields.join("\n")
+    }
+
+    pub fn qr_code(&self) -> Result<QrCode, String> {
+        let payload = self.payload();
+        if payload.chars().count() > MAX_PAYLOAD {
+            return Err(format!("The QR-bill data is {} characters, over the limit of {}", payload.chars().count(), MAX_PAYLOAD));
+        }
+        QrCode::with_error_correction_level(payload, EcLevel::M).map_err(|e| format!("The QR-bill code can't be generated: {}", e))
+    }
+
+    fn formatted_reference(&self) -> String {
+        match self.reference_type {
+            "QRR" => blocks(&self.reference, 5, true),
+            _ => blocks(&self.reference, 4, false),
+        }
+    }
+
+    // Receipt on the left and payment part on the right, with the perforation
+    // lines drawn for printing on plain paper
+    pub fn svg(&self, locale: &str) -> Result<String, String> {
+        let code = self.qr_code()?;
+        let labels = labels(locale);
+        let mut svg = Svg::default();
+
+        svg.out.push_str(&format!(
+            r##"<line x1="0" y1="0.1" x2="{width}" y2="0.1" stroke="#000" stroke-width="0.2" stroke-dasharray="1 0.5"/><line x1="{receipt}" y1="0" x2="{receipt}" y2="{height}" stroke="#000" stroke-width="0.2" stroke-dasharray="1 0.5"/>"##,
+            width = WIDTH,
+            height = HEIGHT,
+            receipt = RECEIPT_WIDTH,
+        ));
+
+        let iban = blocks(&self.iban, 4, false);
+        let reference = self.formatted_reference();
+        let account = || {
+            let mut lines = vec![iban.clone()];
+            lines.extend(self.creditor.lines());
+            lines
+        };
+        let amount = format_amount(self.amount);
+
+        // Receipt
+        svg.text(MARGIN, MARGIN, 11.0, true, labels.receipt, false);
+        let mut y = 12.0;
+        svg.section(MARGIN, &mut y, Font::RECEIPT, labels.account, &account());
+        svg.section(MARGIN, &mut y, Font::RECEIPT, labels.reference, std::slice::from_ref(&reference));
+        svg.section(MARGIN, &mut y, Font::RECEIPT, labels.payable_by, &self.debtor.lines());
+        svg.amount(MARGIN, MARGIN + 13.0, Font::RECEIPT, labels, &self.currency, &amount);
+        svg.text(RECEIPT_WIDTH - MARGIN, 82.0, 6.0, true, labels.acceptance_point, true);
+
+        // Payment part
+        svg.text(QR_X, MARGIN, 11.0, true, labels.payment_part, false);
+        svg.qr_code(&code);
+        svg.amount(QR_X, QR_X + 22.0, Font::PAYMENT_PART, labels, &self.currency, &amount);
+        let mut y = MARGIN;
+        svg.section(INFORMATION_X, &mut y, Font::PAYMENT_PART, labels.account, &account());
+        svg.section(INFORMATION_X, &mut y, Font::PAYMENT_PART, labels.reference, &[reference]);
+        svg.section(INFORMATION_X, &mut y, Font::PAYMENT_PART, labels.additional_information, std::slice::from_ref(&self.message));
+        svg.section(INFORMATION_X, &mut y, Font::PAYMENT_PART, labels.payable_by, &self.debtor.lines());
+
+        Ok(format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}" font-family="Arial, Helvetica, sans-serif"><rect width="{width}" height="{height}" fill="#fff"/>{content}</svg>"##,
+            width = WIDTH,
+            height = HEIGHT,
+            content = svg.out,
+        ))
+    }
+}
+
+// Heading and value sizes in points, with the line spacing used for both
+#[derive(Clone, Copy)]
+struct Font {
+    heading: f32,
+    value: f32,
+    line: f32,
+}
+
+impl Font {
+    const RECEIPT: Self = Self { heading: 6.0, value: 8.0, line: 9.0 };
+    const PAYMENT_PART: Self = Self { heading: 8.0, value: 10.0, line: 11.0 };
+}
+
+// Points to millimetres
+fn mm(points: f32) -> f32 {
+    points * 25.4 / 72.0
+}
+
+#[derive(Default)]
+struct Svg {
+    out: String,
+}
+
+impl Svg {
+    // A line of text with its top at `top`, right aligned at `x` when `end` is set
+    fn text(&mut self, x: f32, top: f32, size: f32, bold: bool, text: &str, end: bool) {
+        self.out.push_str(&format!(
+            r#"<text x="{:.2}" y="{:.2}" font-size="{:.3}"{}{}>{}</text>"#,
+            x,
+            top + mm(size) * 0.8,
+            mm(size),
+            if bold { r#" font-weight="bold""# } else { "" },
+            if end { r#" text-anchor="end""# } else { "" },
+            escape(text),
+        ));
+    }
+
+    fn section(&mut self, x: f32, y: &mut f32, font: Font, heading: &str, lines: &[String]) {
+        self.text(x, *y, font.heading, true, heading, false);
+        *y += mm(font.line);
+        for line in lines {
+            self.text(x, *y, font.value, false, line, false);
+            *y += mm(font.line);
+        }
+        *y += mm(font.line);
+    }
+
+    fn amount(&mut self, x: f32, amount_x: f32, font: Font, labels: &Labels, currency: &str, amount: &str) {
+        self.text(x, AMOUNT_Y, font.heading, true, labels.currency, false);
+        self.text(amount_x, AMOUNT_Y, font.heading, true, labels.amount, false);
+        self.text(x, AMOUNT_Y + mm(font.line), font.value, false, currency, false);
+        self.text(amount_x, AMOUNT_Y + mm(font.line), font.value, false, amount, false);
+    }
+
+    // The code without its quiet zone, with the Swiss cross on top
+    fn qr_code(&mut self, code: &QrCode) {
+        let module = QR_SIZE / code.width() as f32;
+        self.out.push_str(&format!(
+            r##"<g transform="translate({} {}) scale({}) translate(-{offset} -{offset})"><path fill="#000" d="{}"/></g>"##,
+            QR_X,
+            QR_Y,
+            module,
+            svg_path(code),
+            offset = QUIET_ZONE,
+        ));
+
+        // Drawn on a 19.8 unit grid: white outline, black square, white cross
+        let x = QR_X + (QR_SIZE - CROSS_SIZE) / 2.0;
+        let y = QR_Y + (QR_SIZE - CROSS_SIZE) / 2.0;
+        self.out.push_str(&format!(
+            r##"<g transform="translate({} {}) scale({})"><rect width="19.8" height="19.8" fill="#fff"/><rect x="0.7" y="0.7" width="18.4" height="18.4" fill="#000"/><rect x="8.3" y="4" width="3.3" height="11" fill="#fff"/><rect x="4.4" y="7.9" width="11" height="3.3" fill="#fff"/></g>"##,
+            x,
+            y,
+            CROSS_SIZE / 19.8,
+        ));
+    }
+}
+
+impl Database {
+    pub async fn get_swiss_qr_bill(&self, invoice_id: &str) -> Result<SwissQrBill, sqlx::Error> {
+        let invoice = self.get_invoice(invoice_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let organization = self.get_organization(&invoice.organization_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        let client = self.get_client(&invoice.client_id).await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+
+        SwissQrBill::from_invoice(&invoice, &organization, &client).map_err(|errors| {
+            sqlx::Error::Protocol(format!("The QR-bill can't be generated:\n{}", errors.join("\n")))
+        })
+    }
+
+    pub async fn get_swiss_qr_bill_svg(&self, invoice_id: &str, locale: &str) -> Result<QrImage, sqlx::Error> {
+        let bill = self.get_swiss_qr_bill(invoice_id).await?;
+        let svg = bill.svg(locale).map_err(sqlx::Error::Protocol)?;
+        Ok(QrImage { payload: bill.payload(), image: svg })
+    }
+}
@@ -0,0 +1,106 @@
+// Structured creditor references derived from invoice numbers, so incoming
+// payments can be matched back to their invoice
+
+use serde::{Deserialize, Serialize};
+
+use crate::validation::mod97;
+
+// Longest reference that fits after "RF" and the check digits
+const MAX_CREDITOR_REFERENCE: usize = 21;
+
+// ISO 11649 "RF" creditor reference over the letters and digits of `text`
+pub fn creditor_reference(text: &str) -> Result<String, String> {
+    let reference: String = text.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_uppercase();
+    if reference.is_empty() {
+        return Err(format!("{} has no letters or digits for a creditor reference", text));
+    }
+    if reference.len() > MAX_CREDITOR_REFERENCE {
+        return Err(format!(
+            "{} is too long for a creditor reference, which allows {} letters and digits",
+            text, MAX_CREDITOR_REFERENCE
+        ));
+    }
+
+    let check_digits = 98 - mod97(&format!("{}RF00", reference));
+    Ok(format!("RF{:02}{}", check_digits, reference))
+}
+
+// Finnish and Estonian references share the 7-3-1 check digit, calculated
+// over the invoice number's digits from right to left
+fn national_reference(text: &str, min_digits: usize, name: &str) -> Result<String, String> {
+    let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Err(format!("{} has no digits for a {} reference", text, name));
+    }
+    if digits.len() > 19 {
+        return Err(format!("{} has more than the 19 digits allowed in a {} reference", text, name));
+    }
+
+    let digits = format!("{:0>width$}", digits, width = min_digits);
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .zip([7, 3, 1].into_iter().cycle())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    Ok(format!("{}{}", digits, (10 - sum % 10) % 10))
+}
+
+// Set per organization, new invoices store the reference derived from their number
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentReferenceFormat {
+    #[default]
+    None,
+    Rf,
+    // Viitenumero
+    Finnish,
+    // Viitenumber
+    Estonian,
+}
+
+impl PaymentReferenceFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, sqlx::Error> {
+        match value.unwrap_or("none") {
+            "none" => Ok(Self::None),
+            "rf" => Ok(Self::Rf),
+            "finnish" => Ok(Self::Finnish),
+            "estonian" => Ok(Self::Estonian),
+            other => Err(sqlx::Error::Protocol(format!("Invalid payment reference format: {}", other))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Rf => "rf",
+            Self::Finnish => "finnish",
+            Self::Estonian => "estonian",
+        }
+    }
+
+    pub fn derive(&self, invoice_number: &str) -> Result<Option<String>, String> {
+        match self {
+            Self::None => Ok(None),
+            Self::Rf => creditor_reference(invoice_number).map(Some),
+            // At least three digits before the check digit
+            Self::Finnish => national_reference(invoice_number, 3, "Finnish").map(Some),
+            Self::Estonian => national_reference(invoice_number, 1, "Estonian").map(Some),
+        }
+    }
+}
+
+// As compared when matching payments: no spaces and upper case
+pub fn normalize_reference(reference: &str) -> String {
+    reference.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase()
+}
+
+pub fn is_creditor_reference(reference: &str) -> bool {
+    let reference = normalize_reference(reference);
+    reference.len() > 4
+        && reference.starts_with("RF")
+        && reference.chars().all(|c| c.is_ascii_alphanumeric())
+        && mod97(&format!("{}{}", &reference[4..], &reference[..4])) == 1
+}
@@ -0,0 +1,41 @@
+// Units of measure offered for line items (src/utils/units.ts) as UN/ECE
+// Recommendation 20 codes, which e-invoices carry as they are. Labels are
+// printed after quantities on PDFs and translated like other PDF text.
+const UNITS: &[(&str, &str)] = &[
+    ("C62", "unit"),
+    ("H87", "pcs"),
+    ("SET", "set"),
+    ("LS", "lump sum"),
+    ("MIN", "min"),
+    ("HUR", "h"),
+    ("DAY", "day"),
+    ("WEE", "week"),
+    ("MON", "month"),
+    ("ANN", "year"),
+    ("MTR", "m"),
+    ("KMT", "km"),
+    ("MTK", "m²"),
+    ("KGM", "kg"),
+    ("LTR", "l"),
+];
+
+// The code line items without a unit are sent with, "one"
+pub const DEFAULT_UNIT_CODE: &str = "C62";
+
+pub fn unit_label(code: &str) -> Option<&'static str> {
+    UNITS
+        .iter()
+        .find(|(unit_code, _)| unit_code.eq_ignore_ascii_case(code.trim()))
+        .map(|(_, label)| *label)
+}
+
+// Recommendation 20 codes are two or three letters and digits. Codes that
+// aren't in the list above are allowed, e.g. from received e-invoices.
+// Returns the code upper-cased.
+pub fn validate_unit_code(code: &str) -> Result<String, String> {
+    let code = code.trim().to_ascii_uppercase();
+    if !(2..=3).contains(&code.len()) || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("{} isn't a UN/ECE Recommendation 20 unit code", code));
+    }
+    Ok(code)
+}
@@ -0,0 +1,618 @@
+// Offline checks for bank details and VAT numbers entered as free text
+
+use serde::Serialize;
+
+use crate::countries::{country_code, country_name};
+
+// IBAN lengths by country, from the SWIFT IBAN registry
+const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AD", 24), ("AE", 23), ("AL", 28), ("AT", 20), ("AZ", 28), ("BA", 20), ("BE", 16), ("BG", 22),
+    ("BH", 22), ("BI", 27), ("BR", 29), ("BY", 28), ("CH", 21), ("CR", 22), ("CY", 28), ("CZ", 24),
+    ("DE", 22), ("DJ", 27), ("DK", 18), ("DO", 28), ("EE", 20), ("EG", 29), ("ES", 24), ("FI", 18),
+    ("FK", 18), ("FO", 18), ("FR", 27), ("GB", 22), ("GE", 22), ("GI", 23), ("GL", 18), ("GR", 27),
+    ("GT", 28), ("HN", 28), ("HR", 21), ("HU", 28), ("IE", 22), ("IL", 23), ("IQ", 23), ("IS", 26),
+    ("IT", 27), ("JO", 30), ("KW", 30), ("KZ", 20), ("LB", 28), ("LC", 32), ("LI", 21), ("LT", 20),
+    ("LU", 20), ("LV", 21), ("LY", 25), ("MC", 27), ("MD", 24), ("ME", 22), ("MK", 19), ("MN", 20),
+    ("MR", 27), ("MT", 31), ("MU", 30), ("NI", 28), ("NL", 18), ("NO", 15), ("OM", 23), ("PK", 24),
+    ("PL", 28), ("PS", 29), ("PT", 25), ("QA", 29), ("RO", 24), ("RS", 22), ("RU", 33), ("SA", 24),
+    ("SC", 31), ("SD", 18), ("SE", 24), ("SI", 19), ("SK", 24), ("SM", 27), ("SO", 23), ("ST", 25),
+    ("SV", 28), ("TL", 23), ("TN", 24), ("TR", 26), ("UA", 29), ("VA", 22), ("VG", 24), ("XK", 20),
+    ("YE", 30),
+];
+
+// Electronic format: no spaces, upper case
+pub fn normalize_iban(iban: &str) -> String {
+    iban.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase()
+}
+
+// ISO 7064 MOD 97-10 with letters counted as 10 to 35, as used by IBANs and
+// RF creditor references
+pub fn mod97(text: &str) -> u32 {
+    text.chars().fold(0, |remainder, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        let shift = if value < 10 { 10 } else { 100 };
+        (remainder * shift + value) % 97
+    })
+}
+
+// Returns the IBAN in its electronic format
+pub fn validate_iban(iban: &str) -> Result<String, String> {
+    let iban = normalize_iban(iban);
+    if iban.len() < 5 || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("IBAN {} is not valid, it should start with a country code and contain only letters and digits", iban));
+    }
+
+    let country = &iban[..2];
+    let Some((_, length)) = IBAN_LENGTHS.iter().find(|(code, _)| *code == country) else {
+        return Err(format!("IBAN {} has an unknown country code {}", iban, country));
+    };
+    if iban.len() != *length {
+        return Err(format!("IBAN {} should be {} characters long for {}", iban, length, country));
+    }
+    if !iban[2..4].chars().all(|c| c.is_ascii_digit()) || mod97(&format!("{}{}", &iban[4..], &iban[..4])) != 1 {
+        return Err(format!("IBAN {} has an invalid checksum, check it for typos", iban));
+    }
+
+    Ok(iban)
+}
+
+// ISO 9362: bank code, country code, location and an optional branch code.
+// Returns the BIC upper-cased and without spaces.
+pub fn validate_bic(bic: &str) -> Result<String, String> {
+    let bic = normalize_iban(bic);
+    if bic.len() != 8 && bic.len() != 11 {
+        return Err(format!("BIC {} should be 8 or 11 characters long", bic));
+    }
+    if !bic.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("BIC {} should contain only letters and digits", bic));
+    }
+    if !bic[..4].chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!("BIC {} should start with a four letter bank code", bic));
+    }
+
+    let country = &bic[4..6];
+    if country_name(country).is_none() {
+        return Err(format!("BIC {} has an unknown country code {}", bic, country));
+    }
+
+    Ok(bic)
+}
+
+// Prefixes of VAT numbers in the EU VIES system. Greece uses EL and Northern
+// Ireland XI instead of their ISO codes.
+const VAT_PREFIXES: &[&str] = &[
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "EL", "ES", "FI", "FR", "HR", "HU", "IE",
+    "IT", "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK", "XI",
+];
+
+enum VatError {
+    // Expected shape of the national number
+    Format(&'static str),
+    Checksum,
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+fn weighted_sum(digits: &[u32], weights: &[u32]) -> u32 {
+    digits.iter().zip(weights).map(|(digit, weight)| digit * weight).sum()
+}
+
+fn digit_sum(value: u32) -> u32 {
+    value / 10 + value % 10
+}
+
+fn luhn(text: &str) -> bool {
+    let sum: u32 = digits(text)
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| if i % 2 == 1 { digit_sum(digit * 2) } else { digit })
+        .sum();
+    sum % 10 == 0
+}
+
+// ISO 7064 MOD 11,10 check digit
+fn mod11_10(digits: &[u32]) -> u32 {
+    let product = digits.iter().fold(10, |product, digit| {
+        let sum = match (product + digit) % 10 {
+            0 => 10,
+            sum => sum,
+        };
+        (sum * 2) % 11
+    });
+    (11 - product) % 10
+}
+
+fn check(valid: bool) -> Result<(), VatError> {
+    if valid { Ok(()) } else { Err(VatError::Checksum) }
+}
+
+fn parse_u64(text: &str) -> u64 {
+    text.parse().unwrap_or_default()
+}
+
+// National number formats and check digits as published for VIES
+fn check_vat_number(prefix: &str, number: &str) -> Result<(), VatError> {
+    let len = number.len();
+    let all_digits = is_digits(number);
+    let d = digits(number);
+
+    match prefix {
+        "AT" => {
+            if len != 9 || !number.starts_with('U') || !is_digits(&number[1..]) {
+                return Err(VatError::Format("U followed by 8 digits"));
+            }
+            let d = digits(&number[1..]);
+            let sum: u32 = d[..7]
+                .iter()
+                .enumerate()
+                .map(|(i, &digit)| if i % 2 == 1 { digit_sum(digit * 2) } else { digit })
+                .sum();
+            check((10 - (sum + 4) % 10) % 10 == d[7])
+        }
+        "BE" => {
+            // Old numbers had 9 digits, a leading 0 was added
+            let number = if len == 9 && all_digits { format!("0{}", number) } else { number.to_string() };
+            if number.len() != 10 || !is_digits(&number) || !matches!(&number[..1], "0" | "1") {
+                return Err(VatError::Format("10 digits starting with 0 or 1"));
+            }
+            check(97 - parse_u64(&number[..8]) % 97 == parse_u64(&number[8..]))
+        }
+        "BG" => {
+            if !all_digits || (len != 9 && len != 10) {
+                return Err(VatError::Format("9 or 10 digits"));
+            }
+            if len == 9 {
+                let mut sum = weighted_sum(&d[..8], &[1, 2, 3, 4, 5, 6, 7, 8]) % 11;
+                if sum == 10 {
+                    sum = weighted_sum(&d[..8], &[3, 4, 5, 6, 7, 8, 9, 10]) % 11 % 10;
+                }
+                return check(sum == d[8]);
+            }
+            // Personal numbers of citizens, of foreigners, or other registrations
+            let citizen = weighted_sum(&d[..9], &[2, 4, 8, 5, 10, 9, 7, 3, 6]) % 11 % 10 == d[9];
+            let foreigner = weighted_sum(&d[..9], &[21, 19, 17, 13, 11, 9, 7, 3, 1]) % 10 == d[9];
+            let other = match 11 - weighted_sum(&d[..9], &[4, 3, 2, 7, 6, 5, 4, 3, 2]) % 11 {
+                11 => d[9] == 0,
+                10 => false,
+                digit => digit == d[9],
+            };
+            check(citizen || foreigner || other)
+        }
+        "CY" => {
+            let letter = number.chars().last().unwrap_or_default();
+            if len != 9 || !is_digits(&number[..8]) || !letter.is_ascii_uppercase() || number.starts_with("12") {
+                return Err(VatError::Format("8 digits followed by a letter"));
+            }
+            const ODD: [u32; 10] = [1, 0, 5, 7, 9, 13, 15, 17, 19, 21];
+            let sum: u32 = d[..8]
+                .iter()
+                .enumerate()
+                .map(|(i, &digit)| if i % 2 == 0 { ODD[digit as usize] } else { digit })
+                .sum();
+            check(char::from(b'A' + (sum % 26) as u8) == letter)
+        }
+        "CZ" => {
+            if !all_digits || !(8..=10).contains(&len) {
+                return Err(VatError::Format("8, 9 or 10 digits"));
+            }
+            match len {
+                8 => {
+                    let digit = match (11 - weighted_sum(&d[..7], &[8, 7, 6, 5, 4, 3, 2]) % 11) % 11 {
+                        0 => 1,
+                        digit => digit % 10,
+                    };
+                    check(d[0] != 9 && digit == d[7])
+                }
+                // Birth numbers, those issued before 1954 have no check digit
+                9 => Ok(()),
+                _ => check(parse_u64(number) % 11 == 0 || (parse_u64(&number[..9]) % 11 == 10 && d[9] == 0)),
+            }
+        }
+        "DE" => {
+            if len != 9 || !all_digits || d[0] == 0 {
+                return Err(VatError::Format("9 digits"));
+            }
+            check(mod11_10(&d[..8]) == d[8])
+        }
+        "DK" => {
+            if len != 8 || !all_digits || d[0] == 0 {
+                return Err(VatError::Format("8 digits"));
+            }
+            check(weighted_sum(&d, &[2, 7, 6, 5, 4, 3, 2, 1]) % 11 == 0)
+        }
+        "EE" => {
+            if len != 9 || !all_digits || !number.starts_with("10") {
+                return Err(VatError::Format("9 digits starting with 10"));
+            }
+            check(weighted_sum(&d, &[3, 7, 1, 3, 7, 1, 3, 7, 1]) % 10 == 0)
+        }
+        "EL" => {
+            if len != 9 || !all_digits {
+                return Err(VatError::Format("9 digits"));
+            }
+            let sum: u32 = d[..8].iter().enumerate().map(|(i, digit)| digit << (8 - i)).sum();
+            check(sum % 11 % 10 == d[8])
+        }
+        "ES" => {
+            let format = VatError::Format("9 characters: a letter or digit, 7 digits and a letter or digit");
+            if len != 9 || !is_digits(&number[1..8]) {
+                return Err(format);
+            }
+            const LETTERS: &[u8] = b"TRWAGMYFPDXBNJZSQVHLCKE";
+            let first = number.as_bytes()[0];
+            let last = number.as_bytes()[8];
+            let dni = |number: u64| LETTERS[(number % 23) as usize] == last;
+            match first {
+                // DNI of Spanish citizens
+                b'0'..=b'9' => check(dni(parse_u64(&number[..8]))),
+                // NIE of foreigners
+                b'X' | b'Y' | b'Z' => check(dni(u64::from(first - b'X') * 10_000_000 + parse_u64(&number[1..8]))),
+                b'K' | b'L' | b'M' => check(dni(parse_u64(&number[1..8]))),
+                // Legal entities end in a check digit or the matching letter
+                b'A'..=b'H' | b'J' | b'N' | b'P'..=b'S' | b'U'..=b'W' => {
+                    let sum: u32 = d[..7]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &digit)| if i % 2 == 0 { digit_sum(digit * 2) } else { digit })
+                        .sum();
+                    let digit = (10 - sum % 10) % 10;
+                    check(last == b'0' + digit as u8 || last == b"JABCDEFGHI"[digit as usize])
+                }
+                _ => Err(format),
+            }
+        }
+        "FI" => {
+            if len != 8 || !all_digits {
+                return Err(VatError::Format("8 digits"));
+            }
+            match weighted_sum(&d[..7], &[7, 9, 10, 5, 8, 4, 2]) % 11 {
+                0 => check(d[7] == 0),
+                1 => Err(VatError::Checksum),
+                remainder => check(11 - remainder == d[7]),
+            }
+        }
+        "FR" => {
+            let valid_key = |c: char| c.is_ascii_digit() || (c.is_ascii_uppercase() && c != 'I' && c != 'O');
+            if len != 11 || !number[..2].chars().all(valid_key) || !is_digits(&number[2..]) {
+                return Err(VatError::Format("2 characters followed by the 9 digit SIREN"));
+            }
+            // Keys with letters, used for newer numbers, have no published algorithm
+            if !is_digits(&number[..2]) {
+                return Ok(());
+            }
+            check((12 + 3 * (parse_u64(&number[2..]) % 97)) % 97 == parse_u64(&number[..2]))
+        }
+        "HR" => {
+            if len != 11 || !all_digits {
+                return Err(VatError::Format("11 digits"));
+            }
+            check(mod11_10(&d[..10]) == d[10])
+        }
+        "HU" => {
+            if len != 8 || !all_digits {
+                return Err(VatError::Format("8 digits"));
+            }
+            check(weighted_sum(&d, &[9, 7, 3, 1, 9, 7, 3, 1]) % 10 == 0)
+        }
+        "IE" => {
+            let chars: Vec<char> = number.chars().collect();
+            let old_style = len == 8
+                && chars[0].is_ascii_digit()
+                && (chars[1].is_ascii_uppercase() || chars[1] == '+' || chars[1] == '*')
+                && is_digits(&number[2..7]);
+            let new_style = (len == 8 || len == 9) && is_digits(&number[..7]);
+            if !(old_style || new_style) || !chars[7].is_ascii_uppercase() {
+                return Err(VatError::Format("7 digits followed by one or two letters"));
+            }
+            // The old format has the digits moved around
+            let (d, extra) = if old_style && !new_style {
+                (digits(&format!("0{}{}", &number[2..7], chars[0])), 0)
+            } else {
+                let extra = chars.get(8).and_then(|&c| "WABCDEFGHI".find(c));
+                if len == 9 && extra.is_none() {
+                    return Err(VatError::Format("7 digits followed by one or two letters"));
+                }
+                (d[..7].to_vec(), extra.unwrap_or_default() as u32 * 9)
+            };
+            let sum = weighted_sum(&d, &[8, 7, 6, 5, 4, 3, 2]) + extra;
+            check("WABCDEFGHIJKLMNOPQRSTUV".chars().nth((sum % 23) as usize) == Some(chars[7]))
+        }
+        "IT" => {
+            if len != 11 || !all_digits {
+                return Err(VatError::Format("11 digits"));
+            }
+            let office = parse_u64(&number[7..10]);
+            check(
+                luhn(number)
+                    && parse_u64(&number[..7]) != 0
+                    && ((1..=100).contains(&office) || matches!(office, 120 | 121 | 888 | 999)),
+            )
+        }
+        "LT" => {
+            // Legal entities have 9 digits, temporary registrations 12
+            let valid = all_digits && ((len == 9 && d[7] == 1) || (len == 12 && d[10] == 1));
+            if !valid {
+                return Err(VatError::Format("9 or 12 digits"));
+            }
+            let body = &d[..len - 1];
+            let weights = |offset: usize| -> Vec<u32> { (0..body.len()).map(|i| 1 + ((i + offset) % 9) as u32).collect() };
+            let mut sum = weighted_sum(body, &weights(0)) % 11;
+            if sum == 10 {
+                sum = weighted_sum(body, &weights(2)) % 11;
+            }
+            check(sum % 10 == d[len - 1])
+        }
+        "LU" => {
+            if len != 8 || !all_digits {
+                return Err(VatError::Format("8 digits"));
+            }
+            check(parse_u64(&number[..6]) % 89 == parse_u64(&number[6..]))
+        }
+        "LV" => {
+            if len != 11 || !all_digits {
+                return Err(VatError::Format("11 digits"));
+            }
+            if d[0] > 3 {
+                check(weighted_sum(&d, &[9, 1, 4, 8, 3, 10, 2, 5, 7, 6, 1]) % 11 == 3)
+            } else if number.starts_with("32") {
+                // Personal codes issued since 2017 have no check digit
+                Ok(())
+            } else {
+                check((1101 - weighted_sum(&d[..10], &[1, 6, 3, 7, 9, 10, 5, 8, 4, 2])) % 11 == d[10])
+            }
+        }
+        "MT" => {
+            if len != 8 || !all_digits || d[0] == 0 {
+                return Err(VatError::Format("8 digits"));
+            }
+            check(weighted_sum(&d, &[3, 4, 6, 7, 8, 9, 10, 1]) % 37 == 0)
+        }
+        "NL" => {
+            if len != 12 || !is_digits(&number[..9]) || &number[9..10] != "B" || !is_digits(&number[10..]) {
+                return Err(VatError::Format("9 digits, B and 2 digits"));
+            }
+            // Numbers issued to sole proprietors since 2020 use the MOD 97 check
+            let sum = weighted_sum(&d[..8], &[9, 8, 7, 6, 5, 4, 3, 2]);
+            check((sum + 11 - d[8]) % 11 == 0 || mod97(&format!("NL{}", number)) == 1)
+        }
+        "PL" => {
+            if len != 10 || !all_digits {
+                return Err(VatError::Format("10 digits"));
+            }
+            check(weighted_sum(&d[..9], &[6, 5, 7, 2, 3, 4, 5, 6, 7]) % 11 == d[9])
+        }
+        "PT" => {
+            if len != 9 || !all_digits || d[0] == 0 {
+                return Err(VatError::Format("9 digits"));
+            }
+            let digit = match 11 - weighted_sum(&d[..8], &[9, 8, 7, 6, 5, 4, 3, 2]) % 11 {
+                10 | 11 => 0,
+                digit => digit,
+            };
+            check(digit == d[8])
+        }
+        "RO" => {
+            if !all_digits || !(2..=10).contains(&len) || d[0] == 0 {
+                return Err(VatError::Format("2 to 10 digits"));
+            }
+            let padded = digits(&format!("{:0>10}", number));
+            let sum = weighted_sum(&padded[..9], &[7, 5, 3, 2, 1, 7, 5, 3, 2]);
+            check(sum * 10 % 11 % 10 == padded[9])
+        }
+        "SE" => {
+            if len != 12 || !all_digits || !number.ends_with("01") {
+                return Err(VatError::Format("12 digits ending in 01"));
+            }
+            check(luhn(&number[..10]))
+        }
+        "SI" => {
+            if len != 8 || !all_digits || d[0] == 0 {
+                return Err(VatError::Format("8 digits"));
+            }
+            match 11 - weighted_sum(&d[..7], &[8, 7, 6, 5, 4, 3, 2]) % 11 {
+                10 => check(d[7] == 0),
+                11 => Err(VatError::Checksum),
+                digit => check(digit == d[7]),
+            }
+        }
+        "SK" => {
+            if len != 10 || !all_digits || d[0] == 0 || !matches!(d[2], 2 | 3 | 4 | 7 | 8 | 9) {
+                return Err(VatError::Format("10 digits"));
+            }
+            check(parse_u64(number) % 11 == 0)
+        }
+        "XI" => {
+            // Government departments and health authorities
+            if len == 5 && (number.starts_with("GD") || number.starts_with("HA")) && is_digits(&number[2..]) {
+                let value = parse_u64(&number[2..]);
+                return check(if number.starts_with("GD") { value < 500 } else { value >= 500 });
+            }
+            if !all_digits || (len != 9 && len != 12) {
+                return Err(VatError::Format("9 or 12 digits"));
+            }
+            let sum = weighted_sum(&d[..7], &[8, 7, 6, 5, 4, 3, 2]) + d[7] * 10 + d[8];
+            check(sum % 97 == 0 || (sum + 55) % 97 == 0)
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn is_eu_vat_prefix(prefix: &str) -> bool {
+    VAT_PREFIXES.contains(&prefix)
+}
+
+// Strips the separators VAT numbers are often written with
+pub fn normalize_vatin(vatin: &str) -> String {
+    vatin
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '.' | '-' | '/'))
+        .collect::<String>()
+        .to_uppercase()
+}
+
+// Checks EU VAT numbers, which may be given with or without their country
+// prefix. Without one the prefix comes from `country`. Numbers from outside
+// the EU are returned unchecked. Returns the number with its prefix.
+pub fn validate_vatin(vatin: &str, country: Option<&str>) -> Result<String, String> {
+    let vatin = normalize_vatin(vatin);
+    let given_prefix = vatin.get(..2).map(|prefix| if prefix == "GR" { "EL" } else { prefix });
+    let (prefix, number) = match given_prefix {
+        Some(prefix) if VAT_PREFIXES.contains(&prefix) => (prefix, &vatin[2..]),
+        _ => match country.and_then(country_code).map(|code| if code == "GR" { "EL" } else { code }) {
+            Some(prefix) if VAT_PREFIXES.contains(&prefix) => (prefix, vatin.as_str()),
+            _ => return Ok(vatin),
+        },
+    };
+
+    if !number.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '*') {
+        return Err(format!("VAT number {} should contain only letters and digits", vatin));
+    }
+
+    let country = match prefix {
+        "EL" => "Greece",
+        "XI" => "Northern Ireland",
+        code => country_name(code).unwrap_or(code),
+    };
+    match check_vat_number(prefix, number) {
+        Ok(()) => Ok(format!("{}{}", prefix, number)),
+        Err(VatError::Format(format)) => Err(format!("VAT number {} should be {} for {}", vatin, format, country)),
+        Err(VatError::Checksum) => Err(format!("VAT number {} has an invalid check digit, check it for typos", vatin)),
+    }
+}
+
+// A problem with one field of a request, named as it is in the request
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+// Collects the field errors of a request, so they can all be shown at once
+#[derive(Debug, Default)]
+pub struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    // Blank fields are left for the database to store as they are
+    pub fn check(
+        &mut self,
+        field: &'static str,
+        value: Option<&str>,
+        validate: impl FnOnce(&str) -> Result<String, String>,
+    ) {
+        let Some(value) = value.filter(|value| !value.trim().is_empty()) else {
+            return;
+        };
+        if let Err(message) = validate(value) {
+            self.0.push(FieldError { field, message });
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), Vec<FieldError>> {
+        if self.0.is_empty() { Ok(()) } else { Err(self.0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_ibans() {
+        for (iban, electronic) in [
+            ("EE38 2200 2210 2014 5685", "EE382200221020145685"),
+            ("DE89370400440532013000", "DE89370400440532013000"),
+            ("gb82 west 1234 5698 7654 32", "GB82WEST12345698765432"),
+            ("FR1420041010050500013M02606", "FR1420041010050500013M02606"),
+            ("NL91ABNA0417164300", "NL91ABNA0417164300"),
+            ("CH9300762011623852957", "CH9300762011623852957"),
+        ] {
+            assert_eq!(validate_iban(iban).as_deref(), Ok(electronic));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_ibans() {
+        for iban in [
+            "EE382200221020145686",  // checksum
+            "EE832200221020145685",  // check digits swapped
+            "DE8937040044053201300", // too short
+            "ZZ89370400440532013000", // unknown country
+            "DE89-3704-0044-0532-0130-00",
+            "EEXX2200221020145685",
+            "EE3",
+        ] {
+            assert!(validate_iban(iban).is_err(), "{}", iban);
+        }
+    }
+
+    #[test]
+    fn validates_bics() {
+        assert_eq!(validate_bic("DEUTDEFF").as_deref(), Ok("DEUTDEFF"));
+        assert_eq!(validate_bic("hab aee2x").as_deref(), Ok("HABAEE2X"));
+        assert_eq!(validate_bic("DEUTDEFF500").as_deref(), Ok("DEUTDEFF500"));
+        for bic in ["DEUTDEF", "DEUTDEFF5", "1EUTDEFF", "DEUTZZFF", "DEUT-EFF"] {
+            assert!(validate_bic(bic).is_err(), "{}", bic);
+        }
+    }
+
+    #[test]
+    fn computes_check_digits() {
+        // DE89370400440532013000 and the RF creditor reference RF18539007547034
+        // with their first four characters moved to the end
+        assert_eq!(mod97("370400440532013000DE89"), 1);
+        assert_eq!(mod97("539007547034RF18"), 1);
+        assert_eq!(mod97("539007547034RF19"), 2);
+        assert!(luhn("79927398713"));
+        assert!(!luhn("79927398710"));
+        assert_eq!(mod11_10(&digits("13669597")), 6);
+    }
+
+    #[test]
+    fn accepts_a_valid_vat_number_for_every_prefix() {
+        let valid = [
+            "ATU13585627", "BE0403019261", "BG175074752", "CY10259033P", "CZ25123891", "DE136695976", "DK13585628",
+            "EE100931558", "EL094259216", "ES54362315K", "FI20774740", "FR40303265045", "HR33392005961", "HU12892312",
+            "IE6433435F", "IT00743110157", "LT119511515", "LU15027442", "LV40003521600", "MT11679112",
+            "NL004495445B01", "PL8567346215", "PT501964843", "RO18547290", "SE123456789701", "SI50223054",
+            "SK2022749619", "XI980780684",
+        ];
+        for prefix in VAT_PREFIXES {
+            assert!(valid.iter().any(|vatin| vatin.starts_with(prefix)), "no number for {}", prefix);
+        }
+        for vatin in valid {
+            assert_eq!(validate_vatin(vatin, None).as_deref(), Ok(vatin));
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_check_digits() {
+        for vatin in [
+            "ATU13585626", "BE0403019262", "BG175074753", "CY10259033A", "CZ25123892", "DE136695977", "DK13585627",
+            "EE100931559", "EL094259217", "ES54362315Z", "FI20774741", "FR41303265045", "HR33392005962", "HU12892313",
+            "IE6433435G", "IT00743110158", "LT119511516", "LU15027443", "LV40003521601", "MT11679113",
+            "NL004495446B01", "PL8567346216", "PT501964844", "RO18547291", "SE123456789801", "SI50223055",
+            "SK2022749618", "XI980780685",
+        ] {
+            assert!(validate_vatin(vatin, None).is_err(), "{}", vatin);
+        }
+        assert!(validate_vatin("DE12345", None).is_err());
+    }
+
+    #[test]
+    fn takes_the_prefix_from_the_country() {
+        assert_eq!(validate_vatin("de 136.695.976", None).as_deref(), Ok("DE136695976"));
+        assert_eq!(validate_vatin("GR094259216", None).as_deref(), Ok("EL094259216"));
+        assert_eq!(validate_vatin("100931558", Some("Estonia")).as_deref(), Ok("EE100931558"));
+        assert!(validate_vatin("100931559", Some("Estonia")).is_err());
+        // Numbers from outside the EU aren't checked
+        assert_eq!(validate_vatin("100931559", Some("Norway")).as_deref(), Ok("100931559"));
+        assert_eq!(validate_vatin("CHE-123.456.789 MWST", None).as_deref(), Ok("CHE123456789MWST"));
+    }
+}
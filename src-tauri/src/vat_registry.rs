@@ -0,0 +1,166 @@
+// Lookups of VAT numbers in a register of VAT-registered businesses, to
+// confirm a client is registered before invoicing with reverse charge
+
+use std::future::Future;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri_plugin_http::reqwest;
+
+const VIES_URL: &str = "https://ec.europa.eu/taxation_customs/vies/rest-api/check-vat-number";
+const VIES_TIMEOUT: Duration = Duration::from_secs(20);
+
+// A registry's answer for one VAT number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VatLookup {
+    pub valid: bool,
+    pub name: Option<String>,
+    pub address: Option<String>,
+    // Proof of the check, issued when the requester's own VAT number is given
+    pub consultation_number: Option<String>,
+}
+
+pub trait VatRegistry {
+    // `country_code` is the VAT prefix (EL for Greece) and `number` the rest.
+    // `requester` is the organization's own number, split the same way.
+    // Errors mean the registry couldn't answer, not that the number is invalid.
+    fn lookup(
+        &self,
+        country_code: &str,
+        number: &str,
+        requester: Option<(&str, &str)>,
+    ) -> impl Future<Output = Result<VatLookup, String>> + Send;
+}
+
+// The EU's VAT Information Exchange System, through its REST API
+#[derive(Debug, Clone, Default)]
+pub struct ViesRegistry {
+    client: reqwest::Client,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ViesRequest<'a> {
+    country_code: &'a str,
+    vat_number: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requester_member_state_code: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requester_number: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ViesResponse {
+    valid: Option<bool>,
+    name: Option<String>,
+    address: Option<String>,
+    request_identifier: Option<String>,
+    #[serde(default)]
+    error_wrappers: Vec<ViesError>,
+}
+
+#[derive(Deserialize)]
+struct ViesError {
+    error: String,
+    message: Option<String>,
+}
+
+// VIES answers "---" for details a member state doesn't share
+fn disclosed(value: Option<String>) -> Option<String> {
+    value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty() && value != "---")
+}
+
+impl VatRegistry for ViesRegistry {
+    async fn lookup(
+        &self,
+        country_code: &str,
+        number: &str,
+        requester: Option<(&str, &str)>,
+    ) -> Result<VatLookup, String> {
+        let request = ViesRequest {
+            country_code,
+            vat_number: number,
+            requester_member_state_code: requester.map(|(country_code, _)| country_code),
+            requester_number: requester.map(|(_, number)| number),
+        };
+        let body = serde_json::to_string(&request).map_err(|e| e.to_string())?;
+
+        let response = self
+            .client
+            .post(VIES_URL)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .timeout(VIES_TIMEOUT)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("VIES can't be reached: {}", e))?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| format!("VIES response can't be read: {}", e))?;
+        let response: ViesResponse = serde_json::from_str(&text)
+            .map_err(|_| format!("VIES returned an unexpected response ({})", status))?;
+
+        // Member state systems are regularly down or rate limited
+        if let Some(error) = response.error_wrappers.first() {
+            return Err(format!(
+                "VIES couldn't check the number: {}",
+                error.message.as_deref().unwrap_or(&error.error)
+            ));
+        }
+        let valid = response.valid.ok_or_else(|| format!("VIES returned no result ({})", status))?;
+
+        Ok(VatLookup {
+            valid,
+            name: disclosed(response.name),
+            address: disclosed(response.address),
+            consultation_number: disclosed(response.request_identifier),
+        })
+    }
+}
+
+// Answers from a fixed list of registered numbers without going online
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct MockVatRegistry {
+    // Full VAT numbers with their prefix, and the business name
+    registered: Vec<(String, String)>,
+    unavailable: bool,
+}
+
+#[cfg(test)]
+impl MockVatRegistry {
+    pub fn with_registered(mut self, vatin: &str, name: &str) -> Self {
+        self.registered.push((vatin.to_string(), name.to_string()));
+        self
+    }
+
+    // Every lookup fails, as when a member state's system is down
+    pub fn unavailable(mut self) -> Self {
+        self.unavailable = true;
+        self
+    }
+}
+
+#[cfg(test)]
+impl VatRegistry for MockVatRegistry {
+    async fn lookup(
+        &self,
+        country_code: &str,
+        number: &str,
+        requester: Option<(&str, &str)>,
+    ) -> Result<VatLookup, String> {
+        if self.unavailable {
+            return Err(format!("The {} VAT registry is unavailable", country_code));
+        }
+
+        let vatin = format!("{}{}", country_code, number);
+        let registered = self.registered.iter().find(|(registered, _)| *registered == vatin);
+        Ok(VatLookup {
+            valid: registered.is_some(),
+            name: registered.map(|(_, name)| name.clone()),
+            address: None,
+            consultation_number: requester.map(|_| format!("MOCK{}", vatin)),
+        })
+    }
+}
@@ -54,6 +54,8 @@ import InvoiceDetails from "src/routes/invoices/details.tsx";
 import SettingsInvoice from "src/routes/settings/invoice";
 import SettingsOrganization from "src/routes/settings/organization";
 import SettingsTaxRates from "src/routes/settings/tax-rates";
+import SettingsItems from "src/routes/settings/items";
+import SettingsExchangeRates from "src/routes/settings/exchange-rates";
 import SettingsBackup from "src/routes/settings/backup";
 import SettingsAI from "src/routes/settings/ai";
 import TimeTracking from "src/routes/time-tracking/index";
@@ -63,6 +65,7 @@ import NewOrganization from "src/routes/organizations/new";
 // Components
 import Loading from "src/components/loading";
 import TaxRateForm from "src/components/tax-rates/form.tsx";
+import ItemForm from "src/components/items/form.tsx";
 
 dayjs.extend(localizedFormat);
 
@@ -198,6 +201,11 @@ const AppContent = () => {
               <Route path="new" element={<TaxRateForm />} />
               <Route path=":id" element={<TaxRateForm />} />
             </Route>
+            <Route path="items" element={<SettingsItems />}>
+              <Route path="new" element={<ItemForm />} />
+              <Route path=":id" element={<ItemForm />} />
+            </Route>
+            <Route path="exchange-rates" element={<SettingsExchangeRates />} />
             <Route path="backup" element={<SettingsBackup />} />
             <Route path="ai" element={<SettingsAI />} />
           </Route>
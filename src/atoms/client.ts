@@ -9,6 +9,8 @@ import map from "lodash/map";
 import reject from "lodash/reject";
 import { invoke } from "@tauri-apps/api/core";
 
+import { getFieldErrors } from "src/utils/form";
+
 import { organizationIdAtom } from "./organization";
 
 // Clients
@@ -88,6 +90,8 @@ export const clientAtom = atom(
         set(clientsAtom, orderBy(map(mergedClients), "name", "asc"));
       }
     } catch (error) {
+      // Invalid fields are shown on the form instead
+      if (getFieldErrors(error).length > 0) throw error;
       console.error("Client operation failed:", error);
       if (!clientId) {
         message.error(t`Client creation failed`);
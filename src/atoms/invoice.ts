@@ -11,9 +11,21 @@ import map from "lodash/map";
 import reject from "lodash/reject";
 import { invoke } from "@tauri-apps/api/core";
 
-import { centsToUnits, unitsToCents, multiplyDecimal } from "src/utils/currency";
+import { centsToUnits, unitsToCents, multiplyDecimal, minorUnitExponent } from "src/utils/currency";
+import { discountToCents, discountToUnits, getLineItemTaxes } from "src/utils/invoice";
 import { organizationIdAtom, nextInvoiceNumberAtom } from "./organization";
 
+// Amounts are in minor units of the invoice's currency, cents for EUR and yen for JPY
+const totalsToUnits = (invoice: any) => {
+  const precision = minorUnitExponent(invoice.currency);
+  return {
+    ...invoice,
+    total: centsToUnits(invoice.total, precision),
+    taxTotal: centsToUnits(invoice.taxTotal, precision),
+    subTotal: centsToUnits(invoice.subTotal, precision),
+  };
+};
+
 // Invoices
 export const invoicesAtom = atom<any[]>([]);
 export const setInvoicesAtom = atom(null, async (get, set) => {
@@ -21,13 +33,7 @@ export const setInvoicesAtom = atom(null, async (get, set) => {
   try {
     const response = await invoke<any[]>("get_invoices", { organizationId });
     // Convert cents to units for display
-    const invoicesWithUnits = response.map((invoice) => ({
-      ...invoice,
-      total: centsToUnits(invoice.total),
-      taxTotal: centsToUnits(invoice.taxTotal),
-      subTotal: centsToUnits(invoice.subTotal),
-    }));
-    set(invoicesAtom, invoicesWithUnits);
+    set(invoicesAtom, response.map(totalsToUnits));
   } catch (error) {
     console.error("Failed to fetch invoices:", error);
     message.error(t`Failed to fetch invoices`);
@@ -50,18 +56,18 @@ export const invoiceAtom = atom(
 
       if (!invoice) return null;
 
+      const precision = minorUnitExponent(invoice.currency);
       return {
-        ...invoice,
+        // Convert cents to currency units for display
+        ...totalsToUnits(invoice),
         date: dayjs(invoice.date),
         dueDate: invoice.dueDate ? dayjs(invoice.dueDate) : null,
-        // Convert cents to currency units for display
-        total: centsToUnits(invoice.total),
-        taxTotal: centsToUnits(invoice.taxTotal),
-        subTotal: centsToUnits(invoice.subTotal),
+        discount: discountToUnits(invoice.discount, invoice.discountKind, invoice.currency),
         lineItems: (lineItems || []).map((item: any) => ({
           ...item,
-          unitPrice: centsToUnits(item.unitPrice),
-          total: centsToUnits(multiplyDecimal(item.quantity, item.unitPrice)),
+          unitPrice: centsToUnits(item.unitPrice, precision),
+          discount: discountToUnits(item.discount, item.discountKind, invoice.currency),
+          total: centsToUnits(multiplyDecimal(item.quantity, item.unitPrice), precision),
         })),
       };
     } catch (error) {
@@ -74,6 +80,7 @@ export const invoiceAtom = atom(
 
     const invoice = omit(newValues, "lineItems");
     const lineItems = newValues.lineItems || [];
+    const precision = minorUnitExponent(invoice.currency);
 
     try {
       if (!invoiceId) {
@@ -87,13 +94,16 @@ export const invoiceAtom = atom(
           date: invoice.date?.valueOf ? invoice.date.valueOf() : invoice.date,
           dueDate: invoice.dueDate?.valueOf ? invoice.dueDate.valueOf() : invoice.dueDate,
           // Convert currency units to cents for storage
-          total: unitsToCents(invoice.total),
-          taxTotal: unitsToCents(invoice.taxTotal),
-          subTotal: unitsToCents(invoice.subTotal),
+          total: unitsToCents(invoice.total, precision),
+          taxTotal: unitsToCents(invoice.taxTotal, precision),
+          subTotal: unitsToCents(invoice.subTotal, precision),
           overdueCharge: invoice.overdueCharge,
+          discount: discountToCents(invoice.discount, invoice.discountKind, invoice.currency),
           lineItems: lineItems.map((item: any) => ({
-            ...omit(item, ["id", "total"]),
-            unitPrice: unitsToCents(item.unitPrice),
+            ...omit(item, ["id", "total", "taxRate"]),
+            unitPrice: unitsToCents(item.unitPrice, precision),
+            discount: discountToCents(item.discount, item.discountKind, invoice.currency),
+            taxes: item.taxes ?? getLineItemTaxes(item),
           })),
         };
 
@@ -106,7 +116,7 @@ export const invoiceAtom = atom(
 
         // Update the invoices list
         const invoices: any = get(invoicesAtom);
-        set(invoicesAtom, [createdInvoice, ...invoices]);
+        set(invoicesAtom, [totalsToUnits(createdInvoice), ...invoices]);
 
         // Force refresh organization data to get updated invoice counter
         const currentOrgId = get(organizationIdAtom);
@@ -122,14 +132,18 @@ export const invoiceAtom = atom(
           date: invoice.date?.valueOf ? invoice.date.valueOf() : invoice.date,
           dueDate: invoice.dueDate?.valueOf ? invoice.dueDate.valueOf() : invoice.dueDate,
           // Convert currency units to cents for storage
-          total: invoice.total ? unitsToCents(invoice.total) : undefined,
-          taxTotal: invoice.taxTotal ? unitsToCents(invoice.taxTotal) : undefined,
-          subTotal: invoice.subTotal ? unitsToCents(invoice.subTotal) : undefined,
+          total: invoice.total ? unitsToCents(invoice.total, precision) : undefined,
+          taxTotal: invoice.taxTotal ? unitsToCents(invoice.taxTotal, precision) : undefined,
+          subTotal: invoice.subTotal ? unitsToCents(invoice.subTotal, precision) : undefined,
           overdueCharge: invoice.overdueCharge,
+          // A cleared discount is sent as zero, which removes it
+          discount: discountToCents(invoice.discount ?? 0, invoice.discountKind, invoice.currency),
           lineItems: lineItems
             ? lineItems.map((item: any) => ({
-                ...omit(item, ["id", "total"]),
-                unitPrice: unitsToCents(item.unitPrice),
+                ...omit(item, ["id", "total", "taxRate"]),
+                unitPrice: unitsToCents(item.unitPrice, precision),
+                discount: discountToCents(item.discount, item.discountKind, invoice.currency),
+                taxes: item.taxes ?? getLineItemTaxes(item),
               }))
             : undefined,
         };
@@ -143,7 +157,7 @@ export const invoiceAtom = atom(
 
         // Update the invoices list
         const invoices: any = get(invoicesAtom);
-        const mergedInvoices: any = keyBy([...invoices, updatedInvoice], "id");
+        const mergedInvoices: any = keyBy([...invoices, totalsToUnits(updatedInvoice)], "id");
         set(invoicesAtom, orderBy(map(mergedInvoices), "date", "desc"));
       }
     } catch (error) {
@@ -188,13 +202,7 @@ export const updateInvoiceStateAtom = atom(null, async (get, set, { invoiceId, s
 
     // Update the invoices list
     const invoices: any = get(invoicesAtom);
-    const invoiceWithUnits = {
-      ...updatedInvoice,
-      total: centsToUnits(updatedInvoice.total),
-      taxTotal: centsToUnits(updatedInvoice.taxTotal),
-      subTotal: centsToUnits(updatedInvoice.subTotal),
-    };
-    const mergedInvoices: any = keyBy([...invoices, invoiceWithUnits], "id");
+    const mergedInvoices: any = keyBy([...invoices, totalsToUnits(updatedInvoice)], "id");
     set(invoicesAtom, orderBy(map(mergedInvoices), "date", "desc"));
   } catch (error) {
     console.error("Failed to update invoice state:", error);
@@ -240,6 +248,10 @@ export const duplicateInvoiceAtom = atom(null, async (get, set, invoiceId: strin
       subTotal: originalInvoice.subTotal,
       customerNotes: originalInvoice.customerNotes,
       overdueCharge: originalInvoice.overdueCharge,
+      pricesIncludeTax: originalInvoice.pricesIncludeTax,
+      taxRounding: originalInvoice.taxRounding,
+      discount: originalInvoice.discount,
+      discountKind: originalInvoice.discountKind,
       lineItems: (lineItems || []).map((item: any) => ({
         ...omit(item, ["id", "invoiceId", "createdAt"]),
       })),
@@ -254,13 +266,7 @@ export const duplicateInvoiceAtom = atom(null, async (get, set, invoiceId: strin
 
     // Update the invoices list
     const invoices: any = get(invoicesAtom);
-    const invoiceWithUnits = {
-      ...createdInvoice,
-      total: centsToUnits(createdInvoice.total),
-      taxTotal: centsToUnits(createdInvoice.taxTotal),
-      subTotal: centsToUnits(createdInvoice.subTotal),
-    };
-    set(invoicesAtom, [invoiceWithUnits, ...invoices]);
+    set(invoicesAtom, [totalsToUnits(createdInvoice), ...invoices]);
 
     // Force refresh organization data to get updated invoice counter
     const currentOrgId = get(organizationIdAtom);
@@ -0,0 +1,111 @@
+import { atom } from "jotai";
+import { message } from "antd";
+import { nanoid } from "nanoid";
+import { t } from "@lingui/core/macro";
+import orderBy from "lodash/orderBy";
+import keyBy from "lodash/keyBy";
+import map from "lodash/map";
+import { invoke } from "@tauri-apps/api/core";
+
+import { organizationAtom, organizationIdAtom } from "./organization";
+import { centsToUnits, minorUnitExponent, unitsToCents } from "src/utils/currency";
+
+// Prices are stored in minor units of the organization's currency and edited in units
+const fromBackend = (item: any, currency?: string) => ({
+  ...item,
+  unitPrice: centsToUnits(item.unitPrice, minorUnitExponent(currency)),
+});
+const toBackend = (values: any, currency?: string) => ({
+  ...values,
+  unitPrice:
+    values.unitPrice !== undefined && values.unitPrice !== null
+      ? unitsToCents(values.unitPrice, minorUnitExponent(currency))
+      : undefined,
+});
+
+// Items
+export const itemsAtom = atom<any[]>([]);
+export const setItemsAtom = atom(null, async (get, set) => {
+  const organizationId = get(organizationIdAtom);
+  try {
+    const organization = await get(organizationAtom);
+    const response = await invoke<any[]>("get_items", { organizationId });
+    set(itemsAtom, response.map((item) => fromBackend(item, organization?.currency)));
+  } catch (error) {
+    console.error("Failed to fetch items:", error);
+    message.error(t`Failed to fetch items`);
+    set(itemsAtom, []);
+  }
+});
+
+// Item
+export const itemIdAtom = atom<string | null>(null);
+export const itemAtom = atom(
+  async (get) => {
+    const itemId = get(itemIdAtom);
+    if (!itemId) return null;
+
+    try {
+      const organization = await get(organizationAtom);
+      const item = await invoke<any>("get_item", { itemId });
+      return item ? fromBackend(item, organization?.currency) : null;
+    } catch (error) {
+      console.error("Failed to fetch item:", error);
+      return null;
+    }
+  },
+  async (get, set, newValues: any) => {
+    const itemId = get(itemIdAtom);
+    const currency = (await get(organizationAtom))?.currency;
+
+    try {
+      if (!itemId) {
+        // Insert
+        const createdItem = await invoke<any>("create_item", {
+          item: {
+            ...toBackend(newValues, currency),
+            id: nanoid(),
+            organizationId: get(organizationIdAtom),
+          },
+        });
+        set(itemIdAtom, createdItem.id);
+        message.success(t`Item created`);
+
+        // Update the items list
+        const items: any = get(itemsAtom);
+        set(itemsAtom, orderBy([...items, fromBackend(createdItem, currency)], "name", "asc"));
+      } else {
+        // Update, an emptied SKU is sent as "" to clear it
+        const updatedItem = await invoke<any>("update_item", {
+          itemId,
+          updates: { ...toBackend(newValues, currency), sku: newValues.sku ?? "" },
+        });
+        message.success(t`Item updated successfully`);
+
+        // Update the items list
+        const items: any = get(itemsAtom);
+        const mergedItems: any = keyBy([...items, fromBackend(updatedItem, currency)], "id");
+        set(itemsAtom, orderBy(map(mergedItems), "name", "asc"));
+      }
+    } catch (error) {
+      console.error("Item operation failed:", error);
+      if (!itemId) {
+        message.error(t`Item creation failed`);
+      } else {
+        message.error(t`Item update failed`);
+      }
+    }
+  }
+);
+
+// Removes an item from the catalog. Lines filled from it keep their copies.
+export const deleteItemAtom = atom(null, async (get, set, itemId: string) => {
+  try {
+    await invoke<boolean>("delete_item", { itemId });
+    set(itemsAtom, get(itemsAtom).filter((item) => item.id !== itemId));
+    message.success(t`Item deleted`);
+  } catch (error) {
+    console.error("Failed to delete item:", error);
+    message.error(t`Item deletion failed`);
+  }
+});
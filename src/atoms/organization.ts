@@ -8,6 +8,7 @@ import reject from "lodash/reject";
 import first from "lodash/first";
 import { invoke } from "@tauri-apps/api/core";
 
+import { getFieldErrors } from "src/utils/form";
 import { generateInvoiceNumber } from "src/utils/invoice";
 
 // Organizations
@@ -102,11 +103,13 @@ export const organizationAtom = atom(
         set(organizationIdAtom, organizationId);
       }
     } catch (error) {
+      // Invalid fields are shown on the form instead
+      if (getFieldErrors(error).length > 0) throw error;
       console.error("Organization operation failed:", error);
       if (!organizationId) {
         message.error(t`Organization creation failed`);
       } else {
-        message.error(t`Organization update failed`);
+        message.error(t`Organization update failed: ${String(error)}`);
       }
     }
   }
@@ -1,16 +1,19 @@
 import { useEffect, useState, startTransition } from "react";
 import { useLocation, useNavigate } from "react-router";
-import { Form, Input, Modal, Select, Button, Popconfirm, Spin } from "antd";
+import { Form, Input, Modal, Select, Button, Popconfirm, Spin, message } from "antd";
 import { atom, useAtom, useSetAtom } from "jotai";
 import { Trans } from "@lingui/react/macro";
 import { t } from "@lingui/core/macro";
 import { DeleteOutlined } from "@ant-design/icons";
 import isEmpty from "lodash/isEmpty";
 import get from "lodash/get";
+import dayjs from "dayjs";
 import { invoke } from "@tauri-apps/api/core";
 
 import { clientIdAtom, clientAtom, deleteClientAtom } from "src/atoms/client";
 import { generateClientCode } from "src/utils/client";
+import { countries } from "src/utils/countries";
+import { getFieldErrors, toFormFields } from "src/utils/form";
 
 const submittingAtom = atom(false);
 
@@ -25,10 +28,18 @@ const ClientForm = () => {
   const deleteClient = useSetAtom(deleteClientAtom);
   const [invoiceCount, setInvoiceCount] = useState<number | null>(null);
   const [loading, setLoading] = useState(false);
+  const [vatCheck, setVatCheck] = useState<any>(null);
+  const [verifying, setVerifying] = useState(false);
 
   const handleSubmit = async (values: any) => {
     setSubmitting(true);
-    await setClient(values);
+    try {
+      await setClient(values);
+    } catch (error) {
+      form.setFields(toFormFields(getFieldErrors(error)));
+      setSubmitting(false);
+      return;
+    }
     startTransition(() => {
       setClientId(null);
     });
@@ -50,6 +61,21 @@ const ClientForm = () => {
     }
   };
 
+  const vatCheckedOn = vatCheck?.vatin_checked_at ? dayjs(vatCheck.vatin_checked_at).format("L") : null;
+
+  const handleVerifyVatin = async () => {
+    if (!clientId) return;
+    setVerifying(true);
+    try {
+      // Verifies the saved VAT number in VIES
+      setVatCheck(await invoke<any>("verify_client_vatin", { clientId }));
+    } catch (error) {
+      console.error("Failed to verify VAT number:", error);
+      message.error(t`VAT number verification failed`);
+    }
+    setVerifying(false);
+  };
+
   useEffect(() => {
     if (get(location, "state.clientId")) {
       startTransition(() => {
@@ -67,6 +93,7 @@ const ClientForm = () => {
     if (clientId) {
       setLoading(true);
     }
+    setVatCheck(client);
     if (client) {
       form.setFieldsValue(client);
       setLoading(false);
@@ -183,6 +210,15 @@ const ClientForm = () => {
           <Form.Item name="address">
             <Input.TextArea rows={4} placeholder={t`Address`} />
           </Form.Item>
+          <Form.Item name="country">
+            <Select placeholder={t`Select country`} showSearch allowClear>
+              {countries.map((country) => (
+                <Select.Option key={country.name} value={country.name}>
+                  {country.name}
+                </Select.Option>
+              ))}
+            </Select>
+          </Form.Item>
           {/* TODO: E-mail validation */}
           <Form.Item name="emails">
             <Select placeholder={t`E-mails`} mode="tags" tokenSeparators={[",", ";"]} />
@@ -190,8 +226,23 @@ const ClientForm = () => {
           <Form.Item name="phone">
             <Input placeholder={t`Phone`} />
           </Form.Item>
-          <Form.Item name="vatin">
-            <Input placeholder={t`VAT Number`} />
+          <Form.Item
+            name="vatin"
+            extra={
+              vatCheckedOn &&
+              (vatCheck.vatin_valid ? t`Registered in VIES on ${vatCheckedOn}` : t`Not registered in VIES on ${vatCheckedOn}`)
+            }
+          >
+            <Input
+              placeholder={t`VAT Number`}
+              addonAfter={
+                clientId && (
+                  <Button type="link" size="small" loading={verifying} onClick={handleVerifyVatin}>
+                    <Trans>Verify</Trans>
+                  </Button>
+                )
+              }
+            />
           </Form.Item>
           <Form.Item name="website">
             <Input placeholder={t`Website`} />
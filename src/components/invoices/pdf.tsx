@@ -1,9 +1,13 @@
 import { Trans } from "@lingui/react/macro";
 import { I18nProvider } from "@lingui/react";
 import { Document, Font, Page, Text, View, StyleSheet, Image } from "@react-pdf/renderer";
+import sumBy from "lodash/sumBy";
+import uniq from "lodash/uniq";
 
 import { getFormattedNumber } from "src/utils/currencies";
 import { formatDate } from "src/utils/date";
+import { getLineItemTaxes } from "src/utils/invoice";
+import { formatQuantity } from "src/utils/units";
 
 Font.register({
   family: "Montserrat",
@@ -176,41 +180,29 @@ const InvoicePDF = ({
   client,
   organization,
   taxRates,
+  epcQrCode,
   i18n,
 }: {
   invoice: any;
   client: any;
   organization: any;
   taxRates: any;
+  // PNG data URL of the EPC QR code for the outstanding balance
+  epcQrCode?: string | null;
   i18n: any;
 }) => {
   const dateFormat = organization?.date_format;
-  // Group line items by tax rate and calculate tax for each group
-  const taxGroups = (() => {
-    const groups: { [key: string]: { taxRate: any; items: any[]; subtotal: number; tax: number } } = {};
-
-    invoice.lineItems?.forEach((item: any) => {
-      if (item.total) {
-        const taxRateId = item.taxRate || "no-tax";
-        const taxRate = item.taxRate ? taxRates?.find((rate: any) => rate.id === taxRateId) : null;
-
-        if (!groups[taxRateId]) {
-          groups[taxRateId] = {
-            taxRate,
-            items: [],
-            subtotal: 0,
-            tax: 0,
-          };
-        }
-
-        groups[taxRateId].items.push(item);
-        groups[taxRateId].subtotal += item.total;
-        groups[taxRateId].tax = taxRate?.percentage ? (groups[taxRateId].subtotal * taxRate.percentage) / 100 : 0;
-      }
-    });
-
-    return Object.values(groups);
-  })();
+  // Tax groups and line totals come from the backend through the form, untaxed lines show as 0%
+  const taxGroups = [...(invoice.taxGroups || [])];
+  const untaxedItems = (invoice.lineItems || []).filter((item: any) => item.total && !getLineItemTaxes(item).length);
+  if (untaxedItems.length) {
+    const subtotal = sumBy(untaxedItems, "total");
+    taxGroups.push({ taxRate: null, compound: false, subtotal, tax: 0 });
+  }
+  // Exempt and reverse charge supplies must state why no VAT is charged
+  const exemptionReasons = uniq(
+    taxGroups.map((group) => group.taxRate?.exemptionReason?.trim()).filter((reason) => !!reason),
+  );
   return (
     <I18nProvider i18n={i18n}>
       <Document>
@@ -263,6 +255,11 @@ const InvoicePDF = ({
               <Text style={[styles.smallText, { marginBottom: 8 }]}>
                 <Trans>Due date</Trans>
               </Text>
+              {invoice.paymentReference && (
+                <Text style={[styles.smallText, { marginBottom: 8 }]}>
+                  <Trans>Reference</Trans>
+                </Text>
+              )}
               {invoice.overdueCharge && (
                 <Text style={[styles.smallText, { marginBottom: 8 }]}>
                   <Trans>Overdue charge</Trans>
@@ -272,6 +269,9 @@ const InvoicePDF = ({
             <View>
               <Text style={[styles.smallText, { marginBottom: 8 }]}>{formatDate(invoice.date, dateFormat)}</Text>
               <Text style={[styles.smallText, { marginBottom: 8 }]}>{formatDate(invoice.dueDate, dateFormat)}</Text>
+              {invoice.paymentReference && (
+                <Text style={[styles.smallText, { marginBottom: 8 }]}>{invoice.paymentReference}</Text>
+              )}
               {invoice.overdueCharge && (
                 <Text style={[styles.smallText, { marginBottom: 8 }]}>{invoice.overdueCharge}%</Text>
               )}
@@ -298,7 +298,9 @@ const InvoicePDF = ({
               </Text>
             </View>
             {invoice.lineItems?.map((lineItem: any, index: number) => {
-              const taxRate = taxRates?.find((rate: any) => rate.id === lineItem.taxRate);
+              const lineItemTaxRates = getLineItemTaxes(lineItem)
+                .map((lineItemTax) => taxRates?.find((rate: any) => rate.id === lineItemTax.taxRate))
+                .filter((rate: any) => !!rate);
               const isLastItem = index === invoice.lineItems.length - 1;
               return (
                 <View
@@ -310,12 +312,32 @@ const InvoicePDF = ({
                   ]}
                 >
                   <Text style={[styles.tableCol, styles.tableColFirst, styles.lineItemNumber]}>{index + 1}</Text>
-                  <Text style={[styles.tableCol, styles.lineItemDescription]}>{lineItem.description}</Text>
-                  <Text style={[styles.tableCol, styles.lineItemQuantity]}>{lineItem.quantity}</Text>
+                  <View style={[styles.tableCol, styles.lineItemDescription]}>
+                    <Text>{lineItem.description}</Text>
+                    {!!lineItem.discount && (
+                      <Text>
+                        <Trans>Discount</Trans>{" "}
+                        {lineItem.discountKind === "amount"
+                          ? getFormattedNumber(lineItem.discount, invoice.currency, i18n.locale, organization)
+                          : `${lineItem.discount}%`}
+                      </Text>
+                    )}
+                  </View>
+                  <Text style={[styles.tableCol, styles.lineItemQuantity]}>
+                    {formatQuantity(lineItem.quantity, lineItem.unitCode)}
+                  </Text>
                   <Text style={[styles.tableCol, styles.lineItemUnitPrice]}>
                     {getFormattedNumber(lineItem.unitPrice, invoice.currency, i18n.locale, organization)}
                   </Text>
-                  <Text style={[styles.tableCol, styles.lineItemTax]}>{taxRate ? `${taxRate.percentage}%` : ""}</Text>
+                  <Text style={[styles.tableCol, styles.lineItemTax]}>
+                    {lineItemTaxRates
+                      .map((rate: any, index: number) =>
+                        rate.withholding
+                          ? `${index ? " - " : "-"}${rate.percentage}%`
+                          : `${index ? " + " : ""}${rate.percentage}%`
+                      )
+                      .join("")}
+                  </Text>
                   <Text style={[styles.tableCol, styles.tableColLast, styles.lineItemTotal]}>
                     {getFormattedNumber(lineItem.total, invoice.currency, i18n.locale, organization)}
                   </Text>
@@ -327,12 +349,28 @@ const InvoicePDF = ({
           <View style={[styles.row, { marginTop: 20 }]}>
             <View style={[{ width: "60%" }, { paddingRight: 20 }]}>
               {invoice.customerNotes && <Text style={styles.notes}>{invoice.customerNotes}</Text>}
+              {exemptionReasons.map((reason) => (
+                <Text key={reason} style={[styles.notes, { marginTop: 8 }]}>
+                  {reason}
+                </Text>
+              ))}
             </View>
             <View style={{ width: "40%" }}>
               <View>
+                {!!invoice.invoiceDiscount && (
+                  <View style={{ flexDirection: "row", justifyContent: "space-between", paddingVertical: 4 }}>
+                    <Text style={[styles.smallText, styles.nowrap]}>
+                      <Trans>Discount</Trans>
+                      {invoice.discountKind === "amount" ? "" : ` ${invoice.discount}%`}
+                    </Text>
+                    <Text style={[styles.smallText]}>
+                      {getFormattedNumber(-invoice.invoiceDiscount, invoice.currency, i18n.locale, organization)}
+                    </Text>
+                  </View>
+                )}
                 <View style={{ flexDirection: "row", justifyContent: "space-between", paddingVertical: 4 }}>
                   <Text style={[styles.smallText, styles.nowrap]}>
-                    <Trans>Subtotal</Trans>
+                    {invoice.pricesIncludeTax ? <Trans>Subtotal excl. tax</Trans> : <Trans>Subtotal</Trans>}
                   </Text>
                   <Text style={[styles.smallText]}>
                     {getFormattedNumber(invoice.subTotal, invoice.currency, i18n.locale, organization)}
@@ -344,13 +382,36 @@ const InvoicePDF = ({
                     style={{ flexDirection: "row", justifyContent: "space-between", paddingVertical: 4 }}
                   >
                     <Text style={[styles.smallText, styles.nowrap]}>
-                      {group.taxRate ? `${group.taxRate.name} ${group.taxRate.percentage}%` : <Trans>Tax 0%</Trans>}
+                      {group.taxRate ? (
+                        group.compound ? (
+                          <Trans>
+                            {group.taxRate.name} {group.taxRate.percentage}% (compound)
+                          </Trans>
+                        ) : (
+                          `${group.taxRate.name} ${group.taxRate.percentage}%`
+                        )
+                      ) : (
+                        <Trans>Tax 0%</Trans>
+                      )}
                     </Text>
                     <Text style={[styles.smallText]}>
                       {getFormattedNumber(group.tax, invoice.currency, i18n.locale, organization)}
                     </Text>
                   </View>
                 ))}
+                {(invoice.withholdings || []).map((group: any, index: number) => (
+                  <View
+                    key={`withholding-${index}`}
+                    style={{ flexDirection: "row", justifyContent: "space-between", paddingVertical: 4 }}
+                  >
+                    <Text style={[styles.smallText, styles.nowrap]}>
+                      {`${group.taxRate?.name} ${group.taxRate?.percentage}%`}
+                    </Text>
+                    <Text style={[styles.smallText]}>
+                      {getFormattedNumber(-group.tax, invoice.currency, i18n.locale, organization)}
+                    </Text>
+                  </View>
+                ))}
               </View>
               <View
                 style={{
@@ -378,15 +439,28 @@ const InvoicePDF = ({
             </View>
           </View>
 
+          {epcQrCode && (
+            <View style={{ width: 90, alignItems: "center" }}>
+              <Image src={epcQrCode} style={{ width: 90, height: 90 }} />
+              <Text style={styles.smallText}>
+                <Trans>Scan to pay</Trans>
+              </Text>
+            </View>
+          )}
+
           <View style={styles.footer}>
             <Text style={[styles.text]}>
               {organization.bank_name} {organization.iban}
             </Text>
             {organization.registration_number && (
-              <Text style={[styles.text, { textAlign: "center" }]}>Reg. nr {organization.registration_number}</Text>
+              <Text style={[styles.text, { textAlign: "center" }]}>
+                <Trans>Registration number</Trans> {organization.registration_number}
+              </Text>
             )}
             {organization.vatin && (
-              <Text style={[styles.text, { textAlign: "right" }]}>VATIN {organization.vatin}</Text>
+              <Text style={[styles.text, { textAlign: "right" }]}>
+                <Trans>VATIN</Trans> {organization.vatin}
+              </Text>
             )}
           </View>
         </Page>
@@ -10,6 +10,8 @@ import { updateInvoiceStateAtom } from "src/atoms/invoice";
 const stateColor = {
   draft: null,
   sent: "geekblue",
+  partially_paid: "cyan",
+  overdue: "orange",
   paid: "green",
   void: "volcano",
 };
@@ -30,6 +32,14 @@ const InvoiceStateSelect = ({ invoice }: { invoice: any }) => {
       key: "sent",
       label: <Trans>Sent</Trans>,
     },
+    {
+      key: "partially_paid",
+      label: <Trans>Partially paid</Trans>,
+    },
+    {
+      key: "overdue",
+      label: <Trans>Overdue</Trans>,
+    },
     {
       key: "paid",
       label: <Trans>Paid</Trans>,
@@ -58,6 +68,10 @@ const InvoiceStateSelect = ({ invoice }: { invoice: any }) => {
             <Trans>Draft</Trans>
           ) : invoice.state === "sent" ? (
             <Trans>Sent</Trans>
+          ) : invoice.state === "partially_paid" ? (
+            <Trans>Partially paid</Trans>
+          ) : invoice.state === "overdue" ? (
+            <Trans>Overdue</Trans>
           ) : invoice.state === "paid" ? (
             <Trans>Paid</Trans>
           ) : invoice.state === "void" ? (
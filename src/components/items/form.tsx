@@ -0,0 +1,139 @@
+import { useEffect } from "react";
+import { useParams, useNavigate } from "react-router";
+import { Button, Form, Input, InputNumber, Modal, Popconfirm, Select } from "antd";
+import { atom, useAtom, useSetAtom, useAtomValue } from "jotai";
+import { loadable } from "jotai/utils";
+import { DeleteOutlined } from "@ant-design/icons";
+import { Trans } from "@lingui/react/macro";
+import { t } from "@lingui/core/macro";
+import isEmpty from "lodash/isEmpty";
+
+import { itemIdAtom, itemAtom, deleteItemAtom } from "src/atoms/item";
+import { organizationAtom } from "src/atoms/organization";
+import { taxRatesAtom, setTaxRatesAtom } from "src/atoms/tax-rate";
+import { minorUnitExponent } from "src/utils/currency";
+import { getUnits } from "src/utils/units";
+
+const submittingAtom = atom(false);
+const loadableItemAtom = loadable(itemAtom);
+
+const ItemForm = () => {
+  const navigate = useNavigate();
+  const { id } = useParams<string>();
+
+  const [form] = Form.useForm();
+
+  const setItemId = useSetAtom(itemIdAtom);
+  const item = useAtomValue(loadableItemAtom);
+  const setItem = useSetAtom(itemAtom);
+  const deleteItem = useSetAtom(deleteItemAtom);
+  const taxRates = useAtomValue(taxRatesAtom);
+  const setTaxRates = useSetAtom(setTaxRatesAtom);
+  const organization = useAtomValue(organizationAtom);
+  const [submitting, setSubmitting] = useAtom(submittingAtom);
+  const precision = minorUnitExponent(organization?.currency);
+
+  const close = () => {
+    form.resetFields();
+    setItemId(null);
+    navigate("/settings/items");
+  };
+
+  const handleSubmit = async (values: any) => {
+    setSubmitting(true);
+    setItem(values);
+    close();
+    setSubmitting(false);
+  };
+
+  const handleDelete = async () => {
+    if (!id) return;
+    setSubmitting(true);
+    await deleteItem(id);
+    close();
+    setSubmitting(false);
+  };
+
+  useEffect(() => {
+    setTaxRates();
+  }, [setTaxRates]);
+
+  useEffect(() => {
+    if (id) {
+      setItemId(id);
+    } else {
+      // Clear form when opening for new item
+      form.resetFields();
+    }
+  }, [id, form, setItemId]);
+
+  return (
+    <Modal
+      title={id ? <Trans>Edit item</Trans> : <Trans>New item</Trans>}
+      open={true}
+      onCancel={close}
+      footer={[
+        <div key="footer" style={{ display: "flex", justifyContent: "space-between", width: "100%" }}>
+          <div>
+            {id && (
+              <Popconfirm
+                title={<Trans>Are you sure you want to delete this item?</Trans>}
+                description={<Trans>Invoices that use it keep their line items.</Trans>}
+                onConfirm={handleDelete}
+                okText={<Trans>Yes</Trans>}
+                cancelText={<Trans>No</Trans>}
+                placement="topRight"
+              >
+                <Button icon={<DeleteOutlined />} loading={submitting} danger>
+                  <Trans>Delete</Trans>
+                </Button>
+              </Popconfirm>
+            )}
+          </div>
+          <div>
+            <Button onClick={close} style={{ marginRight: 8 }}>
+              <Trans>Cancel</Trans>
+            </Button>
+            <Button type="primary" loading={submitting} onClick={() => form.submit()}>
+              <Trans>Save</Trans>
+            </Button>
+          </div>
+        </div>,
+      ]}
+      forceRender={true}
+    >
+      {(!id || (item.state === "hasData" && !isEmpty(item.data))) && (
+        <Form
+          form={form}
+          layout="vertical"
+          onFinish={handleSubmit}
+          initialValues={item.state === "hasData" && item.data ? item.data : { unitPrice: 0 }}
+        >
+          <Form.Item name="name" rules={[{ required: true, whitespace: true, message: t`Please input name!` }]}>
+            <Input placeholder={t`Name`} />
+          </Form.Item>
+          <Form.Item name="description" extra={t`Copied to line items, the name is used when left empty`}>
+            <Input.TextArea rows={3} placeholder={t`Description`} />
+          </Form.Item>
+          <Form.Item name="unitPrice" label={t`Unit price`}>
+            <InputNumber min={0} step={Math.pow(10, -precision)} precision={precision} style={{ width: "100%" }} />
+          </Form.Item>
+          <Form.Item name="unitCode" label={t`Unit`}>
+            <Select allowClear options={getUnits().map((unit) => ({ value: unit.code, label: unit.name }))} />
+          </Form.Item>
+          <Form.Item name="taxRateId" label={t`Tax rate`}>
+            <Select
+              allowClear
+              options={taxRates.map((taxRate: any) => ({ value: taxRate.id, label: taxRate.name }))}
+            />
+          </Form.Item>
+          <Form.Item name="sku" label={t`SKU`}>
+            <Input />
+          </Form.Item>
+        </Form>
+      )}
+    </Modal>
+  );
+};
+
+export default ItemForm;
@@ -1,6 +1,6 @@
 import { useEffect } from "react";
 import { useParams, useNavigate } from "react-router";
-import { Checkbox, Form, Input, Modal } from "antd";
+import { Checkbox, Form, Input, Modal, Select } from "antd";
 import { atom, useAtom, useSetAtom, useAtomValue } from "jotai";
 import { loadable } from "jotai/utils";
 import { Trans } from "@lingui/react/macro";
@@ -60,7 +60,7 @@ const TaxRateForm = () => {
           form={form}
           layout="vertical"
           onFinish={handleSubmit}
-          initialValues={taxRate.state === "hasData" ? taxRate.data : undefined}
+          initialValues={taxRate.state === "hasData" && taxRate.data ? taxRate.data : { category: "standard" }}
         >
           <Form.Item name="name" rules={[{ required: true, message: t`Please input name!` }]}>
             <Input placeholder={t`Name`} />
@@ -71,6 +71,46 @@ const TaxRateForm = () => {
           <Form.Item name="percentage" rules={[{ required: true, message: t`Please input a percentage!` }]}>
             <Input placeholder={t`Percentage`} />
           </Form.Item>
+          <Form.Item name="category" label={t`Category`}>
+            <Select
+              options={[
+                { value: "standard", label: t`Standard` },
+                { value: "zero_rated", label: t`Zero rated` },
+                { value: "exempt", label: t`Exempt` },
+                { value: "reverse_charge", label: t`Reverse charge` },
+                { value: "outside_scope", label: t`Outside the scope of VAT` },
+              ]}
+            />
+          </Form.Item>
+          <Form.Item noStyle shouldUpdate={(prev, next) => prev.category !== next.category}>
+            {({ getFieldValue }) =>
+              getFieldValue("category") &&
+              getFieldValue("category") !== "standard" &&
+              getFieldValue("category") !== "zero_rated" && (
+                <Form.Item name="exemptionReason" extra={t`Printed on invoices that use this tax rate`}>
+                  <Input.TextArea rows={2} placeholder={t`Exemption reason`} />
+                </Form.Item>
+              )
+            }
+          </Form.Item>
+          <Form.Item
+            name="compound"
+            valuePropName="checked"
+            extra={t`Charged on the line amount plus the line's other taxes, e.g. a provincial tax on top of GST`}
+          >
+            <Checkbox>
+              <Trans>Compound tax</Trans>
+            </Checkbox>
+          </Form.Item>
+          <Form.Item
+            name="withholding"
+            valuePropName="checked"
+            extra={t`Income tax the client withholds, e.g. IRPF or ritenuta d'acconto. Taken off the amount due.`}
+          >
+            <Checkbox>
+              <Trans>Withholding tax</Trans>
+            </Checkbox>
+          </Form.Item>
           <Form.Item name="isDefault" valuePropName="checked">
             <Checkbox>
               <Trans>Default</Trans>
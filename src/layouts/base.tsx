@@ -11,6 +11,7 @@ import {
   BankOutlined,
   FileOutlined,
   CalculatorOutlined,
+  AppstoreOutlined,
   PlusOutlined,
   FieldTimeOutlined,
   DatabaseOutlined,
@@ -19,6 +20,7 @@ import {
   ProjectOutlined,
   RobotOutlined,
   CommentOutlined,
+  SwapOutlined,
 } from "@ant-design/icons";
 import { Trans } from "@lingui/react/macro";
 import { useLingui } from "@lingui/react";
@@ -210,6 +212,24 @@ export default function BaseLayout() {
                   ),
                   key: "settings.tax-rates",
                 },
+                {
+                  icon: <AppstoreOutlined />,
+                  label: (
+                    <Link to="/settings/items">
+                      <Trans>Items</Trans>
+                    </Link>
+                  ),
+                  key: "settings.items",
+                },
+                {
+                  icon: <SwapOutlined />,
+                  label: (
+                    <Link to="/settings/exchange-rates">
+                      <Trans>Exchange rates</Trans>
+                    </Link>
+                  ),
+                  key: "settings.exchange-rates",
+                },
                 {
                   icon: <DatabaseOutlined />,
                   label: (
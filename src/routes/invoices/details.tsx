@@ -1,8 +1,9 @@
-import { useEffect, useState, useMemo, useCallback } from "react";
+import { useEffect, useState, useCallback } from "react";
 import { createPortal } from "react-dom";
 import { useLocation, useNavigate, useParams } from "react-router";
 import {
   Button,
+  Checkbox,
   DatePicker,
   Divider,
   Form,
@@ -18,6 +19,7 @@ import {
   Popconfirm,
   theme,
   Spin,
+  Typography,
 } from "antd";
 import { useAtom, useAtomValue, useSetAtom } from "jotai";
 import { Trans } from "@lingui/react/macro";
@@ -53,6 +55,7 @@ import {
 import {
   CSS,
 } from '@dnd-kit/utilities';
+import { invoke } from "@tauri-apps/api/core";
 import { save } from "@tauri-apps/plugin-dialog";
 import { writeFile } from "@tauri-apps/plugin-fs";
 import { pdf } from "@react-pdf/renderer";
@@ -74,7 +77,6 @@ import lowerCase from "lodash/lowerCase";
 import find from "lodash/find";
 import filter from "lodash/filter";
 import map from "lodash/map";
-import sum from "lodash/sum";
 import isNumber from "lodash/isNumber";
 import toNumber from "lodash/toNumber";
 
@@ -82,14 +84,29 @@ import { clientsAtom, setClientsAtom } from "src/atoms/client";
 import { useDatePickerFormat } from "src/utils/date";
 import { invoiceIdAtom, invoiceAtom, deleteInvoiceAtom, duplicateInvoiceAtom } from "src/atoms/invoice";
 import { organizationAtom, nextInvoiceNumberAtom } from "src/atoms/organization";
+import { itemsAtom, setItemsAtom } from "src/atoms/item";
 import { taxRatesAtom, setTaxRatesAtom } from "src/atoms/tax-rate";
 import { aiInvoiceDataAtom } from "src/atoms/ai";
 import { siderAtom } from "src/atoms/generic";
 import ClientForm from "src/components/clients/form.tsx";
 import InvoicePDF from "src/components/invoices/pdf";
 import { currencies } from "src/utils/currencies";
-import { generateInvoiceNumber } from "src/utils/invoice";
-import { multiplyDecimal, divideDecimal, calculateTax, addDecimal } from "src/utils/currency";
+import {
+  discountToCents,
+  generateInvoiceNumber,
+  getLineItemTaxes,
+  toLineItemTaxes,
+  type TaxGroup,
+} from "src/utils/invoice";
+import {
+  multiplyDecimal,
+  divideDecimal,
+  addDecimal,
+  centsToUnits,
+  minorUnitExponent,
+  unitsToCents,
+} from "src/utils/currency";
+import { getUnits } from "src/utils/units";
 
 const { TextArea } = Input;
 const { Option } = Select;
@@ -298,6 +315,8 @@ const InvoiceDetails: React.FC = () => {
   const setClients = useSetAtom(setClientsAtom);
   const taxRates = useAtomValue(taxRatesAtom);
   const setTaxRates = useSetAtom(setTaxRatesAtom);
+  const items = useAtomValue(itemsAtom);
+  const setItems = useSetAtom(setItemsAtom);
   const deleteInvoice = useSetAtom(deleteInvoiceAtom);
   const duplicateInvoice = useSetAtom(duplicateInvoiceAtom);
   const nextInvoiceNumber = useAtomValue(nextInvoiceNumberAtom);
@@ -323,6 +342,7 @@ const InvoiceDetails: React.FC = () => {
   useEffect(() => {
     setClients();
     setTaxRates();
+    setItems();
     if (!isNew) {
       setInvoiceId(id || null);
     }
@@ -331,7 +351,7 @@ const InvoiceDetails: React.FC = () => {
     return () => {
       setInvoiceId(null);
     };
-  }, [id, isNew, setClients, setInvoiceId, setTaxRates]);
+  }, [id, isNew, setClients, setInvoiceId, setItems, setTaxRates]);
 
   // Navigate to the new invoice after successful creation
   useEffect(() => {
@@ -340,14 +360,22 @@ const InvoiceDetails: React.FC = () => {
     }
   }, [isNew, invoiceId, navigate]);
 
+  // New lines get the default tax rate
+  const defaultTaxes = () => {
+    const defaultTaxRate = find(taxRates, { isDefault: 1 });
+    return defaultTaxRate ? toLineItemTaxes([defaultTaxRate.id], taxRates) : [];
+  };
+
   const getInitialValues = () => {
     let values = {
       currency: organization.currency,
       date: dayjs(),
       dueDate: organization.due_days ? dayjs().add(organization.due_days, "day") : null,
-      lineItems: [{ quantity: 1, taxRate: get(find(taxRates, { isDefault: 1 }), "id") }],
+      lineItems: [{ quantity: 1, taxes: defaultTaxes() }],
       customerNotes: organization.customerNotes,
       overdueCharge: organization.overdueCharge || 0,
+      pricesIncludeTax: !!organization.pricesIncludeTax,
+      taxRounding: organization.taxRounding || "per_total",
       number: isNew ? nextInvoiceNumber || "" : undefined,
     };
 
@@ -362,7 +390,10 @@ const InvoiceDetails: React.FC = () => {
         // Ensure lineItems have taxRate field name (not taxRateId) and calculate totals
         lineItems: aiInvoiceData.lineItems.map((item: any) => ({
           ...item,
-          taxRate: item.taxRateId || item.taxRate || get(find(taxRates, { isDefault: 1 }), "id"),
+          taxes:
+            item.taxRateId || item.taxRate
+              ? toLineItemTaxes([item.taxRateId || item.taxRate], taxRates)
+              : defaultTaxes(),
           total: multiplyDecimal(item.quantity, item.unitPrice),
         })),
       };
@@ -408,7 +439,10 @@ const InvoiceDetails: React.FC = () => {
         lineItems: aiInvoiceData.lineItems
           ? aiInvoiceData.lineItems.map((item: any) => ({
               ...item,
-              taxRate: item.taxRateId || item.taxRate || get(find(taxRates, { isDefault: 1 }), "id"),
+              taxes:
+                item.taxRateId || item.taxRate
+                  ? toLineItemTaxes([item.taxRateId || item.taxRate], taxRates)
+                  : defaultTaxes(),
               total: multiplyDecimal(item.quantity, item.unitPrice),
             }))
           : currentValues.lineItems,
@@ -461,6 +495,24 @@ const InvoiceDetails: React.FC = () => {
     }
   };
 
+  // Fills a line from a catalog item. The line keeps copies, so it can be edited freely.
+  const handleItemSelect = (index: number, itemId?: string) => {
+    const item = find(items, { id: itemId });
+    if (!item) return;
+
+    const quantity = form.getFieldValue(["lineItems", index, "quantity"]) || 1;
+    form.setFieldValue(["lineItems", index], {
+      ...form.getFieldValue(["lineItems", index]),
+      itemId: item.id,
+      description: item.description || item.name,
+      quantity,
+      unitCode: item.unitCode,
+      unitPrice: item.unitPrice,
+      taxes: item.taxRateId ? toLineItemTaxes([item.taxRateId], taxRates) : defaultTaxes(),
+      total: multiplyDecimal(quantity, item.unitPrice),
+    });
+  };
+
   // Handle drag end event
   const handleDragEnd = (event: DragEndEvent) => {
     const { active, over } = event;
@@ -477,46 +529,98 @@ const InvoiceDetails: React.FC = () => {
     }
   };
   const lineItems = Form.useWatch("lineItems", form);
-
-  const subTotal = sum(
-    map(
-      filter(lineItems, (item) => isNumber(get(item, "total"))),
-      "total"
-    )
-  );
-  // Group line items by tax rate and calculate tax for each group
-  const taxGroups = useMemo(() => {
-    const groups: { [key: string]: { taxRate: any; items: any[]; subtotal: number; tax: number } } = {};
-
-    if (lineItems && Array.isArray(lineItems)) {
-      lineItems.forEach((item: any) => {
-        if (isNumber(get(item, "total")) && get(item, "taxRate")) {
-          const taxRateId = get(item, "taxRate");
-          const taxRate = find(taxRates, { id: taxRateId });
-
-          if (!groups[taxRateId]) {
-            groups[taxRateId] = {
-              taxRate,
-              items: [],
-              subtotal: 0,
-              tax: 0,
-            };
-          }
-
-          groups[taxRateId].items.push(item);
-          groups[taxRateId].subtotal = addDecimal(groups[taxRateId].subtotal, item.total);
-          groups[taxRateId].tax = taxRate?.percentage
-            ? calculateTax(groups[taxRateId].subtotal, taxRate.percentage)
-            : 0;
-        }
-      });
+  const pricesIncludeTax = Form.useWatch("pricesIncludeTax", form);
+  const taxRounding = Form.useWatch("taxRounding", form);
+  const discount = Form.useWatch("discount", form);
+  const discountKind = Form.useWatch("discountKind", form);
+  const currency = Form.useWatch("currency", form) || organization?.currency;
+  const precision = minorUnitExponent(currency);
+
+  // Totals are calculated by the backend, the same way they are when the invoice is saved
+  const [backendTotals, setBackendTotals] = useState<any>(null);
+  useEffect(() => {
+    let cancelled = false;
+    invoke<any>("calculate_invoice_totals", {
+      organizationId: organization?.id,
+      lineItems: filter(lineItems, (item) => isNumber(get(item, "total"))).map((item: any) => ({
+        description: item.description,
+        quantity: item.quantity,
+        unitCode: item.unitCode,
+        unitPrice: unitsToCents(item.unitPrice, precision),
+        discount: discountToCents(item.discount, item.discountKind, currency),
+        discountKind: item.discountKind || "percentage",
+        taxes: getLineItemTaxes(item),
+      })),
+      pricesIncludeTax: !!pricesIncludeTax,
+      taxRounding,
+      discount: discountToCents(discount, discountKind, currency),
+      discountKind: discountKind || "percentage",
+      currency,
+    })
+      .then((totals) => {
+        if (!cancelled) setBackendTotals(totals);
+      })
+      .catch((error) => console.error("Failed to calculate invoice totals:", error));
+    return () => {
+      cancelled = true;
+    };
+  }, [
+    lineItems,
+    pricesIncludeTax,
+    taxRounding,
+    discount,
+    discountKind,
+    currency,
+    precision,
+    organization?.id,
+  ]);
+
+  // Tax per rate, a compound tax apart from the same rate charged on its own
+  const taxGroups: TaxGroup[] = (backendTotals?.taxes || []).map((tax: any) => ({
+    taxRate: find(taxRates, { id: tax.taxRateId }) || { id: tax.taxRateId, name: tax.name, percentage: tax.percentage },
+    compound: tax.compound,
+    subtotal: centsToUnits(tax.taxableAmount, precision),
+    tax: centsToUnits(tax.amount, precision),
+  }));
+
+  const subTotal = backendTotals ? centsToUnits(backendTotals.subTotal, precision) : 0;
+  const taxTotal = backendTotals ? centsToUnits(backendTotals.taxTotal, precision) : 0;
+  // Withheld tax is taken off what the client pays
+  const withholdings: TaxGroup[] = (backendTotals?.withholdings || []).map((withholding: any) => ({
+    taxRate: find(taxRates, { id: withholding.taxRateId }) || {
+      id: withholding.taxRateId,
+      name: withholding.name,
+      percentage: withholding.percentage,
+    },
+    compound: false,
+    subtotal: centsToUnits(withholding.taxableAmount, precision),
+    tax: centsToUnits(withholding.amount, precision),
+  }));
+  const withholdingTotal = backendTotals ? centsToUnits(backendTotals.withholdingTotal, precision) : 0;
+  const total = backendTotals ? centsToUnits(backendTotals.total, precision) : 0;
+  const discountTotal = backendTotals ? centsToUnits(backendTotals.discountTotal, precision) : 0;
+
+  // EPC QR code for saved EUR invoices with a balance, left out when the
+  // organization's bank details can't make one
+  const [epcQrCode, setEpcQrCode] = useState<string | null>(null);
+  useEffect(() => {
+    if (!invoice?.id || invoice.currency !== "EUR" || !(invoice.balanceDue > 0)) {
+      setEpcQrCode(null);
+      return;
     }
 
-    return Object.values(groups);
-  }, [lineItems, taxRates]);
-
-  const taxTotal = sum(map(taxGroups, "tax"));
-  const total = addDecimal(subTotal, taxTotal);
+    let cancelled = false;
+    invoke<any>("get_epc_qr_code", { invoiceId: invoice.id, format: "png" })
+      .then((qrCode) => {
+        if (!cancelled) setEpcQrCode(qrCode.image);
+      })
+      .catch(() => {
+        if (!cancelled) setEpcQrCode(null);
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [invoice?.id, invoice?.currency, invoice?.balanceDue]);
 
   // Helper function to create PDF document with current form data
   const createPDFDocument = () => {
@@ -531,6 +635,7 @@ const InvoiceDetails: React.FC = () => {
     }
 
     // Create merged invoice data with form values and computed totals
+    let lineIndex = 0;
     const invoiceForPDF = {
       ...invoice, // Start with database data
       ...formValues, // Override with current form values
@@ -538,8 +643,17 @@ const InvoiceDetails: React.FC = () => {
       subTotal,
       taxTotal,
       total,
-      // Ensure line items have the correct totals
-      lineItems: formValues.lineItems || [],
+      taxGroups,
+      withholdings,
+      withholdingTotal,
+      discountTotal,
+      invoiceDiscount: backendTotals ? centsToUnits(backendTotals.invoiceDiscount, precision) : 0,
+      // Line totals after their own discount, as calculated by the backend
+      lineItems: (formValues.lineItems || []).map((item: any) => {
+        if (!isNumber(get(item, "total"))) return item;
+        const lineTotal = backendTotals?.lines?.[lineIndex++];
+        return lineTotal ? { ...item, total: centsToUnits(lineTotal.total, precision) } : item;
+      }),
     };
 
     return (
@@ -548,6 +662,7 @@ const InvoiceDetails: React.FC = () => {
         client={clientData}
         organization={organization}
         taxRates={taxRates}
+        epcQrCode={epcQrCode}
         i18n={i18n}
       />
     );
@@ -631,7 +746,7 @@ const InvoiceDetails: React.FC = () => {
                   name="number"
                   rules={[{ required: true, message: t`This field is required!` }]}
                 >
-                  <Input />
+                  <Input disabled />
                 </Form.Item>
               </Col>
               <Col span={6}>
@@ -685,6 +800,39 @@ const InvoiceDetails: React.FC = () => {
                 </Form.Item>
               </Col>
             </Row>
+            <Row gutter={24}>
+              <Col span={4} offset={8}>
+                <Form.Item label={t`Discount`} name="discount">
+                  <InputNumber
+                    style={{ width: "100%" }}
+                    min={0}
+                    addonAfter={
+                      <Form.Item name="discountKind" initialValue="percentage" noStyle>
+                        <Select style={{ width: 72 }}>
+                          <Option value="percentage">%</Option>
+                          <Option value="amount">{organization.currency}</Option>
+                        </Select>
+                      </Form.Item>
+                    }
+                  />
+                </Form.Item>
+              </Col>
+              <Col span={8}>
+                <Form.Item name="pricesIncludeTax" valuePropName="checked">
+                  <Checkbox>
+                    <Trans>Prices include tax</Trans>
+                  </Checkbox>
+                </Form.Item>
+              </Col>
+              <Col span={4}>
+                <Form.Item label={t`Tax rounding`} name="taxRounding">
+                  <Select>
+                    <Option value="per_total">{t`Per tax total`}</Option>
+                    <Option value="per_line">{t`Per line`}</Option>
+                  </Select>
+                </Form.Item>
+              </Col>
+            </Row>
 
             <Row gutter={16} style={{ marginTop: "20px" }}>
               <Col span={24}>
@@ -724,6 +872,22 @@ const InvoiceDetails: React.FC = () => {
                               }}
                               render={(field, record) => (
                                 <DragHandleCell rowKey={record.index.toString()}>
+                                  {items.length > 0 && (
+                                    <Form.Item name={[field.name, "itemId"]} noStyle>
+                                      <Select
+                                        showSearch
+                                        allowClear
+                                        optionFilterProp="label"
+                                        placeholder={t`Catalog item`}
+                                        style={{ width: "100%", marginBottom: 4 }}
+                                        options={map(items, (item: any) => ({
+                                          value: item.id,
+                                          label: item.sku ? `${item.name} (${item.sku})` : item.name,
+                                        }))}
+                                        onChange={(itemId) => handleItemSelect(field.name, itemId)}
+                                      />
+                                    </Form.Item>
+                                  )}
                                   <Form.Item
                                     name={[field.name, "description"]}
                                     rules={[{ required: true, message: t`This field is required!` }]}
@@ -746,6 +910,7 @@ const InvoiceDetails: React.FC = () => {
                                 >
                                   <InputNumber
                                     style={{ width: "100%" }}
+                                    precision={organization.quantityPrecision}
                                     onChange={(value) => {
                                       const total = form.getFieldValue(["lineItems", field.key, "total"]);
                                       const unitPrice = form.getFieldValue(["lineItems", field.key, "unitPrice"]);
@@ -769,6 +934,20 @@ const InvoiceDetails: React.FC = () => {
                                 </Form.Item>
                               )}
                             />
+                            <Table.Column
+                              title={t`Unit`}
+                              key="unitCode"
+                              width={110}
+                              render={(field) => (
+                                <Form.Item name={[field.name, "unitCode"]} noStyle>
+                                  <Select
+                                    style={{ width: "100%" }}
+                                    allowClear
+                                    options={map(getUnits(), (unit) => ({ value: unit.code, label: unit.name }))}
+                                  />
+                                </Form.Item>
+                              )}
+                            />
                             <Table.Column
                               title={t`Price`}
                               key="unitPrice"
@@ -781,6 +960,7 @@ const InvoiceDetails: React.FC = () => {
                                 >
                                   <InputNumber
                                     style={{ width: "100%" }}
+                                    precision={precision}
                                     onChange={(value) => {
                                       const total = form.getFieldValue(["lineItems", field.key, "total"]);
                                       const quantity = form.getFieldValue(["lineItems", field.key, "quantity"]);
@@ -804,13 +984,49 @@ const InvoiceDetails: React.FC = () => {
                                 </Form.Item>
                               )}
                             />
+                            <Table.Column
+                              title={t`Discount`}
+                              key="discount"
+                              width={150}
+                              render={(field) => (
+                                <Form.Item name={[field.name, "discount"]} noStyle>
+                                  <InputNumber
+                                    style={{ width: "100%" }}
+                                    min={0}
+                                    addonAfter={
+                                      <Form.Item
+                                        name={[field.name, "discountKind"]}
+                                        initialValue="percentage"
+                                        noStyle
+                                      >
+                                        <Select style={{ width: 72 }}>
+                                          <Option value="percentage">%</Option>
+                                          <Option value="amount">{organization.currency}</Option>
+                                        </Select>
+                                      </Form.Item>
+                                    }
+                                  />
+                                </Form.Item>
+                              )}
+                            />
                             <Table.Column
                               title={t`Tax %`}
-                              key="taxRate"
-                              width={120}
+                              key="taxes"
+                              width={160}
                               render={(field) => (
-                                <Form.Item name={[field.name, "taxRate"]} noStyle>
-                                  <Select style={{ width: "100%" }} allowClear placeholder="Select tax">
+                                <Form.Item
+                                  name={[field.name, "taxes"]}
+                                  noStyle
+                                  getValueProps={(taxes) => ({ value: map(taxes, "taxRate") })}
+                                  normalize={(taxRateIds, previous) => toLineItemTaxes(taxRateIds, taxRates, previous)}
+                                >
+                                  <Select
+                                    mode="multiple"
+                                    maxTagCount="responsive"
+                                    style={{ width: "100%" }}
+                                    allowClear
+                                    placeholder="Select tax"
+                                  >
                                     {map(taxRates, (rate: any) => {
                                       return (
                                         <Option value={rate.id} key={rate.id}>
@@ -878,7 +1094,7 @@ const InvoiceDetails: React.FC = () => {
                         <Button
                           type="default"
                           size="small"
-                          onClick={() => add({ quantity: 1, taxRate: get(find(taxRates, { isDefault: 1 }), "id") })}
+                          onClick={() => add({ quantity: 1, taxes: defaultTaxes() })}
                           icon={<PlusOutlined />}
                         >
                           <Trans>Add line item</Trans>
@@ -921,7 +1137,18 @@ const InvoiceDetails: React.FC = () => {
                     },
                   }}
                 >
-                  <Descriptions.Item label={<Trans>Subtotal</Trans>}>
+                  {discountTotal !== 0 && (
+                    <Descriptions.Item label={<Trans>Discount</Trans>}>
+                      {Intl.NumberFormat(i18n.locale, {
+                        style: "currency",
+                        currency: organization.currency,
+                        minimumFractionDigits: organization.minimum_fraction_digits,
+                      }).format(-discountTotal)}
+                    </Descriptions.Item>
+                  )}
+                  <Descriptions.Item
+                    label={pricesIncludeTax ? <Trans>Subtotal excl. tax</Trans> : <Trans>Subtotal</Trans>}
+                  >
                     {Intl.NumberFormat(i18n.locale, {
                       style: "currency",
                       currency: organization.currency,
@@ -931,8 +1158,12 @@ const InvoiceDetails: React.FC = () => {
                   {taxGroups.length > 0 ? (
                     taxGroups.map((group) => (
                       <Descriptions.Item
-                        key={group.taxRate?.id}
-                        label={`${group.taxRate?.name || "Tax"} ${group.taxRate?.percentage || 0}%`}
+                        key={`${group.taxRate?.id}-${group.compound}`}
+                        label={
+                          group.compound
+                            ? t`${group.taxRate?.name || "Tax"} ${group.taxRate?.percentage || 0}% (compound)`
+                            : `${group.taxRate?.name || "Tax"} ${group.taxRate?.percentage || 0}%`
+                        }
                       >
                         {Intl.NumberFormat(i18n.locale, {
                           style: "currency",
@@ -950,6 +1181,18 @@ const InvoiceDetails: React.FC = () => {
                       }).format(0)}
                     </Descriptions.Item>
                   )}
+                  {withholdings.map((group) => (
+                    <Descriptions.Item
+                      key={`withholding-${group.taxRate?.id}`}
+                      label={`${group.taxRate?.name} ${group.taxRate?.percentage}%`}
+                    >
+                      {Intl.NumberFormat(i18n.locale, {
+                        style: "currency",
+                        currency: organization.currency,
+                        minimumFractionDigits: organization.minimum_fraction_digits,
+                      }).format(-group.tax)}
+                    </Descriptions.Item>
+                  ))}
                   <Descriptions.Item
                     label={
                       <strong>
@@ -965,6 +1208,18 @@ const InvoiceDetails: React.FC = () => {
                       }).format(total)}
                     </strong>
                   </Descriptions.Item>
+                  {invoice?.exchangeRate && invoice.baseCurrency !== invoice.currency && (
+                    <Descriptions.Item label={<Trans>Exchange rate</Trans>}>
+                      {`1 ${invoice.baseCurrency} = ${invoice.exchangeRate} ${invoice.currency} (${invoice.exchangeRateDate})`}
+                    </Descriptions.Item>
+                  )}
+                  {invoice?.baseCurrency && !invoice.exchangeRate && invoice.baseCurrency !== invoice.currency && (
+                    <Descriptions.Item label={<Trans>Exchange rate</Trans>}>
+                      <Typography.Text type="warning">
+                        <Trans>No exchange rate imported for the invoice date yet, it will be recorded when reference rates are imported</Trans>
+                      </Typography.Text>
+                    </Descriptions.Item>
+                  )}
                 </Descriptions>
               </Col>
             </Row>
@@ -0,0 +1,100 @@
+import { useCallback, useEffect, useState } from "react";
+import { Button, Col, Space, Table, Typography, Row, message } from "antd";
+import { CloudUploadOutlined, SwapOutlined } from "@ant-design/icons";
+import { Trans } from "@lingui/react/macro";
+import { t } from "@lingui/core/macro";
+import { useLingui } from "@lingui/react";
+import { invoke } from "@tauri-apps/api/core";
+import { open as openDialog } from "@tauri-apps/plugin-dialog";
+
+const { Title, Paragraph } = Typography;
+
+function SettingsExchangeRates() {
+  useLingui();
+  const [messageApi, contextHolder] = message.useMessage();
+  const [exchangeRates, setExchangeRates] = useState<any[]>([]);
+  const [importing, setImporting] = useState(false);
+
+  const loadExchangeRates = useCallback(async () => {
+    setExchangeRates(await invoke<any[]>("get_exchange_rates"));
+  }, []);
+
+  useEffect(() => {
+    loadExchangeRates();
+  }, [loadExchangeRates]);
+
+  const handleImport = async () => {
+    const path = await openDialog({
+      multiple: false,
+      filters: [{ name: t`Exchange rates`, extensions: ["xml", "csv"] }],
+    });
+    if (!path) return;
+
+    setImporting(true);
+    try {
+      const count = await invoke<number>("import_exchange_rates", { path });
+      messageApi.success(t`Imported ${count} exchange rates`);
+      await loadExchangeRates();
+    } catch (error) {
+      messageApi.error(t`Failed to import exchange rates: ${String(error)}`);
+    } finally {
+      setImporting(false);
+    }
+  };
+
+  return (
+    <>
+      {contextHolder}
+      <Row>
+        <Col span={12}>
+          <Title level={3} style={{ marginTop: 0 }}>
+            <Space>
+              <SwapOutlined />
+              <Trans>Exchange rates</Trans>
+            </Space>
+          </Title>
+        </Col>
+        <Col span={12} style={{ display: "flex", justifyContent: "flex-end" }}>
+          <Button
+            type="primary"
+            icon={<CloudUploadOutlined />}
+            loading={importing}
+            onClick={handleImport}
+            style={{ marginBottom: 10 }}
+          >
+            <Trans>Import rates</Trans>
+          </Button>
+        </Col>
+      </Row>
+      <Row>
+        <Col span={24}>
+          <Paragraph type="secondary">
+            <Trans>
+              Import the euro reference rates of the European Central Bank, as XML or CSV. Invoices in other currencies
+              keep the rate of their date when issued, and reports are totalled in the organization's currency.
+            </Trans>
+          </Paragraph>
+          <Table
+            dataSource={exchangeRates}
+            pagination={false}
+            rowKey="id"
+            size="middle"
+            bordered
+            style={{ width: "100%" }}
+          >
+            <Table.Column title={<Trans>Currency</Trans>} dataIndex="currency" key="currency" />
+            <Table.Column
+              title={<Trans>Rate</Trans>}
+              key="rate"
+              align="right"
+              render={(exchangeRate) => `1 ${exchangeRate.baseCurrency} = ${exchangeRate.rate} ${exchangeRate.currency}`}
+            />
+            <Table.Column title={<Trans>Date</Trans>} dataIndex="date" key="date" />
+          </Table>
+        </Col>
+      </Row>
+    </>
+  );
+}
+
+export default SettingsExchangeRates;
@@ -1,4 +1,4 @@
-import { Button, Col, Form, Input, InputNumber, Select, Space, Typography, Row, Upload, Divider, message } from "antd";
+import { Button, Checkbox, Col, Form, Input, InputNumber, Select, Space, Typography, Row, Upload, Divider, message } from "antd";
 import { atom, useAtom, useSetAtom } from "jotai";
 import { FileTextOutlined, UploadOutlined, CaretRightOutlined, CaretDownOutlined } from "@ant-design/icons";
 import { useState } from "react";
@@ -99,6 +99,13 @@ function SettingsInvoice() {
               <Form.Item label={t`Decimal places`} name="minimum_fraction_digits">
                 <InputNumber min={0} max={10} />
               </Form.Item>
+              <Form.Item
+                label={t`Quantity decimal places`}
+                name="quantityPrecision"
+                extra={t`Line item quantities are rounded to this many decimals`}
+              >
+                <InputNumber min={0} max={4} />
+              </Form.Item>
               <Form.Item label={t`Due days`} name="due_days">
                 <InputNumber min={0} />
               </Form.Item>
@@ -111,6 +118,21 @@ function SettingsInvoice() {
                   placeholder="0%"
                 />
               </Form.Item>
+              <Form.Item
+                name="pricesIncludeTax"
+                valuePropName="checked"
+                extra={t`Unit prices on new invoices are gross, net amounts and tax are backed out of them`}
+              >
+                <Checkbox>
+                  <Trans>Prices include tax</Trans>
+                </Checkbox>
+              </Form.Item>
+              <Form.Item label={t`Tax rounding`} name="taxRounding">
+                <Select>
+                  <Option value="per_total">{t`Per tax total`}</Option>
+                  <Option value="per_line">{t`Per line`}</Option>
+                </Select>
+              </Form.Item>
               <Form.Item label={t`Notes`} name="customerNotes">
                 <TextArea rows={4} />
               </Form.Item>
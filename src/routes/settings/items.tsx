@@ -0,0 +1,95 @@
+import { useEffect } from "react";
+import { Link, Outlet, useLocation } from "react-router";
+import { Button, Col, Space, Table, Typography, Row } from "antd";
+import { useAtomValue, useSetAtom } from "jotai";
+import { AppstoreOutlined } from "@ant-design/icons";
+import { Trans } from "@lingui/react/macro";
+import { useLingui } from "@lingui/react";
+
+import { itemsAtom, setItemsAtom } from "src/atoms/item";
+import { organizationAtom } from "src/atoms/organization";
+import { taxRatesAtom, setTaxRatesAtom } from "src/atoms/tax-rate";
+import { getFormattedNumber } from "src/utils/currencies";
+import { getUnits } from "src/utils/units";
+
+const { Title } = Typography;
+
+function SettingsItems() {
+  const { i18n } = useLingui();
+  const location = useLocation();
+
+  const items = useAtomValue(itemsAtom);
+  const setItems = useSetAtom(setItemsAtom);
+  const taxRates = useAtomValue(taxRatesAtom);
+  const setTaxRates = useSetAtom(setTaxRatesAtom);
+  const organization = useAtomValue(organizationAtom);
+
+  useEffect(() => {
+    if (location.pathname === "/settings/items") {
+      setItems();
+      setTaxRates();
+    }
+  }, [location, setItems, setTaxRates]);
+
+  return (
+    <>
+      <Row>
+        <Col span={12}>
+          <Title level={3} style={{ marginTop: 0 }}>
+            <Space>
+              <AppstoreOutlined />
+              <Trans>Items</Trans>
+            </Space>
+          </Title>
+        </Col>
+        <Col span={12} style={{ display: "flex", justifyContent: "flex-end" }}>
+          <Link to="/settings/items/new">
+            <Button type="primary" style={{ marginBottom: 10 }}>
+              <Trans>New item</Trans>
+            </Button>
+          </Link>
+        </Col>
+      </Row>
+      <Row>
+        <Col span={24}>
+          <Table dataSource={items} pagination={false} rowKey="id" size="middle" bordered style={{ width: "100%" }}>
+            <Table.Column
+              title={<Trans>Name</Trans>}
+              key="name"
+              render={(item) => <Link to={`/settings/items/${item.id}`}>{item.name}</Link>}
+            />
+            <Table.Column title={<Trans>SKU</Trans>} dataIndex="sku" key="sku" />
+            <Table.Column
+              title={<Trans>Unit</Trans>}
+              dataIndex="unitCode"
+              key="unitCode"
+              render={(unitCode) => getUnits().find((unit) => unit.code === unitCode)?.name ?? unitCode ?? "-"}
+            />
+            <Table.Column
+              title={<Trans>Tax rate</Trans>}
+              dataIndex="taxRateId"
+              key="taxRateId"
+              render={(taxRateId) => taxRates.find((taxRate: any) => taxRate.id === taxRateId)?.name ?? "-"}
+            />
+            <Table.Column
+              title={
+                <div style={{ textAlign: "right" }}>
+                  <Trans>Unit price</Trans>
+                </div>
+              }
+              align="right"
+              dataIndex="unitPrice"
+              key="unitPrice"
+              render={(unitPrice) =>
+                organization ? getFormattedNumber(unitPrice, organization.currency, i18n.locale, organization) : unitPrice
+              }
+            />
+          </Table>
+        </Col>
+      </Row>
+      <Outlet />
+    </>
+  );
+}
+
+export default SettingsItems;
@@ -9,6 +9,7 @@ import isEmpty from "lodash/isEmpty";
 import { organizationAtom, setOrganizationsAtom, deleteOrganizationAtom } from "src/atoms/organization";
 import { DATE_FORMATS, type DateFormatKey, getDateFormatLabel } from "src/utils/date";
 import { countries } from "src/utils/countries";
+import { getFieldErrors, toFormFields } from "src/utils/form";
 
 const { Title } = Typography;
 const { TextArea } = Input;
@@ -26,7 +27,13 @@ function SettingsInvoice() {
 
   const onSubmit = async (values: object) => {
     setSubmitting(true);
-    setOrganization(values);
+    try {
+      await setOrganization(values);
+    } catch (error) {
+      form.setFields(toFormFields(getFieldErrors(error)));
+      setSubmitting(false);
+      return;
+    }
     setOrganizations();
     setSubmitting(false);
   };
@@ -91,6 +98,23 @@ function SettingsInvoice() {
                   </Form.Item>
                 </Col>
               </Row>
+              <Row gutter={16}>
+                <Col span={12}>
+                  <Form.Item label={`BIC`} name="bic">
+                    <Input />
+                  </Form.Item>
+                </Col>
+                <Col span={12}>
+                  <Form.Item label={t`Payment reference`} name="paymentReferenceFormat">
+                    <Select>
+                      <Select.Option value="none">{t`None`}</Select.Option>
+                      <Select.Option value="rf">{t`RF creditor reference`}</Select.Option>
+                      <Select.Option value="finnish">{t`Finnish reference number`}</Select.Option>
+                      <Select.Option value="estonian">{t`Estonian reference number`}</Select.Option>
+                    </Select>
+                  </Form.Item>
+                </Col>
+              </Row>
               <Row gutter={16}>
                 <Col span={12}>
                   <Form.Item label={`VATIN`} name="vatin">
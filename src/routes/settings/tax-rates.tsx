@@ -60,7 +60,7 @@ function SettingsTaxRates() {
               align="right"
               dataIndex="percentage"
               key="percentage"
-              render={(percentage) => `${percentage} %`}
+              render={(percentage, taxRate: any) => (taxRate.withholding ? `-${percentage} %` : `${percentage} %`)}
             />
             <Table.Column
               title={
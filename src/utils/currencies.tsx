@@ -1,5 +1,7 @@
 import isNumber from "lodash/isNumber";
 
+import { minorUnitExponent } from "src/utils/currency";
+
 // @ts-expect-error - Intl supportedValuesOf support?
 export const currencies = Intl.supportedValuesOf("currency");
 
@@ -17,6 +19,7 @@ export const getFormattedNumber = (number: number, currency: string, locale: str
   return new Intl.NumberFormat(locale, {
     style: "currency",
     currency: currency,
-    minimumFractionDigits: organization.minimum_fraction_digits,
+    // No more decimals than the currency has, none for JPY
+    minimumFractionDigits: Math.min(organization.minimum_fraction_digits ?? 2, minorUnitExponent(currency)),
   }).format(number);
 };
@@ -6,10 +6,28 @@ Decimal.set({
   rounding: Decimal.ROUND_HALF_UP,
 });
 
+// Decimals of each currency's minor unit by ISO 4217, currencies that aren't
+// listed have two. Mirrors src-tauri/src/money.rs.
+const MINOR_UNIT_EXPONENTS: Record<string, number> = {
+  BIF: 0, CLP: 0, DJF: 0, GNF: 0, ISK: 0, JPY: 0, KMF: 0, KRW: 0, PYG: 0,
+  RWF: 0, UGX: 0, UYI: 0, VND: 0, VUV: 0, XAF: 0, XOF: 0, XPF: 0,
+  BHD: 3, IQD: 3, JOD: 3, KWD: 3, LYD: 3, OMR: 3, TND: 3,
+  CLF: 4, UYW: 4,
+};
+
+/**
+ * Number of decimals of a currency's minor unit
+ * @param currency - Currency code (e.g., 'JPY')
+ * @returns 0 for JPY, 3 for BHD, 2 for most currencies
+ */
+export function minorUnitExponent(currency?: string | null): number {
+  return MINOR_UNIT_EXPONENTS[(currency || "").toUpperCase()] ?? 2;
+}
+
 /**
  * Convert cents to currency units (e.g., dollars)
- * @param cents - Amount in cents (smallest currency unit)
- * @param precision - Number of decimal places (default: 2)
+ * @param cents - Amount in cents (smallest currency unit, yen for JPY)
+ * @param precision - Number of decimal places, see minorUnitExponent (default: 2)
  * @returns Amount in currency units
  */
 export function centsToUnits(cents: number, precision: number = 2): number {
@@ -19,7 +37,7 @@ export function centsToUnits(cents: number, precision: number = 2): number {
 /**
  * Convert currency units to cents
  * @param units - Amount in currency units (e.g., dollars)
- * @param precision - Number of decimal places (default: 2)
+ * @param precision - Number of decimal places, see minorUnitExponent (default: 2)
  * @returns Amount in cents (smallest currency unit)
  */
 export function unitsToCents(units: number, precision: number = 2): number {
@@ -34,7 +52,7 @@ export function unitsToCents(units: number, precision: number = 2): number {
  * @returns Formatted currency string
  */
 export function formatCents(cents: number, currency: string, locale: string): string {
-  const units = centsToUnits(cents);
+  const units = centsToUnits(cents, minorUnitExponent(currency));
   return new Intl.NumberFormat(locale, {
     style: 'currency',
     currency: currency,
@@ -85,12 +103,13 @@ export function subtractDecimal(a: number | string, b: number | string): number
  * Calculate tax amount with precise decimal arithmetic
  * @param amount - Base amount
  * @param percentage - Tax percentage (e.g., 20 for 20%)
+ * @param precision - Number of decimal places to round to (default: 2)
  * @returns Tax amount as a number
  */
-export function calculateTax(amount: number | string, percentage: number | string): number {
+export function calculateTax(amount: number | string, percentage: number | string, precision: number = 2): number {
   return new Decimal(amount)
     .times(percentage)
     .div(100)
-    .toDecimalPlaces(2, Decimal.ROUND_HALF_UP)
+    .toDecimalPlaces(precision, Decimal.ROUND_HALF_UP)
     .toNumber();
 }
\ No newline at end of file
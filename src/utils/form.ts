@@ -0,0 +1,16 @@
+/**
+ * Commands that save forms reject with the invalid fields listed, so the
+ * messages can be shown next to their inputs
+ */
+export interface FieldError {
+  field: string;
+  message: string;
+}
+
+export function getFieldErrors(error: unknown): FieldError[] {
+  return (error as { fields?: FieldError[] } | null)?.fields ?? [];
+}
+
+export function toFormFields(errors: FieldError[]) {
+  return errors.map(({ field, message }) => ({ name: field, errors: [message] }));
+}
@@ -1,3 +1,8 @@
+import find from "lodash/find";
+import isNumber from "lodash/isNumber";
+
+import { centsToUnits, minorUnitExponent, unitsToCents } from "src/utils/currency";
+
 export interface InvoiceFormatValidationResult {
   isValid: boolean;
   error?: string;
@@ -18,9 +23,9 @@ export const validateInvoiceFormat = (format: string): InvoiceFormatValidationRe
   const variablePattern = /\{([^}]+)\}/g;
   const matches = format.match(variablePattern) || [];
 
-  // Check for invalid variables
+  // Check for invalid variables ({number:5} zero-pads the counter to 5 digits)
   for (const match of matches) {
-    if (!validVariables.includes(match)) {
+    if (!validVariables.includes(match) && !/^\{number:\d+\}$/.test(match)) {
       return {
         isValid: false,
         error: `Invalid variable: ${match}. Valid variables are: ${validVariables.join(", ")}`,
@@ -46,8 +51,9 @@ export const generateInvoiceNumber = (
 
   let preview = format;
 
-  // Replace {number} with counter (no padding)
+  // Replace {number} with counter (no padding) or {number:N} with zero-padded counter
   preview = preview.replace("{number}", String(counter));
+  preview = preview.replace(/\{number:(\d+)\}/, (_, width) => String(counter).padStart(Number(width), "0"));
 
   // Replace date variables
   preview = preview.replace("{year}", date.getFullYear().toString());
@@ -61,3 +67,37 @@ export const generateInvoiceNumber = (
 
   return preview;
 };
+
+export interface LineItemTax {
+  taxRate: string;
+  compound?: boolean;
+}
+
+export interface TaxGroup {
+  taxRate: any;
+  compound: boolean;
+  subtotal: number;
+  tax: number;
+}
+
+// A line's taxes in the order they are charged. Lines with a single tax may only carry taxRate.
+export const getLineItemTaxes = (lineItem: any): LineItemTax[] => {
+  if (lineItem?.taxes?.length) {
+    return lineItem.taxes;
+  }
+  return lineItem?.taxRate ? [{ taxRate: lineItem.taxRate, compound: false }] : [];
+};
+
+// Fixed discounts are stored in minor units of the currency, percentages as they are
+export const discountToCents = (discount: number | null | undefined, kind?: string, currency?: string) =>
+  isNumber(discount) && kind === "amount" ? unitsToCents(discount, minorUnitExponent(currency)) : discount;
+
+export const discountToUnits = (discount: number | null | undefined, kind?: string, currency?: string) =>
+  isNumber(discount) && kind === "amount" ? centsToUnits(discount, minorUnitExponent(currency)) : discount;
+
+// New lines get the rate's compound setting
+export const toLineItemTaxes = (taxRateIds: string[], taxRates: any[], current: LineItemTax[] = []): LineItemTax[] =>
+  (taxRateIds || []).map((taxRate) => {
+    const existing = find(current, { taxRate });
+    return { taxRate, compound: existing ? !!existing.compound : !!find(taxRates, { id: taxRate })?.compound };
+  });
@@ -0,0 +1,32 @@
+import { t } from "@lingui/core/macro";
+
+// Units of measure for line items as UN/ECE Recommendation 20 codes, the same
+// list as src-tauri/src/units.rs. Labels are what invoices print after quantities.
+export const DEFAULT_UNIT_CODE = "C62";
+
+export const getUnits = () => [
+  { code: "C62", label: t`unit`, name: t`Unit` },
+  { code: "H87", label: t`pcs`, name: t`Pieces` },
+  { code: "SET", label: t`set`, name: t`Sets` },
+  { code: "LS", label: t`lump sum`, name: t`Lump sum` },
+  { code: "MIN", label: t`min`, name: t`Minutes` },
+  { code: "HUR", label: t`h`, name: t`Hours` },
+  { code: "DAY", label: t`day`, name: t`Days` },
+  { code: "WEE", label: t`week`, name: t`Weeks` },
+  { code: "MON", label: t`month`, name: t`Months` },
+  { code: "ANN", label: t`year`, name: t`Years` },
+  { code: "MTR", label: t`m`, name: t`Metres` },
+  { code: "KMT", label: t`km`, name: t`Kilometres` },
+  { code: "MTK", label: t`m²`, name: t`Square metres` },
+  { code: "KGM", label: t`kg`, name: t`Kilograms` },
+  { code: "LTR", label: t`l`, name: t`Litres` },
+];
+
+export const unitLabel = (code?: string | null) => {
+  if (!code) return undefined;
+  return getUnits().find((unit) => unit.code === code)?.label ?? code;
+};
+
+// Quantity followed by its unit, e.g. "2.5 h". Plain units aren't named.
+export const formatQuantity = (quantity: number, code?: string | null) =>
+  code && code !== DEFAULT_UNIT_CODE ? `${quantity} ${unitLabel(code)}` : `${quantity}`;